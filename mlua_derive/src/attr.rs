@@ -0,0 +1,112 @@
+use proc_macro2::TokenStream as TokenStream2;
+use syn::spanned::Spanned;
+use syn::{parse_quote, Attribute, Error, ExprPath, Fields, GenericParam, Generics, LitStr, Result, Variant};
+
+/// Item that the `#[lua(...)]` attribute is attached to.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Position {
+    Container,
+    Field,
+    Variant,
+    Method,
+}
+
+/// How a missing field value should be filled in.
+pub(crate) enum DefaultValue {
+    Trait,
+    Path(ExprPath),
+}
+
+/// Options that can be set using `#[lua(...)]` attribute.
+///
+/// The attribute is shared between all derive macros, so every macro accepts the full set of
+/// options valid for a position and ignores the ones it does not use.
+#[derive(Default)]
+pub(crate) struct LuaAttributes {
+    pub(crate) rename: Option<String>,
+    pub(crate) skip: bool,
+    pub(crate) readonly: bool,
+    pub(crate) meta: Option<String>,
+    pub(crate) default: Option<DefaultValue>,
+    pub(crate) flatten: bool,
+    pub(crate) table: bool,
+    pub(crate) tag: Option<String>,
+}
+
+impl LuaAttributes {
+    pub(crate) fn parse(attrs: &[Attribute], pos: Position) -> Result<Self> {
+        let mut this = LuaAttributes::default();
+        for attr in attrs.iter().filter(|attr| is_lua_attr(attr)) {
+            attr.parse_nested_meta(|meta| {
+                let path = &meta.path;
+                let is_field = pos == Position::Field;
+                if path.is_ident("rename") && pos != Position::Container {
+                    this.rename = Some(meta.value()?.parse::<LitStr>()?.value());
+                } else if path.is_ident("skip") && (is_field || pos == Position::Method) {
+                    this.skip = true;
+                } else if path.is_ident("readonly") && is_field {
+                    this.readonly = true;
+                } else if path.is_ident("meta") && pos == Position::Method {
+                    this.meta = Some(meta.value()?.parse::<LitStr>()?.value());
+                } else if path.is_ident("default") && is_field {
+                    this.default = Some(match meta.value() {
+                        Ok(value) => DefaultValue::Path(value.parse::<LitStr>()?.parse()?),
+                        Err(_) => DefaultValue::Trait,
+                    });
+                } else if path.is_ident("flatten") && is_field {
+                    this.flatten = true;
+                } else if path.is_ident("table") && pos == Position::Container {
+                    this.table = true;
+                } else if path.is_ident("tag") && pos == Position::Container {
+                    this.tag = Some(meta.value()?.parse::<LitStr>()?.value());
+                } else {
+                    return Err(meta.error("unsupported lua attribute"));
+                }
+                Ok(())
+            })?;
+        }
+        if this.flatten && this.rename.is_some() {
+            let attr = attrs.iter().rfind(|attr| is_lua_attr(attr)).unwrap();
+            return Err(Error::new(
+                attr.span(),
+                "`flatten` cannot be combined with `rename`",
+            ));
+        }
+        Ok(this)
+    }
+}
+
+pub(crate) fn is_lua_attr(attr: &Attribute) -> bool {
+    attr.path().is_ident("lua")
+}
+
+/// Returns Lua name of the enum variant.
+pub(crate) fn variant_name(variant: &Variant) -> Result<String> {
+    let opts = LuaAttributes::parse(&variant.attrs, Position::Variant)?;
+    Ok(opts.rename.unwrap_or_else(|| variant.ident.to_string()))
+}
+
+/// Checks that fields of a tuple struct (or variant) have no `#[lua(...)]` attributes.
+///
+/// Such fields are mapped by position, so renaming or skipping them has no meaning.
+pub(crate) fn check_unnamed_fields(fields: &Fields) -> Result<()> {
+    for field in fields {
+        if let Some(attr) = field.attrs.iter().find(|attr| is_lua_attr(attr)) {
+            return Err(Error::new(
+                attr.span(),
+                "lua attributes are not supported for tuple fields",
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Adds `bound` to every type parameter.
+pub(crate) fn add_trait_bounds(mut generics: Generics, bound: TokenStream2) -> Generics {
+    for param in &mut generics.params {
+        if let GenericParam::Type(param) = param {
+            param.bounds.push(parse_quote!(#bound));
+        }
+    }
+    generics
+}
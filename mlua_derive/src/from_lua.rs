@@ -1,9 +1,92 @@
 use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
 use quote::quote;
-use syn::{parse_macro_input, DeriveInput};
+use syn::{parse_macro_input, Data, DataEnum, DeriveInput, Error, Fields, FieldsNamed, LitByteStr, Result};
+
+use crate::attr::{
+    add_trait_bounds, check_unnamed_fields, variant_name, DefaultValue, LuaAttributes, Position,
+};
 
 pub fn from_lua(input: TokenStream) -> TokenStream {
-    let DeriveInput { ident, generics, .. } = parse_macro_input!(input as DeriveInput);
+    let input = parse_macro_input!(input as DeriveInput);
+    match expand(input) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+fn expand(input: DeriveInput) -> Result<TokenStream2> {
+    let opts = LuaAttributes::parse(&input.attrs, Position::Container)?;
+    if !opts.table {
+        if opts.tag.is_some() {
+            let msg = "`tag` requires the `table` representation, use `#[lua(table, tag = \"...\")]`";
+            return Err(Error::new(input.ident.span(), msg));
+        }
+        return Ok(from_userdata(input));
+    }
+
+    let ident = &input.ident;
+    let ident_str = ident.to_string();
+    let body = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => {
+                let table = expect_table(&ident_str);
+                let ctor = read_named(fields, &ident_str, quote!(Self))?;
+                quote! {
+                    #table
+                    Ok(#ctor)
+                }
+            }
+            Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {
+                check_unnamed_fields(&data.fields)?;
+                quote! { Ok(Self(::mlua::FromLua::from_lua_field(value, path, lua)?)) }
+            }
+            Fields::Unnamed(_) => {
+                let table = expect_table(&ident_str);
+                let ctor = read_unnamed(&data.fields, &ident_str, quote!(Self))?;
+                quote! {
+                    #table
+                    Ok(#ctor)
+                }
+            }
+            Fields::Unit => {
+                let err = conversion_error(&ident_str, "expected nil");
+                quote! {
+                    match value {
+                        ::mlua::Value::Nil => Ok(Self),
+                        _ => Err(#err),
+                    }
+                }
+            }
+        },
+        Data::Enum(data) => enum_body(data, &ident_str, opts.tag.as_deref())?,
+        Data::Union(_) => return Err(Error::new(ident.span(), "unions are not supported")),
+    };
+
+    let generics = add_trait_bounds(input.generics.clone(), quote!(::mlua::FromLua));
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    Ok(quote! {
+      impl #impl_generics ::mlua::FromLua for #ident #ty_generics #where_clause {
+        #[inline]
+        fn from_lua(value: ::mlua::Value, lua: &::mlua::Lua) -> ::mlua::Result<Self> {
+          Self::from_lua_field(value, &::mlua::__private::FieldPath::Root, lua)
+        }
+
+        fn from_lua_field(
+            value: ::mlua::Value,
+            path: &::mlua::__private::FieldPath,
+            lua: &::mlua::Lua,
+        ) -> ::mlua::Result<Self> {
+          #body
+        }
+      }
+    })
+}
+
+/// Generates implementation that takes [`UserData`] value, borrows it and clones.
+fn from_userdata(input: DeriveInput) -> TokenStream2 {
+    let DeriveInput { ident, generics, .. } = input;
 
     let ident_str = ident.to_string();
     let (impl_generics, ty_generics, _) = generics.split_for_impl();
@@ -27,5 +110,220 @@ pub fn from_lua(input: TokenStream) -> TokenStream {
         }
       }
     }
-    .into()
+}
+
+/// Generates an error of the converted type itself, reported at the current field `path`.
+fn conversion_error(to: &str, message: &str) -> TokenStream2 {
+    quote! {
+        path.error(::mlua::Error::FromLuaConversionError {
+            from: value.type_name(),
+            to: #to.to_string(),
+            message: Some(#message.to_string()),
+        })
+    }
+}
+
+/// Binds `table` to the `value` if it's a Lua table, or returns an error.
+fn expect_table(to: &str) -> TokenStream2 {
+    let err = conversion_error(to, "expected table");
+    quote! {
+        let table = match value {
+            ::mlua::Value::Table(table) => table,
+            _ => return Err(#err),
+        };
+    }
+}
+
+fn default_value(default: Option<DefaultValue>) -> TokenStream2 {
+    match default {
+        Some(DefaultValue::Path(path)) => quote! { #path() },
+        Some(DefaultValue::Trait) | None => quote! { ::std::default::Default::default() },
+    }
+}
+
+/// Converts a field `value` of the type `to`, extending the current path with the field `name`.
+fn convert_field(to: &str, name: &str) -> TokenStream2 {
+    quote! {
+        ::mlua::FromLua::from_lua_field(value, &path.field(#to, #name), lua)?
+    }
+}
+
+/// Generates an expression constructing `ctor { .. }` from named fields of `table`.
+fn read_named(fields: &FieldsNamed, to: &str, ctor: TokenStream2) -> Result<TokenStream2> {
+    let mut inits = Vec::new();
+    for field in &fields.named {
+        let opts = LuaAttributes::parse(&field.attrs, Position::Field)?;
+        let field_ident = field.ident.as_ref().unwrap();
+
+        let expr = if opts.skip {
+            default_value(opts.default)
+        } else if opts.flatten {
+            quote! { ::mlua::FromLua::from_lua_field(::mlua::Value::Table(table.clone()), path, lua)? }
+        } else {
+            let name = opts.rename.unwrap_or_else(|| field_ident.to_string());
+            let convert = convert_field(to, &name);
+            match opts.default {
+                Some(default) => {
+                    let default = default_value(Some(default));
+                    quote! {
+                        match table.get::<::mlua::Value>(#name)? {
+                            ::mlua::Value::Nil => #default,
+                            value => #convert,
+                        }
+                    }
+                }
+                None => quote! {{
+                    let value = table.get::<::mlua::Value>(#name)?;
+                    #convert
+                }},
+            }
+        };
+        inits.push(quote! { #field_ident: #expr });
+    }
+    Ok(quote! { #ctor { #(#inits),* } })
+}
+
+/// Generates an expression constructing `ctor(..)` from the sequence part of `table`.
+fn read_unnamed(fields: &Fields, to: &str, ctor: TokenStream2) -> Result<TokenStream2> {
+    check_unnamed_fields(fields)?;
+    let values = (1..=fields.len()).map(|i| {
+        let convert = convert_field(to, &format!("[{i}]"));
+        quote! {{
+            let value = table.get::<::mlua::Value>(#i)?;
+            #convert
+        }}
+    });
+    Ok(quote! { #ctor(#(#values),*) })
+}
+
+fn enum_body(data: &DataEnum, to: &str, tag: Option<&str>) -> Result<TokenStream2> {
+    let mut names = Vec::new();
+    let mut unit_arms = Vec::new();
+    let mut arms = Vec::new();
+    for variant in &data.variants {
+        let var_ident = &variant.ident;
+        let name = variant_name(variant)?;
+        let lit = LitByteStr::new(name.as_bytes(), Span::call_site());
+        let var_to = format!("{to}::{var_ident}");
+
+        let arm = match &variant.fields {
+            Fields::Unit => {
+                unit_arms.push(quote! { #lit => Ok(Self::#var_ident), });
+                quote! { #lit => Ok(Self::#var_ident), }
+            }
+            Fields::Named(fields) => {
+                let ctor = read_named(fields, &var_to, quote!(Self::#var_ident))?;
+                match tag {
+                    Some(_) => quote! { #lit => Ok(#ctor), },
+                    None => {
+                        let table = expect_table(&var_to);
+                        quote! {
+                            #lit => {
+                                #table
+                                Ok(#ctor)
+                            }
+                        }
+                    }
+                }
+            }
+            Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {
+                check_unnamed_fields(&variant.fields)?;
+                match tag {
+                    Some(_) => quote! {
+                        #lit => {
+                            let value = ::mlua::Value::Table(table);
+                            Ok(Self::#var_ident(::mlua::FromLua::from_lua_field(value, path, lua)?))
+                        }
+                    },
+                    None => {
+                        let convert = convert_field(to, &name);
+                        quote! { #lit => Ok(Self::#var_ident(#convert)), }
+                    }
+                }
+            }
+            Fields::Unnamed(_) => {
+                if tag.is_some() {
+                    let msg = "tuple variants cannot be internally tagged";
+                    return Err(Error::new_spanned(variant, msg));
+                }
+                let table = expect_table(&var_to);
+                let ctor = read_unnamed(&variant.fields, &var_to, quote!(Self::#var_ident))?;
+                quote! {
+                    #lit => {
+                        #table
+                        Ok(#ctor)
+                    }
+                }
+            }
+        };
+        names.push(name);
+        arms.push(arm);
+    }
+
+    let unknown_variant =
+        |value| quote! { path.error(::mlua::__private::unknown_variant(&#value, #to, &[#(#names),*])) };
+
+    if let Some(tag) = tag {
+        let table = expect_table(to);
+        let unknown = unknown_variant(quote!(tag));
+        return Ok(quote! {
+            #table
+            let tag = table.get::<::mlua::Value>(#tag)?;
+            let name = match tag {
+                ::mlua::Value::String(ref name) => name.clone(),
+                _ => return Err(#unknown),
+            };
+            match &*name.as_bytes() {
+                #(#arms)*
+                _ => Err(#unknown),
+            }
+        });
+    }
+
+    let unknown = unknown_variant(quote!(value));
+    let from_string = quote! {
+        ::mlua::Value::String(ref name) => match &*name.as_bytes() {
+            #(#unit_arms)*
+            _ => Err(#unknown),
+        },
+    };
+
+    // Enum with unit variants only is represented as a string
+    if unit_arms.len() == data.variants.len() {
+        let err = conversion_error(to, "expected string");
+        return Ok(quote! {
+            match value {
+                #from_string
+                _ => Err(#err),
+            }
+        });
+    }
+
+    // Externally tagged enum: `"Unit"` or `{ Variant = value }`
+    let single_key_err = conversion_error(to, "expected table with a single key");
+    let err = conversion_error(to, "expected string or table");
+    let unknown_key = unknown_variant(quote!(key));
+    Ok(quote! {
+        match value {
+            #from_string
+            ::mlua::Value::Table(ref table) => {
+                let (key, value) = {
+                    let mut pairs = table.pairs::<::mlua::Value, ::mlua::Value>();
+                    match (pairs.next(), pairs.next()) {
+                        (Some(pair), None) => pair?,
+                        _ => return Err(#single_key_err),
+                    }
+                };
+                let name = match key {
+                    ::mlua::Value::String(ref name) => name.clone(),
+                    _ => return Err(#unknown_key),
+                };
+                match &*name.as_bytes() {
+                    #(#arms)*
+                    _ => Err(#unknown_key),
+                }
+            }
+            _ => Err(#err),
+        }
+    })
 }
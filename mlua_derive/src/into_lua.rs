@@ -0,0 +1,234 @@
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::spanned::Spanned;
+use syn::{parse_macro_input, Data, DataEnum, DeriveInput, Error, Fields, FieldsNamed, Result};
+
+use crate::attr::{add_trait_bounds, check_unnamed_fields, variant_name, LuaAttributes, Position};
+
+pub fn into_lua(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match expand(input) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+fn expand(input: DeriveInput) -> Result<TokenStream2> {
+    let opts = LuaAttributes::parse(&input.attrs, Position::Container)?;
+    if !opts.table {
+        if opts.tag.is_some() {
+            let msg = "`tag` requires the `table` representation, use `#[lua(table, tag = \"...\")]`";
+            return Err(Error::new(input.ident.span(), msg));
+        }
+        return Ok(into_userdata(input));
+    }
+
+    let ident = &input.ident;
+    let ident_str = ident.to_string();
+    let body = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => {
+                let (pat, write) = named_fields(fields, &ident_str)?;
+                quote! {
+                    let Self { #pat } = self;
+                    #write
+                    Ok(::mlua::Value::Table(table))
+                }
+            }
+            Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {
+                check_unnamed_fields(&data.fields)?;
+                quote! { ::mlua::IntoLua::into_lua(self.0, lua) }
+            }
+            Fields::Unnamed(_) => {
+                let (pat, write) = unnamed_fields(&data.fields)?;
+                quote! {
+                    let Self(#pat) = self;
+                    #write
+                    Ok(::mlua::Value::Table(table))
+                }
+            }
+            Fields::Unit => quote! { Ok(::mlua::Value::Nil) },
+        },
+        Data::Enum(data) => enum_body(data, &ident_str, opts.tag.as_deref())?,
+        Data::Union(_) => return Err(Error::new(ident.span(), "unions are not supported")),
+    };
+
+    let generics = add_trait_bounds(input.generics.clone(), quote!(::mlua::IntoLua));
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    Ok(quote! {
+      impl #impl_generics ::mlua::IntoLua for #ident #ty_generics #where_clause {
+        fn into_lua(self, lua: &::mlua::Lua) -> ::mlua::Result<::mlua::Value> {
+          #body
+        }
+      }
+    })
+}
+
+fn into_userdata(input: DeriveInput) -> TokenStream2 {
+    let DeriveInput { ident, generics, .. } = input;
+
+    let (impl_generics, ty_generics, _) = generics.split_for_impl();
+    let where_clause = match &generics.where_clause {
+        Some(where_clause) => quote! { #where_clause, Self: 'static + ::mlua::MaybeSend },
+        None => quote! { where Self: 'static + ::mlua::MaybeSend },
+    };
+
+    quote! {
+      impl #impl_generics ::mlua::IntoLua for #ident #ty_generics #where_clause {
+        #[inline]
+        fn into_lua(self, lua: &::mlua::Lua) -> ::mlua::Result<::mlua::Value> {
+          Ok(::mlua::Value::UserData(lua.create_any_userdata(self)?))
+        }
+      }
+    }
+}
+
+/// Generates a destructuring pattern and code to write named fields into a new `table`.
+fn named_fields(fields: &FieldsNamed, to: &str) -> Result<(TokenStream2, TokenStream2)> {
+    let mut pat = Vec::new();
+    let mut write = Vec::new();
+    for (i, field) in fields.named.iter().enumerate() {
+        let opts = LuaAttributes::parse(&field.attrs, Position::Field)?;
+        if opts.skip {
+            continue;
+        }
+
+        let field_ident = field.ident.as_ref().unwrap();
+        let binding = format_ident!("__field{i}");
+        pat.push(quote! { #field_ident: #binding });
+
+        let field_str = field_ident.to_string();
+        if opts.flatten {
+            write.push(quote! {
+                let value = ::mlua::IntoLua::into_lua(#binding, lua)?;
+                ::mlua::__private::flatten_into(&table, value, #to, #field_str)?;
+            });
+        } else {
+            let name = opts.rename.unwrap_or(field_str);
+            write.push(quote! { table.raw_set(#name, #binding)?; });
+        }
+    }
+
+    let nrec = write.len();
+    let pat = quote! { #(#pat,)* .. };
+    let write = quote! {
+        let table = lua.create_table_with_capacity(0, #nrec)?;
+        #(#write)*
+    };
+    Ok((pat, write))
+}
+
+/// Generates a destructuring pattern and code to write unnamed fields as a sequence into a new
+/// `table`.
+fn unnamed_fields(fields: &Fields) -> Result<(TokenStream2, TokenStream2)> {
+    check_unnamed_fields(fields)?;
+    let bindings = (0..fields.len())
+        .map(|i| format_ident!("__field{i}"))
+        .collect::<Vec<_>>();
+    let nfields = bindings.len();
+    let indices = 1..=nfields;
+    let pat = quote! { #(#bindings),* };
+    let write = quote! {
+        let table = lua.create_table_with_capacity(#nfields, 0)?;
+        #(table.raw_set(#indices, #bindings)?;)*
+    };
+    Ok((pat, write))
+}
+
+fn enum_body(data: &DataEnum, to: &str, tag: Option<&str>) -> Result<TokenStream2> {
+    let mut arms = Vec::new();
+    for variant in &data.variants {
+        let var_ident = &variant.ident;
+        let name = variant_name(variant)?;
+
+        let arm = match (&variant.fields, tag) {
+            (Fields::Unit, None) => quote! {
+                Self::#var_ident => ::mlua::IntoLua::into_lua(#name, lua),
+            },
+            (Fields::Unit, Some(tag)) => quote! {
+                Self::#var_ident => {
+                    let table = lua.create_table_with_capacity(0, 1)?;
+                    table.raw_set(#tag, #name)?;
+                    Ok(::mlua::Value::Table(table))
+                }
+            },
+            (Fields::Named(fields), tag) => {
+                let (pat, write) = named_fields(fields, to)?;
+                let finish = match tag {
+                    Some(tag) => quote! {
+                        table.raw_set(#tag, #name)?;
+                        Ok(::mlua::Value::Table(table))
+                    },
+                    None => wrap_external(&name),
+                };
+                quote! {
+                    Self::#var_ident { #pat } => {
+                        #write
+                        #finish
+                    }
+                }
+            }
+            (Fields::Unnamed(fields), tag) if fields.unnamed.len() == 1 => {
+                check_unnamed_fields(&variant.fields)?;
+                let finish = match tag {
+                    Some(tag) => quote! {
+                        match value {
+                            ::mlua::Value::Table(ref table) => table.raw_set(#tag, #name)?,
+                            _ => {
+                                return Err(::mlua::Error::ToLuaConversionError {
+                                    from: #to.to_string(),
+                                    to: "table",
+                                    message: Some("internally tagged variant must be a table".to_string()),
+                                })
+                            }
+                        }
+                        Ok(value)
+                    },
+                    None => quote! {
+                        let table = lua.create_table_with_capacity(0, 1)?;
+                        table.raw_set(#name, value)?;
+                        Ok(::mlua::Value::Table(table))
+                    },
+                };
+                quote! {
+                    Self::#var_ident(__field0) => {
+                        let value = ::mlua::IntoLua::into_lua(__field0, lua)?;
+                        #finish
+                    }
+                }
+            }
+            (Fields::Unnamed(_), Some(_)) => {
+                let msg = "tuple variants cannot be internally tagged";
+                return Err(Error::new(variant.span(), msg));
+            }
+            (Fields::Unnamed(_), None) => {
+                let (pat, write) = unnamed_fields(&variant.fields)?;
+                let finish = wrap_external(&name);
+                quote! {
+                    Self::#var_ident(#pat) => {
+                        #write
+                        #finish
+                    }
+                }
+            }
+        };
+        arms.push(arm);
+    }
+
+    Ok(quote! {
+        match self {
+            #(#arms)*
+        }
+    })
+}
+
+/// Wraps the variant `table` into `{ name = table }`.
+fn wrap_external(name: &str) -> TokenStream2 {
+    quote! {
+        let outer = lua.create_table_with_capacity(0, 1)?;
+        outer.raw_set(#name, table)?;
+        Ok(::mlua::Value::Table(outer))
+    }
+}
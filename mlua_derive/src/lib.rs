@@ -146,14 +146,38 @@ pub fn chunk(input: TokenStream) -> TokenStream {
 }
 
 #[cfg(feature = "macros")]
-#[proc_macro_derive(FromLua)]
+#[proc_macro_derive(FromLua, attributes(lua))]
 pub fn from_lua(input: TokenStream) -> TokenStream {
     from_lua::from_lua(input)
 }
 
+#[cfg(feature = "macros")]
+#[proc_macro_derive(IntoLua, attributes(lua))]
+pub fn into_lua(input: TokenStream) -> TokenStream {
+    into_lua::into_lua(input)
+}
+
+#[cfg(feature = "macros")]
+#[proc_macro_derive(UserData, attributes(lua))]
+pub fn userdata(input: TokenStream) -> TokenStream {
+    userdata::userdata(input)
+}
+
+#[cfg(feature = "macros")]
+#[proc_macro_attribute]
+pub fn methods(attr: TokenStream, item: TokenStream) -> TokenStream {
+    userdata::methods(attr, item)
+}
+
+#[cfg(feature = "macros")]
+mod attr;
 #[cfg(feature = "macros")]
 mod chunk;
 #[cfg(feature = "macros")]
 mod from_lua;
 #[cfg(feature = "macros")]
+mod into_lua;
+#[cfg(feature = "macros")]
 mod token;
+#[cfg(feature = "macros")]
+mod userdata;
@@ -0,0 +1,296 @@
+use proc_macro::TokenStream;
+use proc_macro2::{Ident, Span, TokenStream as TokenStream2};
+use quote::{format_ident, quote};
+use syn::spanned::Spanned;
+use syn::{
+    parse_macro_input, Data, DeriveInput, Error, Fields, FnArg, ImplItem, ImplItemFn, ItemImpl, Result,
+    ReturnType, Type, Visibility,
+};
+
+use crate::attr::{is_lua_attr, LuaAttributes, Position};
+
+pub fn userdata(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match expand_userdata(input) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+fn expand_userdata(input: DeriveInput) -> Result<TokenStream2> {
+    let DeriveInput {
+        ident,
+        generics,
+        data,
+        attrs,
+        ..
+    } = input;
+
+    LuaAttributes::parse(&attrs, Position::Container)?;
+
+    let fields = match data {
+        Data::Struct(data) => data.fields,
+        _ => {
+            return Err(Error::new(
+                ident.span(),
+                "`UserData` can only be derived for structs",
+            ))
+        }
+    };
+
+    let mut add_fields = Vec::new();
+    if let Fields::Named(fields) = fields {
+        for field in fields.named {
+            let opts = LuaAttributes::parse(&field.attrs, Position::Field)?;
+            if opts.skip || !matches!(field.vis, Visibility::Public(_)) {
+                continue;
+            }
+
+            let field_ident = field.ident.as_ref().unwrap();
+            let name = (opts.rename).unwrap_or_else(|| field_ident.to_string());
+            add_fields.push(quote! {
+                fields.add_field_method_get(#name, |_, this| {
+                    Ok(::std::clone::Clone::clone(&this.#field_ident))
+                });
+            });
+            if !opts.readonly {
+                add_fields.push(quote! {
+                    fields.add_field_method_set(#name, |_, this, val| {
+                        this.#field_ident = val;
+                        Ok(())
+                    });
+                });
+            }
+        }
+    }
+
+    let (impl_generics, ty_generics, _) = generics.split_for_impl();
+    let where_clause = match &generics.where_clause {
+        Some(where_clause) => quote! { #where_clause, Self: 'static },
+        None => quote! { where Self: 'static },
+    };
+
+    Ok(quote! {
+      impl #impl_generics ::mlua::UserData for #ident #ty_generics #where_clause {
+        #[allow(unused_variables)]
+        fn add_fields<F: ::mlua::UserDataFields<Self>>(fields: &mut F) {
+          #(#add_fields)*
+        }
+
+        fn add_methods<M: ::mlua::UserDataMethods<Self>>(methods: &mut M) {
+          #[allow(unused_imports)]
+          use ::mlua::__private::UserDataMethodsFallback as _;
+          Self::__mlua_add_methods(methods);
+        }
+      }
+    })
+}
+
+pub fn methods(attr: TokenStream, item: TokenStream) -> TokenStream {
+    if !attr.is_empty() {
+        let err = Error::new(Span::call_site(), "`methods` attribute does not accept arguments");
+        return err.to_compile_error().into();
+    }
+
+    let item = parse_macro_input!(item as ItemImpl);
+    match expand_methods(item) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+fn expand_methods(mut item: ItemImpl) -> Result<TokenStream2> {
+    if let Some((_, path, _)) = &item.trait_ {
+        return Err(Error::new(
+            path.span(),
+            "`methods` attribute can only be used on inherent impls",
+        ));
+    }
+
+    let mut registrations = Vec::new();
+    for impl_item in &mut item.items {
+        if let ImplItem::Fn(func) = impl_item {
+            let opts = LuaAttributes::parse(&func.attrs, Position::Method)?;
+            func.attrs.retain(|attr| !is_lua_attr(attr));
+            for arg in &mut func.sig.inputs {
+                if let FnArg::Typed(arg) = arg {
+                    arg.attrs.retain(|attr| !is_lua_attr(attr));
+                }
+            }
+
+            if opts.skip || !matches!(func.vis, Visibility::Public(_)) {
+                continue;
+            }
+            registrations.push(register_method(func, opts)?);
+        }
+    }
+
+    let (impl_generics, _, where_clause) = item.generics.split_for_impl();
+    let self_ty = &item.self_ty;
+
+    Ok(quote! {
+        #item
+
+        impl #impl_generics #self_ty #where_clause {
+            #[doc(hidden)]
+            #[allow(unused_variables, clippy::needless_question_mark)]
+            pub fn __mlua_add_methods<__M: ::mlua::UserDataMethods<Self>>(methods: &mut __M)
+            where
+                Self: 'static,
+            {
+                #(#registrations)*
+            }
+        }
+    })
+}
+
+/// How the method receives `self`.
+#[derive(Clone, Copy)]
+enum Receiver {
+    None,
+    Ref,
+    RefMut,
+}
+
+fn register_method(func: &ImplItemFn, opts: LuaAttributes) -> Result<TokenStream2> {
+    let sig = &func.sig;
+    let fn_ident = &sig.ident;
+    if !sig.generics.params.is_empty() {
+        return Err(Error::new(
+            sig.generics.span(),
+            "generic methods cannot be registered in Lua",
+        ));
+    }
+    let is_async = sig.asyncness.is_some();
+
+    let mut receiver = Receiver::None;
+    let mut inputs = sig.inputs.iter().peekable();
+    if let Some(FnArg::Receiver(recv)) = inputs.peek() {
+        receiver = match &*recv.ty {
+            Type::Reference(r) if r.mutability.is_some() => Receiver::RefMut,
+            Type::Reference(_) => Receiver::Ref,
+            _ => {
+                return Err(Error::new(
+                    recv.span(),
+                    "methods taking `self` by value are not supported",
+                ))
+            }
+        };
+        inputs.next();
+    }
+
+    // Optional leading `&Lua` (or `Lua`) argument receives the Lua state
+    let mut lua_arg = None;
+    if let Some(FnArg::Typed(arg)) = inputs.peek() {
+        lua_arg = match &*arg.ty {
+            Type::Reference(r) if is_lua_type(&r.elem) => {
+                Some(if is_async { quote!(&__lua) } else { quote!(__lua) })
+            }
+            ty if is_lua_type(ty) => Some(if is_async {
+                quote!(__lua)
+            } else {
+                quote!(__lua.clone())
+            }),
+            _ => None,
+        };
+        if lua_arg.is_some() {
+            inputs.next();
+        }
+    }
+
+    let mut arg_names = Vec::new();
+    let mut arg_types = Vec::new();
+    for (i, arg) in inputs.enumerate() {
+        match arg {
+            FnArg::Typed(arg) => {
+                arg_names.push(format_ident!("__arg{i}"));
+                arg_types.push(&*arg.ty);
+            }
+            FnArg::Receiver(recv) => return Err(Error::new(recv.span(), "unexpected receiver")),
+        }
+    }
+
+    let this = match receiver {
+        Receiver::None => None,
+        Receiver::Ref => Some(if is_async {
+            quote!(&*__this)
+        } else {
+            quote!(__this)
+        }),
+        Receiver::RefMut => Some(if is_async {
+            quote!(&mut *__this)
+        } else {
+            quote!(__this)
+        }),
+    };
+    let call_args = this
+        .into_iter()
+        .chain(lua_arg)
+        .chain(arg_names.iter().map(|n| quote!(#n)));
+    let mut call = quote! { Self::#fn_ident(#(#call_args),*) };
+    if is_async {
+        call = quote! { #call.await };
+    }
+    if returns_result(&sig.output) {
+        call = quote! { #call? };
+    }
+
+    let name = opts.rename.unwrap_or_else(|| fn_ident.to_string());
+    let is_meta = opts.meta.is_some();
+    let name = opts.meta.unwrap_or(name);
+
+    let kind = match receiver {
+        Receiver::None => "function",
+        Receiver::Ref => "method",
+        Receiver::RefMut => "method_mut",
+    };
+    let register = Ident::new(
+        &format!(
+            "add_{}{}{kind}",
+            if is_async { "async_" } else { "" },
+            if is_meta { "meta_" } else { "" },
+        ),
+        fn_ident.span(),
+    );
+
+    let args = quote! { (#(#arg_names,)*): (#(#arg_types,)*) };
+    let tokens = match (receiver, is_async) {
+        (Receiver::None, false) => quote! {
+            methods.#register(#name, |__lua, #args| Ok(#call));
+        },
+        (Receiver::None, true) => quote! {
+            methods.#register(#name, |__lua, #args| async move { Ok(#call) });
+        },
+        (_, false) => quote! {
+            methods.#register(#name, |__lua, __this, #args| Ok(#call));
+        },
+        (Receiver::Ref, true) => quote! {
+            methods.#register(#name, |__lua, __this, #args| async move { Ok(#call) });
+        },
+        (Receiver::RefMut, true) => quote! {
+            methods.#register(#name, |__lua, mut __this, #args| async move { Ok(#call) });
+        },
+    };
+    Ok(tokens)
+}
+
+fn is_lua_type(ty: &Type) -> bool {
+    match ty {
+        Type::Path(path) => {
+            path.qself.is_none() && path.path.segments.last().is_some_and(|s| s.ident == "Lua")
+        }
+        _ => false,
+    }
+}
+
+fn returns_result(output: &ReturnType) -> bool {
+    match output {
+        ReturnType::Type(_, ty) => match &**ty {
+            Type::Path(path) => {
+                (path.path.segments.last()).is_some_and(|s| s.ident == "Result" || s.ident == "LuaResult")
+            }
+            _ => false,
+        },
+        ReturnType::Default => false,
+    }
+}
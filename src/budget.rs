@@ -0,0 +1,394 @@
+use std::fmt;
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+
+use crate::error::{Error, Result};
+use crate::multi::MultiValue;
+use crate::thread::{Thread, ThreadStatus};
+use crate::traits::{FromLuaMulti, IntoLuaMulti};
+use crate::types::VmState;
+
+#[cfg(not(feature = "luau"))]
+use crate::{debug::DebugEvent, state::ThreadHook, types::HookCallback};
+
+#[cfg(feature = "luau")]
+use crate::types::InterruptCallback;
+
+#[cfg(any(feature = "async", feature = "luau"))]
+use crate::state::Lua;
+
+// How often (in instructions) to check the time limit
+#[cfg(not(feature = "luau"))]
+const TIME_CHECK_INTERVAL: u64 = 1000;
+
+/// Limits the amount of work Lua code can do before being preempted.
+///
+/// A budget can limit the number of executed VM instructions, the wall-clock time, or both.
+/// Execution is preempted as soon as any of the limits is exceeded.
+///
+/// For Luau, the number of interrupts (which are triggered on function calls and loop iterations)
+/// is counted instead of VM instructions.
+///
+/// See [`Function::call_with_budget`] for more details.
+///
+/// [`Function::call_with_budget`]: crate::Function::call_with_budget
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ExecutionBudget {
+    instructions: Option<u64>,
+    time: Option<Duration>,
+}
+
+impl ExecutionBudget {
+    /// Creates a budget limited to the given number of VM instructions.
+    pub const fn instructions(instructions: u64) -> Self {
+        ExecutionBudget {
+            instructions: Some(instructions),
+            time: None,
+        }
+    }
+
+    /// Creates a budget limited to the given amount of time.
+    ///
+    /// Time is checked periodically, so execution can slightly exceed the limit.
+    pub const fn time(time: Duration) -> Self {
+        ExecutionBudget {
+            instructions: None,
+            time: Some(time),
+        }
+    }
+
+    /// Sets the limit on the number of VM instructions.
+    pub const fn with_instructions(mut self, instructions: u64) -> Self {
+        self.instructions = Some(instructions);
+        self
+    }
+
+    /// Sets the limit on the execution time.
+    pub const fn with_time(mut self, time: Duration) -> Self {
+        self.time = Some(time);
+        self
+    }
+}
+
+/// Outcome of running Lua code with an [`ExecutionBudget`].
+pub enum Budget<R> {
+    /// Execution finished within the budget, with the given results.
+    Completed(R),
+    /// The budget was exhausted before execution finished.
+    ///
+    /// Execution can be continued later using [`Preempted::resume`].
+    Exhausted(Preempted<R>),
+}
+
+impl<R> Budget<R> {
+    /// Returns `true` if execution finished within the budget.
+    pub fn is_completed(&self) -> bool {
+        matches!(self, Budget::Completed(_))
+    }
+
+    /// Returns the results if execution finished within the budget.
+    pub fn completed(self) -> Option<R> {
+        match self {
+            Budget::Completed(r) => Some(r),
+            Budget::Exhausted(_) => None,
+        }
+    }
+}
+
+impl<R: fmt::Debug> fmt::Debug for Budget<R> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Budget::Completed(r) => f.debug_tuple("Completed").field(r).finish(),
+            Budget::Exhausted(preempted) => f.debug_tuple("Exhausted").field(preempted).finish(),
+        }
+    }
+}
+
+/// Handle to Lua code preempted after exhausting its [`ExecutionBudget`].
+///
+/// The code is suspended in its own Lua thread (coroutine) and can be resumed any time later.
+pub struct Preempted<R> {
+    thread: Thread,
+    _phantom: PhantomData<fn() -> R>,
+}
+
+impl<R> fmt::Debug for Preempted<R> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("Preempted").field(&self.thread).finish()
+    }
+}
+
+impl<R: FromLuaMulti> Preempted<R> {
+    /// Continues execution with a new budget.
+    pub fn resume(self, budget: ExecutionBudget) -> Result<Budget<R>> {
+        run(self.thread, (), budget)
+    }
+}
+
+impl<R> Preempted<R> {
+    /// Returns the Lua thread where the preempted code is suspended.
+    pub fn thread(&self) -> &Thread {
+        &self.thread
+    }
+
+    /// Consumes the handle, returning the Lua thread where the preempted code is suspended.
+    pub fn into_thread(self) -> Thread {
+        self.thread
+    }
+}
+
+/// Resumes a thread with the given budget.
+pub(crate) fn run<R: FromLuaMulti>(
+    thread: Thread,
+    args: impl IntoLuaMulti,
+    budget: ExecutionBudget,
+) -> Result<Budget<R>> {
+    let (values, exhausted) = resume(&thread, args, budget)?;
+    match thread.status() {
+        ThreadStatus::Resumable if exhausted => Ok(Budget::Exhausted(Preempted {
+            thread,
+            _phantom: PhantomData,
+        })),
+        ThreadStatus::Resumable => Err(Error::runtime(
+            "attempt to yield from a function running with execution budget",
+        )),
+        _ => R::from_lua_multi(values, &thread.0.lua.upgrade()).map(Budget::Completed),
+    }
+}
+
+/// Resumes a thread with the given budget.
+///
+/// Returns the values returned (or yielded) by the thread and whether it was preempted.
+pub(crate) fn resume(
+    thread: &Thread,
+    args: impl IntoLuaMulti,
+    budget: ExecutionBudget,
+) -> Result<(MultiValue, bool)> {
+    let meter = Meter::new(budget);
+    let values = meter.run(thread, || thread.resume::<MultiValue>(args))?;
+
+    // The thread could also yield on its own after the budget was exhausted
+    let exhausted = meter.is_exhausted() && values.as_ref().is_ok_and(|v| v.is_empty());
+    Ok((values?, exhausted))
+}
+
+// Tracks the amount of work done by a thread against the budget
+pub(crate) struct Meter {
+    budget: ExecutionBudget,
+    executed: AtomicU64,
+    deadline: Mutex<Option<Instant>>,
+    exhausted: AtomicBool,
+}
+
+impl Meter {
+    pub(crate) fn new(budget: ExecutionBudget) -> Arc<Self> {
+        Arc::new(Meter {
+            budget,
+            executed: AtomicU64::new(0),
+            deadline: Mutex::new(None),
+            exhausted: AtomicBool::new(false),
+        })
+    }
+
+    #[cfg(feature = "async")]
+    pub(crate) fn budget(&self) -> ExecutionBudget {
+        self.budget
+    }
+
+    /// Returns `true` if the budget was exhausted during the last run.
+    pub(crate) fn is_exhausted(&self) -> bool {
+        self.exhausted.load(Ordering::Relaxed)
+    }
+
+    // Accounts for the executed work, returns `true` if the budget is exhausted
+    fn charge(&self, work: u64) -> bool {
+        let executed = self.executed.fetch_add(work, Ordering::Relaxed) + work;
+        if self.budget.instructions.is_some_and(|n| executed >= n)
+            || self.deadline.lock().is_some_and(|d| Instant::now() >= d)
+        {
+            self.exhausted.store(true, Ordering::Relaxed);
+        }
+        self.is_exhausted()
+    }
+
+    /// Runs `f` (that resumes the thread) with the full budget.
+    ///
+    /// The thread yields once the budget is exhausted.
+    pub(crate) fn run<T>(self: &Arc<Self>, thread: &Thread, f: impl FnOnce() -> T) -> Result<T> {
+        self.executed.store(0, Ordering::Relaxed);
+        self.exhausted.store(false, Ordering::Relaxed);
+        *self.deadline.lock() = self.budget.time.map(|time| Instant::now() + time);
+
+        #[cfg(not(feature = "luau"))]
+        {
+            let step = match self.budget.instructions {
+                Some(n) if self.budget.time.is_none() => n,
+                Some(n) => n.min(TIME_CHECK_INTERVAL),
+                None => TIME_CHECK_INTERVAL,
+            };
+            let step = step.clamp(1, u32::MAX as u64);
+
+            // The hook of the thread (e.g. set by a debugger) keeps running and is restored
+            // afterwards. Its count trigger is emulated using the budget hook.
+            let previous = unsafe { thread.0.lua.lock().thread_hook(thread.state(), &thread.0) };
+            let mut triggers = previous.triggers();
+            let prev_count = (triggers.every_nth_instruction.replace(step as u32)).map(u64::from);
+            let prev_callback = previous.callback.clone().map(PrevHook);
+            let _guard = HookGuard {
+                thread,
+                previous: Some(previous),
+            };
+
+            let meter = self.clone();
+            let counted = AtomicU64::new(0);
+            thread.set_hook(triggers, move |lua, debug| {
+                if debug.event() != DebugEvent::Count {
+                    return match &prev_callback {
+                        Some(PrevHook(callback)) => callback(lua, debug),
+                        None => Ok(VmState::Continue),
+                    };
+                }
+                let mut state = VmState::Continue;
+                if let (Some(count), Some(PrevHook(callback))) = (prev_count, &prev_callback) {
+                    if counted.fetch_add(step, Ordering::Relaxed) + step >= count {
+                        counted.store(0, Ordering::Relaxed);
+                        state = callback(lua, debug)?;
+                    }
+                }
+                if meter.charge(step) {
+                    return Ok(VmState::Yield);
+                }
+                Ok(state)
+            })?;
+            Ok(f())
+        }
+
+        #[cfg(feature = "luau")]
+        {
+            // Interrupts are global, so the previous callback is restored afterwards
+            let lua = thread.0.lua.upgrade();
+            let previous = lua.interrupt_callback();
+            let thread_state = thread.state() as usize;
+            let meter = self.clone();
+            lua.set_interrupt(move |lua| {
+                // Only the budgeted thread can be preempted (not the coroutines it resumes)
+                if meter.charge(1) && lua.lock().state() as usize == thread_state {
+                    return Ok(VmState::Yield);
+                }
+                Ok(VmState::Continue)
+            });
+            let _guard = InterruptGuard { lua, previous };
+            Ok(f())
+        }
+    }
+}
+
+// The hook replaced by the budget hook
+#[cfg(not(feature = "luau"))]
+struct PrevHook(HookCallback);
+
+// Hook callbacks are called only while the Lua instance is locked
+#[cfg(all(feature = "send", not(feature = "luau")))]
+unsafe impl Send for PrevHook {}
+
+// Restores the previous hook of a thread, even if running the thread panics
+#[cfg(not(feature = "luau"))]
+struct HookGuard<'a> {
+    thread: &'a Thread,
+    previous: Option<ThreadHook>,
+}
+
+#[cfg(not(feature = "luau"))]
+impl Drop for HookGuard<'_> {
+    fn drop(&mut self) {
+        if let Some(previous) = self.previous.take() {
+            let lua = self.thread.0.lua.lock();
+            let _ = unsafe { lua.restore_thread_hook(self.thread.state(), &self.thread.0, previous) };
+        }
+    }
+}
+
+// Restores the previous interrupt callback, even if running the thread panics
+#[cfg(feature = "luau")]
+struct InterruptGuard {
+    lua: Lua,
+    previous: Option<InterruptCallback>,
+}
+
+#[cfg(feature = "luau")]
+impl Drop for InterruptGuard {
+    fn drop(&mut self) {
+        self.lua.restore_interrupt_callback(self.previous.take());
+    }
+}
+
+// Default budget for async threads
+#[cfg(feature = "async")]
+#[derive(Clone, Copy)]
+pub(crate) struct AsyncBudget(pub(crate) ExecutionBudget);
+
+#[cfg(feature = "async")]
+impl Lua {
+    /// Sets the execution budget for every poll of async Lua code.
+    ///
+    /// By default, Lua code running in an [`AsyncThread`] gives control back to the async
+    /// executor only when it awaits a Rust future, so a long running loop blocks other tasks.
+    /// With the budget set, the thread is preempted once the budget is exhausted: the poll
+    /// returns [`Poll::Pending`] and the task is rescheduled immediately to continue where it
+    /// stopped. No changes to Lua code are required.
+    ///
+    /// The budget applies to threads created afterwards (e.g. by [`Function::call_async`] or
+    /// [`Chunk::exec_async`]) and can be changed for a single thread using
+    /// [`AsyncThread::with_budget`]. Pass `None` to disable preemption (default).
+    ///
+    /// See [`Function::call_with_budget`] for the details how the budget is enforced.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::time::Duration;
+    /// # use mlua::{ExecutionBudget, Lua, Result};
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    /// lua.set_async_budget(Some(ExecutionBudget::time(Duration::from_millis(1))));
+    ///
+    /// // Other tasks keep running while the loop executes
+    /// let ticker = tokio::spawn(async {
+    ///     tokio::time::sleep(Duration::from_millis(10)).await;
+    /// });
+    /// lua.load("local n = 0 for i = 1, 10000000 do n = n + i end").exec_async().await?;
+    /// # ticker.await.unwrap();
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`AsyncThread`]: crate::AsyncThread
+    /// [`AsyncThread::with_budget`]: crate::AsyncThread::with_budget
+    /// [`Poll::Pending`]: std::task::Poll::Pending
+    /// [`Function::call_async`]: crate::Function::call_async
+    /// [`Function::call_with_budget`]: crate::Function::call_with_budget
+    /// [`Chunk::exec_async`]: crate::Chunk::exec_async
+    #[cfg_attr(
+        docsrs,
+        doc(cfg(all(
+            feature = "async",
+            any(feature = "lua54", feature = "lua53", feature = "luau")
+        )))
+    )]
+    pub fn set_async_budget(&self, budget: Option<ExecutionBudget>) {
+        let lua = self.lock();
+        match budget {
+            Some(budget) => {
+                lua.set_priv_app_data(AsyncBudget(budget));
+            }
+            None => {
+                lua.remove_priv_app_data::<AsyncBudget>();
+            }
+        }
+    }
+}
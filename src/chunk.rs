@@ -5,7 +5,9 @@ use std::io::Result as IoResult;
 use std::panic::Location;
 use std::path::{Path, PathBuf};
 use std::string::String as StdString;
+use std::sync::Arc;
 
+use crate::chunk_cache::{ChunkCache, ChunkCacheKey};
 use crate::error::{Error, Result};
 use crate::function::Function;
 use crate::state::{Lua, WeakLua};
@@ -13,6 +15,9 @@ use crate::table::Table;
 use crate::traits::{FromLuaMulti, IntoLua, IntoLuaMulti};
 use crate::value::Value;
 
+#[cfg(any(feature = "lua54", feature = "lua53", feature = "luau"))]
+use crate::budget::{Budget, ExecutionBudget};
+
 /// Trait for types [loadable by Lua] and convertible to a [`Chunk`]
 ///
 /// [loadable by Lua]: https://www.lua.org/manual/5.4/manual.html#3.3.2
@@ -388,6 +393,31 @@ impl Compiler {
         self
     }
 
+    // Returns a stable representation of the compiler options
+    pub(crate) fn fingerprint(&self) -> StdString {
+        let mut constants = (self.library_constants.iter().flatten())
+            .map(|(key, value)| format!("{key:?}={value:?}"))
+            .collect::<Vec<_>>();
+        constants.sort();
+        format!(
+            "{:?}",
+            (
+                self.optimization_level,
+                self.debug_level,
+                self.type_info_level,
+                self.coverage_level,
+                &self.vector_lib,
+                &self.vector_ctor,
+                &self.vector_type,
+                &self.mutable_globals,
+                &self.userdata_types,
+                &self.libraries_with_known_members,
+                constants,
+                &self.disabled_builtins,
+            )
+        )
+    }
+
     /// Compiles the `source` into bytecode.
     ///
     /// Returns [`Error::SyntaxError`] if the source code is invalid.
@@ -570,6 +600,15 @@ impl Chunk<'_> {
         self.call(())
     }
 
+    /// Execute this chunk of code with a limited execution budget.
+    ///
+    /// See [`Function::call_with_budget`] for more details.
+    #[cfg(any(feature = "lua54", feature = "lua53", feature = "luau"))]
+    #[cfg_attr(docsrs, doc(cfg(any(feature = "lua54", feature = "lua53", feature = "luau"))))]
+    pub fn exec_with_budget(self, budget: ExecutionBudget) -> Result<Budget<()>> {
+        self.into_function()?.call_with_budget((), budget)
+    }
+
     /// Asynchronously execute this chunk of code.
     ///
     /// See [`exec`] for more details.
@@ -646,6 +685,15 @@ impl Chunk<'_> {
     /// This simply compiles the chunk without actually executing it.
     #[cfg_attr(not(feature = "luau"), allow(unused_mut))]
     pub fn into_function(mut self) -> Result<Function> {
+        if let Some(cache) = self.chunk_cache() {
+            if let Some(key) = self.cache_key() {
+                let name = Self::convert_name(self.name.clone())?;
+                let env = self.env.clone()?;
+                let source = self.source.as_ref().map_err(Error::runtime)?;
+                return self.load_with_cache(&*cache, &key, &name, env.as_ref(), source);
+            }
+        }
+
         #[cfg(feature = "luau")]
         if self.compiler.is_some() {
             // We don't need to compile source if no compiler set
@@ -658,6 +706,73 @@ impl Chunk<'_> {
             .load_chunk(Some(&name), self.env?.as_ref(), self.mode, self.source?.as_ref())
     }
 
+    /// Returns the key of this chunk in a [`ChunkCache`].
+    ///
+    /// The key can be used to remove the compiled chunk from the cache. Returns `None` for binary
+    /// chunks or if the chunk source cannot be read.
+    pub fn cache_key(&self) -> Option<ChunkCacheKey> {
+        let source = self.source.as_ref().ok()?;
+        if self.detect_mode() == ChunkMode::Binary {
+            return None;
+        }
+        Some(ChunkCacheKey::new(&self.name, source, &self.compiler_options()))
+    }
+
+    fn chunk_cache(&self) -> Option<Arc<dyn ChunkCache>> {
+        let lua = self.lua.lock();
+        lua.priv_app_data_ref::<Arc<dyn ChunkCache>>()
+            .map(|cache| cache.clone())
+    }
+
+    // Representation of the compiler options to use in cache keys
+    fn compiler_options(&self) -> StdString {
+        #[cfg(feature = "luau")]
+        return (self.compiler.as_ref())
+            .map(|compiler| compiler.fingerprint())
+            .unwrap_or_else(|| Compiler::default().fingerprint());
+        #[cfg(not(feature = "luau"))]
+        StdString::new()
+    }
+
+    /// Loads the text source using bytecode from the cache.
+    ///
+    /// On a cache miss, compiles the source and stores the bytecode to the cache.
+    fn load_with_cache(
+        &self,
+        cache: &dyn ChunkCache,
+        key: &ChunkCacheKey,
+        name: &CString,
+        env: Option<&Table>,
+        source: &[u8],
+    ) -> Result<Function> {
+        let lua = self.lua.lock();
+        if let Ok(Some(bytecode)) = cache.get(key) {
+            // Outdated or damaged bytecode is replaced by the freshly compiled one
+            if let Ok(func) = lua.load_chunk(Some(name), env, Some(ChunkMode::Binary), &bytecode) {
+                return Ok(func);
+            }
+        }
+
+        #[cfg(feature = "luau")]
+        {
+            let compiler = self.compiler.clone().unwrap_or_default();
+            match compiler.compile(source) {
+                Ok(bytecode) => {
+                    let _ = cache.put(key, &bytecode);
+                    lua.load_chunk(Some(name), env, Some(ChunkMode::Binary), &bytecode)
+                }
+                // Let Lua report the syntax error
+                Err(_) => lua.load_chunk(Some(name), env, Some(ChunkMode::Text), source),
+            }
+        }
+        #[cfg(not(feature = "luau"))]
+        {
+            let func = lua.load_chunk(Some(name), env, Some(ChunkMode::Text), source)?;
+            let _ = cache.put(key, &func.dump(false));
+            Ok(func)
+        }
+    }
+
     /// Compiles the chunk and changes mode to binary.
     ///
     /// It does nothing if the chunk is already binary or invalid.
@@ -726,6 +841,12 @@ impl Chunk<'_> {
         let source = self.source.as_ref();
         let source = source.map_err(Error::runtime)?;
         let source = Self::expression_source(source);
+        if let Some(cache) = self.chunk_cache() {
+            let key = ChunkCacheKey::new(&self.name, &source, &self.compiler_options());
+            let name = Self::convert_name(self.name.clone())?;
+            let env = self.env.clone()?;
+            return self.load_with_cache(&*cache, &key, &name, env.as_ref(), &source);
+        }
         // We don't need to compile source if no compiler options set
         #[cfg(feature = "luau")]
         let source = self
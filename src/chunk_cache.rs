@@ -0,0 +1,326 @@
+use std::fs;
+use std::io::{self, Write as _};
+use std::path::{Path, PathBuf};
+use std::string::String as StdString;
+use std::sync::Arc;
+
+use crate::state::Lua;
+use crate::types::{MaybeSend, MaybeSync};
+
+const MAGIC: &[u8] = b"MLUAC\x01";
+const FILE_EXTENSION: &str = "luac";
+
+/// A cache of compiled Lua chunks.
+///
+/// When set using [`Lua::set_chunk_cache`], it is consulted every time a text chunk is loaded
+/// (using [`Chunk::into_function`] or any method calling it). On a cache miss the chunk is compiled
+/// and the resulting bytecode is stored to the cache, so the next time the chunk can be loaded
+/// without parsing and compiling it again.
+///
+/// Errors returned by the cache are ignored, in which case the chunk is loaded from source.
+///
+/// [`Chunk::into_function`]: crate::Chunk::into_function
+pub trait ChunkCache: MaybeSend + MaybeSync + 'static {
+    /// Returns the bytecode stored for the given key, if any.
+    fn get(&self, key: &ChunkCacheKey) -> io::Result<Option<Vec<u8>>>;
+
+    /// Stores the bytecode for the given key.
+    fn put(&self, key: &ChunkCacheKey, bytecode: &[u8]) -> io::Result<()>;
+
+    /// Removes the bytecode stored for the given key.
+    fn remove(&self, key: &ChunkCacheKey) -> io::Result<()>;
+
+    /// Removes all entries from the cache.
+    fn clear(&self) -> io::Result<()>;
+}
+
+impl std::fmt::Debug for dyn ChunkCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<dyn ChunkCache>")
+    }
+}
+
+impl<T: ChunkCache + ?Sized> ChunkCache for Arc<T> {
+    fn get(&self, key: &ChunkCacheKey) -> io::Result<Option<Vec<u8>>> {
+        (**self).get(key)
+    }
+
+    fn put(&self, key: &ChunkCacheKey, bytecode: &[u8]) -> io::Result<()> {
+        (**self).put(key, bytecode)
+    }
+
+    fn remove(&self, key: &ChunkCacheKey) -> io::Result<()> {
+        (**self).remove(key)
+    }
+
+    fn clear(&self) -> io::Result<()> {
+        (**self).clear()
+    }
+}
+
+/// A key identifying a compiled chunk in a [`ChunkCache`].
+///
+/// Consists of the chunk name, hash of the chunk source, Lua version and (for Luau) compiler
+/// options. Any change of these produces a different key.
+///
+/// Use [`Chunk::cache_key`] to get the key of a chunk.
+///
+/// [`Chunk::cache_key`]: crate::Chunk::cache_key
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ChunkCacheKey {
+    name: StdString,
+    source_hash: u128,
+    version: StdString,
+    options_hash: u64,
+}
+
+impl ChunkCacheKey {
+    pub(crate) fn new(name: &str, source: &[u8], options: &str) -> Self {
+        ChunkCacheKey {
+            name: name.to_string(),
+            source_hash: fnv1a_128(source),
+            version: lua_version(),
+            options_hash: fnv1a_64(options.as_bytes()),
+        }
+    }
+
+    /// Returns the chunk name.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the hash of the chunk source.
+    pub fn source_hash(&self) -> u128 {
+        self.source_hash
+    }
+
+    /// Returns the Lua version the chunk is compiled for.
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+
+    /// Returns the hash of the compiler options.
+    ///
+    /// Always the same for Lua versions other than Luau.
+    pub fn options_hash(&self) -> u64 {
+        self.options_hash
+    }
+
+    // Hash of all key components except the source, which identifies a chunk regardless of its
+    // contents
+    fn origin_hash(&self) -> u64 {
+        let mut data = Vec::with_capacity(self.name.len() + self.version.len() + 10);
+        data.extend_from_slice(self.name.as_bytes());
+        data.push(0);
+        data.extend_from_slice(self.version.as_bytes());
+        data.push(0);
+        data.extend_from_slice(&self.options_hash.to_le_bytes());
+        fnv1a_64(&data)
+    }
+
+    // Entries of the same chunk share the file name prefix
+    fn file_prefix(&self) -> StdString {
+        format!("{:016x}-", self.origin_hash())
+    }
+
+    fn file_name(&self) -> StdString {
+        format!("{}{:032x}.{FILE_EXTENSION}", self.file_prefix(), self.source_hash)
+    }
+}
+
+/// A [`ChunkCache`] that stores compiled chunks as files in a directory on disk.
+///
+/// Each entry is a separate file with the `.luac` extension, which name is derived from the cache
+/// key. Entries are never removed automatically: when a script changes, the entry for its
+/// previous source stays in the cache until it is removed using [`DirChunkCache::prune`],
+/// [`ChunkCache::remove`] or [`ChunkCache::clear`]. Damaged or truncated files are ignored.
+///
+/// Bytecode is loaded without any verification, so the directory must not be writable by
+/// untrusted parties. Running maliciously crafted bytecode can crash the interpreter.
+///
+/// # Examples
+///
+/// ```
+/// # use mlua::{DirChunkCache, Lua, Result};
+/// # fn main() -> Result<()> {
+/// let lua = Lua::new();
+/// let dir = std::env::temp_dir().join("mlua-chunk-cache-doc");
+/// lua.set_chunk_cache(DirChunkCache::new(&dir)?);
+///
+/// // The first time the chunk is compiled, the second time it is loaded from the cache
+/// for _ in 0..2 {
+///     let sum: i64 = lua.load("return 1 + 2").set_name("sum").eval()?;
+///     assert_eq!(sum, 3);
+/// }
+/// # std::fs::remove_dir_all(dir).ok();
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct DirChunkCache {
+    dir: PathBuf,
+}
+
+impl DirChunkCache {
+    /// Creates a new cache in the given directory, creating it if it does not exist.
+    pub fn new(dir: impl AsRef<Path>) -> io::Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+        Ok(DirChunkCache { dir })
+    }
+
+    /// Returns the cache directory.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn entry_path(&self, key: &ChunkCacheKey) -> PathBuf {
+        self.dir.join(key.file_name())
+    }
+
+    /// Removes outdated entries of the chunk identified by `key`.
+    ///
+    /// Entries with the same chunk name, Lua version and compiler options as `key`, but a
+    /// different source are removed. This is useful to keep the cache small when scripts with
+    /// stable names (see [`Chunk::set_name`]) are changed. Chunks without an explicitly set name
+    /// are named after the place in the Rust code where they are loaded, so unrelated chunks
+    /// loaded in the same place (e.g. by a shared helper) are considered the same chunk.
+    ///
+    /// Scans the whole cache directory.
+    ///
+    /// [`Chunk::set_name`]: crate::Chunk::set_name
+    pub fn prune(&self, key: &ChunkCacheKey) -> io::Result<()> {
+        let (prefix, current) = (key.file_prefix(), key.file_name());
+        self.remove_entries(|name| name.starts_with(&prefix) && name != current)
+    }
+
+    fn remove_entries(&self, mut filter: impl FnMut(&str) -> bool) -> io::Result<()> {
+        for entry in fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            let file_name = entry.file_name();
+            let Some(file_name) = file_name.to_str() else {
+                continue;
+            };
+            if filter(file_name) {
+                match fs::remove_file(entry.path()) {
+                    Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+                    _ => {}
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ChunkCache for DirChunkCache {
+    fn get(&self, key: &ChunkCacheKey) -> io::Result<Option<Vec<u8>>> {
+        let data = match fs::read(self.entry_path(key)) {
+            Ok(data) => data,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err),
+        };
+        Ok(decode_entry(&data).map(|bytecode| bytecode.to_vec()))
+    }
+
+    fn put(&self, key: &ChunkCacheKey, bytecode: &[u8]) -> io::Result<()> {
+        let path = self.entry_path(key);
+
+        // Write to a temporary file first to not expose partially written entries
+        let tmp_path = path.with_extension(format!("tmp{}", std::process::id()));
+        let mut file = fs::File::create(&tmp_path)?;
+        let result = (file.write_all(&encode_entry(bytecode)))
+            .and_then(|_| file.sync_all())
+            .and_then(|_| fs::rename(&tmp_path, &path));
+        if result.is_err() {
+            let _ = fs::remove_file(&tmp_path);
+        }
+        result
+    }
+
+    fn remove(&self, key: &ChunkCacheKey) -> io::Result<()> {
+        match fs::remove_file(self.entry_path(key)) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+            _ => Ok(()),
+        }
+    }
+
+    fn clear(&self) -> io::Result<()> {
+        self.remove_entries(|name| name.ends_with(&format!(".{FILE_EXTENSION}")))
+    }
+}
+
+impl Lua {
+    /// Sets a cache of compiled chunks.
+    ///
+    /// See [`ChunkCache`] for details.
+    pub fn set_chunk_cache(&self, cache: impl ChunkCache) {
+        let cache: Arc<dyn ChunkCache> = Arc::new(cache);
+        self.lock().set_priv_app_data(cache);
+    }
+
+    /// Removes the cache of compiled chunks previously set by [`Lua::set_chunk_cache`].
+    pub fn remove_chunk_cache(&self) {
+        self.lock().remove_priv_app_data::<Arc<dyn ChunkCache>>();
+    }
+}
+
+// Version of Lua (and mlua) the bytecode is produced by
+fn lua_version() -> StdString {
+    let version = if cfg!(feature = "lua54") {
+        "Lua 5.4"
+    } else if cfg!(feature = "lua53") {
+        "Lua 5.3"
+    } else if cfg!(feature = "lua52") {
+        "Lua 5.2"
+    } else if cfg!(feature = "luajit52") {
+        "LuaJIT (5.2)"
+    } else if cfg!(feature = "luajit") {
+        "LuaJIT"
+    } else if cfg!(feature = "luau") {
+        "Luau"
+    } else {
+        "Lua 5.1"
+    };
+    #[cfg(feature = "luau")]
+    if let Some(luau_version) = ffi::luau_version() {
+        return format!("{version} {luau_version} (mlua {})", env!("CARGO_PKG_VERSION"));
+    }
+    format!("{version} (mlua {})", env!("CARGO_PKG_VERSION"))
+}
+
+// Stable (across runs and platforms) hash functions used to produce cache keys
+pub(crate) fn fnv1a_64(data: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for &b in data {
+        hash ^= b as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+fn fnv1a_128(data: &[u8]) -> u128 {
+    let mut hash: u128 = 0x6c62272e07bb014262b821756295c58d;
+    for &b in data {
+        hash ^= b as u128;
+        hash = hash.wrapping_mul(0x0000000001000000000000000000013b);
+    }
+    hash
+}
+
+// Entry layout: magic, bytecode length (u64 LE), bytecode hash (u64 LE), bytecode
+fn encode_entry(bytecode: &[u8]) -> Vec<u8> {
+    let mut data = Vec::with_capacity(MAGIC.len() + 16 + bytecode.len());
+    data.extend_from_slice(MAGIC);
+    data.extend_from_slice(&(bytecode.len() as u64).to_le_bytes());
+    data.extend_from_slice(&fnv1a_64(bytecode).to_le_bytes());
+    data.extend_from_slice(bytecode);
+    data
+}
+
+fn decode_entry(data: &[u8]) -> Option<&[u8]> {
+    let data = data.strip_prefix(MAGIC)?;
+    let len = u64::from_le_bytes(data.get(..8)?.try_into().ok()?);
+    let hash = u64::from_le_bytes(data.get(8..16)?.try_into().ok()?);
+    let bytecode = data.get(16..)?;
+    (bytecode.len() as u64 == len && fnv1a_64(bytecode) == hash).then_some(bytecode)
+}
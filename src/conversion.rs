@@ -17,6 +17,7 @@ use crate::string::{BorrowedBytes, BorrowedStr, String};
 use crate::table::Table;
 use crate::thread::Thread;
 use crate::traits::{FromLua, IntoLua, ShortTypeName as _};
+use crate::typedef::TypeInfo;
 use crate::types::{Either, LightUserData, MaybeSend, RegistryKey};
 use crate::userdata::{AnyUserData, UserData};
 use crate::value::{Nil, Value};
@@ -48,6 +49,11 @@ impl FromLua for Value {
 }
 
 impl IntoLua for String {
+    #[inline]
+    fn type_info() -> TypeInfo {
+        TypeInfo::String
+    }
+
     #[inline]
     fn into_lua(self, _: &Lua) -> Result<Value> {
         Ok(Value::String(self))
@@ -55,6 +61,11 @@ impl IntoLua for String {
 }
 
 impl IntoLua for &String {
+    #[inline]
+    fn type_info() -> TypeInfo {
+        TypeInfo::String
+    }
+
     #[inline]
     fn into_lua(self, _: &Lua) -> Result<Value> {
         Ok(Value::String(self.clone()))
@@ -68,6 +79,11 @@ impl IntoLua for &String {
 }
 
 impl FromLua for String {
+    #[inline]
+    fn type_info() -> TypeInfo {
+        TypeInfo::String
+    }
+
     #[inline]
     fn from_lua(value: Value, lua: &Lua) -> Result<String> {
         let ty = value.type_name();
@@ -92,6 +108,11 @@ impl FromLua for String {
 }
 
 impl IntoLua for BorrowedStr<'_> {
+    #[inline]
+    fn type_info() -> TypeInfo {
+        TypeInfo::String
+    }
+
     #[inline]
     fn into_lua(self, _: &Lua) -> Result<Value> {
         Ok(Value::String(self.borrow.into_owned()))
@@ -105,6 +126,11 @@ impl IntoLua for BorrowedStr<'_> {
 }
 
 impl IntoLua for &BorrowedStr<'_> {
+    #[inline]
+    fn type_info() -> TypeInfo {
+        TypeInfo::String
+    }
+
     #[inline]
     fn into_lua(self, _: &Lua) -> Result<Value> {
         Ok(Value::String(self.borrow.clone().into_owned()))
@@ -118,6 +144,11 @@ impl IntoLua for &BorrowedStr<'_> {
 }
 
 impl FromLua for BorrowedStr<'_> {
+    #[inline]
+    fn type_info() -> TypeInfo {
+        TypeInfo::String
+    }
+
     fn from_lua(value: Value, lua: &Lua) -> Result<Self> {
         let s = String::from_lua(value, lua)?;
         let BorrowedStr { buf, _lua, .. } = BorrowedStr::try_from(&s)?;
@@ -136,6 +167,11 @@ impl FromLua for BorrowedStr<'_> {
 }
 
 impl IntoLua for BorrowedBytes<'_> {
+    #[inline]
+    fn type_info() -> TypeInfo {
+        TypeInfo::String
+    }
+
     #[inline]
     fn into_lua(self, _: &Lua) -> Result<Value> {
         Ok(Value::String(self.borrow.into_owned()))
@@ -149,6 +185,11 @@ impl IntoLua for BorrowedBytes<'_> {
 }
 
 impl IntoLua for &BorrowedBytes<'_> {
+    #[inline]
+    fn type_info() -> TypeInfo {
+        TypeInfo::String
+    }
+
     #[inline]
     fn into_lua(self, _: &Lua) -> Result<Value> {
         Ok(Value::String(self.borrow.clone().into_owned()))
@@ -162,6 +203,11 @@ impl IntoLua for &BorrowedBytes<'_> {
 }
 
 impl FromLua for BorrowedBytes<'_> {
+    #[inline]
+    fn type_info() -> TypeInfo {
+        TypeInfo::String
+    }
+
     fn from_lua(value: Value, lua: &Lua) -> Result<Self> {
         let s = String::from_lua(value, lua)?;
         let BorrowedBytes { buf, _lua, .. } = BorrowedBytes::from(&s);
@@ -180,6 +226,11 @@ impl FromLua for BorrowedBytes<'_> {
 }
 
 impl IntoLua for Table {
+    #[inline]
+    fn type_info() -> TypeInfo {
+        TypeInfo::Table
+    }
+
     #[inline]
     fn into_lua(self, _: &Lua) -> Result<Value> {
         Ok(Value::Table(self))
@@ -187,6 +238,11 @@ impl IntoLua for Table {
 }
 
 impl IntoLua for &Table {
+    #[inline]
+    fn type_info() -> TypeInfo {
+        TypeInfo::Table
+    }
+
     #[inline]
     fn into_lua(self, _: &Lua) -> Result<Value> {
         Ok(Value::Table(self.clone()))
@@ -200,6 +256,11 @@ impl IntoLua for &Table {
 }
 
 impl FromLua for Table {
+    #[inline]
+    fn type_info() -> TypeInfo {
+        TypeInfo::Table
+    }
+
     #[inline]
     fn from_lua(value: Value, _: &Lua) -> Result<Table> {
         match value {
@@ -214,6 +275,11 @@ impl FromLua for Table {
 }
 
 impl IntoLua for Function {
+    #[inline]
+    fn type_info() -> TypeInfo {
+        TypeInfo::Function
+    }
+
     #[inline]
     fn into_lua(self, _: &Lua) -> Result<Value> {
         Ok(Value::Function(self))
@@ -221,6 +287,11 @@ impl IntoLua for Function {
 }
 
 impl IntoLua for &Function {
+    #[inline]
+    fn type_info() -> TypeInfo {
+        TypeInfo::Function
+    }
+
     #[inline]
     fn into_lua(self, _: &Lua) -> Result<Value> {
         Ok(Value::Function(self.clone()))
@@ -234,6 +305,11 @@ impl IntoLua for &Function {
 }
 
 impl FromLua for Function {
+    #[inline]
+    fn type_info() -> TypeInfo {
+        TypeInfo::Function
+    }
+
     #[inline]
     fn from_lua(value: Value, _: &Lua) -> Result<Function> {
         match value {
@@ -248,6 +324,11 @@ impl FromLua for Function {
 }
 
 impl IntoLua for Thread {
+    #[inline]
+    fn type_info() -> TypeInfo {
+        TypeInfo::Thread
+    }
+
     #[inline]
     fn into_lua(self, _: &Lua) -> Result<Value> {
         Ok(Value::Thread(self))
@@ -255,6 +336,11 @@ impl IntoLua for Thread {
 }
 
 impl IntoLua for &Thread {
+    #[inline]
+    fn type_info() -> TypeInfo {
+        TypeInfo::Thread
+    }
+
     #[inline]
     fn into_lua(self, _: &Lua) -> Result<Value> {
         Ok(Value::Thread(self.clone()))
@@ -268,6 +354,11 @@ impl IntoLua for &Thread {
 }
 
 impl FromLua for Thread {
+    #[inline]
+    fn type_info() -> TypeInfo {
+        TypeInfo::Thread
+    }
+
     #[inline]
     fn from_lua(value: Value, _: &Lua) -> Result<Thread> {
         match value {
@@ -282,6 +373,11 @@ impl FromLua for Thread {
 }
 
 impl IntoLua for AnyUserData {
+    #[inline]
+    fn type_info() -> TypeInfo {
+        TypeInfo::UserData
+    }
+
     #[inline]
     fn into_lua(self, _: &Lua) -> Result<Value> {
         Ok(Value::UserData(self))
@@ -289,6 +385,11 @@ impl IntoLua for AnyUserData {
 }
 
 impl IntoLua for &AnyUserData {
+    #[inline]
+    fn type_info() -> TypeInfo {
+        TypeInfo::UserData
+    }
+
     #[inline]
     fn into_lua(self, _: &Lua) -> Result<Value> {
         Ok(Value::UserData(self.clone()))
@@ -302,6 +403,11 @@ impl IntoLua for &AnyUserData {
 }
 
 impl FromLua for AnyUserData {
+    #[inline]
+    fn type_info() -> TypeInfo {
+        TypeInfo::UserData
+    }
+
     #[inline]
     fn from_lua(value: Value, _: &Lua) -> Result<AnyUserData> {
         match value {
@@ -316,6 +422,11 @@ impl FromLua for AnyUserData {
 }
 
 impl<T: UserData + MaybeSend + 'static> IntoLua for T {
+    #[inline]
+    fn type_info() -> TypeInfo {
+        TypeInfo::Named(T::type_name())
+    }
+
     #[inline]
     fn into_lua(self, lua: &Lua) -> Result<Value> {
         Ok(Value::UserData(lua.create_userdata(self)?))
@@ -388,6 +499,11 @@ impl FromLua for RegistryKey {
 }
 
 impl IntoLua for bool {
+    #[inline]
+    fn type_info() -> TypeInfo {
+        TypeInfo::Boolean
+    }
+
     #[inline]
     fn into_lua(self, _: &Lua) -> Result<Value> {
         Ok(Value::Boolean(self))
@@ -401,6 +517,11 @@ impl IntoLua for bool {
 }
 
 impl FromLua for bool {
+    #[inline]
+    fn type_info() -> TypeInfo {
+        TypeInfo::Boolean
+    }
+
     #[inline]
     fn from_lua(v: Value, _: &Lua) -> Result<Self> {
         match v {
@@ -417,6 +538,11 @@ impl FromLua for bool {
 }
 
 impl IntoLua for LightUserData {
+    #[inline]
+    fn type_info() -> TypeInfo {
+        TypeInfo::LightUserData
+    }
+
     #[inline]
     fn into_lua(self, _: &Lua) -> Result<Value> {
         Ok(Value::LightUserData(self))
@@ -424,6 +550,11 @@ impl IntoLua for LightUserData {
 }
 
 impl FromLua for LightUserData {
+    #[inline]
+    fn type_info() -> TypeInfo {
+        TypeInfo::LightUserData
+    }
+
     #[inline]
     fn from_lua(value: Value, _: &Lua) -> Result<Self> {
         match value {
@@ -439,6 +570,11 @@ impl FromLua for LightUserData {
 
 #[cfg(feature = "luau")]
 impl IntoLua for crate::Vector {
+    #[inline]
+    fn type_info() -> TypeInfo {
+        TypeInfo::Vector
+    }
+
     #[inline]
     fn into_lua(self, _: &Lua) -> Result<Value> {
         Ok(Value::Vector(self))
@@ -447,6 +583,11 @@ impl IntoLua for crate::Vector {
 
 #[cfg(feature = "luau")]
 impl FromLua for crate::Vector {
+    #[inline]
+    fn type_info() -> TypeInfo {
+        TypeInfo::Vector
+    }
+
     #[inline]
     fn from_lua(value: Value, _: &Lua) -> Result<Self> {
         match value {
@@ -462,6 +603,11 @@ impl FromLua for crate::Vector {
 
 #[cfg(feature = "luau")]
 impl IntoLua for crate::Buffer {
+    #[inline]
+    fn type_info() -> TypeInfo {
+        TypeInfo::Buffer
+    }
+
     #[inline]
     fn into_lua(self, _: &Lua) -> Result<Value> {
         Ok(Value::Buffer(self))
@@ -470,6 +616,11 @@ impl IntoLua for crate::Buffer {
 
 #[cfg(feature = "luau")]
 impl IntoLua for &crate::Buffer {
+    #[inline]
+    fn type_info() -> TypeInfo {
+        TypeInfo::Buffer
+    }
+
     #[inline]
     fn into_lua(self, _: &Lua) -> Result<Value> {
         Ok(Value::Buffer(self.clone()))
@@ -484,6 +635,11 @@ impl IntoLua for &crate::Buffer {
 
 #[cfg(feature = "luau")]
 impl FromLua for crate::Buffer {
+    #[inline]
+    fn type_info() -> TypeInfo {
+        TypeInfo::Buffer
+    }
+
     #[inline]
     fn from_lua(value: Value, _: &Lua) -> Result<Self> {
         match value {
@@ -498,6 +654,11 @@ impl FromLua for crate::Buffer {
 }
 
 impl IntoLua for StdString {
+    #[inline]
+    fn type_info() -> TypeInfo {
+        TypeInfo::String
+    }
+
     #[inline]
     fn into_lua(self, lua: &Lua) -> Result<Value> {
         Ok(Value::String(lua.create_string(self)?))
@@ -510,6 +671,11 @@ impl IntoLua for StdString {
 }
 
 impl FromLua for StdString {
+    #[inline]
+    fn type_info() -> TypeInfo {
+        TypeInfo::String
+    }
+
     #[inline]
     fn from_lua(value: Value, lua: &Lua) -> Result<Self> {
         let ty = value.type_name();
@@ -546,6 +712,11 @@ impl FromLua for StdString {
 }
 
 impl IntoLua for &str {
+    #[inline]
+    fn type_info() -> TypeInfo {
+        TypeInfo::String
+    }
+
     #[inline]
     fn into_lua(self, lua: &Lua) -> Result<Value> {
         Ok(Value::String(lua.create_string(self)?))
@@ -558,6 +729,11 @@ impl IntoLua for &str {
 }
 
 impl IntoLua for Cow<'_, str> {
+    #[inline]
+    fn type_info() -> TypeInfo {
+        TypeInfo::String
+    }
+
     #[inline]
     fn into_lua(self, lua: &Lua) -> Result<Value> {
         Ok(Value::String(lua.create_string(self.as_bytes())?))
@@ -565,6 +741,11 @@ impl IntoLua for Cow<'_, str> {
 }
 
 impl IntoLua for Box<str> {
+    #[inline]
+    fn type_info() -> TypeInfo {
+        TypeInfo::String
+    }
+
     #[inline]
     fn into_lua(self, lua: &Lua) -> Result<Value> {
         Ok(Value::String(lua.create_string(&*self)?))
@@ -572,6 +753,11 @@ impl IntoLua for Box<str> {
 }
 
 impl FromLua for Box<str> {
+    #[inline]
+    fn type_info() -> TypeInfo {
+        TypeInfo::String
+    }
+
     #[inline]
     fn from_lua(value: Value, lua: &Lua) -> Result<Self> {
         let ty = value.type_name();
@@ -589,6 +775,11 @@ impl FromLua for Box<str> {
 }
 
 impl IntoLua for CString {
+    #[inline]
+    fn type_info() -> TypeInfo {
+        TypeInfo::String
+    }
+
     #[inline]
     fn into_lua(self, lua: &Lua) -> Result<Value> {
         Ok(Value::String(lua.create_string(self.as_bytes())?))
@@ -596,6 +787,11 @@ impl IntoLua for CString {
 }
 
 impl FromLua for CString {
+    #[inline]
+    fn type_info() -> TypeInfo {
+        TypeInfo::String
+    }
+
     #[inline]
     fn from_lua(value: Value, lua: &Lua) -> Result<Self> {
         let ty = value.type_name();
@@ -619,6 +815,11 @@ impl FromLua for CString {
 }
 
 impl IntoLua for &CStr {
+    #[inline]
+    fn type_info() -> TypeInfo {
+        TypeInfo::String
+    }
+
     #[inline]
     fn into_lua(self, lua: &Lua) -> Result<Value> {
         Ok(Value::String(lua.create_string(self.to_bytes())?))
@@ -626,6 +827,11 @@ impl IntoLua for &CStr {
 }
 
 impl IntoLua for Cow<'_, CStr> {
+    #[inline]
+    fn type_info() -> TypeInfo {
+        TypeInfo::String
+    }
+
     #[inline]
     fn into_lua(self, lua: &Lua) -> Result<Value> {
         Ok(Value::String(lua.create_string(self.to_bytes())?))
@@ -633,6 +839,11 @@ impl IntoLua for Cow<'_, CStr> {
 }
 
 impl IntoLua for BString {
+    #[inline]
+    fn type_info() -> TypeInfo {
+        TypeInfo::String
+    }
+
     #[inline]
     fn into_lua(self, lua: &Lua) -> Result<Value> {
         Ok(Value::String(lua.create_string(self)?))
@@ -640,6 +851,11 @@ impl IntoLua for BString {
 }
 
 impl FromLua for BString {
+    #[inline]
+    fn type_info() -> TypeInfo {
+        TypeInfo::String
+    }
+
     fn from_lua(value: Value, lua: &Lua) -> Result<Self> {
         let ty = value.type_name();
         match value {
@@ -682,6 +898,11 @@ impl FromLua for BString {
 }
 
 impl IntoLua for &BStr {
+    #[inline]
+    fn type_info() -> TypeInfo {
+        TypeInfo::String
+    }
+
     #[inline]
     fn into_lua(self, lua: &Lua) -> Result<Value> {
         Ok(Value::String(lua.create_string(self)?))
@@ -689,6 +910,11 @@ impl IntoLua for &BStr {
 }
 
 impl IntoLua for OsString {
+    #[inline]
+    fn type_info() -> TypeInfo {
+        TypeInfo::String
+    }
+
     #[inline]
     fn into_lua(self, lua: &Lua) -> Result<Value> {
         self.as_os_str().into_lua(lua)
@@ -696,6 +922,11 @@ impl IntoLua for OsString {
 }
 
 impl FromLua for OsString {
+    #[inline]
+    fn type_info() -> TypeInfo {
+        TypeInfo::String
+    }
+
     #[inline]
     fn from_lua(value: Value, lua: &Lua) -> Result<Self> {
         let ty = value.type_name();
@@ -711,6 +942,11 @@ impl FromLua for OsString {
 }
 
 impl IntoLua for &OsStr {
+    #[inline]
+    fn type_info() -> TypeInfo {
+        TypeInfo::String
+    }
+
     #[inline]
     fn into_lua(self, lua: &Lua) -> Result<Value> {
         let s = <[u8]>::from_os_str(self).ok_or_else(|| Error::ToLuaConversionError {
@@ -723,6 +959,11 @@ impl IntoLua for &OsStr {
 }
 
 impl IntoLua for PathBuf {
+    #[inline]
+    fn type_info() -> TypeInfo {
+        TypeInfo::String
+    }
+
     #[inline]
     fn into_lua(self, lua: &Lua) -> Result<Value> {
         self.as_os_str().into_lua(lua)
@@ -730,6 +971,11 @@ impl IntoLua for PathBuf {
 }
 
 impl FromLua for PathBuf {
+    #[inline]
+    fn type_info() -> TypeInfo {
+        TypeInfo::String
+    }
+
     #[inline]
     fn from_lua(value: Value, lua: &Lua) -> Result<Self> {
         OsString::from_lua(value, lua).map(PathBuf::from)
@@ -737,6 +983,11 @@ impl FromLua for PathBuf {
 }
 
 impl IntoLua for &Path {
+    #[inline]
+    fn type_info() -> TypeInfo {
+        TypeInfo::String
+    }
+
     #[inline]
     fn into_lua(self, lua: &Lua) -> Result<Value> {
         self.as_os_str().into_lua(lua)
@@ -744,6 +995,11 @@ impl IntoLua for &Path {
 }
 
 impl IntoLua for char {
+    #[inline]
+    fn type_info() -> TypeInfo {
+        TypeInfo::String
+    }
+
     #[inline]
     fn into_lua(self, lua: &Lua) -> Result<Value> {
         let mut char_bytes = [0; 4];
@@ -753,6 +1009,11 @@ impl IntoLua for char {
 }
 
 impl FromLua for char {
+    #[inline]
+    fn type_info() -> TypeInfo {
+        TypeInfo::String
+    }
+
     fn from_lua(value: Value, _lua: &Lua) -> Result<Self> {
         let ty = value.type_name();
         match value {
@@ -806,6 +1067,11 @@ where
 macro_rules! lua_convert_int {
     ($x:ty) => {
         impl IntoLua for $x {
+            #[inline]
+            fn type_info() -> TypeInfo {
+                TypeInfo::Integer
+            }
+
             #[inline]
             fn into_lua(self, _: &Lua) -> Result<Value> {
                 Ok(cast(self)
@@ -824,6 +1090,11 @@ macro_rules! lua_convert_int {
         }
 
         impl FromLua for $x {
+            #[inline]
+            fn type_info() -> TypeInfo {
+                TypeInfo::Integer
+            }
+
             #[inline]
             fn from_lua(value: Value, lua: &Lua) -> Result<Self> {
                 let ty = value.type_name();
@@ -891,6 +1162,11 @@ lua_convert_int!(usize);
 macro_rules! lua_convert_float {
     ($x:ty) => {
         impl IntoLua for $x {
+            #[inline]
+            fn type_info() -> TypeInfo {
+                TypeInfo::Number
+            }
+
             #[inline]
             fn into_lua(self, _: &Lua) -> Result<Value> {
                 Ok(Value::Number(self as _))
@@ -898,6 +1174,11 @@ macro_rules! lua_convert_float {
         }
 
         impl FromLua for $x {
+            #[inline]
+            fn type_info() -> TypeInfo {
+                TypeInfo::Number
+            }
+
             #[inline]
             fn from_lua(value: Value, lua: &Lua) -> Result<Self> {
                 let ty = value.type_name();
@@ -930,6 +1211,11 @@ impl<T> IntoLua for &[T]
 where
     T: IntoLua + Clone,
 {
+    #[inline]
+    fn type_info() -> TypeInfo {
+        TypeInfo::Array(Box::new(T::type_info()))
+    }
+
     #[inline]
     fn into_lua(self, lua: &Lua) -> Result<Value> {
         Ok(Value::Table(lua.create_sequence_from(self.iter().cloned())?))
@@ -940,6 +1226,11 @@ impl<T, const N: usize> IntoLua for [T; N]
 where
     T: IntoLua,
 {
+    #[inline]
+    fn type_info() -> TypeInfo {
+        TypeInfo::Array(Box::new(T::type_info()))
+    }
+
     #[inline]
     fn into_lua(self, lua: &Lua) -> Result<Value> {
         Ok(Value::Table(lua.create_sequence_from(self)?))
@@ -950,6 +1241,11 @@ impl<T, const N: usize> FromLua for [T; N]
 where
     T: FromLua,
 {
+    #[inline]
+    fn type_info() -> TypeInfo {
+        TypeInfo::Array(Box::new(T::type_info()))
+    }
+
     #[inline]
     fn from_lua(value: Value, _lua: &Lua) -> Result<Self> {
         match value {
@@ -984,6 +1280,11 @@ where
 }
 
 impl<T: IntoLua> IntoLua for Box<[T]> {
+    #[inline]
+    fn type_info() -> TypeInfo {
+        TypeInfo::Array(Box::new(T::type_info()))
+    }
+
     #[inline]
     fn into_lua(self, lua: &Lua) -> Result<Value> {
         Ok(Value::Table(lua.create_sequence_from(self.into_vec())?))
@@ -991,6 +1292,11 @@ impl<T: IntoLua> IntoLua for Box<[T]> {
 }
 
 impl<T: FromLua> FromLua for Box<[T]> {
+    #[inline]
+    fn type_info() -> TypeInfo {
+        TypeInfo::Array(Box::new(T::type_info()))
+    }
+
     #[inline]
     fn from_lua(value: Value, lua: &Lua) -> Result<Self> {
         Ok(Vec::<T>::from_lua(value, lua)?.into_boxed_slice())
@@ -998,6 +1304,11 @@ impl<T: FromLua> FromLua for Box<[T]> {
 }
 
 impl<T: IntoLua> IntoLua for Vec<T> {
+    #[inline]
+    fn type_info() -> TypeInfo {
+        TypeInfo::Array(Box::new(T::type_info()))
+    }
+
     #[inline]
     fn into_lua(self, lua: &Lua) -> Result<Value> {
         Ok(Value::Table(lua.create_sequence_from(self)?))
@@ -1005,6 +1316,11 @@ impl<T: IntoLua> IntoLua for Vec<T> {
 }
 
 impl<T: FromLua> FromLua for Vec<T> {
+    #[inline]
+    fn type_info() -> TypeInfo {
+        TypeInfo::Array(Box::new(T::type_info()))
+    }
+
     #[inline]
     fn from_lua(value: Value, _lua: &Lua) -> Result<Self> {
         match value {
@@ -1019,6 +1335,11 @@ impl<T: FromLua> FromLua for Vec<T> {
 }
 
 impl<K: Eq + Hash + IntoLua, V: IntoLua, S: BuildHasher> IntoLua for HashMap<K, V, S> {
+    #[inline]
+    fn type_info() -> TypeInfo {
+        TypeInfo::Map(Box::new(K::type_info()), Box::new(V::type_info()))
+    }
+
     #[inline]
     fn into_lua(self, lua: &Lua) -> Result<Value> {
         Ok(Value::Table(lua.create_table_from(self)?))
@@ -1026,6 +1347,11 @@ impl<K: Eq + Hash + IntoLua, V: IntoLua, S: BuildHasher> IntoLua for HashMap<K,
 }
 
 impl<K: Eq + Hash + FromLua, V: FromLua, S: BuildHasher + Default> FromLua for HashMap<K, V, S> {
+    #[inline]
+    fn type_info() -> TypeInfo {
+        TypeInfo::Map(Box::new(K::type_info()), Box::new(V::type_info()))
+    }
+
     #[inline]
     fn from_lua(value: Value, _: &Lua) -> Result<Self> {
         if let Value::Table(table) = value {
@@ -1041,6 +1367,11 @@ impl<K: Eq + Hash + FromLua, V: FromLua, S: BuildHasher + Default> FromLua for H
 }
 
 impl<K: Ord + IntoLua, V: IntoLua> IntoLua for BTreeMap<K, V> {
+    #[inline]
+    fn type_info() -> TypeInfo {
+        TypeInfo::Map(Box::new(K::type_info()), Box::new(V::type_info()))
+    }
+
     #[inline]
     fn into_lua(self, lua: &Lua) -> Result<Value> {
         Ok(Value::Table(lua.create_table_from(self)?))
@@ -1048,6 +1379,11 @@ impl<K: Ord + IntoLua, V: IntoLua> IntoLua for BTreeMap<K, V> {
 }
 
 impl<K: Ord + FromLua, V: FromLua> FromLua for BTreeMap<K, V> {
+    #[inline]
+    fn type_info() -> TypeInfo {
+        TypeInfo::Map(Box::new(K::type_info()), Box::new(V::type_info()))
+    }
+
     #[inline]
     fn from_lua(value: Value, _: &Lua) -> Result<Self> {
         if let Value::Table(table) = value {
@@ -1063,6 +1399,11 @@ impl<K: Ord + FromLua, V: FromLua> FromLua for BTreeMap<K, V> {
 }
 
 impl<T: Eq + Hash + IntoLua, S: BuildHasher> IntoLua for HashSet<T, S> {
+    #[inline]
+    fn type_info() -> TypeInfo {
+        TypeInfo::Map(Box::new(T::type_info()), Box::new(TypeInfo::Boolean))
+    }
+
     #[inline]
     fn into_lua(self, lua: &Lua) -> Result<Value> {
         Ok(Value::Table(
@@ -1072,6 +1413,11 @@ impl<T: Eq + Hash + IntoLua, S: BuildHasher> IntoLua for HashSet<T, S> {
 }
 
 impl<T: Eq + Hash + FromLua, S: BuildHasher + Default> FromLua for HashSet<T, S> {
+    #[inline]
+    fn type_info() -> TypeInfo {
+        TypeInfo::Map(Box::new(T::type_info()), Box::new(TypeInfo::Boolean))
+    }
+
     #[inline]
     fn from_lua(value: Value, _: &Lua) -> Result<Self> {
         match value {
@@ -1087,6 +1433,11 @@ impl<T: Eq + Hash + FromLua, S: BuildHasher + Default> FromLua for HashSet<T, S>
 }
 
 impl<T: Ord + IntoLua> IntoLua for BTreeSet<T> {
+    #[inline]
+    fn type_info() -> TypeInfo {
+        TypeInfo::Map(Box::new(T::type_info()), Box::new(TypeInfo::Boolean))
+    }
+
     #[inline]
     fn into_lua(self, lua: &Lua) -> Result<Value> {
         Ok(Value::Table(
@@ -1096,6 +1447,11 @@ impl<T: Ord + IntoLua> IntoLua for BTreeSet<T> {
 }
 
 impl<T: Ord + FromLua> FromLua for BTreeSet<T> {
+    #[inline]
+    fn type_info() -> TypeInfo {
+        TypeInfo::Map(Box::new(T::type_info()), Box::new(TypeInfo::Boolean))
+    }
+
     #[inline]
     fn from_lua(value: Value, _: &Lua) -> Result<Self> {
         match value {
@@ -1111,6 +1467,11 @@ impl<T: Ord + FromLua> FromLua for BTreeSet<T> {
 }
 
 impl<T: IntoLua> IntoLua for Option<T> {
+    #[inline]
+    fn type_info() -> TypeInfo {
+        TypeInfo::Optional(Box::new(T::type_info()))
+    }
+
     #[inline]
     fn into_lua(self, lua: &Lua) -> Result<Value> {
         match self {
@@ -1130,6 +1491,11 @@ impl<T: IntoLua> IntoLua for Option<T> {
 }
 
 impl<T: FromLua> FromLua for Option<T> {
+    #[inline]
+    fn type_info() -> TypeInfo {
+        TypeInfo::Optional(Box::new(T::type_info()))
+    }
+
     #[inline]
     fn from_lua(value: Value, lua: &Lua) -> Result<Self> {
         match value {
@@ -1148,6 +1514,11 @@ impl<T: FromLua> FromLua for Option<T> {
 }
 
 impl<L: IntoLua, R: IntoLua> IntoLua for Either<L, R> {
+    #[inline]
+    fn type_info() -> TypeInfo {
+        TypeInfo::Union(vec![L::type_info(), R::type_info()])
+    }
+
     #[inline]
     fn into_lua(self, lua: &Lua) -> Result<Value> {
         match self {
@@ -1166,6 +1537,11 @@ impl<L: IntoLua, R: IntoLua> IntoLua for Either<L, R> {
 }
 
 impl<L: FromLua, R: FromLua> FromLua for Either<L, R> {
+    #[inline]
+    fn type_info() -> TypeInfo {
+        TypeInfo::Union(vec![L::type_info(), R::type_info()])
+    }
+
     #[inline]
     fn from_lua(value: Value, lua: &Lua) -> Result<Self> {
         let value_type_name = value.type_name();
@@ -1,11 +1,44 @@
+//! Debugging facilities.
+//!
+//! This module provides the [`Debug`] structure to inspect running Lua code (from hooks or
+//! [`Lua::inspect_stack`], a sampling [`Profiler`], memory profiles (see [`Lua::memory_profile`]),
+//! [`StackTrace`]s of errors and, for non-Luau builds, a [`Debugger`] with
+//! breakpoints and stepping.
+//!
+//! [`Lua::inspect_stack`]: crate::Lua::inspect_stack
+//! [`Lua::memory_profile`]: crate::Lua::memory_profile
+
 use std::borrow::Cow;
-use std::os::raw::c_int;
+use std::os::raw::{c_char, c_int};
+use std::string::String as StdString;
+use std::vec;
 
 use ffi::{lua_Debug, lua_State};
 
+use crate::error::{Error, Result};
 use crate::function::Function;
 use crate::state::RawLua;
-use crate::util::{assert_stack, linenumber_to_usize, ptr_to_lossy_str, ptr_to_str, StackGuard};
+use crate::traits::IntoLua;
+use crate::util::{assert_stack, check_stack, linenumber_to_usize, ptr_to_lossy_str, ptr_to_str, StackGuard};
+use crate::value::Value;
+
+#[cfg(not(feature = "luau"))]
+pub use debugger::{Breakpoint, Debugger, PauseReason, StepAction};
+
+#[cfg(all(feature = "dap", not(feature = "luau")))]
+#[cfg_attr(docsrs, doc(cfg(feature = "dap")))]
+pub use dap::DapServer;
+
+pub use crate::memory::{AllocationDiff, AllocationSite, MemoryProfile};
+pub use profiler::{Frame, FunctionSamples, LineSamples, Profile, Profiler};
+pub use trace::{FrameKind, StackFrame, StackTrace};
+
+#[cfg(all(feature = "dap", not(feature = "luau")))]
+mod dap;
+#[cfg(not(feature = "luau"))]
+mod debugger;
+mod profiler;
+mod trace;
 
 /// Contains information about currently executing Lua code.
 ///
@@ -60,17 +93,7 @@ impl<'a> Debug<'a> {
             let _sg = StackGuard::new(self.state);
             assert_stack(self.state, 1);
 
-            #[cfg(not(feature = "luau"))]
-            mlua_assert!(
-                ffi::lua_getinfo(self.state, cstr!("f"), self.ar) != 0,
-                "lua_getinfo failed with `f`"
-            );
-            #[cfg(feature = "luau")]
-            mlua_assert!(
-                ffi::lua_getinfo(self.state, self.level, cstr!("f"), self.ar) != 0,
-                "lua_getinfo failed with `f`"
-            );
-
+            self.push_function();
             ffi::lua_xmove(self.state, self.lua.ref_thread(), 1);
             Function(self.lua.pop_ref_thread())
         }
@@ -205,6 +228,153 @@ impl<'a> Debug<'a> {
             stack
         }
     }
+
+    /// Returns an iterator over the local variables active at the given level.
+    ///
+    /// Each item is a `(name, value)` pair, in the order of declaration. Internal (temporary)
+    /// variables, whose names start with `(`, are skipped.
+    pub fn locals(&self) -> vec::IntoIter<(StdString, Value)> {
+        let mut locals = Vec::new();
+        unsafe {
+            let _sg = StackGuard::new(self.state);
+            assert_stack(self.state, 2);
+
+            for n in 1.. {
+                let name = self.get_local(n);
+                if name.is_null() {
+                    break;
+                }
+                let value = self.lua.pop_value();
+                match ptr_to_lossy_str(name) {
+                    Some(name) if !name.starts_with('(') => locals.push((name.into_owned(), value)),
+                    _ => {}
+                }
+            }
+        }
+        locals.into_iter()
+    }
+
+    /// Sets the value of a local variable active at the given level.
+    ///
+    /// If several active locals share the same name, the innermost one (the one that is currently
+    /// visible to the running code) is changed.
+    /// Returns an error if there is no local variable with such name.
+    pub fn set_local(&self, name: &str, value: impl IntoLua) -> Result<()> {
+        unsafe {
+            let _sg = StackGuard::new(self.state);
+            check_stack(self.state, 3)?;
+
+            let mut index = None;
+            for n in 1.. {
+                let local_name = self.get_local(n);
+                if local_name.is_null() {
+                    break;
+                }
+                ffi::lua_pop(self.state, 1);
+                if ptr_to_str(local_name) == Some(name) {
+                    index = Some(n);
+                }
+            }
+            let n = index.ok_or_else(|| Error::runtime(format!("local variable '{name}' not found")))?;
+
+            self.lua.push(value)?;
+            #[cfg(not(feature = "luau"))]
+            ffi::lua_setlocal(self.state, self.ar, n);
+            #[cfg(feature = "luau")]
+            ffi::lua_setlocal(self.state, self.level, n);
+            Ok(())
+        }
+    }
+
+    /// Returns an iterator over the upvalues of the function running at the given level.
+    ///
+    /// Each item is a `(name, value)` pair. Upvalues of C functions have empty names.
+    pub fn upvalues(&self) -> vec::IntoIter<(StdString, Value)> {
+        let mut upvalues = Vec::new();
+        unsafe {
+            let _sg = StackGuard::new(self.state);
+            assert_stack(self.state, 3);
+
+            let func_index = self.push_function();
+            for n in 1.. {
+                let name = ffi::lua_getupvalue(self.state, func_index, n);
+                if name.is_null() {
+                    break;
+                }
+                let value = self.lua.pop_value();
+                let name = ptr_to_lossy_str(name).map(Cow::into_owned).unwrap_or_default();
+                upvalues.push((name, value));
+            }
+        }
+        upvalues.into_iter()
+    }
+
+    /// Sets the value of an upvalue of the function running at the given level.
+    ///
+    /// Returns an error if the function has no upvalue with such name.
+    pub fn set_upvalue(&self, name: &str, value: impl IntoLua) -> Result<()> {
+        unsafe {
+            let _sg = StackGuard::new(self.state);
+            check_stack(self.state, 4)?;
+
+            let func_index = self.push_function();
+            for n in 1.. {
+                let upvalue_name = ffi::lua_getupvalue(self.state, func_index, n);
+                if upvalue_name.is_null() {
+                    break;
+                }
+                ffi::lua_pop(self.state, 1);
+                if ptr_to_str(upvalue_name) == Some(name) {
+                    self.lua.push(value)?;
+                    ffi::lua_setupvalue(self.state, func_index, n);
+                    return Ok(());
+                }
+            }
+            Err(Error::runtime(format!("upvalue '{name}' not found")))
+        }
+    }
+
+    // Pushes the local variable `n` onto the stack and returns its name (or null if there is no
+    // such variable).
+    unsafe fn get_local(&self, n: c_int) -> *const c_char {
+        #[cfg(not(feature = "luau"))]
+        return ffi::lua_getlocal(self.state, self.ar, n);
+        #[cfg(feature = "luau")]
+        return ffi::lua_getlocal(self.state, self.level, n);
+    }
+
+    // Pushes the running function onto the stack and returns its (absolute) index.
+    unsafe fn push_function(&self) -> c_int {
+        #[cfg(not(feature = "luau"))]
+        mlua_assert!(
+            ffi::lua_getinfo(self.state, cstr!("f"), self.ar) != 0,
+            "lua_getinfo failed with `f`"
+        );
+        #[cfg(feature = "luau")]
+        mlua_assert!(
+            ffi::lua_getinfo(self.state, self.level, cstr!("f"), self.ar) != 0,
+            "lua_getinfo failed with `f`"
+        );
+        ffi::lua_gettop(self.state)
+    }
+
+    // Returns the number of active stack levels (including the current one).
+    #[cfg(not(feature = "luau"))]
+    pub(crate) fn stack_depth(&self) -> usize {
+        unsafe {
+            let mut ar = std::mem::zeroed::<lua_Debug>();
+            let mut depth = 0;
+            while ffi::lua_getstack(self.state, depth, &mut ar) != 0 {
+                depth += 1;
+            }
+            depth as usize
+        }
+    }
+}
+
+// Strips the `@`/`=` prefix that Lua uses to mark file names and literal chunk names.
+fn chunk_name(source: &str) -> &str {
+    source.strip_prefix(['@', '=']).unwrap_or(source)
 }
 
 /// Represents a specific event that triggered the hook.
@@ -362,6 +532,20 @@ impl HookTriggers {
             None => 0,
         }
     }
+
+    // Reconstructs the triggers from the `lua_sethook` parameters.
+    #[cfg(any(feature = "lua54", feature = "lua53"))]
+    pub(crate) const fn from_mask(mask: c_int, count: c_int) -> Self {
+        HookTriggers {
+            on_calls: mask & ffi::LUA_MASKCALL != 0,
+            on_returns: mask & ffi::LUA_MASKRET != 0,
+            every_line: mask & ffi::LUA_MASKLINE != 0,
+            every_nth_instruction: match mask & ffi::LUA_MASKCOUNT != 0 {
+                true => Some(count as u32),
+                false => None,
+            },
+        }
+    }
 }
 
 #[cfg(not(feature = "luau"))]
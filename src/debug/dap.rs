@@ -0,0 +1,626 @@
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{Shutdown, TcpListener, TcpStream, ToSocketAddrs};
+use std::path::PathBuf;
+use std::string::String as StdString;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+use parking_lot::Mutex;
+
+use self::json::Json;
+use super::{chunk_name, Breakpoint, Debugger, PauseReason, StepAction};
+use crate::error::{Error, Result};
+use crate::state::Lua;
+use crate::table::Table;
+use crate::value::Value;
+
+mod json;
+
+// The only thread reported to the client
+const THREAD_ID: i64 = 1;
+
+/// A [Debug Adapter Protocol] server for a Lua instance.
+///
+/// The server drives a [`Debugger`] using requests from a DAP client (such as VS Code), which
+/// is connected over stdio or TCP. Incoming messages are read on a background thread, while all
+/// work that requires access to Lua (stack traces, variables, evaluation) is done on the thread
+/// running the Lua code, when it is paused.
+///
+/// Chunks are reported to the client as source files. By default, the chunk name (see
+/// [`Chunk::set_name`]) is used as the path, which is correct for chunks loaded from files;
+/// other chunks can be mapped to source paths using [`DapServer::map_source`].
+///
+/// # Example
+///
+/// ```no_run
+/// # use mlua::{DapServer, Lua, Result};
+/// # fn main() -> Result<()> {
+/// let lua = Lua::new();
+/// let server = DapServer::listen("127.0.0.1:4711")?;
+/// server.map_source("main", "/path/to/scripts/main.lua");
+/// server.attach(&lua)?;
+///
+/// lua.load(std::fs::read_to_string("/path/to/scripts/main.lua")?)
+///     .set_name("main")
+///     .exec()?;
+///
+/// server.detach(&lua);
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [Debug Adapter Protocol]: https://microsoft.github.io/debug-adapter-protocol/
+/// [`Chunk::set_name`]: crate::Chunk::set_name
+pub struct DapServer {
+    debugger: Debugger,
+    shared: Arc<Shared>,
+    requests: Arc<Mutex<Receiver<Request>>>,
+    reader: Option<JoinHandle<()>>,
+    shutdown: Option<Box<dyn FnOnce() + Send>>,
+}
+
+struct Shared {
+    output: Mutex<Output>,
+    sources: Mutex<SourceMap>,
+    paused: AtomicBool,
+    lines_start_at1: AtomicBool,
+    closed: AtomicBool,
+}
+
+struct Output {
+    writer: Box<dyn Write + Send>,
+    seq: i64,
+}
+
+#[derive(Default)]
+struct SourceMap {
+    paths: HashMap<StdString, PathBuf>,
+    chunks: HashMap<PathBuf, StdString>,
+}
+
+struct Request {
+    seq: i64,
+    command: StdString,
+    arguments: Json,
+}
+
+// Objects that can be expanded by the client while paused
+enum VarRef {
+    Locals(usize),
+    Upvalues(usize),
+    Table(Table),
+}
+
+impl DapServer {
+    /// Creates a new server that communicates with a client using the given reader and writer.
+    ///
+    /// The reader is used on a background thread, which cannot be interrupted while it's blocked
+    /// reading. When the server is dropped, the thread stops at the next message or at the end of
+    /// input, and is joined only if it has already stopped.
+    pub fn new<R, W>(reader: R, writer: W) -> Self
+    where
+        R: Read + Send + 'static,
+        W: Write + Send + 'static,
+    {
+        Self::with_shutdown(reader, writer, None)
+    }
+
+    fn with_shutdown<R, W>(reader: R, writer: W, shutdown: Option<Box<dyn FnOnce() + Send>>) -> Self
+    where
+        R: Read + Send + 'static,
+        W: Write + Send + 'static,
+    {
+        let debugger = Debugger::new();
+        let shared = Arc::new(Shared {
+            output: Mutex::new(Output {
+                writer: Box::new(writer),
+                seq: 0,
+            }),
+            sources: Mutex::new(SourceMap::default()),
+            paused: AtomicBool::new(false),
+            lines_start_at1: AtomicBool::new(true),
+            closed: AtomicBool::new(false),
+        });
+        let (sender, receiver) = mpsc::channel();
+
+        let (reader_debugger, reader_shared) = (debugger.clone(), shared.clone());
+        let reader = thread::spawn(move || {
+            let mut reader = BufReader::new(reader);
+            while let Ok(Some(request)) = read_request(&mut reader) {
+                if reader_shared.closed.load(Ordering::Acquire) {
+                    break;
+                }
+                reader_shared.handle_request(&reader_debugger, request, &sender);
+            }
+            // The client has gone, let the Lua code run freely
+            reader_debugger.clear_breakpoints();
+        });
+
+        DapServer {
+            debugger,
+            shared,
+            requests: Arc::new(Mutex::new(receiver)),
+            reader: Some(reader),
+            shutdown,
+        }
+    }
+
+    /// Creates a new server that communicates with a client over the process stdin and stdout.
+    ///
+    /// Nothing else must be written to stdout while the server is in use. Stdin cannot be closed,
+    /// so the background thread reading it outlives the server until the next message or the end
+    /// of input.
+    pub fn stdio() -> Self {
+        Self::new(io::stdin(), io::stdout())
+    }
+
+    /// Creates a new server that communicates with a client over the given TCP stream.
+    ///
+    /// The connection is shut down when the server is dropped.
+    pub fn tcp(stream: TcpStream) -> io::Result<Self> {
+        let control = stream.try_clone()?;
+        let shutdown = Box::new(move || drop(control.shutdown(Shutdown::Both)));
+        Ok(Self::with_shutdown(stream.try_clone()?, stream, Some(shutdown)))
+    }
+
+    /// Listens on the given address and waits for a client to connect.
+    pub fn listen(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        let (stream, _) = TcpListener::bind(addr)?.accept()?;
+        Self::tcp(stream)
+    }
+
+    /// Maps a chunk name to the path of its source file.
+    pub fn map_source(&self, chunk: impl AsRef<str>, path: impl Into<PathBuf>) {
+        let chunk = chunk_name(chunk.as_ref()).to_string();
+        let path = path.into();
+        let mut sources = self.shared.sources.lock();
+        sources.chunks.insert(path.clone(), chunk.clone());
+        sources.paths.insert(chunk, path);
+    }
+
+    /// Returns the underlying [`Debugger`].
+    pub fn debugger(&self) -> &Debugger {
+        &self.debugger
+    }
+
+    /// Attaches the server to a Lua instance.
+    ///
+    /// See [`Debugger::attach`] for details.
+    pub fn attach(&self, lua: &Lua) -> Result<()> {
+        let shared = self.shared.clone();
+        let requests = self.requests.clone();
+        (self.debugger).attach(lua, move |lua, _debug, reason| {
+            shared.paused(lua, reason, &requests.lock())
+        })
+    }
+
+    /// Detaches the server from a Lua instance and notifies the client that debugging has ended.
+    pub fn detach(&self, lua: &Lua) {
+        self.debugger.detach(lua);
+        self.shared.send_event("terminated", Json::object([]));
+    }
+}
+
+impl Drop for DapServer {
+    fn drop(&mut self) {
+        self.shared.closed.store(true, Ordering::Release);
+        let shutdown = self.shutdown.take();
+        let can_join = shutdown.is_some();
+        if let Some(shutdown) = shutdown {
+            shutdown();
+        }
+        if let Some(reader) = self.reader.take() {
+            // Without shutting down the transport, the thread can be blocked reading forever
+            if can_join || reader.is_finished() {
+                let _ = reader.join();
+            }
+        }
+    }
+}
+
+impl Shared {
+    // Handles a request on the reader thread, forwarding it to the Lua thread if needed
+    fn handle_request(&self, debugger: &Debugger, request: Request, sender: &Sender<Request>) {
+        let args = &request.arguments;
+        match request.command.as_str() {
+            "initialize" => {
+                let lines_start_at1 = args["linesStartAt1"].as_bool().unwrap_or(true);
+                self.lines_start_at1.store(lines_start_at1, Ordering::Relaxed);
+                let capabilities = Json::object([
+                    ("supportsConfigurationDoneRequest", true.into()),
+                    ("supportsFunctionBreakpoints", true.into()),
+                    ("supportsSetVariable", true.into()),
+                    ("supportsEvaluateForHovers", true.into()),
+                ]);
+                self.send_response(&request, capabilities);
+                self.send_event("initialized", Json::object([]));
+            }
+            "launch" | "attach" | "configurationDone" => self.send_response(&request, Json::object([])),
+            "setBreakpoints" => {
+                let source = &args["source"];
+                let Some(path) = source["path"].as_str().or(source["name"].as_str()) else {
+                    return self.send_error(&request, "missing source path");
+                };
+                let chunk = self.sources.lock().chunk(path);
+                debugger.clear_chunk_breakpoints(&chunk);
+                let mut breakpoints = Vec::new();
+                for bp in args["breakpoints"].as_array().into_iter().flatten() {
+                    let Some(line) = bp["line"].as_u64() else { continue };
+                    debugger.add_breakpoint(Breakpoint::line(&chunk, self.client_line_to_lua(line)));
+                    breakpoints.push(Json::object([("verified", true.into()), ("line", line.into())]));
+                }
+                self.send_response(&request, Json::object([("breakpoints", breakpoints.into())]));
+            }
+            "setFunctionBreakpoints" => {
+                for bp in debugger.breakpoints() {
+                    if let Breakpoint::Function(_) = bp {
+                        debugger.remove_breakpoint(&bp);
+                    }
+                }
+                let mut breakpoints = Vec::new();
+                for bp in args["breakpoints"].as_array().into_iter().flatten() {
+                    let Some(name) = bp["name"].as_str() else { continue };
+                    debugger.add_breakpoint(Breakpoint::function(name));
+                    breakpoints.push(Json::object([("verified", true.into())]));
+                }
+                self.send_response(&request, Json::object([("breakpoints", breakpoints.into())]));
+            }
+            "threads" => {
+                let thread = Json::object([("id", THREAD_ID.into()), ("name", "main".into())]);
+                let threads = Json::object([("threads", vec![thread].into())]);
+                self.send_response(&request, threads);
+            }
+            "pause" => {
+                debugger.pause();
+                self.send_response(&request, Json::object([]));
+            }
+            "disconnect" => {
+                debugger.clear_breakpoints();
+                self.send_response(&request, Json::object([]));
+                if self.paused.load(Ordering::Acquire) {
+                    let _ = sender.send(request);
+                }
+            }
+            "stackTrace" | "scopes" | "variables" | "setVariable" | "evaluate" | "continue" | "next"
+            | "stepIn" | "stepOut" => {
+                if !self.paused.load(Ordering::Acquire) {
+                    return self.send_error(&request, "execution is not paused");
+                }
+                let _ = sender.send(request);
+            }
+            command => self.send_error(&request, &format!("unsupported request '{command}'")),
+        }
+    }
+
+    // Serves requests on the Lua thread until execution is resumed
+    fn paused(&self, lua: &Lua, reason: PauseReason, requests: &Receiver<Request>) -> Result<StepAction> {
+        let reason = match reason {
+            PauseReason::Breakpoint(Breakpoint::Line { .. }) => "breakpoint",
+            PauseReason::Breakpoint(Breakpoint::Function(_)) => "function breakpoint",
+            PauseReason::Step => "step",
+            _ => "pause",
+        };
+        self.paused.store(true, Ordering::Release);
+        let event = Json::object([
+            ("reason", reason.into()),
+            ("threadId", THREAD_ID.into()),
+            ("allThreadsStopped", true.into()),
+        ]);
+        self.send_event("stopped", event);
+
+        let mut refs = Vec::new();
+        let action = loop {
+            // The client has gone
+            let Ok(request) = requests.recv() else {
+                break StepAction::Continue;
+            };
+            let action = match request.command.as_str() {
+                "continue" => StepAction::Continue,
+                "next" => StepAction::StepOver,
+                "stepIn" => StepAction::StepIn,
+                "stepOut" => StepAction::StepOut,
+                "disconnect" => break StepAction::Continue,
+                _ => {
+                    match self.inspect(lua, &request, &mut refs) {
+                        Ok(body) => self.send_response(&request, body),
+                        Err(err) => self.send_error(&request, &err.to_string()),
+                    }
+                    continue;
+                }
+            };
+            self.paused.store(false, Ordering::Release);
+            self.send_response(&request, Json::object([("allThreadsContinued", true.into())]));
+            break action;
+        };
+        self.paused.store(false, Ordering::Release);
+        Ok(action)
+    }
+
+    fn inspect(&self, lua: &Lua, request: &Request, refs: &mut Vec<VarRef>) -> Result<Json> {
+        let args = &request.arguments;
+        match request.command.as_str() {
+            "stackTrace" => {
+                let start = args["startFrame"].as_u64().unwrap_or(0) as usize;
+                let levels = args["levels"].as_u64().filter(|&n| n > 0).unwrap_or(u64::MAX) as usize;
+                let mut frames = Vec::new();
+                for level in start.. {
+                    let frame = lua.inspect_stack(level, |debug| {
+                        let source = debug.source();
+                        let name = match debug.names().name {
+                            Some(name) => name.into_owned(),
+                            None if source.what == "main" => "main chunk".to_string(),
+                            None => "?".to_string(),
+                        };
+                        let line = debug.current_line().map(|line| self.lua_line_to_client(line));
+                        let mut frame = Json::object([
+                            ("id", (level + 1).into()),
+                            ("name", name.into()),
+                            ("line", line.unwrap_or(0).into()),
+                            ("column", 0usize.into()),
+                        ]);
+                        if let Some(source) = source.source.filter(|_| source.what != "C") {
+                            let path = self.sources.lock().path(chunk_name(&source));
+                            let name = path.file_name().unwrap_or(path.as_os_str());
+                            frame["source"] = Json::object([
+                                ("name", name.to_string_lossy().into_owned().into()),
+                                ("path", path.to_string_lossy().into_owned().into()),
+                            ]);
+                        }
+                        frame
+                    });
+                    match frame {
+                        Some(frame) => frames.push(frame),
+                        None => break,
+                    }
+                }
+                let total = frames.len() + start;
+                frames.truncate(levels);
+                Ok(Json::object([
+                    ("stackFrames", frames.into()),
+                    ("totalFrames", total.into()),
+                ]))
+            }
+            "scopes" => {
+                let level = frame_level(args);
+                refs.push(VarRef::Locals(level));
+                let locals_ref = refs.len();
+                refs.push(VarRef::Upvalues(level));
+                let upvalues_ref = refs.len();
+                let scope = |name: &str, reference: usize| {
+                    Json::object([
+                        ("name", name.into()),
+                        ("variablesReference", reference.into()),
+                        ("expensive", false.into()),
+                    ])
+                };
+                let scopes = vec![scope("Locals", locals_ref), scope("Upvalues", upvalues_ref)];
+                Ok(Json::object([("scopes", scopes.into())]))
+            }
+            "variables" => {
+                let vars = match lookup_ref(args, refs)? {
+                    VarRef::Locals(level) => lua.inspect_stack(*level, |d| d.locals().collect()),
+                    VarRef::Upvalues(level) => lua.inspect_stack(*level, |d| d.upvalues().collect()),
+                    VarRef::Table(table) => Some(table_fields(table)?),
+                };
+                let variables = (vars.unwrap_or_default().into_iter())
+                    .map(|(name, value)| {
+                        let mut var = variable(&value, refs);
+                        var["name"] = name.into();
+                        var
+                    })
+                    .collect::<Vec<_>>();
+                Ok(Json::object([("variables", variables.into())]))
+            }
+            "setVariable" => {
+                let name = args["name"].as_str().unwrap_or_default();
+                let expr = args["value"].as_str().unwrap_or_default();
+                let value = lua
+                    .load(format!("return {expr}"))
+                    .set_name("=setVariable")
+                    .eval::<Value>()?;
+                match lookup_ref(args, refs)? {
+                    VarRef::Locals(level) => {
+                        let result = lua.inspect_stack(*level, |d| d.set_local(name, &value));
+                        result.unwrap_or_else(|| Err(stack_level_error()))?
+                    }
+                    VarRef::Upvalues(level) => {
+                        let result = lua.inspect_stack(*level, |d| d.set_upvalue(name, &value));
+                        result.unwrap_or_else(|| Err(stack_level_error()))?
+                    }
+                    VarRef::Table(table) => match table_index(name) {
+                        Some(index) => table.set(index, &value)?,
+                        None => table.set(name, &value)?,
+                    },
+                }
+                Ok(variable(&value, refs))
+            }
+            "evaluate" => {
+                let expr = args["expression"].as_str().unwrap_or_default();
+                let env = frame_environment(lua, frame_level(args))?;
+                let chunk = lua.load(format!("return {expr}")).set_name("=evaluate");
+                let value = chunk.set_environment(env).eval::<Value>()?;
+                let mut var = variable(&value, refs);
+                var["result"] = var["value"].take();
+                Ok(var)
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    fn send_response(&self, request: &Request, body: Json) {
+        self.send(Json::object([
+            ("type", "response".into()),
+            ("request_seq", request.seq.into()),
+            ("success", true.into()),
+            ("command", request.command.as_str().into()),
+            ("body", body),
+        ]));
+    }
+
+    fn send_error(&self, request: &Request, message: &str) {
+        self.send(Json::object([
+            ("type", "response".into()),
+            ("request_seq", request.seq.into()),
+            ("success", false.into()),
+            ("command", request.command.as_str().into()),
+            ("message", message.into()),
+        ]));
+    }
+
+    fn send_event(&self, event: &str, body: Json) {
+        self.send(Json::object([
+            ("type", "event".into()),
+            ("event", event.into()),
+            ("body", body),
+        ]));
+    }
+
+    fn send(&self, mut message: Json) {
+        let mut output = self.output.lock();
+        output.seq += 1;
+        message["seq"] = output.seq.into();
+        let body = message.to_string();
+        // Errors mean that the client has gone, which is detected by the reader thread
+        let _ = write!(output.writer, "Content-Length: {}\r\n\r\n{body}", body.len());
+        let _ = output.writer.flush();
+    }
+
+    fn lua_line_to_client(&self, line: usize) -> usize {
+        match self.lines_start_at1.load(Ordering::Relaxed) {
+            true => line,
+            false => line.saturating_sub(1),
+        }
+    }
+
+    fn client_line_to_lua(&self, line: u64) -> usize {
+        match self.lines_start_at1.load(Ordering::Relaxed) {
+            true => line as usize,
+            false => line as usize + 1,
+        }
+    }
+}
+
+impl SourceMap {
+    fn path(&self, chunk: &str) -> PathBuf {
+        (self.paths.get(chunk).cloned()).unwrap_or_else(|| PathBuf::from(chunk))
+    }
+
+    fn chunk(&self, path: &str) -> StdString {
+        let chunk = self.chunks.get(&PathBuf::from(path));
+        chunk.cloned().unwrap_or_else(|| path.to_string())
+    }
+}
+
+// Reads a single message framed with a `Content-Length` header. Returns `None` on EOF.
+fn read_request(reader: &mut impl BufRead) -> io::Result<Option<Request>> {
+    let mut content_length = None;
+    loop {
+        let mut line = StdString::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("Content-Length") {
+                content_length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+    let Some(content_length) = content_length else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "missing Content-Length header",
+        ));
+    };
+
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body)?;
+    let mut message = Json::parse(&body)?;
+    Ok(Some(Request {
+        seq: message["seq"].as_i64().unwrap_or_default(),
+        command: message["command"].as_str().unwrap_or_default().to_string(),
+        arguments: message["arguments"].take(),
+    }))
+}
+
+fn frame_level(args: &Json) -> usize {
+    (args["frameId"].as_u64()).map_or(0, |id| id.saturating_sub(1) as usize)
+}
+
+fn lookup_ref<'a>(args: &Json, refs: &'a [VarRef]) -> Result<&'a VarRef> {
+    let index = args["variablesReference"].as_u64().unwrap_or_default() as usize;
+    (index.checked_sub(1).and_then(|i| refs.get(i)))
+        .ok_or_else(|| Error::runtime("invalid variables reference"))
+}
+
+fn stack_level_error() -> Error {
+    Error::runtime("invalid stack frame")
+}
+
+// Builds a table to evaluate expressions in, with the frame locals and upvalues visible
+fn frame_environment(lua: &Lua, level: usize) -> Result<Table> {
+    let env = lua.create_table()?;
+    let vars = lua.inspect_stack(level, |debug| {
+        debug.upvalues().chain(debug.locals()).collect::<Vec<_>>()
+    });
+    for (name, value) in vars.unwrap_or_default() {
+        if !name.is_empty() {
+            env.raw_set(name, value)?;
+        }
+    }
+    let meta = lua.create_table()?;
+    meta.raw_set("__index", lua.globals())?;
+    env.set_metatable(Some(meta))?;
+    Ok(env)
+}
+
+fn table_fields(table: &Table) -> Result<Vec<(StdString, Value)>> {
+    (table.pairs::<Value, Value>())
+        .map(|kv| {
+            let (key, value) = kv?;
+            let name = match key {
+                Value::String(s) => s.to_string_lossy(),
+                key => format!("[{}]", display_value(&key)),
+            };
+            Ok((name, value))
+        })
+        .collect()
+}
+
+// Parses a non-string key name, as displayed by `table_fields`
+fn table_index(name: &str) -> Option<Value> {
+    let index = name.strip_prefix('[')?.strip_suffix(']')?;
+    match index.parse() {
+        Ok(i) => Some(Value::Integer(i)),
+        Err(_) => index.parse().ok().map(Value::Number),
+    }
+}
+
+// Describes a value as a DAP `Variable` (without the name)
+fn variable(value: &Value, refs: &mut Vec<VarRef>) -> Json {
+    let reference = match value {
+        Value::Table(table) => {
+            refs.push(VarRef::Table(table.clone()));
+            refs.len()
+        }
+        _ => 0,
+    };
+    Json::object([
+        ("value", display_value(value).into()),
+        ("type", value.type_name().into()),
+        ("variablesReference", reference.into()),
+    ])
+}
+
+fn display_value(value: &Value) -> StdString {
+    match value {
+        Value::String(s) => format!("{:?}", s.to_string_lossy()),
+        value => (value.to_string()).unwrap_or_else(|_| value.type_name().to_string()),
+    }
+}
@@ -0,0 +1,389 @@
+//! Minimal JSON support for the Debug Adapter Protocol messages.
+//!
+//! A JSON library is not used as a dependency, because its trait implementations (such as
+//! `PartialEq` for the standard types) would be visible to all crates depending on `mlua` and
+//! could break type inference in their code.
+
+use std::fmt::{self, Write as _};
+use std::io;
+use std::ops::{Index, IndexMut};
+use std::string::String as StdString;
+
+static NULL: Json = Json::Null;
+
+/// A JSON value.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(super) enum Json {
+    #[default]
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(StdString),
+    Array(Vec<Json>),
+    Object(Vec<(StdString, Json)>),
+}
+
+impl Json {
+    /// Creates a JSON object from the list of key-value pairs.
+    pub(super) fn object<const N: usize>(pairs: [(&str, Json); N]) -> Self {
+        Json::Object(pairs.into_iter().map(|(k, v)| (k.to_string(), v)).collect())
+    }
+
+    pub(super) fn as_bool(&self) -> Option<bool> {
+        match *self {
+            Json::Bool(b) => Some(b),
+            _ => None,
+        }
+    }
+
+    pub(super) fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub(super) fn as_i64(&self) -> Option<i64> {
+        match *self {
+            Json::Number(n) if n.fract() == 0.0 && n.abs() < (1u64 << 53) as f64 => Some(n as i64),
+            _ => None,
+        }
+    }
+
+    pub(super) fn as_u64(&self) -> Option<u64> {
+        self.as_i64().and_then(|n| u64::try_from(n).ok())
+    }
+
+    pub(super) fn as_array(&self) -> Option<&Vec<Json>> {
+        match self {
+            Json::Array(array) => Some(array),
+            _ => None,
+        }
+    }
+
+    /// Takes the value out, leaving `Null` in its place.
+    pub(super) fn take(&mut self) -> Json {
+        std::mem::take(self)
+    }
+
+    /// Parses a JSON document.
+    pub(super) fn parse(input: &[u8]) -> io::Result<Json> {
+        let mut parser = Parser { input, pos: 0 };
+        let value = parser.value(0)?;
+        parser.skip_whitespace();
+        if parser.pos != input.len() {
+            return Err(parser.error("trailing characters"));
+        }
+        Ok(value)
+    }
+}
+
+impl Index<&str> for Json {
+    type Output = Json;
+
+    fn index(&self, key: &str) -> &Json {
+        match self {
+            Json::Object(pairs) => (pairs.iter().find(|(k, _)| k == key)).map_or(&NULL, |(_, v)| v),
+            _ => &NULL,
+        }
+    }
+}
+
+impl IndexMut<&str> for Json {
+    /// Returns the value of the `key` in an object, inserting `Null` if it's missing.
+    ///
+    /// Panics if the value is not an object (or `Null`, which is turned into an empty object).
+    fn index_mut(&mut self, key: &str) -> &mut Json {
+        if let Json::Null = self {
+            *self = Json::Object(Vec::new());
+        }
+        let Json::Object(pairs) = self else {
+            panic!("cannot index JSON value with a string key");
+        };
+        let i = match pairs.iter().position(|(k, _)| k == key) {
+            Some(i) => i,
+            None => {
+                pairs.push((key.to_string(), Json::Null));
+                pairs.len() - 1
+            }
+        };
+        &mut pairs[i].1
+    }
+}
+
+impl From<bool> for Json {
+    fn from(b: bool) -> Self {
+        Json::Bool(b)
+    }
+}
+
+macro_rules! impl_from_number {
+    ($($t:ty),*) => {
+        $(
+            impl From<$t> for Json {
+                fn from(n: $t) -> Self {
+                    Json::Number(n as f64)
+                }
+            }
+        )*
+    };
+}
+
+impl_from_number!(i64, u64, usize);
+
+impl From<&str> for Json {
+    fn from(s: &str) -> Self {
+        Json::String(s.to_string())
+    }
+}
+
+impl From<StdString> for Json {
+    fn from(s: StdString) -> Self {
+        Json::String(s)
+    }
+}
+
+impl From<Vec<Json>> for Json {
+    fn from(array: Vec<Json>) -> Self {
+        Json::Array(array)
+    }
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Json::Null => f.write_str("null"),
+            Json::Bool(b) => write!(f, "{b}"),
+            Json::Number(n) => match self.as_i64() {
+                Some(i) => write!(f, "{i}"),
+                None if n.is_finite() => write!(f, "{n}"),
+                None => f.write_str("null"),
+            },
+            Json::String(s) => write_string(f, s),
+            Json::Array(array) => {
+                f.write_char('[')?;
+                for (i, value) in array.iter().enumerate() {
+                    if i > 0 {
+                        f.write_char(',')?;
+                    }
+                    write!(f, "{value}")?;
+                }
+                f.write_char(']')
+            }
+            Json::Object(pairs) => {
+                f.write_char('{')?;
+                for (i, (key, value)) in pairs.iter().enumerate() {
+                    if i > 0 {
+                        f.write_char(',')?;
+                    }
+                    write_string(f, key)?;
+                    write!(f, ":{value}")?;
+                }
+                f.write_char('}')
+            }
+        }
+    }
+}
+
+fn write_string(f: &mut fmt::Formatter, s: &str) -> fmt::Result {
+    f.write_char('"')?;
+    for c in s.chars() {
+        match c {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => f.write_char(c)?,
+        }
+    }
+    f.write_char('"')
+}
+
+// Protects against stack overflow on malicious input
+const MAX_DEPTH: usize = 128;
+
+struct Parser<'a> {
+    input: &'a [u8],
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, msg: &str) -> io::Error {
+        let msg = format!("invalid JSON: {msg} at position {}", self.pos);
+        io::Error::new(io::ErrorKind::InvalidData, msg)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.input.get(self.pos) {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&mut self) -> Option<u8> {
+        self.skip_whitespace();
+        self.input.get(self.pos).copied()
+    }
+
+    fn expect(&mut self, c: u8) -> io::Result<()> {
+        match self.peek() {
+            Some(b) if b == c => {
+                self.pos += 1;
+                Ok(())
+            }
+            _ => Err(self.error(&format!("expected '{}'", c as char))),
+        }
+    }
+
+    fn literal(&mut self, literal: &str, value: Json) -> io::Result<Json> {
+        if !self.input[self.pos..].starts_with(literal.as_bytes()) {
+            return Err(self.error("unexpected token"));
+        }
+        self.pos += literal.len();
+        Ok(value)
+    }
+
+    fn value(&mut self, depth: usize) -> io::Result<Json> {
+        if depth > MAX_DEPTH {
+            return Err(self.error("recursion limit exceeded"));
+        }
+        match self.peek() {
+            Some(b'n') => self.literal("null", Json::Null),
+            Some(b't') => self.literal("true", Json::Bool(true)),
+            Some(b'f') => self.literal("false", Json::Bool(false)),
+            Some(b'"') => self.string().map(Json::String),
+            Some(b'[') => {
+                self.pos += 1;
+                let mut array = Vec::new();
+                if self.peek() == Some(b']') {
+                    self.pos += 1;
+                    return Ok(Json::Array(array));
+                }
+                loop {
+                    array.push(self.value(depth + 1)?);
+                    match self.peek() {
+                        Some(b',') => self.pos += 1,
+                        _ => break,
+                    }
+                }
+                self.expect(b']')?;
+                Ok(Json::Array(array))
+            }
+            Some(b'{') => {
+                self.pos += 1;
+                let mut pairs = Vec::new();
+                if self.peek() == Some(b'}') {
+                    self.pos += 1;
+                    return Ok(Json::Object(pairs));
+                }
+                loop {
+                    if self.peek() != Some(b'"') {
+                        return Err(self.error("expected string key"));
+                    }
+                    let key = self.string()?;
+                    self.expect(b':')?;
+                    pairs.push((key, self.value(depth + 1)?));
+                    match self.peek() {
+                        Some(b',') => self.pos += 1,
+                        _ => break,
+                    }
+                }
+                self.expect(b'}')?;
+                Ok(Json::Object(pairs))
+            }
+            Some(b'-' | b'0'..=b'9') => self.number(),
+            _ => Err(self.error("unexpected token")),
+        }
+    }
+
+    fn number(&mut self) -> io::Result<Json> {
+        let start = self.pos;
+        while let Some(b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9') = self.input.get(self.pos) {
+            self.pos += 1;
+        }
+        let number = std::str::from_utf8(&self.input[start..self.pos]).ok();
+        match number.and_then(|n| n.parse::<f64>().ok()) {
+            Some(n) => Ok(Json::Number(n)),
+            None => Err(self.error("invalid number")),
+        }
+    }
+
+    fn hex4(&mut self) -> io::Result<u32> {
+        let digits = self.input.get(self.pos..self.pos + 4);
+        let digits = digits.and_then(|d| std::str::from_utf8(d).ok());
+        match digits.and_then(|d| u32::from_str_radix(d, 16).ok()) {
+            Some(n) => {
+                self.pos += 4;
+                Ok(n)
+            }
+            None => Err(self.error("invalid unicode escape")),
+        }
+    }
+
+    fn string(&mut self) -> io::Result<StdString> {
+        // Skip the opening quote
+        self.pos += 1;
+        let mut bytes = Vec::new();
+        loop {
+            let Some(&b) = self.input.get(self.pos) else {
+                return Err(self.error("unterminated string"));
+            };
+            self.pos += 1;
+            match b {
+                b'"' => break,
+                b'\\' => {
+                    let Some(&escape) = self.input.get(self.pos) else {
+                        return Err(self.error("unterminated string"));
+                    };
+                    self.pos += 1;
+                    let c = match escape {
+                        b'"' => '"',
+                        b'\\' => '\\',
+                        b'/' => '/',
+                        b'b' => '\u{8}',
+                        b'f' => '\u{c}',
+                        b'n' => '\n',
+                        b'r' => '\r',
+                        b't' => '\t',
+                        b'u' => {
+                            let mut code = self.hex4()?;
+                            // Surrogate pair
+                            if (0xD800..0xDC00).contains(&code) && self.input[self.pos..].starts_with(b"\\u")
+                            {
+                                self.pos += 2;
+                                let low = self.hex4()?;
+                                code = 0x10000 + ((code - 0xD800) << 10) + (low.wrapping_sub(0xDC00) & 0x3FF);
+                            }
+                            char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER)
+                        }
+                        _ => return Err(self.error("invalid escape")),
+                    };
+                    bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                }
+                b => bytes.push(b),
+            }
+        }
+        StdString::from_utf8(bytes).map_err(|_| self.error("invalid UTF-8 in string"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Json;
+
+    #[test]
+    fn test_roundtrip() {
+        let input = r#" {"seq": 3, "arguments": {"a": [1, -2.5, true, null], "s": "x\"\né\ud83d\ude00"}} "#;
+        let value = Json::parse(input.as_bytes()).unwrap();
+        assert_eq!(value["seq"].as_i64(), Some(3));
+        assert_eq!(value["arguments"]["s"].as_str(), Some("x\"\né😀"));
+        assert_eq!(value["missing"]["key"], Json::Null);
+        assert_eq!(
+            value.to_string(),
+            r#"{"seq":3,"arguments":{"a":[1,-2.5,true,null],"s":"x\"\né😀"}}"#
+        );
+        assert!(Json::parse(b"[1,").is_err());
+        assert!(Json::parse(&[b'['; 1000]).is_err());
+    }
+}
@@ -0,0 +1,303 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::string::String as StdString;
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+
+use super::{chunk_name, Debug, DebugEvent, HookTriggers};
+use crate::error::Result;
+use crate::state::Lua;
+use crate::types::{MaybeSend, VmState};
+
+/// A breakpoint that pauses execution when reached.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Breakpoint {
+    /// Pauses before executing the given line of a chunk.
+    ///
+    /// Chunks are identified by their name (see [`Chunk::set_name`]). A leading `@` or `=` is not
+    /// part of the name, so `"@main.lua"` and `"main.lua"` refer to the same chunk.
+    ///
+    /// [`Chunk::set_name`]: crate::Chunk::set_name
+    Line { chunk: StdString, line: usize },
+    /// Pauses at the first line of a Lua function called with the given name.
+    ///
+    /// The name is the one reported by [`Debug::names`] for the call, e.g. `"foo"` for both
+    /// `foo()` and `obj:foo()`.
+    Function(StdString),
+}
+
+impl Breakpoint {
+    /// Creates a new breakpoint at the given line of a chunk.
+    pub fn line(chunk: impl AsRef<str>, line: usize) -> Self {
+        let chunk = chunk_name(chunk.as_ref()).to_string();
+        Breakpoint::Line { chunk, line }
+    }
+
+    /// Creates a new breakpoint on calls to a function with the given name.
+    pub fn function(name: impl Into<StdString>) -> Self {
+        Breakpoint::Function(name.into())
+    }
+}
+
+/// The reason why [`Debugger`] paused execution.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum PauseReason {
+    /// A breakpoint was hit.
+    Breakpoint(Breakpoint),
+    /// A step requested by [`StepAction`] was completed.
+    Step,
+    /// A pause was requested by [`Debugger::pause`].
+    Pause,
+}
+
+/// Determines how execution continues after [`Debugger`] paused.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum StepAction {
+    /// Continue execution until the next breakpoint.
+    #[default]
+    Continue,
+    /// Pause at the next line, entering called functions.
+    StepIn,
+    /// Pause at the next line of the current function (or its caller when returning).
+    StepOver,
+    /// Pause once the current function returns to its caller.
+    StepOut,
+}
+
+/// A step debugger built on top of Lua hooks.
+///
+/// `Debugger` keeps a set of [breakpoints] and calls the pause handler provided to
+/// [`Debugger::attach`] every time execution pauses. The handler receives the [`Debug`] structure
+/// for the paused function, which can be used to inspect (and modify) local variables and
+/// upvalues, and decides how to continue by returning a [`StepAction`].
+///
+/// The handler is called synchronously from the Lua code being debugged, so it can block until a
+/// user decides how to proceed. Errors returned from the handler are propagated through the Lua
+/// code, which can be used to abort execution.
+///
+/// `Debugger` is cheap to clone and can be sent to other threads; all clones share the same
+/// state, so breakpoints can be changed (including from within the pause handler) while the
+/// debugger is attached.
+///
+/// # Example
+///
+/// ```
+/// # use mlua::{Breakpoint, Debugger, Lua, Result, StepAction};
+/// # fn main() -> Result<()> {
+/// let lua = Lua::new();
+/// let debugger = Debugger::new();
+/// debugger.add_breakpoint(Breakpoint::line("main.lua", 3));
+/// debugger.attach(&lua, |_lua, debug, _reason| {
+///     for (name, value) in debug.locals() {
+///         println!("{name} = {value:?}");
+///     }
+///     Ok(StepAction::Continue)
+/// })?;
+///
+/// lua.load(r#"
+///     local x = 1
+///     local y = x + 1
+/// "#)
+/// .set_name("main.lua")
+/// .exec()?;
+///
+/// debugger.detach(&lua);
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [breakpoints]: Breakpoint
+#[derive(Clone, Default)]
+pub struct Debugger {
+    state: Arc<Mutex<DebuggerState>>,
+}
+
+#[derive(Default)]
+struct DebuggerState {
+    line_breakpoints: BTreeMap<StdString, BTreeSet<usize>>,
+    function_breakpoints: BTreeSet<StdString>,
+    // Function breakpoint to report at the next line event
+    pending_function: Option<StdString>,
+    pause_requested: bool,
+    step: Option<Step>,
+}
+
+#[derive(Clone, Copy)]
+enum Step {
+    In,
+    // Thread (`lua_State` address) and stack depth where stepping started
+    Over(usize, usize),
+    Out(usize, usize),
+}
+
+impl Debugger {
+    /// Creates a new debugger without breakpoints.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a breakpoint.
+    ///
+    /// Returns `false` if the breakpoint was already set.
+    pub fn add_breakpoint(&self, breakpoint: Breakpoint) -> bool {
+        let mut state = self.state.lock();
+        match breakpoint {
+            Breakpoint::Line { chunk, line } => state.line_breakpoints.entry(chunk).or_default().insert(line),
+            Breakpoint::Function(name) => state.function_breakpoints.insert(name),
+        }
+    }
+
+    /// Removes a breakpoint.
+    ///
+    /// Returns `false` if the breakpoint was not set.
+    pub fn remove_breakpoint(&self, breakpoint: &Breakpoint) -> bool {
+        let mut state = self.state.lock();
+        match breakpoint {
+            Breakpoint::Line { chunk, line } => {
+                let Some(lines) = state.line_breakpoints.get_mut(chunk) else {
+                    return false;
+                };
+                let removed = lines.remove(line);
+                if lines.is_empty() {
+                    state.line_breakpoints.remove(chunk);
+                }
+                removed
+            }
+            Breakpoint::Function(name) => state.function_breakpoints.remove(name),
+        }
+    }
+
+    /// Removes all line breakpoints set in the given chunk.
+    pub fn clear_chunk_breakpoints(&self, chunk: &str) {
+        self.state.lock().line_breakpoints.remove(chunk_name(chunk));
+    }
+
+    /// Removes all breakpoints.
+    pub fn clear_breakpoints(&self) {
+        let mut state = self.state.lock();
+        state.line_breakpoints.clear();
+        state.function_breakpoints.clear();
+    }
+
+    /// Returns a list of all breakpoints.
+    pub fn breakpoints(&self) -> Vec<Breakpoint> {
+        let state = self.state.lock();
+        let lines = state.line_breakpoints.iter().flat_map(|(chunk, lines)| {
+            (lines.iter()).map(|&line| Breakpoint::Line {
+                chunk: chunk.clone(),
+                line,
+            })
+        });
+        let functions = (state.function_breakpoints.iter()).map(|name| Breakpoint::Function(name.clone()));
+        lines.chain(functions).collect()
+    }
+
+    /// Requests to pause execution at the next executed line.
+    ///
+    /// This method can be called from another thread while the Lua code is running.
+    pub fn pause(&self) {
+        self.state.lock().pause_requested = true;
+    }
+
+    /// Attaches the debugger to a Lua instance.
+    ///
+    /// The `handler` is called every time execution pauses, with the reason of the pause.
+    ///
+    /// The debugger is installed as a global hook (see [`Lua::set_global_hook`]) and so applies
+    /// to all threads (coroutines) created after this call. Any previously set global hook is
+    /// replaced.
+    pub fn attach<F>(&self, lua: &Lua, handler: F) -> Result<()>
+    where
+        F: Fn(&Lua, &Debug, PauseReason) -> Result<StepAction> + MaybeSend + 'static,
+    {
+        let state = self.state.clone();
+        let triggers = HookTriggers::ON_CALLS | HookTriggers::EVERY_LINE;
+        lua.set_global_hook(triggers, move |lua, debug| {
+            match debug.event() {
+                DebugEvent::Line => {
+                    // The lock must not be held while the handler is running
+                    let reason = state.lock().check_pause(debug);
+                    if let Some(reason) = reason {
+                        let action = handler(lua, debug, reason)?;
+                        state.lock().step = Step::new(action, debug);
+                    }
+                }
+                #[cfg(any(feature = "lua54", feature = "lua53", feature = "lua52"))]
+                DebugEvent::Call | DebugEvent::TailCall => state.lock().check_call(debug),
+                #[cfg(not(any(feature = "lua54", feature = "lua53", feature = "lua52")))]
+                DebugEvent::Call => state.lock().check_call(debug),
+                _ => {}
+            }
+            Ok(VmState::Continue)
+        })
+    }
+
+    /// Detaches the debugger from a Lua instance, removing the global hook.
+    ///
+    /// Breakpoints are kept, so the debugger can be attached again later.
+    pub fn detach(&self, lua: &Lua) {
+        lua.remove_global_hook();
+        let mut state = self.state.lock();
+        state.pending_function = None;
+        state.pause_requested = false;
+        state.step = None;
+    }
+}
+
+impl DebuggerState {
+    fn check_call(&mut self, debug: &Debug) {
+        if self.function_breakpoints.is_empty() || debug.source().what == "C" {
+            return;
+        }
+        if let Some(name) = debug.names().name {
+            if self.function_breakpoints.contains(&*name) {
+                self.pending_function = Some(name.into_owned());
+            }
+        }
+    }
+
+    fn check_pause(&mut self, debug: &Debug) -> Option<PauseReason> {
+        if let Some(name) = self.pending_function.take() {
+            return Some(self.paused(PauseReason::Breakpoint(Breakpoint::Function(name))));
+        }
+
+        if !self.line_breakpoints.is_empty() {
+            if let (Some(source), Some(line)) = (debug.source().source, debug.current_line()) {
+                let chunk = chunk_name(&source);
+                if (self.line_breakpoints.get(chunk)).is_some_and(|lines| lines.contains(&line)) {
+                    let chunk = chunk.to_string();
+                    return Some(self.paused(PauseReason::Breakpoint(Breakpoint::Line { chunk, line })));
+                }
+            }
+        }
+
+        if self.pause_requested {
+            return Some(self.paused(PauseReason::Pause));
+        }
+
+        let step_completed = match self.step? {
+            Step::In => true,
+            Step::Over(thread, depth) => thread == debug.state as usize && debug.stack_depth() <= depth,
+            Step::Out(thread, depth) => thread == debug.state as usize && debug.stack_depth() < depth,
+        };
+        step_completed.then(|| self.paused(PauseReason::Step))
+    }
+
+    fn paused(&mut self, reason: PauseReason) -> PauseReason {
+        self.pause_requested = false;
+        self.step = None;
+        reason
+    }
+}
+
+impl Step {
+    fn new(action: StepAction, debug: &Debug) -> Option<Self> {
+        match action {
+            StepAction::Continue => None,
+            StepAction::StepIn => Some(Step::In),
+            StepAction::StepOver => Some(Step::Over(debug.state as usize, debug.stack_depth())),
+            StepAction::StepOut => Some(Step::Out(debug.state as usize, debug.stack_depth())),
+        }
+    }
+}
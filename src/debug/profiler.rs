@@ -0,0 +1,512 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write as _;
+use std::string::String as StdString;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use parking_lot::Mutex;
+
+use super::{chunk_name, Debug};
+use crate::error::Result;
+use crate::state::Lua;
+use crate::types::VmState;
+
+#[cfg(not(feature = "luau"))]
+use super::HookTriggers;
+
+// Maximum number of stack frames captured per sample
+const MAX_DEPTH: usize = 256;
+
+/// A sampling CPU profiler for Lua code.
+///
+/// The profiler periodically captures the call stack of the running Lua code. For Lua 5.x and
+/// LuaJIT it uses a count hook (see [`HookTriggers::every_nth_instruction`]) and samples once per
+/// configured number of VM instructions; for Luau it uses [`Lua::set_interrupt`] and samples once
+/// per configured number of interrupts.
+///
+/// Collected samples are returned as a [`Profile`] that can be exported as folded stacks (for
+/// flamegraph tools) or as a [pprof] profile.
+///
+/// # Example
+///
+/// ```
+/// # use mlua::{Lua, Profiler, Result};
+/// # fn main() -> Result<()> {
+/// let lua = Lua::new();
+/// let profiler = Profiler::new().sample_interval(100);
+/// profiler.start(&lua)?;
+///
+/// lua.load(r#"
+///     local function fib(n) return n < 2 and n or fib(n - 1) + fib(n - 2) end
+///     fib(20)
+/// "#)
+/// .set_name("fib.lua")
+/// .exec()?;
+///
+/// let profile = profiler.stop(&lua);
+/// println!("{}", profile.to_folded());
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`HookTriggers::every_nth_instruction`]: crate::HookTriggers::every_nth_instruction
+/// [pprof]: https://github.com/google/pprof
+#[derive(Clone)]
+pub struct Profiler {
+    interval: u32,
+    state: Arc<Mutex<ProfilerState>>,
+}
+
+#[derive(Default)]
+struct ProfilerState {
+    started: Option<(Instant, SystemTime)>,
+    last_sample: Option<Instant>,
+    #[cfg(feature = "luau")]
+    ticks: u64,
+    stacks: HashMap<Vec<Frame>, SampleValue>,
+}
+
+/// A stack frame captured by [`Profiler`].
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Frame {
+    /// Name of the function (`main` for the main part of a chunk).
+    pub function: StdString,
+    /// Name of the chunk where the function is defined (`[C]` for C functions).
+    pub source: StdString,
+    /// The line where the function definition starts.
+    pub line_defined: Option<usize>,
+    /// The line that was executing when the sample was taken.
+    pub line: Option<usize>,
+}
+
+#[derive(Clone, Copy, Default)]
+struct SampleValue {
+    count: u64,
+    nanos: u64,
+}
+
+/// Samples collected by [`Profiler`].
+#[derive(Clone, Debug)]
+pub struct Profile {
+    // Stacks are stored from the outermost frame to the innermost one
+    stacks: Vec<(Vec<Frame>, u64, u64)>,
+    start_time: SystemTime,
+    duration: Duration,
+    interval: u32,
+}
+
+/// Number of samples attributed to a function.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FunctionSamples {
+    /// Name of the function.
+    pub function: StdString,
+    /// Name of the chunk where the function is defined.
+    pub source: StdString,
+    /// The line where the function definition starts.
+    pub line_defined: Option<usize>,
+    /// Number of samples where the function was running.
+    pub self_samples: u64,
+    /// Number of samples where the function was on the stack.
+    pub total_samples: u64,
+}
+
+/// Number of samples attributed to a line of code.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LineSamples {
+    /// Name of the chunk.
+    pub source: StdString,
+    /// Line number.
+    pub line: usize,
+    /// Number of samples where the line was running.
+    pub samples: u64,
+}
+
+impl Default for Profiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Profiler {
+    /// Creates a new profiler that samples every 1000 VM instructions (or interrupts for Luau).
+    pub fn new() -> Self {
+        Profiler {
+            interval: 1000,
+            state: Arc::default(),
+        }
+    }
+
+    /// Sets the number of VM instructions (or interrupts for Luau) between samples.
+    ///
+    /// # Performance
+    ///
+    /// Setting this option to a low value can incur a very high overhead.
+    pub fn sample_interval(mut self, interval: u32) -> Self {
+        self.interval = interval.max(1);
+        self
+    }
+
+    /// Starts profiling Lua code executed by the given Lua instance.
+    ///
+    /// Samples collected previously are discarded.
+    ///
+    /// For Lua 5.x and LuaJIT, the profiler is installed as a global hook (see
+    /// [`Lua::set_global_hook`]), replacing any previously set global hook.
+    /// For Luau, it replaces the interrupt function (see [`Lua::set_interrupt`]).
+    ///
+    /// [`Lua::set_global_hook`]: crate::Lua::set_global_hook
+    pub fn start(&self, lua: &Lua) -> Result<()> {
+        {
+            let mut state = self.state.lock();
+            *state = ProfilerState::default();
+            state.started = Some((Instant::now(), SystemTime::now()));
+        }
+
+        let state = self.state.clone();
+        #[cfg(not(feature = "luau"))]
+        {
+            let triggers = HookTriggers::new().every_nth_instruction(self.interval);
+            lua.set_global_hook(triggers, move |lua, _debug| {
+                state.lock().sample(lua);
+                Ok(VmState::Continue)
+            })
+        }
+        #[cfg(feature = "luau")]
+        {
+            let interval = self.interval as u64;
+            lua.set_interrupt(move |lua| {
+                let mut state = state.lock();
+                state.ticks += 1;
+                if state.ticks % interval == 0 {
+                    state.sample(lua);
+                }
+                Ok(VmState::Continue)
+            });
+            Ok(())
+        }
+    }
+
+    /// Stops profiling and returns the collected samples.
+    pub fn stop(&self, lua: &Lua) -> Profile {
+        #[cfg(not(feature = "luau"))]
+        lua.remove_global_hook();
+        #[cfg(feature = "luau")]
+        lua.remove_interrupt();
+
+        let mut state = self.state.lock();
+        let (started, start_time) =
+            (state.started.take()).unwrap_or_else(|| (Instant::now(), SystemTime::now()));
+        let mut stacks = (state.stacks.drain())
+            .map(|(stack, value)| (stack, value.count, value.nanos))
+            .collect::<Vec<_>>();
+        stacks.sort_by(|a, b| a.0.cmp(&b.0));
+        Profile {
+            stacks,
+            start_time,
+            duration: started.elapsed(),
+            interval: self.interval,
+        }
+    }
+}
+
+impl ProfilerState {
+    fn sample(&mut self, lua: &Lua) {
+        let now = Instant::now();
+        let since = self.last_sample.or(self.started.map(|(started, _)| started));
+        let nanos = since.map_or(0, |since| now.duration_since(since).as_nanos() as u64);
+        self.last_sample = Some(now);
+
+        let mut stack = Vec::new();
+        while stack.len() < MAX_DEPTH {
+            match lua.inspect_stack(stack.len(), Frame::new) {
+                Some(frame) => stack.push(frame),
+                None => break,
+            }
+        }
+        if stack.is_empty() {
+            return;
+        }
+        stack.reverse();
+
+        let value = self.stacks.entry(stack).or_default();
+        value.count += 1;
+        value.nanos += nanos;
+    }
+}
+
+impl Frame {
+    pub(crate) fn new(debug: &Debug) -> Self {
+        let source = debug.source();
+        let function = match debug.names().name {
+            Some(name) => name.into_owned(),
+            None if source.what == "main" => "main".to_string(),
+            None => "?".to_string(),
+        };
+        let is_c = source.what == "C";
+        Frame {
+            function,
+            source: match source.source {
+                Some(source) if !is_c => chunk_name(&source).to_string(),
+                _ => "[C]".to_string(),
+            },
+            line_defined: source.line_defined.filter(|_| !is_c),
+            line: debug.current_line().filter(|_| !is_c),
+        }
+    }
+
+    // Label of the frame in folded stacks
+    fn label(&self) -> StdString {
+        format!("{}:{}", self.source, self.function)
+    }
+}
+
+impl Profile {
+    /// Returns the total number of samples.
+    pub fn samples(&self) -> u64 {
+        self.stacks.iter().map(|(_, count, _)| count).sum()
+    }
+
+    /// Returns the duration of profiling.
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+
+    /// Returns sampled stacks with the number of samples for each.
+    ///
+    /// Stacks are listed from the outermost frame to the innermost one.
+    pub fn stacks(&self) -> impl Iterator<Item = (&[Frame], u64)> {
+        self.stacks
+            .iter()
+            .map(|(stack, count, _)| (stack.as_slice(), *count))
+    }
+
+    /// Returns the number of samples per function, sorted by the number of self samples (in
+    /// descending order).
+    pub fn functions(&self) -> Vec<FunctionSamples> {
+        let mut functions = BTreeMap::<_, (u64, u64)>::new();
+        for (stack, count, _) in &self.stacks {
+            let mut seen = Vec::with_capacity(stack.len());
+            for (i, frame) in stack.iter().enumerate() {
+                let key = (&frame.source, &frame.function, frame.line_defined);
+                let entry = functions.entry(key).or_default();
+                // Count recursive functions once per stack
+                if !seen.contains(&key) {
+                    entry.1 += count;
+                    seen.push(key);
+                }
+                if i == stack.len() - 1 {
+                    entry.0 += count;
+                }
+            }
+        }
+        let mut functions = (functions.into_iter())
+            .map(
+                |((source, function, line_defined), (self_samples, total_samples))| FunctionSamples {
+                    function: function.clone(),
+                    source: source.clone(),
+                    line_defined,
+                    self_samples,
+                    total_samples,
+                },
+            )
+            .collect::<Vec<_>>();
+        functions.sort_by_key(|f| std::cmp::Reverse(f.self_samples));
+        functions
+    }
+
+    /// Returns the number of samples per line of code, sorted by the number of samples (in
+    /// descending order).
+    ///
+    /// Only the innermost Lua frame of each sample is taken into account.
+    pub fn lines(&self) -> Vec<LineSamples> {
+        let mut lines = BTreeMap::<_, u64>::new();
+        for (stack, count, _) in &self.stacks {
+            if let Some(key) = stack
+                .iter()
+                .rev()
+                .find_map(|frame| Some((&frame.source, frame.line?)))
+            {
+                *lines.entry(key).or_default() += count;
+            }
+        }
+        let mut lines = (lines.into_iter())
+            .map(|((source, line), samples)| LineSamples {
+                source: source.clone(),
+                line,
+                samples,
+            })
+            .collect::<Vec<_>>();
+        lines.sort_by_key(|l| std::cmp::Reverse(l.samples));
+        lines
+    }
+
+    /// Exports samples in the folded stacks format, compatible with flamegraph tools such as
+    /// [inferno] or `flamegraph.pl`.
+    ///
+    /// Each line contains a stack of `source:function` frames separated by `;` followed by the
+    /// number of samples.
+    ///
+    /// [inferno]: https://github.com/jonhoo/inferno
+    pub fn to_folded(&self) -> StdString {
+        let mut folded = BTreeMap::<StdString, u64>::new();
+        for (stack, count, _) in &self.stacks {
+            let labels = stack.iter().map(Frame::label).collect::<Vec<_>>();
+            *folded.entry(labels.join(";")).or_default() += count;
+        }
+        let mut output = StdString::new();
+        for (stack, count) in folded {
+            let _ = writeln!(output, "{stack} {count}");
+        }
+        output
+    }
+
+    /// Exports samples as a [pprof] profile (uncompressed protocol buffer).
+    ///
+    /// The profile contains two sample types: `samples/count` and `cpu/nanoseconds` (the time
+    /// elapsed since the previous sample). The output can be gzip-compressed before passing it
+    /// to tools that expect compressed profiles.
+    ///
+    /// [pprof]: https://github.com/google/pprof/blob/main/proto/profile.proto
+    pub fn to_pprof(&self) -> Vec<u8> {
+        let mut strings = StringTable::default();
+        let mut functions = HashMap::<(&str, &str, Option<usize>), u64>::new();
+        let mut locations = HashMap::<(u64, Option<usize>), u64>::new();
+        let mut profile = Vec::new();
+
+        // sample_type
+        for (ty, unit) in [("samples", "count"), ("cpu", "nanoseconds")] {
+            let value_type = value_type(&mut strings, ty, unit);
+            write_bytes(&mut profile, 1, &value_type);
+        }
+
+        let mut function_msgs = Vec::new();
+        let mut location_msgs = Vec::new();
+        for (stack, count, nanos) in &self.stacks {
+            let mut location_ids = Vec::with_capacity(stack.len());
+            // Samples list locations from the innermost frame
+            for frame in stack.iter().rev() {
+                let fkey = (frame.function.as_str(), frame.source.as_str(), frame.line_defined);
+                let function_id = match functions.get(&fkey) {
+                    Some(&id) => id,
+                    None => {
+                        let id = functions.len() as u64 + 1;
+                        let mut msg = Vec::new();
+                        write_varint_field(&mut msg, 1, id);
+                        write_varint_field(&mut msg, 2, strings.get(&frame.function));
+                        write_varint_field(&mut msg, 3, strings.get(&frame.function));
+                        write_varint_field(&mut msg, 4, strings.get(&frame.source));
+                        write_varint_field(&mut msg, 5, frame.line_defined.unwrap_or(0) as u64);
+                        function_msgs.push(msg);
+                        functions.insert(fkey, id);
+                        id
+                    }
+                };
+                let location_id = *locations.entry((function_id, frame.line)).or_insert_with(|| {
+                    let id = location_msgs.len() as u64 + 1;
+                    let mut line = Vec::new();
+                    write_varint_field(&mut line, 1, function_id);
+                    write_varint_field(&mut line, 2, frame.line.unwrap_or(0) as u64);
+                    let mut msg = Vec::new();
+                    write_varint_field(&mut msg, 1, id);
+                    write_bytes(&mut msg, 4, &line);
+                    location_msgs.push(msg);
+                    id
+                });
+                location_ids.push(location_id);
+            }
+
+            let mut sample = Vec::new();
+            write_packed(&mut sample, 1, &location_ids);
+            write_packed(&mut sample, 2, &[*count, *nanos]);
+            write_bytes(&mut profile, 2, &sample);
+        }
+
+        for msg in location_msgs {
+            write_bytes(&mut profile, 4, &msg);
+        }
+        for msg in function_msgs {
+            write_bytes(&mut profile, 5, &msg);
+        }
+
+        let time_nanos = (self.start_time.duration_since(UNIX_EPOCH)).map_or(0, |d| d.as_nanos() as u64);
+        let period = if cfg!(feature = "luau") {
+            "interrupts"
+        } else {
+            "instructions"
+        };
+        let period_type = value_type(&mut strings, period, "count");
+        for s in &strings.strings {
+            write_bytes(&mut profile, 6, s.as_bytes());
+        }
+        write_varint_field(&mut profile, 9, time_nanos);
+        write_varint_field(&mut profile, 10, self.duration.as_nanos() as u64);
+        write_bytes(&mut profile, 11, &period_type);
+        write_varint_field(&mut profile, 12, self.interval as u64);
+        profile
+    }
+}
+
+struct StringTable {
+    strings: Vec<StdString>,
+    indices: HashMap<StdString, u64>,
+}
+
+impl Default for StringTable {
+    fn default() -> Self {
+        // The first string in the table must be empty
+        StringTable {
+            strings: vec![StdString::new()],
+            indices: HashMap::from([(StdString::new(), 0)]),
+        }
+    }
+}
+
+impl StringTable {
+    fn get(&mut self, s: &str) -> u64 {
+        if let Some(&index) = self.indices.get(s) {
+            return index;
+        }
+        let index = self.strings.len() as u64;
+        self.strings.push(s.to_string());
+        self.indices.insert(s.to_string(), index);
+        index
+    }
+}
+
+fn value_type(strings: &mut StringTable, ty: &str, unit: &str) -> Vec<u8> {
+    let mut msg = Vec::new();
+    write_varint_field(&mut msg, 1, strings.get(ty));
+    write_varint_field(&mut msg, 2, strings.get(unit));
+    msg
+}
+
+// Protocol buffer encoding helpers
+
+fn write_varint(buf: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buf.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+fn write_varint_field(buf: &mut Vec<u8>, field: u32, value: u64) {
+    if value != 0 {
+        write_varint(buf, (field as u64) << 3);
+        write_varint(buf, value);
+    }
+}
+
+fn write_bytes(buf: &mut Vec<u8>, field: u32, bytes: &[u8]) {
+    write_varint(buf, ((field as u64) << 3) | 2);
+    write_varint(buf, bytes.len() as u64);
+    buf.extend_from_slice(bytes);
+}
+
+fn write_packed(buf: &mut Vec<u8>, field: u32, values: &[u64]) {
+    let mut packed = Vec::new();
+    for &value in values {
+        write_varint(&mut packed, value);
+    }
+    write_bytes(buf, field, &packed);
+}
@@ -0,0 +1,224 @@
+use std::fmt;
+use std::mem;
+use std::os::raw::c_int;
+use std::string::String as StdString;
+
+use ffi::{lua_Debug, lua_State};
+
+use crate::util::{linenumber_to_usize, ptr_to_lossy_str, ptr_to_str};
+
+// Number of the innermost and outermost levels captured from deep stacks (the rest are skipped)
+const HEAD_LEVELS: c_int = 16;
+const TAIL_LEVELS: c_int = 16;
+
+/// A Lua call stack captured at the time an error occurred.
+///
+/// Frames are ordered from the innermost (where the error was raised) to the outermost one.
+/// The [`Display`] implementation renders it in the same format as the Lua `debug.traceback`
+/// function.
+///
+/// Stack traces are obtained from errors using [`Error::stack_trace`]. For very deep stacks,
+/// only the innermost and the outermost frames are captured, the frames in between are skipped.
+///
+/// [`Error::stack_trace`]: crate::Error::stack_trace
+/// [`Display`]: fmt::Display
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StackTrace {
+    frames: Vec<StackFrame>,
+    skipped: usize,
+    skipped_at: usize,
+}
+
+/// A frame of a [`StackTrace`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StackFrame {
+    /// Name of the function, if it can be found.
+    pub function: Option<StdString>,
+    /// Source of the chunk where the function is defined.
+    pub source: Option<StdString>,
+    /// A "printable" version of `source` (`[C]` for C functions), as used in error messages.
+    pub short_src: Option<StdString>,
+    /// The line that was executing (not set for C functions).
+    pub line: Option<usize>,
+    /// The line where the definition of the function starts.
+    pub line_defined: Option<usize>,
+    /// Kind of the function.
+    pub kind: FrameKind,
+    /// Whether the function was invoked by a tail call (in which case Lua does not have
+    /// information about its callers).
+    ///
+    /// Always `false` for Luau.
+    pub is_tail_call: bool,
+}
+
+/// Kind of the function running in a [`StackFrame`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FrameKind {
+    /// A Lua function.
+    Lua,
+    /// A C (or Rust) function.
+    C,
+    /// The main part of a chunk.
+    ///
+    /// Luau does not distinguish it from Lua functions.
+    Main,
+}
+
+impl StackTrace {
+    /// Returns the captured frames, from the innermost one.
+    pub fn frames(&self) -> &[StackFrame] {
+        &self.frames
+    }
+
+    /// Returns the number of frames that were skipped because the stack is too deep.
+    pub fn skipped_frames(&self) -> usize {
+        self.skipped
+    }
+
+    /// Returns the position in [`frames`] where the frames were skipped (if any).
+    ///
+    /// The frames before it are the innermost ones, the frames from it are the outermost ones.
+    ///
+    /// [`frames`]: StackTrace::frames
+    pub fn skipped_at(&self) -> Option<usize> {
+        (self.skipped > 0).then_some(self.skipped_at)
+    }
+
+    /// Returns `true` if the stack trace does not contain any frames.
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    // Captures the call stack of the given thread, starting from the given level
+    pub(crate) unsafe fn capture(state: *mut lua_State, level: c_int) -> Self {
+        let mut ar: lua_Debug = mem::zeroed();
+        let mut trace = StackTrace::default();
+        if !get_stack(state, level, &mut ar) {
+            return trace;
+        }
+
+        // Levels in the `[skip_from, skip_to)` range are not captured
+        let (mut skip_from, mut skip_to) = (c_int::MAX, c_int::MAX);
+        let last = last_level(state);
+        if last - level + 1 > HEAD_LEVELS + TAIL_LEVELS {
+            (skip_from, skip_to) = (level + HEAD_LEVELS, last - TAIL_LEVELS + 1);
+        }
+
+        let mut level = level;
+        while get_stack(state, level, &mut ar) {
+            if level == skip_from {
+                trace.skipped = (skip_to - skip_from) as usize;
+                trace.skipped_at = trace.frames.len();
+                level = skip_to;
+                continue;
+            }
+            match StackFrame::new(state, level, &mut ar) {
+                Some(frame) => trace.frames.push(frame),
+                // Lua 5.1 represents tail calls as separate frames
+                None => {
+                    if let Some(frame) = trace.frames.last_mut() {
+                        frame.is_tail_call = true;
+                    }
+                }
+            }
+            level += 1;
+        }
+        trace
+    }
+}
+
+impl fmt::Display for StackTrace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "stack traceback:")?;
+        for (i, frame) in self.frames.iter().enumerate() {
+            if self.skipped > 0 && i == self.skipped_at {
+                write!(f, "\n\t...\t(skipping {} levels)", self.skipped)?;
+            }
+            write!(f, "\n\t{frame}")?;
+            if frame.is_tail_call {
+                write!(f, "\n\t(...tail calls...)")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl StackFrame {
+    // Returns `None` for Lua 5.1 tail call frames
+    unsafe fn new(state: *mut lua_State, level: c_int, ar: &mut lua_Debug) -> Option<Self> {
+        #[cfg(any(feature = "lua54", feature = "lua53", feature = "lua52"))]
+        ffi::lua_getinfo(state, cstr!("Slnt"), ar);
+        #[cfg(any(feature = "lua51", feature = "luajit"))]
+        ffi::lua_getinfo(state, cstr!("Sln"), ar);
+        #[cfg(feature = "luau")]
+        ffi::lua_getinfo(state, level, cstr!("snl"), ar);
+        #[cfg(not(feature = "luau"))]
+        let _ = level;
+
+        let kind = match ptr_to_str(ar.what) {
+            Some("C") => FrameKind::C,
+            Some("main") => FrameKind::Main,
+            Some("tail") => return None,
+            _ => FrameKind::Lua,
+        };
+        #[cfg(not(feature = "luau"))]
+        let short_src = ptr_to_lossy_str(ar.short_src.as_ptr());
+        #[cfg(feature = "luau")]
+        let short_src = ptr_to_lossy_str(ar.short_src);
+
+        Some(StackFrame {
+            function: ptr_to_lossy_str(ar.name).map(|name| name.into_owned()),
+            source: ptr_to_lossy_str(ar.source).map(|source| source.into_owned()),
+            short_src: short_src.map(|short_src| short_src.into_owned()),
+            line: linenumber_to_usize(ar.currentline).filter(|_| kind != FrameKind::C),
+            line_defined: linenumber_to_usize(ar.linedefined).filter(|_| kind != FrameKind::C),
+            kind,
+            #[cfg(any(feature = "lua54", feature = "lua53", feature = "lua52"))]
+            is_tail_call: ar.istailcall != 0,
+            #[cfg(not(any(feature = "lua54", feature = "lua53", feature = "lua52")))]
+            is_tail_call: false,
+        })
+    }
+}
+
+impl fmt::Display for StackFrame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let short_src = self.short_src.as_deref().unwrap_or("?");
+        match self.line {
+            Some(line) => write!(f, "{short_src}:{line}: in ")?,
+            None => write!(f, "{short_src}: in ")?,
+        }
+        match (&self.function, self.line_defined) {
+            _ if self.kind == FrameKind::Main => write!(f, "main chunk"),
+            (Some(name), _) => write!(f, "function '{name}'"),
+            (None, Some(line)) if self.kind == FrameKind::Lua => write!(f, "function <{short_src}:{line}>"),
+            _ => write!(f, "?"),
+        }
+    }
+}
+
+unsafe fn get_stack(state: *mut lua_State, level: c_int, ar: &mut lua_Debug) -> bool {
+    #[cfg(not(feature = "luau"))]
+    return ffi::lua_getstack(state, level, ar) != 0;
+    #[cfg(feature = "luau")]
+    return ffi::lua_getinfo(state, level, cstr!(""), ar) != 0;
+}
+
+// Finds the last valid stack level using binary search (as `luaL_traceback` does)
+unsafe fn last_level(state: *mut lua_State) -> c_int {
+    let mut ar: lua_Debug = mem::zeroed();
+    let (mut li, mut le) = (1, 1);
+    while get_stack(state, le, &mut ar) {
+        li = le;
+        le *= 2;
+    }
+    while li < le {
+        let m = (li + le) / 2;
+        if get_stack(state, m, &mut ar) {
+            li = m + 1;
+        } else {
+            le = m;
+        }
+    }
+    le - 1
+}
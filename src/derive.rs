@@ -0,0 +1,103 @@
+//! Support code for the derive macros from `mlua_derive`.
+//!
+//! Items here are public only to be reachable from the generated code and are not part of the
+//! stable API.
+
+use crate::error::Error;
+
+#[cfg(feature = "macros")]
+use crate::{error::Result, table::Table, userdata::UserDataMethods, value::Value};
+
+/// Used by `#[derive(UserData)]` when the type has no `#[mlua::methods]` impl block.
+///
+/// Inherent `__mlua_add_methods` generated by the attribute takes precedence over this one.
+#[cfg(feature = "macros")]
+pub trait UserDataMethodsFallback: Sized {
+    fn __mlua_add_methods<M: UserDataMethods<Self>>(_methods: &mut M) {}
+}
+
+#[cfg(feature = "macros")]
+impl<T> UserDataMethodsFallback for T {}
+
+/// Location of a field within the outermost type converted by the derived `FromLua`.
+///
+/// Derived implementations pass it down to the nested conversions (see `FromLua::from_lua_field`),
+/// so the error of a deeply nested field can name the whole path (eg. `outer.inner.value`).
+#[derive(Clone, Copy, Debug)]
+pub enum FieldPath<'a> {
+    Root,
+    Field {
+        parent: &'a FieldPath<'a>,
+        to: &'a str,
+        name: &'a str,
+    },
+}
+
+impl<'a> FieldPath<'a> {
+    /// Returns the path of the field `name` of type `to`, nested in `self`.
+    pub fn field(&'a self, to: &'a str, name: &'a str) -> Self {
+        FieldPath::Field {
+            parent: self,
+            to,
+            name,
+        }
+    }
+
+    /// Rewrites an error returned by the conversion of the field to name the field path.
+    ///
+    /// Only conversion errors are changed, other errors are returned as is.
+    pub fn error(&self, err: Error) -> Error {
+        let (mut names, mut outer_to) = (Vec::new(), "");
+        let mut path = self;
+        while let FieldPath::Field { parent, to, name } = path {
+            names.push(*name);
+            outer_to = to;
+            path = parent;
+        }
+        if names.is_empty() {
+            return err;
+        }
+        names.reverse();
+        let field = names.join(".");
+
+        match err {
+            Error::FromLuaConversionError { from, to, message } => {
+                let message = match message {
+                    Some(message) => format!("field `{field}`: {message}"),
+                    None => format!("field `{field}`: expected {to}"),
+                };
+                Error::from_lua_conversion(from, outer_to, message)
+            }
+            err => err,
+        }
+    }
+}
+
+/// Copies all pairs of a flattened field `value` (that must be a table) into `table`.
+#[cfg(feature = "macros")]
+pub fn flatten_into(table: &Table, value: Value, to: &str, field: &str) -> Result<()> {
+    match value {
+        Value::Table(value) => value.for_each(|k: Value, v: Value| table.raw_set(k, v)),
+        Value::Nil => Ok(()),
+        _ => Err(Error::ToLuaConversionError {
+            from: to.to_string(),
+            to: "table",
+            message: Some(format!("flattened field `{field}` must be converted to a table")),
+        }),
+    }
+}
+
+/// Returns an error for a value that is not one of the expected enum variants.
+#[cfg(feature = "macros")]
+pub fn unknown_variant(value: &Value, to: &str, variants: &[&str]) -> Error {
+    let variants = variants.iter().map(|v| format!("`{v}`")).collect::<Vec<_>>();
+    let message = match value {
+        Value::String(s) => format!("unknown variant `{}`", s.display()),
+        _ => "unknown variant".to_string(),
+    };
+    Error::from_lua_conversion(
+        value.type_name(),
+        to,
+        format!("{message}, expected one of {}", variants.join(", ")),
+    )
+}
@@ -7,6 +7,7 @@ use std::str::Utf8Error;
 use std::string::String as StdString;
 use std::sync::Arc;
 
+use crate::debug::StackTrace;
 use crate::private::Sealed;
 
 #[cfg(feature = "error-send")]
@@ -34,7 +35,10 @@ pub enum Error {
     /// The Lua VM returns this error when a builtin operation is performed on incompatible types.
     /// Among other things, this includes invoking operators on wrong types (such as calling or
     /// indexing a `nil` value).
-    RuntimeError(StdString),
+    ///
+    /// Contains the error message and, for errors raised by Lua code, the Lua call stack captured
+    /// when the error occurred (see [`Error::stack_trace`]).
+    RuntimeError(StdString, Option<Arc<StackTrace>>),
     /// Lua memory error, aka `LUA_ERRMEM`
     ///
     /// The Lua VM returns this error when the allocator does not return the requested memory, aka
@@ -117,6 +121,12 @@ pub enum Error {
     /// [`Thread::resume`]: crate::Thread::resume
     /// [`Thread::status`]: crate::Thread::status
     CoroutineUnresumable,
+    /// An asynchronous operation was cancelled.
+    ///
+    /// Returned by a cancelled (or timed out) `AsyncThread` and raised inside its coroutine at
+    /// the point where it awaits, so Lua code can catch it and clean up. Also returned when
+    /// awaiting a cancelled `Promise`.
+    Cancelled,
     /// An [`AnyUserData`] is not the expected type in a borrow.
     ///
     /// This error can only happen when manually using [`AnyUserData`], or when implementing
@@ -176,6 +186,8 @@ pub enum Error {
         traceback: StdString,
         /// Original error returned by the Rust code.
         cause: Arc<Error>,
+        /// Lua call stack captured when the error occurred.
+        stack_trace: Arc<StackTrace>,
     },
     /// A Rust panic that was previously resumed, returned again.
     ///
@@ -215,7 +227,7 @@ impl fmt::Display for Error {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
         match self {
             Error::SyntaxError { message, .. } => write!(fmt, "syntax error: {message}"),
-            Error::RuntimeError(msg) => write!(fmt, "runtime error: {msg}"),
+            Error::RuntimeError(msg, _) => write!(fmt, "runtime error: {msg}"),
             Error::MemoryError(msg) => {
                 write!(fmt, "memory error: {msg}")
             }
@@ -268,6 +280,7 @@ impl fmt::Display for Error {
                 }
             }
             Error::CoroutineUnresumable => write!(fmt, "coroutine is non-resumable"),
+            Error::Cancelled => write!(fmt, "operation was cancelled"),
             Error::UserDataTypeMismatch => write!(fmt, "userdata is not expected type"),
             Error::UserDataDestructed => write!(fmt, "userdata has been destructed"),
             Error::UserDataBorrowError => write!(fmt, "error borrowing userdata"),
@@ -283,10 +296,10 @@ impl fmt::Display for Error {
             Error::MismatchedRegistryKey => {
                 write!(fmt, "RegistryKey used from different Lua state")
             }
-            Error::CallbackError { cause, traceback } => {
+            Error::CallbackError { cause, traceback, .. } => {
                 // Trace errors down to the root
                 let (mut cause, mut full_traceback) = (cause, None);
-                while let Error::CallbackError { cause: cause2, traceback: traceback2 } = &**cause {
+                while let Error::CallbackError { cause: cause2, traceback: traceback2, .. } = &**cause {
                     cause = cause2;
                     full_traceback = Some(traceback2);
                 }
@@ -346,7 +359,7 @@ impl Error {
     /// Creates a new `RuntimeError` with the given message.
     #[inline]
     pub fn runtime<S: fmt::Display>(message: S) -> Self {
-        Error::RuntimeError(message.to_string())
+        Error::RuntimeError(message.to_string(), None)
     }
 
     /// Wraps an external error object.
@@ -367,6 +380,19 @@ impl Error {
         }
     }
 
+    /// Returns the Lua call stack captured when the error occurred.
+    ///
+    /// Available for errors raised by Lua code ([`Error::RuntimeError`]) and errors returned by
+    /// Rust callbacks ([`Error::CallbackError`]).
+    pub fn stack_trace(&self) -> Option<&StackTrace> {
+        match self {
+            Error::RuntimeError(_, stack_trace) => stack_trace.as_deref(),
+            Error::CallbackError { stack_trace, .. } => Some(stack_trace),
+            Error::WithContext { cause, .. } => cause.stack_trace(),
+            _ => None,
+        }
+    }
+
     /// An iterator over the chain of nested errors wrapped by this Error.
     pub fn chain(&self) -> impl Iterator<Item = &(dyn StdError + 'static)> {
         Chain {
@@ -385,6 +411,17 @@ impl Error {
         }
     }
 
+    /// Returns the location details of a syntax error.
+    ///
+    /// The details are parsed from the error message, `None` is returned if this is not a syntax
+    /// error or the message does not contain a location.
+    pub fn syntax_error_details(&self) -> Option<SyntaxErrorDetails> {
+        match self {
+            Error::SyntaxError { message, .. } => SyntaxErrorDetails::parse(message),
+            _ => None,
+        }
+    }
+
     pub(crate) fn bad_self_argument(to: &str, cause: Error) -> Self {
         Error::BadArgument {
             to: Some(to.to_string()),
@@ -407,6 +444,165 @@ impl Error {
     }
 }
 
+/// Location and other details of a syntax error.
+///
+/// Lua reports syntax errors as messages in the `chunkname:line: message` format. This type
+/// provides the components of such message, see [`Error::syntax_error_details`].
+///
+/// # Examples
+///
+/// ```
+/// # use mlua::Lua;
+/// let lua = Lua::new();
+/// let source = "local a = 1\nlocal b = a +\nreturn b";
+/// let err = lua.load(source).set_name("=example").exec().unwrap_err();
+/// let details = err.syntax_error_details().unwrap();
+/// assert_eq!(details.chunk_id(), "example");
+/// assert_eq!(details.line(), 3);
+/// # #[cfg(not(feature = "luau"))]
+/// assert_eq!(details.token(), Some("return"));
+/// println!("{}", details.code_frame(source));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyntaxErrorDetails {
+    chunk_id: StdString,
+    line: usize,
+    token: Option<StdString>,
+    message: StdString,
+}
+
+impl SyntaxErrorDetails {
+    /// Returns the chunk identifier as it appears in the error message.
+    ///
+    /// It's derived by Lua from the chunk name (see [`Chunk::set_name`]), for example `example`
+    /// for the `=example` name or `[string "..."]` for chunks without a special prefix.
+    ///
+    /// [`Chunk::set_name`]: crate::Chunk::set_name
+    pub fn chunk_id(&self) -> &str {
+        &self.chunk_id
+    }
+
+    /// Returns the (1-based) line number where the error occurred.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// Returns the offending token as reported by Lua (`<eof>` for the end of the source).
+    pub fn token(&self) -> Option<&str> {
+        self.token.as_deref()
+    }
+
+    /// Returns the error message without the location prefix.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Returns the (1-based) column of the offending token in bytes, if it can be found in the
+    /// `source`.
+    ///
+    /// Lua does not report columns, so this is a best-effort guess: the first occurrence of the
+    /// token on the error line is used, which may be an earlier occurrence of the same token
+    /// (e.g. the first `x` in `local x = x +`). The `source` must be the source code of the chunk
+    /// that failed to load.
+    pub fn column(&self, source: impl AsRef<[u8]>) -> Option<usize> {
+        let source = source.as_ref();
+        let text = (source.split(|&b| b == b'\n')).nth(self.line.checked_sub(1)?)?;
+        let text = text.strip_suffix(b"\r").unwrap_or(text);
+        match self.token.as_deref()? {
+            "<eof>" => Some(text.len() + 1),
+            "" => None,
+            token => (text.windows(token.len()))
+                .position(|w| w == token.as_bytes())
+                .map(|i| i + 1),
+        }
+    }
+
+    /// Renders the lines of the source code around the error, marking the offending token.
+    ///
+    /// The `source` must be the source code of the chunk that failed to load. The token position
+    /// is determined by [`SyntaxErrorDetails::column`].
+    pub fn code_frame(&self, source: impl AsRef<[u8]>) -> StdString {
+        let column = self.column(source.as_ref());
+        let source = StdString::from_utf8_lossy(source.as_ref());
+        let lines = (source.split('\n'))
+            .map(|line| line.strip_suffix('\r').unwrap_or(line))
+            .collect::<Vec<_>>();
+        let first = self.line.saturating_sub(2).max(1);
+        let last = (self.line + 2).min(lines.len()).max(self.line);
+        let width = last.to_string().len();
+
+        let mut frame = Vec::new();
+        for n in first..=last {
+            let text = lines.get(n - 1).copied().unwrap_or_default();
+            let marker = if n == self.line { '>' } else { ' ' };
+            frame.push(format!("{marker} {n:>width$} | {text}").trim_end().to_string());
+            if n != self.line {
+                continue;
+            }
+            let pointer = match column {
+                Some(column) => {
+                    // Keep tabs to align the pointer with the token
+                    let prefix = text.get(..column - 1).unwrap_or(text);
+                    let indent = (prefix.chars())
+                        .map(|c| if c == '\t' { '\t' } else { ' ' })
+                        .collect::<StdString>();
+                    let len = match self.token.as_deref() {
+                        Some("<eof>") | None => 1,
+                        Some(token) => token.chars().count().max(1),
+                    };
+                    format!("{indent}{} ", "^".repeat(len))
+                }
+                None => StdString::new(),
+            };
+            frame.push(format!("  {:width$} | {pointer}{}", "", self.message));
+        }
+        frame.join("\n")
+    }
+
+    // Extracts details from the error message reported by Lua
+    fn parse(message: &str) -> Option<Self> {
+        // Skip the chunk id of string chunks as it can contain arbitrary characters
+        let start = match message.strip_prefix("[string \"") {
+            Some(rest) => message.len() - rest.len() + rest.find("\"]")? + 2,
+            None => 0,
+        };
+        // Luau compiler reports errors without the chunk id
+        let candidates = (message[start..].match_indices(':')).map(|(i, _)| start + i + 1);
+        let (chunk_end, line, reason) =
+            (start == 0)
+                .then_some(0)
+                .into_iter()
+                .chain(candidates)
+                .find_map(|pos| {
+                    let rest = &message[pos..];
+                    let digits = rest.find(|c: char| !c.is_ascii_digit())?;
+                    let line = rest[..digits].parse::<usize>().ok()?;
+                    Some((pos.saturating_sub(1), line, rest[digits..].strip_prefix(": ")?))
+                })?;
+
+        // Lua reports the token at the end of message ("near 'token'"), Luau in the middle of it
+        // ("got 'token'")
+        let token = if let Some((_, token)) = reason.rsplit_once(" near ") {
+            let unquoted = token.strip_prefix('\'').and_then(|t| t.strip_suffix('\''));
+            Some(unquoted.unwrap_or(token))
+        } else if let Some((_, token)) = reason.rsplit_once(" got ") {
+            match token.strip_prefix('\'') {
+                Some(quoted) => quoted.find('\'').map(|end| &quoted[..end]),
+                None => token.split(';').next(),
+            }
+        } else {
+            None
+        };
+
+        Some(SyntaxErrorDetails {
+            chunk_id: message[..chunk_end].to_string(),
+            line,
+            token: token.map(|t| t.to_string()),
+            message: reason.to_string(),
+        })
+    }
+}
+
 /// Trait for converting [`std::error::Error`] into Lua [`Error`].
 pub trait ExternalError {
     fn into_lua_err(self) -> Error;
@@ -12,6 +12,9 @@ use crate::util::{
 };
 use crate::value::Value;
 
+#[cfg(any(feature = "lua54", feature = "lua53", feature = "luau"))]
+use crate::budget::{self, Budget, ExecutionBudget};
+
 #[cfg(feature = "async")]
 use {
     crate::thread::AsyncThread,
@@ -20,6 +23,7 @@ use {
     std::future::{self, Future},
     std::pin::Pin,
     std::task::{Context, Poll},
+    std::time::Duration,
 };
 
 /// Handle to an internal Lua function.
@@ -128,6 +132,71 @@ impl Function {
         }
     }
 
+    /// Calls the function with a limited execution budget.
+    ///
+    /// The function runs in a new Lua thread (coroutine) which is preempted once the
+    /// [`ExecutionBudget`] is exhausted. In this case [`Budget::Exhausted`] is returned with a
+    /// handle that can resume execution later (with a new budget), otherwise the results are
+    /// returned in [`Budget::Completed`]. This allows to fairly run many untrusted scripts on a
+    /// single thread.
+    ///
+    /// Code can only be preempted where it is allowed to yield, e.g. not inside Rust callbacks.
+    /// Yielding from the function itself (using `coroutine.yield`) is an error.
+    ///
+    /// For Lua 5.3/5.4, the budget is enforced by a hook set on the thread, which applies only
+    /// to the function itself (not to coroutines it creates). The hook previously set on the
+    /// thread (e.g. the global hook) keeps being called and is put back afterwards. For Luau, the
+    /// interrupt function (see [`Lua::set_interrupt`]) is replaced while the function is running.
+    ///
+    /// Lua 5.2 is not supported. It allows yielding from hooks, but has no means to check whether
+    /// the running code can yield (`lua_isyieldable`), so preempting code called by a C function
+    /// (such as a `table.sort` comparator) would raise an error instead of continuing.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Budget, ExecutionBudget, Function, Lua, Result};
+    /// # fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    /// let sum: Function = lua.load(r#"
+    ///     function(n)
+    ///         local sum = 0
+    ///         for i = 1, n do sum = sum + i end
+    ///         return sum
+    ///     end
+    /// "#).eval()?;
+    ///
+    /// let budget = ExecutionBudget::instructions(1000);
+    /// let mut state = sum.call_with_budget::<i64>(100_000, budget)?;
+    /// let mut slices = 1;
+    /// let result = loop {
+    ///     match state {
+    ///         Budget::Completed(result) => break result,
+    ///         Budget::Exhausted(preempted) => {
+    ///             // Other work can be done here
+    ///             state = preempted.resume(budget)?;
+    ///             slices += 1;
+    ///         }
+    ///     }
+    /// };
+    /// assert_eq!(result, 5_000_050_000);
+    /// assert!(slices > 1);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`Lua::set_interrupt`]: crate::Lua::set_interrupt
+    #[cfg(any(feature = "lua54", feature = "lua53", feature = "luau"))]
+    #[cfg_attr(docsrs, doc(cfg(any(feature = "lua54", feature = "lua53", feature = "luau"))))]
+    pub fn call_with_budget<R: FromLuaMulti>(
+        &self,
+        args: impl IntoLuaMulti,
+        budget: ExecutionBudget,
+    ) -> Result<Budget<R>> {
+        let thread = unsafe { self.0.lua.lock().create_thread(self)? };
+        budget::run(thread, args, budget)
+    }
+
     /// Returns a future that, when polled, calls `self`, passing `args` as function arguments,
     /// and drives the execution.
     ///
@@ -672,6 +741,33 @@ impl<R: FromLuaMulti> AsyncCallFuture<R> {
     pub(crate) fn error(err: Error) -> Self {
         AsyncCallFuture(Err(err))
     }
+
+    /// Cancels the call.
+    ///
+    /// See [`AsyncThread::cancel`] for details.
+    pub fn cancel(&mut self) -> bool {
+        match &mut self.0 {
+            Ok(thread) => thread.cancel(),
+            Err(_) => false,
+        }
+    }
+
+    /// Sets a time limit for the call, counting from now.
+    ///
+    /// See [`AsyncThread::with_timeout`] for details. If the time limit cannot be set, the error is
+    /// returned when the call is awaited.
+    pub fn with_timeout(self, timeout: Duration) -> Self {
+        AsyncCallFuture(self.0.and_then(|thread| thread.with_timeout(timeout)))
+    }
+
+    /// Sets the execution budget for every poll of the call.
+    ///
+    /// See [`AsyncThread::with_budget`] for details.
+    #[cfg(any(feature = "lua54", feature = "lua53", feature = "luau"))]
+    #[cfg_attr(docsrs, doc(cfg(any(feature = "lua54", feature = "lua53", feature = "luau"))))]
+    pub fn with_budget(self, budget: Option<ExecutionBudget>) -> Self {
+        AsyncCallFuture(self.0.map(|thread| thread.with_budget(budget)))
+    }
 }
 
 #[cfg(feature = "async")]
@@ -0,0 +1,390 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::string::String as StdString;
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+use rustc_hash::{FxHashMap, FxHashSet};
+
+use crate::chunk_cache::fnv1a_64;
+use crate::error::{Error, ErrorContext, Result};
+use crate::function::Function;
+use crate::multi::MultiValue;
+use crate::state::Lua;
+use crate::table::Table;
+use crate::value::{Nil, Value};
+use crate::vfs::{normalize_path, Vfs};
+
+/// A registry of Lua modules that supports hot reloading.
+///
+/// Once created using [`ModuleRegistry::new`], it replaces the global `require` function with the
+/// one that keeps track of the loaded modules, which modules each of them requires and the files
+/// they are loaded from (using `package.path`, through the virtual filesystem if it is set).
+///
+/// When a module changes, it can be reloaded together with all the modules that depend on it
+/// (directly or indirectly). Modules are reloaded in the dependency order, so a dependent module
+/// always gets new versions of the modules it requires. Changes are either detected by
+/// [`ModuleRegistry::poll`], which compares the source files with the versions that were loaded,
+/// or reported by [`ModuleRegistry::notify_changed`] (e.g. from a file watcher).
+///
+/// If the new version of a module is a table with the `__reload` function, the function is
+/// called with the previous version of the module, so it can migrate its state.
+///
+/// Only modules required after the registry is created are tracked. Values referencing the old
+/// versions of modules outside of them (e.g. in the main script) are not updated.
+///
+/// Not available with Luau, which does not have the `package` library and loads modules using
+/// a `Require` implementation instead.
+///
+/// # Examples
+///
+/// ```
+/// # use std::sync::Arc;
+/// # use mlua::{Lua, MemoryVfs, ModuleRegistry, Result};
+/// # fn main() -> Result<()> {
+/// let lua = Lua::new();
+/// let vfs = Arc::new(MemoryVfs::new().with_file("counter.lua", "return { count = 0 }"));
+/// lua.set_vfs(vfs.clone())?;
+/// let registry = ModuleRegistry::new(&lua)?;
+///
+/// lua.load("require('counter').count = 5").exec()?;
+///
+/// vfs.insert("counter.lua", r#"
+///     local M = { count = 0 }
+///     function M.__reload(old) M.count = old.count end
+///     return M
+/// "#);
+/// assert_eq!(registry.poll()?, ["counter"]);
+/// assert_eq!(lua.load("return require('counter').count").eval::<i64>()?, 5);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct ModuleRegistry {
+    require: Function,
+    loaded: Table,
+    state: Arc<Mutex<RegistryState>>,
+}
+
+#[derive(Default)]
+struct RegistryState {
+    modules: FxHashMap<StdString, Module>,
+    // Modules being loaded (the innermost is the last one)
+    loading: Vec<StdString>,
+    changed: BTreeSet<StdString>,
+}
+
+#[derive(Clone, Default)]
+struct Module {
+    source: Option<Source>,
+    dependencies: BTreeSet<StdString>,
+}
+
+#[derive(Clone)]
+struct Source {
+    path: PathBuf,
+    // Hash of the file contents when the module was loaded
+    hash: Option<u64>,
+}
+
+impl ModuleRegistry {
+    /// Creates a new registry, replacing the global `require` function with the tracking one.
+    ///
+    /// Requires the `package` standard library to be loaded.
+    pub fn new(lua: &Lua) -> Result<Self> {
+        let globals = lua.globals();
+        let require = match globals.raw_get("require")? {
+            Value::Function(require) => require,
+            _ => return Err(Error::runtime("package library is not loaded")),
+        };
+        let loaded = unsafe {
+            lua.exec_raw::<Table>((), |state| {
+                ffi::luaL_getsubtable(state, ffi::LUA_REGISTRYINDEX, ffi::LUA_LOADED_TABLE);
+            })?
+        };
+        let registry = ModuleRegistry {
+            require,
+            loaded,
+            state: Arc::new(Mutex::new(RegistryState::default())),
+        };
+
+        let this = registry.clone();
+        let require = lua.create_function(move |lua, name: StdString| this.require(lua, name))?;
+        globals.raw_set("require", require)?;
+        Ok(registry)
+    }
+
+    /// Returns names of the tracked modules, sorted alphabetically.
+    pub fn modules(&self) -> Vec<StdString> {
+        let mut modules = self.state.lock().modules.keys().cloned().collect::<Vec<_>>();
+        modules.sort();
+        modules
+    }
+
+    /// Returns names of the modules required by the given module.
+    pub fn dependencies(&self, name: &str) -> Vec<StdString> {
+        let state = self.state.lock();
+        let module = state.modules.get(name);
+        module
+            .map(|m| m.dependencies.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Returns names of the modules that require the given module.
+    pub fn dependents(&self, name: &str) -> Vec<StdString> {
+        let mut dependents = self.state.lock().dependents(name);
+        dependents.sort();
+        dependents
+    }
+
+    /// Returns path to the file the module is loaded from.
+    ///
+    /// Modules loaded without a file (e.g. from `package.preload`) have no path.
+    pub fn source_path(&self, name: &str) -> Option<PathBuf> {
+        let state = self.state.lock();
+        let module = state.modules.get(name)?;
+        module.source.as_ref().map(|source| source.path.clone())
+    }
+
+    /// Marks the module as changed, so it is reloaded by the next [`ModuleRegistry::reload_changed`]
+    /// or [`ModuleRegistry::poll`] call.
+    pub fn notify_changed(&self, name: &str) {
+        self.state.lock().changed.insert(name.to_string());
+    }
+
+    /// Marks the modules loaded from the given file as changed.
+    ///
+    /// Returns `false` if there are no such modules or the Lua instance is destroyed.
+    pub fn notify_file_changed(&self, path: impl AsRef<Path>) -> bool {
+        let Ok(lua) = self.lua() else {
+            return false;
+        };
+        let vfs = lua.vfs();
+        let path = normalize_source_path(vfs.as_deref(), path.as_ref());
+        let mut state = self.state.lock();
+        let names = (state.modules.iter())
+            .filter(|(_, m)| m.source.as_ref().is_some_and(|source| source.path == path))
+            .map(|(name, _)| name.clone())
+            .collect::<Vec<_>>();
+        let found = !names.is_empty();
+        state.changed.extend(names);
+        found
+    }
+
+    /// Checks the source files of the tracked modules for changes and reloads the changed modules
+    /// together with their dependents.
+    ///
+    /// Returns names of the reloaded modules in the order they were reloaded.
+    /// See [`ModuleRegistry::reload_changed`] for details.
+    pub fn poll(&self) -> Result<Vec<StdString>> {
+        let vfs = self.lua()?.vfs();
+        let sources = (self.state.lock().modules.iter())
+            .filter_map(|(name, m)| Some((name.clone(), m.source.clone()?)))
+            .collect::<Vec<_>>();
+        for (name, source) in sources {
+            let hash = source_hash(vfs.as_deref(), &source.path);
+            // Removed (or unreadable) files do not trigger reloading
+            if hash.is_some() && hash != source.hash {
+                self.notify_changed(&name);
+            }
+        }
+        self.reload_changed()
+    }
+
+    /// Reloads the given module together with its dependents.
+    ///
+    /// Returns names of the reloaded modules in the order they were reloaded.
+    pub fn reload(&self, name: &str) -> Result<Vec<StdString>> {
+        self.notify_changed(name);
+        self.reload_changed()
+    }
+
+    /// Reloads the modules marked as changed together with their dependents.
+    ///
+    /// Returns names of the reloaded modules in the order they were reloaded.
+    ///
+    /// Reloading stops at the first module that fails to load (or which `__reload` function
+    /// fails). Such module keeps its previous version until it changes again, the modules
+    /// reloaded before it keep their new versions.
+    pub fn reload_changed(&self) -> Result<Vec<StdString>> {
+        let lua = self.lua()?;
+        let order = {
+            let mut state = self.state.lock();
+            let changed = std::mem::take(&mut state.changed);
+            state.reload_order(changed)
+        };
+
+        let mut reloaded = Vec::with_capacity(order.len());
+        for name in order {
+            let old_value = self.loaded.raw_get::<Value>(&*name)?;
+            let old_module = self.state.lock().modules.get(&name).cloned();
+            self.loaded.raw_set(&*name, Nil)?;
+
+            if let Err(err) = self.reload_module(&lua, &name, &old_value) {
+                self.loaded.raw_set(&*name, old_value)?;
+                let mut state = self.state.lock();
+                match old_module {
+                    Some(mut module) => {
+                        // Do not try to reload the same version again
+                        if let Some(source) = &mut module.source {
+                            source.hash = source_hash(lua.vfs().as_deref(), &source.path);
+                        }
+                        state.modules.insert(name.clone(), module);
+                    }
+                    None => {
+                        state.modules.remove(&name);
+                    }
+                }
+                return Err(err.context(format!("failed to reload module '{name}'")));
+            }
+            reloaded.push(name);
+        }
+        Ok(reloaded)
+    }
+
+    fn reload_module(&self, lua: &Lua, name: &str, old_value: &Value) -> Result<()> {
+        self.require(lua, name.to_string())?;
+        if old_value.is_nil() {
+            return Ok(());
+        }
+        if let Value::Table(module) = self.loaded.raw_get::<Value>(name)? {
+            if let Value::Function(hook) = module.raw_get::<Value>("__reload")? {
+                hook.call::<()>(old_value)?;
+            }
+        }
+        Ok(())
+    }
+
+    // Implementation of the tracking `require` function
+    fn require(&self, lua: &Lua, name: StdString) -> Result<MultiValue> {
+        let is_loaded = !matches!(
+            self.loaded.raw_get::<Value>(&*name)?,
+            Value::Nil | Value::Boolean(false)
+        );
+        {
+            let mut state = self.state.lock();
+            if let Some(parent) = state.loading.last().cloned() {
+                let parent = state.modules.entry(parent).or_default();
+                parent.dependencies.insert(name.clone());
+            }
+            if !is_loaded {
+                state.loading.push(name.clone());
+                state.modules.insert(name.clone(), Module::default());
+            }
+        }
+        if is_loaded {
+            return self.require.call(&*name);
+        }
+
+        let result = self.require.call::<MultiValue>(&*name);
+        let source = match result {
+            Ok(_) => find_source(lua, &name)?,
+            Err(_) => None,
+        };
+        let mut state = self.state.lock();
+        state.loading.pop();
+        if result.is_ok() {
+            state.modules.entry(name).or_default().source = source;
+        } else {
+            state.modules.remove(&name);
+        }
+        result
+    }
+
+    fn lua(&self) -> Result<Lua> {
+        (self.loaded.0.lua.try_upgrade()).ok_or_else(|| Error::runtime("Lua instance is destroyed"))
+    }
+}
+
+impl RegistryState {
+    fn dependents(&self, name: &str) -> Vec<StdString> {
+        (self.modules.iter())
+            .filter(|(_, m)| m.dependencies.contains(name))
+            .map(|(name, _)| name.clone())
+            .collect()
+    }
+
+    // Returns the changed modules and their dependents, dependencies first
+    fn reload_order(&self, changed: BTreeSet<StdString>) -> Vec<StdString> {
+        let mut affected = BTreeSet::new();
+        let mut queue = changed.into_iter().collect::<Vec<_>>();
+        while let Some(name) = queue.pop() {
+            if !affected.contains(&name) {
+                queue.extend(self.dependents(&name));
+                affected.insert(name);
+            }
+        }
+
+        let mut order = Vec::with_capacity(affected.len());
+        let mut visited = FxHashSet::default();
+        for name in &affected {
+            self.visit(name, &affected, &mut visited, &mut order);
+        }
+        order
+    }
+
+    fn visit<'a>(
+        &'a self,
+        name: &'a str,
+        affected: &BTreeSet<StdString>,
+        visited: &mut FxHashSet<&'a str>,
+        order: &mut Vec<StdString>,
+    ) {
+        if !visited.insert(name) {
+            return;
+        }
+        if let Some(module) = self.modules.get(name) {
+            for dep in module.dependencies.iter().filter(|dep| affected.contains(*dep)) {
+                self.visit(dep, affected, visited, order);
+            }
+        }
+        order.push(name.to_string());
+    }
+}
+
+// Finds the file the module is loaded from using `package.path` (as the standard searcher does)
+fn find_source(lua: &Lua, name: &str) -> Result<Option<Source>> {
+    let Some(package) = lua.globals().raw_get::<Option<Table>>("package")? else {
+        return Ok(None);
+    };
+    if let Some(preload) = package.raw_get::<Option<Table>>("preload")? {
+        if !preload.raw_get::<Value>(name)?.is_nil() {
+            return Ok(None);
+        }
+    }
+    let Some(template) = package.raw_get::<Option<StdString>>("path")? else {
+        return Ok(None);
+    };
+
+    let vfs = lua.vfs();
+    let name_path = name.replace('.', "/");
+    for template in template.split(';').filter(|t| !t.is_empty()) {
+        let path = PathBuf::from(template.replace('?', &name_path));
+        let is_file = match &vfs {
+            Some(vfs) => vfs.is_file(&path),
+            None => path.is_file(),
+        };
+        if is_file {
+            let path = normalize_source_path(vfs.as_deref(), &path);
+            let hash = source_hash(vfs.as_deref(), &path);
+            return Ok(Some(Source { path, hash }));
+        }
+    }
+    Ok(None)
+}
+
+fn normalize_source_path(vfs: Option<&dyn Vfs>, path: &Path) -> PathBuf {
+    match vfs {
+        Some(_) => normalize_path(path),
+        None => fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf()),
+    }
+}
+
+fn source_hash(vfs: Option<&dyn Vfs>, path: &Path) -> Option<u64> {
+    let data = match vfs {
+        Some(vfs) => vfs.read(path),
+        None => fs::read(path),
+    };
+    data.ok().map(|data| fnv1a_64(&data))
+}
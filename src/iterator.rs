@@ -0,0 +1,192 @@
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+
+use crate::error::{Error, Result};
+use crate::function::Function;
+use crate::multi::MultiValue;
+use crate::state::Lua;
+use crate::traits::IntoLuaMulti;
+use crate::types::MaybeSend;
+use crate::value::Nil;
+
+#[cfg(feature = "lua54")]
+use crate::table::Table;
+
+#[cfg(feature = "async")]
+use {
+    futures_util::stream::Stream,
+    std::future,
+    std::task::{ready, Poll},
+};
+
+/// Iterator for the Lua generic `for` loop, created from a Rust iterator or stream.
+///
+/// Converts to the values expected by the generic `for`: the iterator function, the (unused)
+/// state and control values, and in Lua 5.4 the closing value that drops the underlying Rust
+/// iterator when the loop exits (even by `break` or an error).
+///
+/// See [`Lua::create_iterator`] and [`Lua::create_async_iterator`].
+#[derive(Clone, Debug)]
+pub struct ForIterator {
+    func: Function,
+    #[cfg(feature = "lua54")]
+    closing: Table,
+}
+
+impl ForIterator {
+    fn new<T: MaybeSend + 'static>(lua: &Lua, func: Function, source: Arc<Mutex<Option<T>>>) -> Result<Self> {
+        #[cfg(feature = "lua54")]
+        let closing = {
+            let metatable = lua.create_table()?;
+            let close = lua.create_function(move |_, ()| {
+                drop(source.lock().take());
+                Ok(())
+            })?;
+            metatable.raw_set("__close", close)?;
+            let closing = lua.create_table()?;
+            closing.set_metatable(Some(metatable))?;
+            closing
+        };
+        #[cfg(not(feature = "lua54"))]
+        let _ = (lua, source);
+
+        Ok(ForIterator {
+            func,
+            #[cfg(feature = "lua54")]
+            closing,
+        })
+    }
+
+    /// Returns the iterator function.
+    ///
+    /// Each call of the function returns the next item, or nothing when the iteration is finished.
+    pub fn function(&self) -> &Function {
+        &self.func
+    }
+}
+
+impl IntoLuaMulti for ForIterator {
+    #[inline]
+    fn into_lua_multi(self, lua: &Lua) -> Result<MultiValue> {
+        #[cfg(feature = "lua54")]
+        return (self.func, Nil, Nil, self.closing).into_lua_multi(lua);
+        #[cfg(not(feature = "lua54"))]
+        return (self.func, Nil, Nil).into_lua_multi(lua);
+    }
+}
+
+impl Lua {
+    /// Creates a Lua iterator for the generic `for` loop from a Rust iterator.
+    ///
+    /// Each item is converted to the loop variables, so the iterator should produce values whose
+    /// first element is not `nil` (which would end the loop). The returned [`ForIterator`] can be
+    /// returned from a Rust function (or passed to Lua in any other way as multiple values).
+    ///
+    /// The underlying iterator is dropped once it's exhausted. In Lua 5.4, it's also dropped as
+    /// soon as the loop exits early (by `break`, `return` or an error), using a to-be-closed
+    /// value. Otherwise it's dropped when the iterator function is garbage collected.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Lua, Result};
+    /// # fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    /// let items = lua.create_function(|lua, ()| {
+    ///     lua.create_iterator(["a", "b", "c"].into_iter().enumerate().map(|(i, s)| (i + 1, s)))
+    /// })?;
+    /// lua.globals().set("items", items)?;
+    /// let joined = lua.load(r#"
+    ///     local s = ""
+    ///     for i, item in items() do s = s .. i .. "=" .. item .. " " end
+    ///     return s
+    /// "#).eval::<String>()?;
+    /// assert_eq!(joined, "1=a 2=b 3=c ");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn create_iterator<I>(&self, iter: I) -> Result<ForIterator>
+    where
+        I: Iterator + MaybeSend + 'static,
+        I::Item: IntoLuaMulti,
+    {
+        let source = Arc::new(Mutex::new(Some(iter)));
+        let source2 = source.clone();
+        let func = self.create_function(move |lua, ()| {
+            let mut source = source2.try_lock().ok_or(Error::RecursiveMutCallback)?;
+            match source.as_mut().and_then(Iterator::next) {
+                Some(item) => item.into_lua_multi(lua),
+                None => {
+                    let iter = source.take();
+                    drop(source);
+                    drop(iter);
+                    Ok(MultiValue::new())
+                }
+            }
+        })?;
+        ForIterator::new(self, func, source)
+    }
+
+    /// Creates a Lua iterator for the generic `for` loop from a Rust stream.
+    ///
+    /// The iterator function is asynchronous (see [`Lua::create_async_function`]), so the loop
+    /// can only be run in an async context, e.g. using [`Chunk::exec_async`]. Otherwise it works
+    /// the same way as [`Lua::create_iterator`].
+    ///
+    /// Lua 5.1 does not allow yielding from the generic `for` iterator function, so there the
+    /// iterator function can only be called directly.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Lua, Result};
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    /// let numbers = lua.create_function(|lua, n: i64| {
+    ///     lua.create_async_iterator(futures_util::stream::iter(1..=n))
+    /// })?;
+    /// lua.globals().set("numbers", numbers)?;
+    /// let sum = lua.load(r#"
+    ///     local sum = 0
+    ///     for i in numbers(10) do sum = sum + i end
+    ///     return sum
+    /// "#).eval_async::<i64>().await?;
+    /// assert_eq!(sum, 55);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`Chunk::exec_async`]: crate::Chunk::exec_async
+    #[cfg(feature = "async")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+    pub fn create_async_iterator<S>(&self, stream: S) -> Result<ForIterator>
+    where
+        S: Stream + MaybeSend + 'static,
+        S::Item: IntoLuaMulti,
+    {
+        let source = Arc::new(Mutex::new(Some(Box::pin(stream))));
+        let source2 = source.clone();
+        let func = self.create_async_function(move |lua, ()| {
+            let source = source2.clone();
+            future::poll_fn(move |cx| {
+                let mut source = source.lock();
+                let item = match source.as_mut() {
+                    Some(stream) => ready!(stream.as_mut().poll_next(cx)),
+                    None => None,
+                };
+                Poll::Ready(match item {
+                    Some(item) => item.into_lua_multi(&lua),
+                    None => {
+                        let stream = source.take();
+                        drop(source);
+                        drop(stream);
+                        Ok(MultiValue::new())
+                    }
+                })
+            })
+        })?;
+        ForIterator::new(self, func, source)
+    }
+}
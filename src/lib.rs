@@ -72,16 +72,30 @@
 #[macro_use]
 mod macros;
 
+#[cfg(any(feature = "lua54", feature = "lua53", feature = "luau"))]
+mod budget;
 mod buffer;
 mod chunk;
+mod chunk_cache;
 mod conversion;
 mod debug;
+mod derive;
 mod error;
 mod function;
+#[cfg(not(feature = "luau"))]
+mod hot_reload;
+mod iterator;
 #[cfg(any(feature = "luau", doc))]
 mod luau;
 mod memory;
 mod multi;
+mod persist;
+#[cfg(feature = "send")]
+mod pool;
+#[cfg(feature = "async")]
+mod promise;
+mod sandbox;
+mod scheduler;
 mod scope;
 mod state;
 mod stdlib;
@@ -89,11 +103,14 @@ mod string;
 mod table;
 mod thread;
 mod traits;
+mod transfer;
+mod typedef;
 mod types;
 mod userdata;
 mod util;
 mod value;
 mod vector;
+mod vfs;
 
 pub mod prelude;
 
@@ -101,10 +118,18 @@ pub use bstr::BString;
 pub use ffi::{self, lua_CFunction, lua_State};
 
 pub use crate::chunk::{AsChunk, Chunk, ChunkMode};
-pub use crate::debug::{Debug, DebugEvent, DebugNames, DebugSource, DebugStack};
-pub use crate::error::{Error, ErrorContext, ExternalError, ExternalResult, Result};
+pub use crate::chunk_cache::{ChunkCache, ChunkCacheKey, DirChunkCache};
+pub use crate::debug::{
+    AllocationDiff, AllocationSite, Debug, DebugEvent, DebugNames, DebugSource, DebugStack, Frame, FrameKind,
+    FunctionSamples, LineSamples, MemoryProfile, Profile, Profiler, StackFrame, StackTrace,
+};
+pub use crate::error::{Error, ErrorContext, ExternalError, ExternalResult, Result, SyntaxErrorDetails};
 pub use crate::function::{Function, FunctionInfo};
+pub use crate::iterator::ForIterator;
 pub use crate::multi::{MultiValue, Variadic};
+pub use crate::persist::{PersistUserData, Persister};
+pub use crate::sandbox::SandboxPolicy;
+pub use crate::scheduler::{Clock, ManualClock, Scheduler, SystemClock, TaskId};
 pub use crate::scope::Scope;
 pub use crate::state::{GCMode, Lua, LuaOptions, WeakLua};
 pub use crate::stdlib::StdLib;
@@ -114,18 +139,38 @@ pub use crate::thread::{Thread, ThreadStatus};
 pub use crate::traits::{
     FromLua, FromLuaMulti, IntoLua, IntoLuaMulti, LuaNativeFn, LuaNativeFnMut, ObjectLike,
 };
+pub use crate::transfer::CloneAcrossStates;
+pub use crate::typedef::TypeInfo;
 pub use crate::types::{
-    AppDataRef, AppDataRefMut, Either, Integer, LightUserData, MaybeSend, Number, RegistryKey, VmState,
+    AppDataRef, AppDataRefMut, Either, Integer, LightUserData, MaybeSend, MaybeSync, Number, RegistryKey,
+    VmState,
 };
 pub use crate::userdata::{
     AnyUserData, MetaMethod, UserData, UserDataFields, UserDataMetatable, UserDataMethods, UserDataRef,
     UserDataRefMut, UserDataRegistry,
 };
 pub use crate::value::{Nil, Value};
+pub use crate::vfs::{ArchiveVfs, DirVfs, MemoryVfs, Vfs};
 
 #[cfg(not(feature = "luau"))]
 pub use crate::debug::HookTriggers;
 
+#[cfg(not(feature = "luau"))]
+#[cfg_attr(docsrs, doc(cfg(not(feature = "luau"))))]
+pub use crate::debug::{Breakpoint, Debugger, PauseReason, StepAction};
+
+#[cfg(all(feature = "dap", not(feature = "luau")))]
+#[cfg_attr(docsrs, doc(cfg(feature = "dap")))]
+pub use crate::debug::DapServer;
+
+#[cfg(not(feature = "luau"))]
+#[cfg_attr(docsrs, doc(cfg(not(feature = "luau"))))]
+pub use crate::hot_reload::ModuleRegistry;
+
+#[cfg(any(feature = "lua54", feature = "lua53", feature = "luau"))]
+#[cfg_attr(docsrs, doc(cfg(any(feature = "lua54", feature = "lua53", feature = "luau"))))]
+pub use crate::budget::{Budget, ExecutionBudget, Preempted};
+
 #[cfg(any(feature = "luau", doc))]
 #[cfg_attr(docsrs, doc(cfg(feature = "luau")))]
 pub use crate::{
@@ -136,9 +181,17 @@ pub use crate::{
     vector::Vector,
 };
 
+#[cfg(feature = "send")]
+#[cfg_attr(docsrs, doc(cfg(feature = "send")))]
+pub use crate::pool::{Channel, JobHandle, LuaPool, LuaPoolBuilder};
+
 #[cfg(feature = "async")]
 #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
-pub use crate::{thread::AsyncThread, traits::LuaNativeAsyncFn};
+pub use crate::{
+    promise::{Promise, PromiseStatus},
+    thread::AsyncThread,
+    traits::LuaNativeAsyncFn,
+};
 
 #[cfg(feature = "serde")]
 #[doc(inline)]
@@ -148,6 +201,10 @@ pub use crate::serde::{de::Options as DeserializeOptions, ser::Options as Serial
 #[cfg_attr(docsrs, doc(cfg(feature = "serde")))]
 pub mod serde;
 
+#[cfg(feature = "stdlib-ext")]
+#[cfg_attr(docsrs, doc(cfg(feature = "stdlib-ext")))]
+pub mod stdlib_ext;
+
 #[cfg(feature = "mlua_derive")]
 #[allow(unused_imports)]
 #[macro_use]
@@ -207,12 +264,159 @@ pub use mlua_derive::chunk;
 
 /// Derive [`FromLua`] for a Rust type.
 ///
-/// Current implementation generate code that takes [`UserData`] value, borrow it (of the Rust type)
-/// and clone.
+/// By default, the generated code takes a [`UserData`] value, borrows it (of the Rust type) and
+/// clones.
+///
+/// With the `#[lua(table)]` attribute, it instead reads a Lua value of the same shape as produced
+/// by [`derive@IntoLua`]:
+///
+/// * structs with named fields are read from a table, field by field
+/// * newtype structs are read transparently as the inner value
+/// * tuple structs are read from a sequence table
+/// * enums with unit variants only are read from a string
+/// * other enums are read from a table `{ Variant = value }` (or a string for unit variants), or
+///   from a table with a tag field, if `#[lua(table, tag = "...")]` is set
+///
+/// Errors name the path of the field that failed to convert, eg.
+/// ``error converting Lua boolean to Config (field `server.port`: expected number or string coercible
+/// to number)``.
+///
+/// The following `#[lua(...)]` attributes are supported:
+///
+/// * `table` (on type) - read a table (or a string for enums) instead of a [`UserData`] value
+/// * `tag = "key"` (on enum, with `table`) - use internally tagged representation, with variant
+///   name stored in the `key` field
+/// * `rename = "name"` (on field or variant) - use a different name in Lua
+/// * `default` or `default = "path"` (on field) - use [`Default::default`] (or the given function)
+///   if the value is `nil`
+/// * `skip` (on field) - do not read the field, use default value instead
+/// * `flatten` (on field) - read the field from the same table as the parent struct
+///
+/// ```
+/// use mlua::{FromLua, IntoLua, Lua, Result};
+///
+/// #[derive(Debug, PartialEq, FromLua, IntoLua)]
+/// #[lua(table)]
+/// struct Server {
+///     host: String,
+///     #[lua(default)]
+///     port: u16,
+/// }
+///
+/// #[derive(Debug, PartialEq, FromLua, IntoLua)]
+/// #[lua(table, tag = "kind")]
+/// enum Shape {
+///     Circle { r: f64 },
+///     #[lua(rename = "rect")]
+///     Rect { w: f64, h: f64 },
+/// }
+///
+/// fn main() -> Result<()> {
+///     let lua = Lua::new();
+///     let server: Server = lua.load("{ host = 'localhost' }").eval()?;
+///     assert_eq!(server, Server { host: "localhost".into(), port: 0 });
+///
+///     let shape: Shape = lua.load("{ kind = 'rect', w = 1, h = 2 }").eval()?;
+///     assert_eq!(shape, Shape::Rect { w: 1.0, h: 2.0 });
+///
+///     let err = lua.load("{ host = 1, port = 'x' }").eval::<Server>().unwrap_err();
+///     assert!(err.to_string().contains("field `port`"));
+///     Ok(())
+/// }
+/// ```
 #[cfg(feature = "macros")]
 #[cfg_attr(docsrs, doc(cfg(feature = "macros")))]
 pub use mlua_derive::FromLua;
 
+/// Derive [`IntoLua`] for a Rust type.
+///
+/// By default, the value is moved into a new userdata using [`Lua::create_any_userdata`], so that
+/// it can be read back by [`derive@FromLua`] (the type is usually registered with
+/// [`Lua::register_userdata_type`]).
+///
+/// With the `#[lua(table)]` attribute, structs are converted to tables (each field using its own
+/// [`IntoLua`] implementation) and enums to strings or tagged tables. Refer to [`derive@FromLua`]
+/// for the representation details and the supported `#[lua(...)]` attributes.
+#[cfg(feature = "macros")]
+#[cfg_attr(docsrs, doc(cfg(feature = "macros")))]
+pub use mlua_derive::IntoLua;
+
+/// Derive [`UserData`] for a Rust struct.
+///
+/// Every `pub` named field is exposed to Lua as a field with getter (requires `Clone`) and setter
+/// (requires [`FromLua`]). Methods are taken from an inherent impl block marked with the
+/// [`macro@methods`] attribute, if any.
+///
+/// Fields can be customized using the `#[lua(...)]` attribute:
+///
+/// * `rename = "name"` - use a different name in Lua
+/// * `skip` - do not expose the field
+/// * `readonly` - do not generate a setter
+///
+/// ```
+/// use mlua::{Lua, Result, UserData};
+///
+/// #[derive(Clone, mlua::FromLua, UserData)]
+/// struct Point {
+///     pub x: f64,
+///     #[lua(readonly)]
+///     pub y: f64,
+///     #[lua(skip)]
+///     pub tag: String,
+/// }
+///
+/// #[mlua::methods]
+/// impl Point {
+///     pub fn len(&self) -> f64 {
+///         (self.x * self.x + self.y * self.y).sqrt()
+///     }
+///
+///     pub fn scale(&mut self, k: f64) {
+///         self.x *= k;
+///         self.y *= k;
+///     }
+///
+///     #[lua(meta = "__add")]
+///     pub fn add(&self, other: Point) -> Point {
+///         Point { x: self.x + other.x, y: self.y + other.y, tag: String::new() }
+///     }
+/// }
+///
+/// fn main() -> Result<()> {
+///     let lua = Lua::new();
+///     lua.globals().set("p", Point { x: 3.0, y: 4.0, tag: String::new() })?;
+///     lua.load("assert(p:len() == 5); p:scale(2); assert(p.x == 6); assert((p + p).y == 16)").exec()
+/// }
+/// ```
+#[cfg(feature = "macros")]
+#[cfg_attr(docsrs, doc(cfg(feature = "macros")))]
+pub use mlua_derive::UserData;
+
+/// Registers methods of an inherent impl block for a type deriving [`UserData`].
+///
+/// Every `pub` function in the block is registered:
+///
+/// * `&self` methods as [`UserDataMethods::add_method`]
+/// * `&mut self` methods as [`UserDataMethods::add_method_mut`]
+/// * functions without receiver as [`UserDataMethods::add_function`]
+/// * `async fn` as their async counterparts (e.g. [`add_async_method`])
+///
+/// A leading `&Lua` argument (after receiver) receives the current Lua state.
+/// If the return type is `Result<T, E>`, the error is converted to [`Error`] using `?` operator.
+///
+/// Methods can be customized using the `#[lua(...)]` attribute:
+///
+/// * `rename = "name"` - use a different name in Lua
+/// * `skip` - do not register the method
+/// * `meta = "__name"` - register as a metamethod
+///
+/// Only one impl block per type can use this attribute.
+///
+/// [`add_async_method`]: UserDataMethods::add_async_method
+#[cfg(feature = "macros")]
+#[cfg_attr(docsrs, doc(cfg(feature = "macros")))]
+pub use mlua_derive::methods;
+
 /// Registers Lua module entrypoint.
 ///
 /// You can register multiple entrypoints as required.
@@ -261,6 +465,12 @@ pub use mlua_derive::lua_module;
 #[cfg(all(feature = "module", feature = "send"))]
 compile_error!("`send` feature is not supported in module mode");
 
+#[cfg(feature = "macros")]
+#[doc(hidden)]
+pub mod __private {
+    pub use crate::derive::*;
+}
+
 pub(crate) mod private {
     use super::*;
 
@@ -6,13 +6,15 @@ use std::ops::{Deref, DerefMut};
 use std::os::raw::{c_char, c_int, c_void};
 use std::path::{Component, Path, PathBuf};
 use std::result::Result as StdResult;
+use std::sync::Arc;
 use std::{env, fmt, fs, mem, ptr};
 
-use crate::error::{Error, Result};
+use crate::error::{Error, ExternalResult, Result};
 use crate::function::Function;
 use crate::state::{callback_error_ext, Lua};
 use crate::table::Table;
 use crate::types::MaybeSend;
+use crate::vfs::Vfs;
 
 /// An error that can occur during navigation in the Luau `require-by-string` system.
 #[cfg(any(feature = "luau", doc))]
@@ -113,6 +115,8 @@ pub struct TextRequirer {
     /// A physical path to the current Luau module, which is a file or a directory with an
     /// `init.lua(u)` file
     resolved_path: Option<PathBuf>,
+    /// A virtual filesystem to use instead of the real one
+    vfs: Option<Arc<dyn Vfs>>,
 }
 
 impl TextRequirer {
@@ -128,6 +132,44 @@ impl TextRequirer {
         Self::default()
     }
 
+    /// Creates a new `TextRequirer` instance that loads modules from the given virtual filesystem.
+    ///
+    /// The root of the filesystem is used as the current directory.
+    pub fn with_vfs(vfs: impl Vfs) -> Self {
+        TextRequirer {
+            vfs: Some(Arc::new(vfs)),
+            ..Self::default()
+        }
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        match &self.vfs {
+            Some(vfs) => vfs.is_file(path),
+            None => path.is_file(),
+        }
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        match &self.vfs {
+            Some(vfs) => vfs.is_dir(path),
+            None => path.is_dir(),
+        }
+    }
+
+    fn read(&self, path: &Path) -> IoResult<Vec<u8>> {
+        match &self.vfs {
+            Some(vfs) => vfs.read(path),
+            None => fs::read(path),
+        }
+    }
+
+    fn current_dir(&self) -> StdResult<PathBuf, NavigateError> {
+        match &self.vfs {
+            Some(_) => Ok(PathBuf::from("/")),
+            None => env::current_dir().map_err(|_| NavigateError::NotFound),
+        }
+    }
+
     fn normalize_chunk_name(chunk_name: &str) -> &str {
         if let Some((path, line)) = chunk_name.rsplit_once(':') {
             if line.parse::<u32>().is_ok() {
@@ -169,7 +211,7 @@ impl TextRequirer {
     /// Resolve a Luau module path to a physical file or directory.
     ///
     /// Empty directories without init files are considered valid as "intermediate" directories.
-    fn resolve_module(path: &Path) -> StdResult<Option<PathBuf>, NavigateError> {
+    fn resolve_module(&self, path: &Path) -> StdResult<Option<PathBuf>, NavigateError> {
         let mut found_path = None;
 
         if path.components().next_back() != Some(Component::Normal("init".as_ref())) {
@@ -178,15 +220,15 @@ impl TextRequirer {
                 .unwrap_or_default();
             for ext in Self::FILE_EXTENSIONS {
                 let candidate = path.with_extension(format!("{current_ext}{ext}"));
-                if candidate.is_file() && found_path.replace(candidate).is_some() {
+                if self.is_file(&candidate) && found_path.replace(candidate).is_some() {
                     return Err(NavigateError::Ambiguous);
                 }
             }
         }
-        if path.is_dir() {
+        if self.is_dir(path) {
             for component in Self::FILE_EXTENSIONS.iter().map(|ext| format!("init.{ext}")) {
                 let candidate = path.join(component);
-                if candidate.is_file() && found_path.replace(candidate).is_some() {
+                if self.is_file(&candidate) && found_path.replace(candidate).is_some() {
                     return Err(NavigateError::Ambiguous);
                 }
             }
@@ -216,7 +258,7 @@ impl Require for TextRequirer {
         if chunk_path.extension() == Some("rs".as_ref()) {
             // Special case for Rust source files, reset to the current directory
             let chunk_filename = chunk_path.file_name().unwrap();
-            let cwd = env::current_dir().map_err(|_| NavigateError::NotFound)?;
+            let cwd = self.current_dir()?;
             self.abs_path = Self::normalize_path(&cwd.join(chunk_filename));
             self.rel_path = ([Component::CurDir, Component::Normal(chunk_filename)].into_iter()).collect();
             self.resolved_path = None;
@@ -225,15 +267,15 @@ impl Require for TextRequirer {
         }
 
         if chunk_path.is_absolute() {
-            let resolved_path = Self::resolve_module(&chunk_path)?;
+            let resolved_path = self.resolve_module(&chunk_path)?;
             self.abs_path = chunk_path.clone();
             self.rel_path = chunk_path;
             self.resolved_path = resolved_path;
         } else {
             // Relative path
-            let cwd = env::current_dir().map_err(|_| NavigateError::NotFound)?;
+            let cwd = self.current_dir()?;
             let abs_path = Self::normalize_path(&cwd.join(&chunk_path));
-            let resolved_path = Self::resolve_module(&abs_path)?;
+            let resolved_path = self.resolve_module(&abs_path)?;
             self.abs_path = abs_path;
             self.rel_path = chunk_path;
             self.resolved_path = resolved_path;
@@ -244,7 +286,7 @@ impl Require for TextRequirer {
 
     fn jump_to_alias(&mut self, path: &str) -> StdResult<(), NavigateError> {
         let path = Self::normalize_path(path.as_ref());
-        let resolved_path = Self::resolve_module(&path)?;
+        let resolved_path = self.resolve_module(&path)?;
 
         self.abs_path = path.clone();
         self.rel_path = path;
@@ -263,7 +305,7 @@ impl Require for TextRequirer {
         }
         let mut rel_parent = self.rel_path.clone();
         rel_parent.pop();
-        let resolved_path = Self::resolve_module(&abs_path)?;
+        let resolved_path = self.resolve_module(&abs_path)?;
 
         self.abs_path = abs_path;
         self.rel_path = Self::normalize_path(&rel_parent);
@@ -275,7 +317,7 @@ impl Require for TextRequirer {
     fn to_child(&mut self, name: &str) -> StdResult<(), NavigateError> {
         let abs_path = self.abs_path.join(name);
         let rel_path = self.rel_path.join(name);
-        let resolved_path = Self::resolve_module(&abs_path)?;
+        let resolved_path = self.resolve_module(&abs_path)?;
 
         self.abs_path = abs_path;
         self.rel_path = rel_path;
@@ -286,7 +328,7 @@ impl Require for TextRequirer {
 
     fn has_module(&self) -> bool {
         (self.resolved_path.as_deref())
-            .map(Path::is_file)
+            .map(|path| self.is_file(path))
             .unwrap_or(false)
     }
 
@@ -295,18 +337,17 @@ impl Require for TextRequirer {
     }
 
     fn has_config(&self) -> bool {
-        self.abs_path.is_dir() && self.abs_path.join(".luaurc").is_file()
+        self.is_dir(&self.abs_path) && self.is_file(&self.abs_path.join(".luaurc"))
     }
 
     fn config(&self) -> IoResult<Vec<u8>> {
-        fs::read(self.abs_path.join(".luaurc"))
+        self.read(&self.abs_path.join(".luaurc"))
     }
 
     fn loader(&self, lua: &Lua) -> Result<Function> {
         let name = format!("@{}", self.rel_path.display());
-        lua.load(self.resolved_path.as_deref().unwrap())
-            .set_name(name)
-            .into_function()
+        let source = self.read(self.resolved_path.as_deref().unwrap()).into_lua_err()?;
+        lua.load(source).set_name(name).into_function()
     }
 }
 
@@ -1,7 +1,58 @@
 use std::alloc::{self, Layout};
-use std::os::raw::c_void;
+use std::os::raw::{c_int, c_void};
 use std::ptr;
 
+use rustc_hash::FxHashMap;
+
+use crate::debug::Frame;
+use crate::error::Result;
+use crate::state::Lua;
+use crate::types::XRc;
+
+#[cfg(not(feature = "luau"))]
+use crate::{debug::HookTriggers, types::HookCallback};
+
+#[cfg(feature = "luau")]
+use crate::types::InterruptCallback;
+
+#[cfg(not(feature = "luau"))]
+type Hook = (HookTriggers, HookCallback);
+
+#[cfg(feature = "luau")]
+type Hook = InterruptCallback;
+
+/// The hook installed by the memory profiler, along with the hook it has replaced.
+pub(crate) struct MemoryProfilerHook {
+    pub(crate) installed: Hook,
+    pub(crate) previous: Option<Hook>,
+}
+
+impl MemoryProfilerHook {
+    /// Checks that the profiler hook has not been replaced since it was installed.
+    pub(crate) fn is_current(&self, lua: &Lua) -> bool {
+        #[cfg(not(feature = "luau"))]
+        let current = lua.global_hook().map(|(_, callback)| callback);
+        #[cfg(not(feature = "luau"))]
+        let installed = &self.installed.1;
+        #[cfg(feature = "luau")]
+        let current = lua.interrupt_callback();
+        #[cfg(feature = "luau")]
+        let installed = &self.installed;
+        current.is_some_and(|current| XRc::ptr_eq(&current, installed))
+    }
+
+    /// Puts back the hook that was replaced by the profiler.
+    pub(crate) fn restore(self, lua: &Lua) -> Result<()> {
+        #[cfg(not(feature = "luau"))]
+        return lua.restore_global_hook(self.previous);
+        #[cfg(feature = "luau")]
+        {
+            lua.restore_interrupt_callback(self.previous);
+            Ok(())
+        }
+    }
+}
+
 pub(crate) static ALLOCATOR: ffi::lua_Alloc = allocator;
 
 #[repr(C)]
@@ -15,6 +66,8 @@ pub(crate) struct MemoryState {
     // Indicates that the memory limit was reached on the last allocation.
     #[cfg(feature = "luau")]
     limit_reached: bool,
+    // Allocation tracker used for memory profiling (if enabled).
+    pub(crate) tracker: Option<Box<AllocationTracker>>,
 }
 
 impl MemoryState {
@@ -121,6 +174,9 @@ unsafe extern "C" fn allocator(
             let layout = Layout::from_size_align_unchecked(osize, ffi::SYS_MIN_ALIGN);
             alloc::dealloc(ptr as *mut u8, layout);
             mem_state.used_memory -= osize as isize;
+            if let Some(tracker) = mem_state.tracker.as_deref_mut() {
+                tracker.free(ptr);
+            }
         }
         return ptr::null_mut();
     }
@@ -156,6 +212,10 @@ unsafe extern "C" fn allocator(
         if new_ptr.is_null() {
             alloc::handle_alloc_error(new_layout);
         }
+        if let Some(tracker) = mem_state.tracker.as_deref_mut() {
+            // For new objects `osize` encodes the object type
+            tracker.alloc(new_ptr, osize as c_int, nsize);
+        }
         return new_ptr;
     }
 
@@ -165,5 +225,213 @@ unsafe extern "C" fn allocator(
     if new_ptr.is_null() {
         alloc::handle_alloc_error(old_layout);
     }
+    if let Some(tracker) = mem_state.tracker.as_deref_mut() {
+        tracker.realloc(ptr, new_ptr, nsize);
+    }
     new_ptr
 }
+
+/// Tracks live allocations made by Lua, attributing them to the code that was executing.
+pub(crate) struct AllocationTracker {
+    // Site of the currently executing code (`0` if unknown)
+    current_site: usize,
+    sites: Vec<Option<Frame>>,
+    site_ids: FxHashMap<Frame, usize>,
+    stats: FxHashMap<(usize, &'static str), SiteStats>,
+    live: FxHashMap<usize, LiveAllocation>,
+}
+
+#[derive(Clone, Copy, Default)]
+struct SiteStats {
+    allocated_bytes: usize,
+    freed_bytes: usize,
+    live_bytes: usize,
+    live_count: usize,
+}
+
+struct LiveAllocation {
+    site: usize,
+    kind: &'static str,
+    size: usize,
+}
+
+impl AllocationTracker {
+    pub(crate) fn new() -> Self {
+        AllocationTracker {
+            current_site: 0,
+            sites: vec![None],
+            site_ids: FxHashMap::default(),
+            stats: FxHashMap::default(),
+            live: FxHashMap::default(),
+        }
+    }
+
+    /// Sets the location of the currently executing code.
+    pub(crate) fn set_site(&mut self, frame: Option<Frame>) {
+        self.current_site = match frame {
+            Some(frame) => match self.site_ids.get(&frame) {
+                Some(&id) => id,
+                None => {
+                    let id = self.sites.len();
+                    self.sites.push(Some(frame.clone()));
+                    self.site_ids.insert(frame, id);
+                    id
+                }
+            },
+            None => 0,
+        };
+    }
+
+    fn alloc(&mut self, ptr: *mut c_void, tag: c_int, size: usize) {
+        let (site, kind) = (self.current_site, object_kind(tag));
+        let stats = self.stats.entry((site, kind)).or_default();
+        stats.allocated_bytes += size;
+        stats.live_bytes += size;
+        stats.live_count += 1;
+        self.live
+            .insert(ptr as usize, LiveAllocation { site, kind, size });
+    }
+
+    fn realloc(&mut self, old_ptr: *mut c_void, new_ptr: *mut c_void, new_size: usize) {
+        // Blocks allocated before tracking was enabled are ignored
+        let Some(mut alloc) = self.live.remove(&(old_ptr as usize)) else {
+            return;
+        };
+        let stats = self.stats.entry((alloc.site, alloc.kind)).or_default();
+        if new_size > alloc.size {
+            stats.allocated_bytes += new_size - alloc.size;
+        } else {
+            stats.freed_bytes += alloc.size - new_size;
+        }
+        stats.live_bytes = stats.live_bytes + new_size - alloc.size;
+        alloc.size = new_size;
+        self.live.insert(new_ptr as usize, alloc);
+    }
+
+    fn free(&mut self, ptr: *mut c_void) {
+        if let Some(alloc) = self.live.remove(&(ptr as usize)) {
+            let stats = self.stats.entry((alloc.site, alloc.kind)).or_default();
+            stats.freed_bytes += alloc.size;
+            stats.live_bytes -= alloc.size;
+            stats.live_count -= 1;
+        }
+    }
+
+    /// Returns a snapshot of the collected statistics.
+    pub(crate) fn snapshot(&self) -> MemoryProfile {
+        let mut sites = (self.stats.iter())
+            .map(|(&(site, kind), stats)| AllocationSite {
+                frame: self.sites[site].clone(),
+                kind,
+                allocated_bytes: stats.allocated_bytes,
+                freed_bytes: stats.freed_bytes,
+                live_bytes: stats.live_bytes,
+                live_count: stats.live_count,
+            })
+            .collect::<Vec<_>>();
+        sites.sort_by(|a, b| (b.live_bytes, &a.frame, a.kind).cmp(&(a.live_bytes, &b.frame, b.kind)));
+        MemoryProfile { sites }
+    }
+}
+
+// Returns the type of object being allocated (Lua 5.2+ only)
+fn object_kind(tag: c_int) -> &'static str {
+    if cfg!(any(feature = "lua54", feature = "lua53", feature = "lua52")) {
+        // Lua 5.4 passes variant tags, where the lower 4 bits are the basic type
+        match tag & 0x0f {
+            ffi::LUA_TSTRING => return "string",
+            ffi::LUA_TTABLE => return "table",
+            ffi::LUA_TFUNCTION => return "function",
+            ffi::LUA_TUSERDATA => return "userdata",
+            ffi::LUA_TTHREAD => return "thread",
+            _ => {}
+        }
+    }
+    "other"
+}
+
+/// A snapshot of live Lua allocations, grouped by allocation site.
+///
+/// Returned by [`Lua::memory_profile`].
+///
+/// [`Lua::memory_profile`]: crate::Lua::memory_profile
+#[derive(Clone, Debug, Default)]
+pub struct MemoryProfile {
+    sites: Vec<AllocationSite>,
+}
+
+/// Memory statistics of a single allocation site.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AllocationSite {
+    /// Location of the code that made allocations (`None` when no Lua code was running).
+    ///
+    /// Allocations made by Rust callbacks are attributed to the calling Lua code.
+    pub frame: Option<Frame>,
+    /// Type of the allocated objects: `string`, `table`, `function`, `userdata`, `thread`
+    /// or `other`.
+    ///
+    /// Only Lua 5.2+ reports object types, for other versions it's always `other`.
+    pub kind: &'static str,
+    /// Total number of bytes allocated.
+    pub allocated_bytes: usize,
+    /// Total number of bytes freed.
+    pub freed_bytes: usize,
+    /// Number of bytes currently in use.
+    pub live_bytes: usize,
+    /// Number of memory blocks currently in use.
+    pub live_count: usize,
+}
+
+/// Change in live memory of an allocation site between two [`MemoryProfile`] snapshots.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AllocationDiff {
+    /// Location of the code that made allocations.
+    pub frame: Option<Frame>,
+    /// Type of the allocated objects.
+    pub kind: &'static str,
+    /// Change in the number of bytes in use.
+    pub bytes: isize,
+    /// Change in the number of memory blocks in use.
+    pub count: isize,
+}
+
+impl MemoryProfile {
+    /// Returns allocation sites, sorted by the number of live bytes (in descending order).
+    pub fn sites(&self) -> &[AllocationSite] {
+        &self.sites
+    }
+
+    /// Returns the total number of bytes in use by tracked allocations.
+    pub fn live_bytes(&self) -> usize {
+        self.sites.iter().map(|site| site.live_bytes).sum()
+    }
+
+    /// Compares live memory with an earlier snapshot.
+    ///
+    /// Returns allocation sites whose live memory has changed, sorted by growth (in descending
+    /// order).
+    pub fn diff(&self, earlier: &MemoryProfile) -> Vec<AllocationDiff> {
+        let mut changes = FxHashMap::<_, (isize, isize)>::default();
+        for site in &self.sites {
+            let entry = changes.entry((&site.frame, site.kind)).or_default();
+            entry.0 += site.live_bytes as isize;
+            entry.1 += site.live_count as isize;
+        }
+        for site in &earlier.sites {
+            let entry = changes.entry((&site.frame, site.kind)).or_default();
+            entry.0 -= site.live_bytes as isize;
+            entry.1 -= site.live_count as isize;
+        }
+        let mut diff = (changes.into_iter())
+            .filter(|(_, (bytes, count))| *bytes != 0 || *count != 0)
+            .map(|((frame, kind), (bytes, count))| AllocationDiff {
+                frame: frame.clone(),
+                kind,
+                bytes,
+                count,
+            })
+            .collect::<Vec<_>>();
+        diff.sort_by(|a, b| (b.bytes, &a.frame, a.kind).cmp(&(a.bytes, &b.frame, b.kind)));
+        diff
+    }
+}
@@ -8,12 +8,19 @@ use std::result::Result as StdResult;
 use crate::error::Result;
 use crate::state::{Lua, RawLua};
 use crate::traits::{FromLua, FromLuaMulti, IntoLua, IntoLuaMulti};
+use crate::typedef::TypeInfo;
 use crate::util::check_stack;
 use crate::value::{Nil, Value};
 
 /// Result is convertible to [`MultiValue`] following the common Lua idiom of returning the result
 /// on success, or in the case of an error, returning `nil` and an error message.
 impl<T: IntoLua, E: IntoLua> IntoLuaMulti for StdResult<T, E> {
+    #[inline]
+    fn type_info_multi() -> Vec<TypeInfo> {
+        let ok = TypeInfo::Optional(Box::new(T::type_info()));
+        vec![ok, TypeInfo::Optional(Box::new(E::type_info()))]
+    }
+
     #[inline]
     fn into_lua_multi(self, lua: &Lua) -> Result<MultiValue> {
         match self {
@@ -32,6 +39,11 @@ impl<T: IntoLua, E: IntoLua> IntoLuaMulti for StdResult<T, E> {
 }
 
 impl<E: IntoLua> IntoLuaMulti for StdResult<(), E> {
+    #[inline]
+    fn type_info_multi() -> Vec<TypeInfo> {
+        vec![TypeInfo::Nil, TypeInfo::Optional(Box::new(E::type_info()))]
+    }
+
     #[inline]
     fn into_lua_multi(self, lua: &Lua) -> Result<MultiValue> {
         match self {
@@ -50,6 +62,11 @@ impl<E: IntoLua> IntoLuaMulti for StdResult<(), E> {
 }
 
 impl<T: IntoLua> IntoLuaMulti for T {
+    #[inline]
+    fn type_info_multi() -> Vec<TypeInfo> {
+        vec![T::type_info()]
+    }
+
     #[inline]
     fn into_lua_multi(self, lua: &Lua) -> Result<MultiValue> {
         let mut v = MultiValue::with_capacity(1);
@@ -65,6 +82,11 @@ impl<T: IntoLua> IntoLuaMulti for T {
 }
 
 impl<T: FromLua> FromLuaMulti for T {
+    #[inline]
+    fn type_info_multi() -> Vec<TypeInfo> {
+        vec![T::type_info()]
+    }
+
     #[inline]
     fn from_lua_multi(mut values: MultiValue, lua: &Lua) -> Result<Self> {
         T::from_lua(values.pop_front().unwrap_or(Nil), lua)
@@ -293,6 +315,11 @@ impl<T> IntoIterator for Variadic<T> {
 }
 
 impl<T: IntoLua> IntoLuaMulti for Variadic<T> {
+    #[inline]
+    fn type_info_multi() -> Vec<TypeInfo> {
+        vec![TypeInfo::Variadic(Box::new(T::type_info()))]
+    }
+
     #[inline]
     fn into_lua_multi(self, lua: &Lua) -> Result<MultiValue> {
         MultiValue::from_lua_iter(lua, self)
@@ -309,6 +336,11 @@ impl<T: IntoLua> IntoLuaMulti for Variadic<T> {
 }
 
 impl<T: FromLua> FromLuaMulti for Variadic<T> {
+    #[inline]
+    fn type_info_multi() -> Vec<TypeInfo> {
+        vec![TypeInfo::Variadic(Box::new(T::type_info()))]
+    }
+
     #[inline]
     fn from_lua_multi(mut values: MultiValue, lua: &Lua) -> Result<Self> {
         values
@@ -322,6 +354,11 @@ impl<T: FromLua> FromLuaMulti for Variadic<T> {
 macro_rules! impl_tuple {
     () => (
         impl IntoLuaMulti for () {
+            #[inline]
+            fn type_info_multi() -> Vec<TypeInfo> {
+                Vec::new()
+            }
+
             #[inline]
             fn into_lua_multi(self, _: &Lua) -> Result<MultiValue> {
                 const { Ok(MultiValue::new()) }
@@ -334,6 +371,11 @@ macro_rules! impl_tuple {
         }
 
         impl FromLuaMulti for () {
+            #[inline]
+            fn type_info_multi() -> Vec<TypeInfo> {
+                Vec::new()
+            }
+
             #[inline]
             fn from_lua_multi(_values: MultiValue, _lua: &Lua) -> Result<Self> {
                 Ok(())
@@ -351,6 +393,13 @@ macro_rules! impl_tuple {
             where $($name: IntoLua,)*
                   $last: IntoLuaMulti
         {
+            #[inline]
+            fn type_info_multi() -> Vec<TypeInfo> {
+                let mut types = vec![$($name::type_info(),)*];
+                types.extend($last::type_info_multi());
+                types
+            }
+
             #[allow(unused_mut, non_snake_case)]
             #[inline]
             fn into_lua_multi(self, lua: &Lua) -> Result<MultiValue> {
@@ -383,6 +432,13 @@ macro_rules! impl_tuple {
             where $($name: FromLua,)*
                   $last: FromLuaMulti
         {
+            #[inline]
+            fn type_info_multi() -> Vec<TypeInfo> {
+                let mut types = vec![$($name::type_info(),)*];
+                types.extend($last::type_info_multi());
+                types
+            }
+
             #[allow(unused_mut, non_snake_case)]
             #[inline]
             fn from_lua_multi(mut values: MultiValue, lua: &Lua) -> Result<Self> {
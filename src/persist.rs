@@ -0,0 +1,682 @@
+//! Persisting (serializing) Lua values.
+
+use std::any::TypeId;
+use std::collections::VecDeque;
+use std::os::raw::c_void;
+use std::string::String as StdString;
+
+use rustc_hash::{FxHashMap, FxHashSet};
+
+use crate::error::{Error, Result};
+use crate::function::Function;
+use crate::multi::MultiValue;
+use crate::state::Lua;
+use crate::table::Table;
+use crate::traits::{FromLuaMulti, IntoLuaMulti};
+use crate::types::{Integer, MaybeSend};
+use crate::userdata::{AnyUserData, UserData};
+use crate::value::Value;
+
+#[cfg(feature = "luau")]
+use crate::vector::Vector;
+
+#[cfg(not(feature = "luau"))]
+use crate::{
+    chunk::ChunkMode,
+    util::{check_stack, StackGuard},
+};
+
+const MAGIC: &[u8] = b"MLUAP\x01";
+
+const TAG_NIL: u8 = 0;
+const TAG_FALSE: u8 = 1;
+const TAG_TRUE: u8 = 2;
+const TAG_INTEGER: u8 = 3;
+const TAG_NUMBER: u8 = 4;
+const TAG_STRING: u8 = 5;
+const TAG_REF: u8 = 6;
+const TAG_TABLE: u8 = 7;
+const TAG_ANCHOR: u8 = 8;
+#[cfg_attr(feature = "luau", allow(unused))]
+const TAG_FUNCTION: u8 = 9;
+const TAG_PERMANENT: u8 = 10;
+const TAG_USERDATA: u8 = 11;
+#[cfg(feature = "luau")]
+const TAG_VECTOR: u8 = 12;
+
+/// Trait for userdata types that can be persisted by [`Persister`].
+///
+/// Userdata is persisted as a Lua value returned by [`PersistUserData::persist`], which can
+/// reference other Lua values (including tables and functions).
+pub trait PersistUserData: Sized {
+    /// Converts the userdata into a Lua value to persist.
+    fn persist(&self, lua: &Lua) -> Result<Value>;
+
+    /// Recreates the userdata from a value previously returned by [`PersistUserData::persist`].
+    fn unpersist(lua: &Lua, value: Value) -> Result<Self>;
+}
+
+type PersistFn = Box<dyn Fn(&Lua, &AnyUserData) -> Result<Value> + Send + Sync>;
+type UnpersistFn = Box<dyn Fn(&Lua, Value) -> Result<AnyUserData> + Send + Sync>;
+
+/// Saves and restores graphs of Lua values.
+///
+/// Data is restored possibly into a different [`Lua`] instance, similar to the [Eris] and [Pluto]
+/// libraries for Lua. Persisted data containing Lua functions must be trusted, see
+/// [`Persister::unpersist`].
+///
+/// Values are persisted starting from one or more roots, such as the globals table or values
+/// stored in the registry. The following values are supported:
+/// - `nil`, booleans, numbers and strings
+/// - tables (including their metatables), preserving identity and cycles
+/// - Lua functions, as bytecode (see [`Function::dump`]) together with their upvalues; upvalues
+///   shared between functions remain shared (Lua 5.2+ only)
+/// - userdata of types registered with [`Persister::register_userdata`]
+/// - Luau vectors
+///
+/// Values that cannot be serialized, like C (Rust) functions and standard libraries, are stored
+/// as *permanent* references by their path from the globals table (e.g. `string.format`) and
+/// looked up at the same path when restoring. All C functions reachable from globals through
+/// tables are permanent automatically (using the shortest path, with ties broken by key order);
+/// other values can be added using [`Persister::permanent`].
+///
+/// The globals table and tables stored directly in globals (e.g. `string`) are restored by
+/// merging their contents into the existing tables of the target Lua instance (if present).
+///
+/// Threads (coroutines) cannot be persisted. Lua functions cannot be persisted in Luau.
+///
+/// # Example
+///
+/// ```
+/// # use mlua::{Lua, Persister, Result};
+/// # fn main() -> Result<()> {
+/// let lua = Lua::new();
+/// lua.load(r#"
+///     local count = 0
+///     function counter()
+///         count = count + 1
+///         return count
+///     end
+///     counter()
+/// "#).exec()?;
+///
+/// let persister = Persister::new();
+/// let data = persister.persist(&lua, lua.globals())?;
+///
+/// let lua2 = Lua::new();
+/// persister.unpersist::<()>(&lua2, &data)?;
+/// assert_eq!(lua2.load("counter()").eval::<i64>()?, 2);
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [Eris]: https://github.com/fnuecke/eris
+/// [Pluto]: https://github.com/hoelzro/pluto
+#[derive(Default)]
+pub struct Persister {
+    permanents: Vec<StdString>,
+    userdata: FxHashMap<StdString, (PersistFn, UnpersistFn)>,
+    userdata_names: FxHashMap<TypeId, StdString>,
+    // Do not look up permanent values and anchored tables in globals
+    detached: bool,
+}
+
+impl Persister {
+    /// Creates a new persister.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Marks a value at the given path from the globals table (e.g. `"game.api"`) as permanent.
+    ///
+    /// Permanent values are not serialized; they are stored as a reference to their path instead.
+    pub fn permanent(mut self, path: impl Into<StdString>) -> Self {
+        self.permanents.push(path.into());
+        self
+    }
+
+    /// Registers a userdata type that can be persisted under the given name.
+    ///
+    /// The same name must be used to register the type when restoring.
+    pub fn register_userdata<T>(mut self, name: impl Into<StdString>) -> Self
+    where
+        T: UserData + PersistUserData + MaybeSend + 'static,
+    {
+        let name = name.into();
+        let persist: PersistFn = Box::new(|lua, ud| ud.borrow::<T>()?.persist(lua));
+        let unpersist: UnpersistFn = Box::new(|lua, value| lua.create_userdata(T::unpersist(lua, value)?));
+        self.userdata_names.insert(TypeId::of::<T>(), name.clone());
+        self.userdata.insert(name, (persist, unpersist));
+        self
+    }
+
+    /// Makes the persisted data independent of the globals of the Lua instance.
+    ///
+    /// Values reachable from globals are serialized as any other values, and all C functions
+    /// are rejected. Used to send self-contained messages between Lua states.
+    #[cfg(feature = "send")]
+    pub(crate) fn detached(mut self) -> Self {
+        self.detached = true;
+        self
+    }
+
+    /// Persists the given root values to bytes.
+    pub fn persist(&self, lua: &Lua, roots: impl IntoLuaMulti) -> Result<Vec<u8>> {
+        let roots = roots.into_lua_multi(lua)?;
+        let mut writer = Writer {
+            persister: self,
+            lua,
+            buf: MAGIC.to_vec(),
+            objects: FxHashMap::default(),
+            upvalues: FxHashMap::default(),
+            next_upvalue: 0,
+            permanents: FxHashMap::default(),
+            anchors: FxHashMap::default(),
+        };
+        if !self.detached {
+            writer.scan_globals()?;
+        }
+        writer.write_varint(roots.len() as u64);
+        for root in &roots {
+            writer.write_value(root)?;
+        }
+        Ok(writer.buf)
+    }
+
+    /// Restores values previously persisted by [`Persister::persist`].
+    ///
+    /// Returns the root values.
+    ///
+    /// Be aware, Lua functions are restored by loading their bytecode from `data`, and Lua does
+    /// not check the consistency of the code inside binary chunks. Running malformed or
+    /// maliciously crafted bytecode can crash the interpreter, so `data` must come from a trusted
+    /// source.
+    pub fn unpersist<R: FromLuaMulti>(&self, lua: &Lua, data: &[u8]) -> Result<R> {
+        let data = (data.strip_prefix(MAGIC)).ok_or_else(|| Error::runtime("invalid persisted data"))?;
+        let mut reader = Reader {
+            persister: self,
+            lua,
+            data,
+            objects: Vec::new(),
+            upvalues: Vec::new(),
+        };
+        let count = reader.read_varint()?;
+        let roots = (0..count)
+            .map(|_| reader.read_value())
+            .collect::<Result<MultiValue>>()?;
+        R::from_lua_multi(roots, lua)
+    }
+}
+
+struct Writer<'a> {
+    persister: &'a Persister,
+    lua: &'a Lua,
+    buf: Vec<u8>,
+    objects: FxHashMap<*const c_void, u64>,
+    #[cfg_attr(feature = "luau", allow(unused))]
+    upvalues: FxHashMap<usize, u64>,
+    #[cfg_attr(feature = "luau", allow(unused))]
+    next_upvalue: u64,
+    permanents: FxHashMap<*const c_void, Vec<Value>>,
+    anchors: FxHashMap<*const c_void, Vec<Value>>,
+}
+
+impl Writer<'_> {
+    // Finds permanent values and anchored tables reachable from globals
+    fn scan_globals(&mut self) -> Result<()> {
+        let globals = self.lua.globals();
+        self.anchors.insert(globals.to_pointer(), Vec::new());
+        let mut visited = FxHashSet::default();
+        visited.insert(globals.to_pointer());
+        let mut queue = VecDeque::from([(globals, Vec::new())]);
+        while let Some((table, path)) = queue.pop_front() {
+            let mut entries = Vec::new();
+            for pair in table.pairs::<Value, Value>() {
+                let (key, value) = pair?;
+                if matches!(key, Value::String(_) | Value::Integer(_)) {
+                    entries.push((key.to_string()?, key, value));
+                }
+            }
+            // Visit keys in a stable order, so the chosen path does not depend on table layout
+            entries.sort_by(|(a, ..), (b, ..)| a.cmp(b));
+            for (_, key, value) in entries {
+                let mut value_path = path.clone();
+                value_path.push(key);
+                match value {
+                    Value::Table(t) if visited.insert(t.to_pointer()) => {
+                        if path.is_empty() {
+                            self.anchors.insert(t.to_pointer(), value_path.clone());
+                        }
+                        queue.push_back((t, value_path));
+                    }
+                    Value::Function(f) if f.info().what == "C" => {
+                        self.permanents.entry(f.to_pointer()).or_insert(value_path);
+                    }
+                    Value::UserData(ud) if !self.is_registered(&ud) => {
+                        self.permanents.entry(ud.to_pointer()).or_insert(value_path);
+                    }
+                    _ => {}
+                }
+            }
+        }
+        for path in &self.persister.permanents {
+            let path = parse_path(self.lua, path)?;
+            let value = resolve_path(self.lua, &path)?;
+            if value.is_nil() {
+                let path = display_path(&path);
+                return Err(Error::runtime(format!("permanent value '{path}' not found")));
+            }
+            self.permanents.insert(value.to_pointer(), path);
+        }
+        Ok(())
+    }
+
+    fn is_registered(&self, ud: &AnyUserData) -> bool {
+        (ud.type_id()).is_some_and(|type_id| self.persister.userdata_names.contains_key(&type_id))
+    }
+
+    fn write_value(&mut self, value: &Value) -> Result<()> {
+        match value {
+            Value::Nil => self.buf.push(TAG_NIL),
+            Value::Boolean(false) => self.buf.push(TAG_FALSE),
+            Value::Boolean(true) => self.buf.push(TAG_TRUE),
+            Value::Integer(i) => {
+                self.buf.push(TAG_INTEGER);
+                #[allow(clippy::useless_conversion)]
+                self.buf.extend_from_slice(&i64::from(*i).to_le_bytes());
+            }
+            Value::Number(n) => {
+                self.buf.push(TAG_NUMBER);
+                self.buf.extend_from_slice(&n.to_le_bytes());
+            }
+            Value::String(s) => {
+                self.buf.push(TAG_STRING);
+                self.write_bytes(&s.as_bytes());
+            }
+            #[cfg(feature = "luau")]
+            Value::Vector(v) => {
+                self.buf.push(TAG_VECTOR);
+                for n in v.0 {
+                    self.buf.extend_from_slice(&n.to_le_bytes());
+                }
+            }
+            Value::Table(_) | Value::Function(_) | Value::UserData(_) => {
+                let ptr = value.to_pointer();
+                if let Some(&id) = self.objects.get(&ptr) {
+                    self.buf.push(TAG_REF);
+                    self.write_varint(id);
+                    return Ok(());
+                }
+                if let Some(path) = self.permanents.get(&ptr) {
+                    let path = path.clone();
+                    self.buf.push(TAG_PERMANENT);
+                    self.write_path(&path)?;
+                    return Ok(());
+                }
+                self.objects.insert(ptr, self.objects.len() as u64);
+                match value {
+                    Value::Table(t) => self.write_table(t)?,
+                    Value::Function(f) => self.write_function(f)?,
+                    Value::UserData(ud) => self.write_userdata(ud)?,
+                    _ => unreachable!(),
+                }
+            }
+            _ => {
+                let type_name = value.type_name();
+                return Err(Error::runtime(format!(
+                    "cannot persist value of type {type_name}"
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    fn write_path(&mut self, path: &[Value]) -> Result<()> {
+        self.write_varint(path.len() as u64);
+        for key in path {
+            self.write_value(key)?;
+        }
+        Ok(())
+    }
+
+    fn write_table(&mut self, table: &Table) -> Result<()> {
+        match self.anchors.get(&table.to_pointer()) {
+            Some(path) => {
+                let path = path.clone();
+                self.buf.push(TAG_ANCHOR);
+                self.write_path(&path)?;
+            }
+            None => self.buf.push(TAG_TABLE),
+        }
+        for pair in table.pairs::<Value, Value>() {
+            let (key, value) = pair?;
+            self.write_value(&key)?;
+            self.write_value(&value)?;
+        }
+        // `nil` key marks the end of the table
+        self.buf.push(TAG_NIL);
+        match table.metatable() {
+            Some(mt) => self.write_value(&Value::Table(mt)),
+            None => {
+                self.buf.push(TAG_NIL);
+                Ok(())
+            }
+        }
+    }
+
+    fn write_function(&mut self, func: &Function) -> Result<()> {
+        if func.info().what == "C" {
+            return Err(Error::runtime(
+                "cannot persist C function (not reachable from globals)",
+            ));
+        }
+        #[cfg(feature = "luau")]
+        return Err(Error::runtime("cannot persist Lua function in Luau"));
+
+        #[cfg(not(feature = "luau"))]
+        {
+            self.buf.push(TAG_FUNCTION);
+            self.write_bytes(&func.dump(false));
+            let upvalues = unsafe { get_upvalues(func)? };
+            self.write_varint(upvalues.len() as u64);
+            for (value, upvalue_id) in upvalues {
+                // Shared upvalues are written once and referenced by their index
+                if let Some(&index) = upvalue_id.and_then(|id| self.upvalues.get(&id)) {
+                    self.write_varint(index);
+                    continue;
+                }
+                let index = self.next_upvalue;
+                self.next_upvalue += 1;
+                if let Some(id) = upvalue_id {
+                    self.upvalues.insert(id, index);
+                }
+                self.write_varint(index);
+                self.write_value(&value)?;
+            }
+            Ok(())
+        }
+    }
+
+    fn write_userdata(&mut self, ud: &AnyUserData) -> Result<()> {
+        let name = (ud.type_id())
+            .and_then(|type_id| self.persister.userdata_names.get(&type_id))
+            .ok_or_else(|| Error::runtime("cannot persist userdata of unregistered type"))?;
+        let (persist, _) = &self.persister.userdata[name];
+        let value = persist(self.lua, ud)?;
+        self.buf.push(TAG_USERDATA);
+        self.write_bytes(name.as_bytes());
+        self.write_value(&value)
+    }
+
+    fn write_varint(&mut self, mut n: u64) {
+        while n >= 0x80 {
+            self.buf.push((n as u8) | 0x80);
+            n >>= 7;
+        }
+        self.buf.push(n as u8);
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8]) {
+        self.write_varint(bytes.len() as u64);
+        self.buf.extend_from_slice(bytes);
+    }
+}
+
+struct Reader<'a> {
+    persister: &'a Persister,
+    lua: &'a Lua,
+    data: &'a [u8],
+    // Objects are `None` while being restored (only for userdata)
+    objects: Vec<Option<Value>>,
+    #[cfg_attr(feature = "luau", allow(unused))]
+    upvalues: Vec<(Function, usize)>,
+}
+
+impl<'a> Reader<'a> {
+    fn read_value(&mut self) -> Result<Value> {
+        let value = match self.read_byte()? {
+            TAG_NIL => Value::Nil,
+            TAG_FALSE => Value::Boolean(false),
+            TAG_TRUE => Value::Boolean(true),
+            TAG_INTEGER => {
+                let i = i64::from_le_bytes(self.read_array()?);
+                #[allow(clippy::useless_conversion)]
+                let i = i
+                    .try_into()
+                    .map_err(|_| Error::runtime("integer is out of range"))?;
+                Value::Integer(i)
+            }
+            TAG_NUMBER => Value::Number(f64::from_le_bytes(self.read_array()?)),
+            TAG_STRING => Value::String(self.lua.create_string(self.read_bytes()?)?),
+            #[cfg(feature = "luau")]
+            TAG_VECTOR => {
+                let mut v = [0.0; Vector::SIZE];
+                for n in &mut v {
+                    *n = f32::from_le_bytes(self.read_array()?);
+                }
+                Value::Vector(Vector(v))
+            }
+            TAG_REF => {
+                let id = self.read_varint()? as usize;
+                match self.objects.get(id) {
+                    Some(Some(value)) => value.clone(),
+                    Some(None) => return Err(Error::runtime("cyclic reference through userdata")),
+                    None => return Err(Error::runtime("invalid persisted data")),
+                }
+            }
+            TAG_PERMANENT => {
+                let path = self.read_path()?;
+                let value = resolve_path(self.lua, &path)?;
+                if value.is_nil() {
+                    let path = display_path(&path);
+                    return Err(Error::runtime(format!("permanent value '{path}' not found")));
+                }
+                value
+            }
+            TAG_TABLE => Value::Table(self.read_table(None)?),
+            TAG_ANCHOR => {
+                let path = self.read_path()?;
+                let table = match resolve_path(self.lua, &path)? {
+                    Value::Table(table) => table,
+                    _ => self.lua.create_table()?,
+                };
+                Value::Table(self.read_table(Some(table))?)
+            }
+            #[cfg(not(feature = "luau"))]
+            TAG_FUNCTION => Value::Function(self.read_function()?),
+            TAG_USERDATA => {
+                let id = self.objects.len();
+                self.objects.push(None);
+                let name = StdString::from_utf8_lossy(self.read_bytes()?).into_owned();
+                let (_, unpersist) = (self.persister.userdata.get(&name))
+                    .ok_or_else(|| Error::runtime(format!("userdata type '{name}' is not registered")))?;
+                let value = self.read_value()?;
+                let ud = Value::UserData(unpersist(self.lua, value)?);
+                self.objects[id] = Some(ud.clone());
+                ud
+            }
+            _ => return Err(Error::runtime("invalid persisted data")),
+        };
+        Ok(value)
+    }
+
+    fn read_path(&mut self) -> Result<Vec<Value>> {
+        let len = self.read_varint()?;
+        (0..len)
+            .map(|_| match self.read_value()? {
+                key @ (Value::String(_) | Value::Integer(_)) => Ok(key),
+                _ => Err(Error::runtime("invalid persisted data")),
+            })
+            .collect()
+    }
+
+    fn read_table(&mut self, table: Option<Table>) -> Result<Table> {
+        let table = match table {
+            Some(table) => table,
+            None => self.lua.create_table()?,
+        };
+        self.objects.push(Some(Value::Table(table.clone())));
+        loop {
+            let key = self.read_value()?;
+            if key.is_nil() {
+                break;
+            }
+            let value = self.read_value()?;
+            table.raw_set(key, value)?;
+        }
+        match self.read_value()? {
+            Value::Table(mt) => table.set_metatable(Some(mt))?,
+            Value::Nil => {}
+            _ => return Err(Error::runtime("invalid persisted data")),
+        }
+        Ok(table)
+    }
+
+    #[cfg(not(feature = "luau"))]
+    fn read_function(&mut self) -> Result<Function> {
+        let bytecode = self.read_bytes()?;
+        let func = (self.lua.load(bytecode))
+            .set_mode(ChunkMode::Binary)
+            .into_function()?;
+        self.objects.push(Some(Value::Function(func.clone())));
+        let count = self.read_varint()? as usize;
+        for n in 1..=count {
+            let index = self.read_varint()? as usize;
+            if index < self.upvalues.len() {
+                #[cfg(any(feature = "lua54", feature = "lua53", feature = "lua52"))]
+                {
+                    let (other, m) = &self.upvalues[index];
+                    unsafe { join_upvalues(&func, n, other, *m)? };
+                    continue;
+                }
+                #[cfg(not(any(feature = "lua54", feature = "lua53", feature = "lua52")))]
+                return Err(Error::runtime("invalid persisted data"));
+            }
+            if index != self.upvalues.len() {
+                return Err(Error::runtime("invalid persisted data"));
+            }
+            self.upvalues.push((func.clone(), n));
+            let value = self.read_value()?;
+            unsafe { set_upvalue(&func, n, &value)? };
+        }
+        Ok(func)
+    }
+
+    fn read_byte(&mut self) -> Result<u8> {
+        let (&byte, rest) =
+            (self.data.split_first()).ok_or_else(|| Error::runtime("invalid persisted data"))?;
+        self.data = rest;
+        Ok(byte)
+    }
+
+    fn read_array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        let bytes = self.read_slice(N)?;
+        Ok(bytes.try_into().unwrap())
+    }
+
+    fn read_slice(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.data.len() < len {
+            return Err(Error::runtime("invalid persisted data"));
+        }
+        let (bytes, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(bytes)
+    }
+
+    fn read_bytes(&mut self) -> Result<&'a [u8]> {
+        let len = self.read_varint()? as usize;
+        self.read_slice(len)
+    }
+
+    fn read_varint(&mut self) -> Result<u64> {
+        let mut n = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.read_byte()?;
+            n |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(n);
+            }
+        }
+        Err(Error::runtime("invalid persisted data"))
+    }
+}
+
+// Splits a dotted path into keys.
+//
+// Path components that are numbers refer to integer keys.
+fn parse_path(lua: &Lua, path: &str) -> Result<Vec<Value>> {
+    (path.split('.').filter(|key| !key.is_empty()))
+        .map(|key| match key.parse::<Integer>() {
+            Ok(i) => Ok(Value::Integer(i)),
+            Err(_) => lua.create_string(key).map(Value::String),
+        })
+        .collect()
+}
+
+fn display_path(path: &[Value]) -> StdString {
+    let keys = path.iter().map(|key| key.to_string().unwrap_or_default());
+    keys.collect::<Vec<_>>().join(".")
+}
+
+// Returns a value at the given path from the globals table.
+fn resolve_path(lua: &Lua, path: &[Value]) -> Result<Value> {
+    let mut value = Value::Table(lua.globals());
+    for key in path {
+        value = match value {
+            Value::Table(table) => table.raw_get(key)?,
+            _ => return Ok(Value::Nil),
+        };
+    }
+    Ok(value)
+}
+
+// Returns values of function upvalues along with their unique identifiers (Lua 5.2+).
+#[cfg(not(feature = "luau"))]
+pub(crate) unsafe fn get_upvalues(func: &Function) -> Result<Vec<(Value, Option<usize>)>> {
+    let lua = func.0.lua.lock();
+    let state = lua.state();
+    let _sg = StackGuard::new(state);
+    check_stack(state, 2)?;
+
+    lua.push_ref(&func.0);
+    let mut upvalues = Vec::new();
+    for n in 1.. {
+        if ffi::lua_getupvalue(state, -1, n).is_null() {
+            break;
+        }
+        let value = lua.pop_value();
+        #[cfg(any(feature = "lua54", feature = "lua53", feature = "lua52"))]
+        let id = Some(ffi::lua_upvalueid(state, -1, n) as usize);
+        #[cfg(not(any(feature = "lua54", feature = "lua53", feature = "lua52")))]
+        let id = None;
+        upvalues.push((value, id));
+    }
+    Ok(upvalues)
+}
+
+#[cfg(not(feature = "luau"))]
+pub(crate) unsafe fn set_upvalue(func: &Function, n: usize, value: &Value) -> Result<()> {
+    let lua = func.0.lua.lock();
+    let state = lua.state();
+    let _sg = StackGuard::new(state);
+    check_stack(state, 2)?;
+
+    lua.push_ref(&func.0);
+    lua.push_value(value)?;
+    ffi::lua_setupvalue(state, -2, n as _);
+    Ok(())
+}
+
+// Makes the `n`-th upvalue of `func` refer to the `m`-th upvalue of `other`.
+#[cfg(any(feature = "lua54", feature = "lua53", feature = "lua52"))]
+pub(crate) unsafe fn join_upvalues(func: &Function, n: usize, other: &Function, m: usize) -> Result<()> {
+    let lua = func.0.lua.lock();
+    let state = lua.state();
+    let _sg = StackGuard::new(state);
+    check_stack(state, 2)?;
+
+    lua.push_ref(&func.0);
+    lua.push_ref(&other.0);
+    ffi::lua_upvaluejoin(state, -2, n as _, -1, m as _);
+    Ok(())
+}
@@ -0,0 +1,547 @@
+//! Pool of independent Lua states running on separate OS threads.
+
+use std::any;
+use std::collections::VecDeque;
+use std::panic::{self, AssertUnwindSafe};
+use std::string::String as StdString;
+use std::sync::{mpsc, Arc};
+use std::thread::{self, JoinHandle as ThreadJoinHandle};
+use std::time::{Duration, Instant};
+
+use parking_lot::{Condvar, Mutex};
+use rustc_hash::FxHashMap;
+
+use crate::error::{Error, Result};
+use crate::multi::MultiValue;
+use crate::persist::{PersistUserData, Persister};
+use crate::state::{Lua, LuaOptions};
+use crate::stdlib::StdLib;
+use crate::traits::{FromLuaMulti, IntoLuaMulti};
+use crate::types::Number;
+use crate::userdata::{UserData, UserDataFields, UserDataMethods};
+
+type SetupFn = dyn Fn(&Lua) -> Result<()> + Send + Sync;
+type Job = Box<dyn FnOnce(&Lua) + Send>;
+
+/// A pool of worker threads, each owning an independent [`Lua`] state.
+///
+/// A single [`Lua`] instance can only be used by one thread at a time. `LuaPool` runs several
+/// instances in parallel, on their own threads, and dispatches jobs to them. Each state is
+/// initialized by a shared setup function (see [`LuaPoolBuilder::setup`]).
+///
+/// States do not share any Lua values, but can exchange messages through named [`Channel`]s.
+/// Messages are deep copied using the [`Persister`] format: `nil`, booleans, numbers, strings,
+/// tables (including cycles and metatables), Lua functions with their upvalues (except in Luau)
+/// and userdata of types registered with [`LuaPoolBuilder::register_userdata`] can be sent.
+/// C (Rust) functions cannot be sent.
+///
+/// Every state has a global `pool` table with the following fields:
+/// - `pool.worker` - index of the current worker (starting from zero)
+/// - `pool.size` - number of workers in the pool
+/// - `pool.channel(name)` - returns the channel with the given name
+///
+/// Dropping the pool closes all channels and waits for the queued jobs to finish.
+///
+/// Requires `feature = "send"`
+///
+/// # Example
+///
+/// ```
+/// # use mlua::{LuaPool, Result};
+/// # fn main() -> Result<()> {
+/// let pool = LuaPool::builder()
+///     .size(2)
+///     .setup(|lua| lua.load("function square(x) return x * x end").exec())
+///     .build()?;
+///
+/// let results = pool.channel("results");
+/// for i in 1..=10 {
+///     pool.execute(move |lua| {
+///         lua.load(format!("pool.channel('results'):send(square({i}))")).exec()
+///     });
+/// }
+///
+/// let lua = mlua::Lua::new();
+/// let mut sum = 0;
+/// for _ in 1..=10 {
+///     sum += results.recv::<i64>(&lua)?.unwrap();
+/// }
+/// assert_eq!(sum, 385);
+/// # Ok(())
+/// # }
+/// ```
+#[cfg_attr(docsrs, doc(cfg(feature = "send")))]
+pub struct LuaPool {
+    shared: Arc<PoolShared>,
+    workers: Vec<ThreadJoinHandle<()>>,
+}
+
+struct PoolShared {
+    size: usize,
+    queue: Mutex<JobQueue>,
+    ready: Condvar,
+    channels: Mutex<FxHashMap<StdString, Channel>>,
+    persister: Arc<Persister>,
+}
+
+struct JobQueue {
+    jobs: VecDeque<Job>,
+    targeted: Vec<VecDeque<Job>>,
+    shutdown: bool,
+}
+
+/// Builder for [`LuaPool`].
+#[cfg_attr(docsrs, doc(cfg(feature = "send")))]
+pub struct LuaPoolBuilder {
+    size: usize,
+    libs: StdLib,
+    options: LuaOptions,
+    setup: Option<Arc<SetupFn>>,
+    persister: Persister,
+}
+
+impl LuaPoolBuilder {
+    /// Sets the number of worker threads (and Lua states) in the pool.
+    ///
+    /// Defaults to the available parallelism of the machine.
+    pub fn size(mut self, size: usize) -> Self {
+        self.size = size;
+        self
+    }
+
+    /// Sets the standard libraries to load into every Lua state in the pool.
+    ///
+    /// Defaults to [`StdLib::ALL_SAFE`]. See [`Lua::new_with`] for the restrictions.
+    pub fn libs(mut self, libs: StdLib) -> Self {
+        self.libs = libs;
+        self
+    }
+
+    /// Sets the options used to create every Lua state in the pool.
+    pub fn options(mut self, options: LuaOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Sets a function to initialize every Lua state in the pool.
+    ///
+    /// The function is called once per state, on the worker thread owning it.
+    pub fn setup<F>(mut self, setup: F) -> Self
+    where
+        F: Fn(&Lua) -> Result<()> + Send + Sync + 'static,
+    {
+        self.setup = Some(Arc::new(setup));
+        self
+    }
+
+    /// Registers a userdata type that can be sent through channels.
+    ///
+    /// Userdata is converted to a value using [`PersistUserData::persist`], copied to the
+    /// receiving state and recreated there using [`PersistUserData::unpersist`].
+    pub fn register_userdata<T>(mut self) -> Self
+    where
+        T: UserData + PersistUserData + Send + 'static,
+    {
+        self.persister = self.persister.register_userdata::<T>(any::type_name::<T>());
+        self
+    }
+
+    /// Spawns the worker threads and initializes their Lua states.
+    ///
+    /// Returns an error if a Lua state cannot be created, or the setup function fails (or panics)
+    /// in any of the states.
+    pub fn build(self) -> Result<LuaPool> {
+        if self.size == 0 {
+            return Err(Error::runtime("pool size must be greater than zero"));
+        }
+
+        let shared = Arc::new(PoolShared {
+            size: self.size,
+            queue: Mutex::new(JobQueue {
+                jobs: VecDeque::new(),
+                targeted: (0..self.size).map(|_| VecDeque::new()).collect(),
+                shutdown: false,
+            }),
+            ready: Condvar::new(),
+            channels: Mutex::new(FxHashMap::default()),
+            persister: Arc::new(self.persister),
+        });
+
+        let mut pool = LuaPool {
+            shared: shared.clone(),
+            workers: Vec::with_capacity(self.size),
+        };
+        let (init_tx, init_rx) = mpsc::channel();
+        for index in 0..self.size {
+            let shared = shared.clone();
+            let (libs, options, setup) = (self.libs, self.options.clone(), self.setup.clone());
+            let init_tx = init_tx.clone();
+            let worker = thread::Builder::new()
+                .name(format!("mlua-pool-{index}"))
+                .spawn(move || {
+                    let lua = panic::catch_unwind(AssertUnwindSafe(|| {
+                        let lua = Lua::new_with(libs, options)?;
+                        init_worker(&lua, &shared, index, setup.as_deref())?;
+                        Ok(lua)
+                    }));
+                    let lua = lua.unwrap_or_else(|payload| {
+                        let message = (payload.downcast_ref::<&str>().copied())
+                            .or_else(|| payload.downcast_ref::<StdString>().map(|s| s.as_str()))
+                            .unwrap_or("unknown panic");
+                        Err(Error::runtime(format!("pool worker setup panicked: {message}")))
+                    });
+                    let _ = init_tx.send(lua.as_ref().err().cloned());
+                    drop(init_tx);
+                    if let Ok(lua) = lua {
+                        run_worker(&lua, &shared, index);
+                    }
+                })
+                .map_err(Error::external)?;
+            pool.workers.push(worker);
+        }
+        drop(init_tx);
+
+        // On error the pool is dropped, which stops the other workers
+        if let Some(err) = init_rx.into_iter().flatten().next() {
+            return Err(err);
+        }
+        Ok(pool)
+    }
+}
+
+fn init_worker(lua: &Lua, shared: &Arc<PoolShared>, index: usize, setup: Option<&SetupFn>) -> Result<()> {
+    let library = lua.create_table()?;
+    library.set("worker", index)?;
+    library.set("size", shared.size)?;
+    let weak_shared = Arc::downgrade(shared);
+    library.set(
+        "channel",
+        lua.create_function(move |_, name: StdString| match weak_shared.upgrade() {
+            Some(shared) => Ok(shared.channel(&name)),
+            None => Err(Error::runtime("worker pool is shut down")),
+        })?,
+    )?;
+    lua.globals().set("pool", library)?;
+    match setup {
+        Some(setup) => setup(lua),
+        None => Ok(()),
+    }
+}
+
+fn run_worker(lua: &Lua, shared: &PoolShared, index: usize) {
+    loop {
+        let job = {
+            let mut queue = shared.queue.lock();
+            loop {
+                if let Some(job) = (queue.targeted[index].pop_front()).or_else(|| queue.jobs.pop_front()) {
+                    break job;
+                }
+                if queue.shutdown {
+                    return;
+                }
+                shared.ready.wait(&mut queue);
+            }
+        };
+        job(lua);
+    }
+}
+
+impl PoolShared {
+    fn channel(&self, name: &str) -> Channel {
+        let mut channels = self.channels.lock();
+        if let Some(channel) = channels.get(name) {
+            return channel.clone();
+        }
+        let channel = Channel {
+            inner: Arc::new(ChannelInner {
+                name: name.to_string(),
+                queue: Mutex::new(ChannelQueue {
+                    messages: VecDeque::new(),
+                    // Channels created after the pool is dropped are closed immediately
+                    closed: self.queue.lock().shutdown,
+                }),
+                ready: Condvar::new(),
+            }),
+            persister: self.persister.clone(),
+        };
+        channels.insert(name.to_string(), channel.clone());
+        channel
+    }
+}
+
+impl LuaPool {
+    /// Creates a new pool with the given number of workers, each initialized by `setup`.
+    pub fn new<F>(size: usize, setup: F) -> Result<Self>
+    where
+        F: Fn(&Lua) -> Result<()> + Send + Sync + 'static,
+    {
+        Self::builder().size(size).setup(setup).build()
+    }
+
+    /// Returns a builder to configure a new pool.
+    pub fn builder() -> LuaPoolBuilder {
+        LuaPoolBuilder {
+            size: thread::available_parallelism().map_or(1, |n| n.get()),
+            libs: StdLib::ALL_SAFE,
+            options: LuaOptions::default(),
+            setup: None,
+            persister: Persister::new().detached(),
+        }
+    }
+
+    /// Returns the number of workers in the pool.
+    pub fn size(&self) -> usize {
+        self.shared.size
+    }
+
+    /// Queues a job to run on the first available worker.
+    ///
+    /// Returns a [`JobHandle`] to wait for the result.
+    pub fn execute<F, R>(&self, job: F) -> JobHandle<R>
+    where
+        F: FnOnce(&Lua) -> Result<R> + Send + 'static,
+        R: Send + 'static,
+    {
+        self.push_job(None, job)
+    }
+
+    /// Queues a job to run on the worker with the given index.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is not less than [`LuaPool::size`].
+    pub fn execute_on<F, R>(&self, index: usize, job: F) -> JobHandle<R>
+    where
+        F: FnOnce(&Lua) -> Result<R> + Send + 'static,
+        R: Send + 'static,
+    {
+        assert!(index < self.size(), "worker index out of bounds");
+        self.push_job(Some(index), job)
+    }
+
+    /// Queues a job to run once on every worker.
+    ///
+    /// Returns handles ordered by worker index.
+    pub fn broadcast<F, R>(&self, job: F) -> Vec<JobHandle<R>>
+    where
+        F: Fn(&Lua) -> Result<R> + Clone + Send + 'static,
+        R: Send + 'static,
+    {
+        (0..self.size())
+            .map(|index| self.push_job(Some(index), job.clone()))
+            .collect()
+    }
+
+    /// Returns the channel with the given name, creating it if it does not exist.
+    ///
+    /// The same channel is available to Lua states as `pool.channel(name)`.
+    pub fn channel(&self, name: &str) -> Channel {
+        self.shared.channel(name)
+    }
+
+    fn push_job<F, R>(&self, index: Option<usize>, job: F) -> JobHandle<R>
+    where
+        F: FnOnce(&Lua) -> Result<R> + Send + 'static,
+        R: Send + 'static,
+    {
+        let (tx, rx) = mpsc::sync_channel(1);
+        let job: Job = Box::new(move |lua| {
+            let result = panic::catch_unwind(AssertUnwindSafe(|| job(lua)));
+            let _ = tx.send(result);
+        });
+        let mut queue = self.shared.queue.lock();
+        match index {
+            Some(index) => queue.targeted[index].push_back(job),
+            None => queue.jobs.push_back(job),
+        }
+        drop(queue);
+        self.shared.ready.notify_all();
+        JobHandle { rx }
+    }
+}
+
+impl Drop for LuaPool {
+    fn drop(&mut self) {
+        self.shared.queue.lock().shutdown = true;
+        self.shared.ready.notify_all();
+        for channel in self.shared.channels.lock().values() {
+            channel.close();
+        }
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+/// A handle to wait for the result of a job queued in [`LuaPool`].
+#[cfg_attr(docsrs, doc(cfg(feature = "send")))]
+pub struct JobHandle<R> {
+    rx: mpsc::Receiver<thread::Result<Result<R>>>,
+}
+
+impl<R> JobHandle<R> {
+    /// Waits for the job to finish and returns its result.
+    ///
+    /// If the job panicked, the panic is resumed on the calling thread.
+    pub fn join(self) -> Result<R> {
+        match self.rx.recv() {
+            Ok(Ok(result)) => result,
+            Ok(Err(payload)) => panic::resume_unwind(payload),
+            Err(_) => Err(Error::runtime("worker pool is shut down")),
+        }
+    }
+}
+
+/// A named multi-producer, multi-consumer message queue shared by states in [`LuaPool`].
+///
+/// Each message is a list of values that are deep copied from the sending state into the
+/// receiving state. Channels can be used from Rust with any [`Lua`] instance, and from Lua
+/// through the userdata methods `send(...)`, `recv([timeout])`, `try_recv()`, `close()` and
+/// `len()`. In Lua, receiving from a closed and empty channel (or after the timeout expires)
+/// returns `nil`.
+///
+/// `Channel` is cheap to clone; all clones refer to the same queue.
+#[cfg_attr(docsrs, doc(cfg(feature = "send")))]
+#[derive(Clone)]
+pub struct Channel {
+    inner: Arc<ChannelInner>,
+    persister: Arc<Persister>,
+}
+
+struct ChannelInner {
+    name: StdString,
+    queue: Mutex<ChannelQueue>,
+    ready: Condvar,
+}
+
+struct ChannelQueue {
+    messages: VecDeque<Vec<u8>>,
+    closed: bool,
+}
+
+impl Channel {
+    /// Returns the name of the channel.
+    pub fn name(&self) -> &str {
+        &self.inner.name
+    }
+
+    /// Sends a message consisting of the given values.
+    ///
+    /// Returns an error if the values cannot be transferred or the channel is closed.
+    pub fn send(&self, lua: &Lua, args: impl IntoLuaMulti) -> Result<()> {
+        let message = self.persister.persist(lua, args)?;
+        let mut queue = self.inner.queue.lock();
+        if queue.closed {
+            return Err(Error::runtime(format!("channel '{}' is closed", self.inner.name)));
+        }
+        queue.messages.push_back(message);
+        drop(queue);
+        self.inner.ready.notify_one();
+        Ok(())
+    }
+
+    /// Receives a message, blocking until one is available.
+    ///
+    /// Returns `None` if the channel is closed and empty.
+    pub fn recv<R: FromLuaMulti>(&self, lua: &Lua) -> Result<Option<R>> {
+        self.receive(lua, true, None)
+    }
+
+    /// Receives a message, blocking until one is available or the timeout expires.
+    ///
+    /// Returns `None` on timeout or if the channel is closed and empty.
+    pub fn recv_timeout<R: FromLuaMulti>(&self, lua: &Lua, timeout: Duration) -> Result<Option<R>> {
+        // Timeouts too large to represent are treated as infinite
+        self.receive(lua, true, Instant::now().checked_add(timeout))
+    }
+
+    /// Receives a message if one is available, without blocking.
+    pub fn try_recv<R: FromLuaMulti>(&self, lua: &Lua) -> Result<Option<R>> {
+        self.receive(lua, false, None)
+    }
+
+    /// Closes the channel.
+    ///
+    /// Messages already in the channel can still be received, but no new messages can be sent.
+    /// Receivers waiting for a message are woken up.
+    pub fn close(&self) {
+        self.inner.queue.lock().closed = true;
+        self.inner.ready.notify_all();
+    }
+
+    /// Returns `true` if the channel is closed.
+    pub fn is_closed(&self) -> bool {
+        self.inner.queue.lock().closed
+    }
+
+    /// Returns the number of messages in the channel.
+    pub fn len(&self) -> usize {
+        self.inner.queue.lock().messages.len()
+    }
+
+    /// Returns `true` if the channel has no messages.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn receive<R: FromLuaMulti>(
+        &self,
+        lua: &Lua,
+        block: bool,
+        deadline: Option<Instant>,
+    ) -> Result<Option<R>> {
+        match self.pop(block, deadline) {
+            Some(message) => self.persister.unpersist(lua, &message).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn pop(&self, block: bool, deadline: Option<Instant>) -> Option<Vec<u8>> {
+        let mut queue = self.inner.queue.lock();
+        loop {
+            if let Some(message) = queue.messages.pop_front() {
+                return Some(message);
+            }
+            if !block || queue.closed {
+                return None;
+            }
+            match deadline {
+                Some(deadline) => {
+                    if self.inner.ready.wait_until(&mut queue, deadline).timed_out() {
+                        return queue.messages.pop_front();
+                    }
+                }
+                None => self.inner.ready.wait(&mut queue),
+            }
+        }
+    }
+}
+
+impl UserData for Channel {
+    fn add_fields<F: UserDataFields<Self>>(fields: &mut F) {
+        fields.add_field_method_get("name", |_, this| Ok(this.inner.name.clone()));
+    }
+
+    fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+        methods.add_method("send", |lua, this, args: MultiValue| this.send(lua, args));
+        methods.add_method("recv", |lua, this, timeout: Option<Number>| {
+            let values = match timeout {
+                Some(timeout) => {
+                    let timeout = Duration::try_from_secs_f64(timeout.max(0.0)).unwrap_or(Duration::MAX);
+                    this.recv_timeout::<MultiValue>(lua, timeout)?
+                }
+                None => this.recv::<MultiValue>(lua)?,
+            };
+            Ok(values.unwrap_or_default())
+        });
+        methods.add_method("try_recv", |lua, this, ()| {
+            Ok(this.try_recv::<MultiValue>(lua)?.unwrap_or_default())
+        });
+        methods.add_method("close", |_, this, ()| {
+            this.close();
+            Ok(())
+        });
+        methods.add_method("is_closed", |_, this, ()| Ok(this.is_closed()));
+        methods.add_method("len", |_, this, ()| Ok(this.len()));
+    }
+}
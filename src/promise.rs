@@ -0,0 +1,531 @@
+use std::fmt;
+use std::future::{self, Future};
+use std::pin::Pin;
+use std::sync::{Arc, Weak};
+use std::task::{Context, Poll, Waker};
+
+use parking_lot::Mutex;
+
+use crate::error::{Error, Result};
+use crate::function::Function;
+use crate::multi::MultiValue;
+use crate::state::{Lua, WeakLua};
+use crate::table::Table;
+use crate::traits::{FromLua, FromLuaMulti, IntoLuaMulti};
+use crate::types::{BoxFuture, MaybeSend};
+use crate::userdata::{MetaMethod, UserData, UserDataMethods};
+use crate::value::Value;
+
+/// A handle to the result of an asynchronous operation that can be awaited later.
+///
+/// Unlike functions created using [`Lua::create_async_function`], which suspend the calling
+/// coroutine until the Rust future completes, functions created using
+/// [`Lua::create_promise_function`] immediately return a promise. This allows Lua code to start
+/// several operations and await them later.
+///
+/// Promises can be backed by Rust futures ([`Lua::create_promise`]) or created in Lua code using
+/// `Promise.new(function(resolve, reject) ... end)` (see [`Promise::create_library`]).
+///
+/// In Lua, a promise has the following methods:
+/// - `await()`: suspends the current coroutine until the promise is settled, returns the
+///   promise values or raises its error
+/// - `next([on_fulfilled], [on_rejected])`: returns a new promise settled with the results of the
+///   corresponding handler (which can be an async function or return another promise); also
+///   available as `then` (e.g. `promise["then"](promise, handler)`)
+/// - `catch(on_rejected)`: same as `next(nil, on_rejected)`
+/// - `cancel()`: cancels the promise, dropping the underlying future
+/// - `status()`: returns `"pending"`, `"fulfilled"`, `"rejected"` or `"cancelled"`
+///
+/// In Rust, a promise implements [`Future`] resolving to its values.
+///
+/// # Execution model
+///
+/// Rust futures backing promises are not spawned on an executor. Instead, futures of all pending
+/// promises of a Lua state are polled every time any of its promises is awaited (from Lua or
+/// Rust), so they make progress concurrently. Consequently, a promise makes no progress while
+/// nothing is awaited, e.g. a "fire-and-forget" call like `fetch(url)` does not run unless some
+/// other promise is awaited later.
+///
+/// A pending promise is cancelled (its future is dropped) once all handles to it are dropped,
+/// including the Lua userdata after it's garbage collected.
+///
+/// # Examples
+///
+/// ```
+/// # use std::time::Duration;
+/// # use mlua::{Lua, Promise, Result};
+/// # #[tokio::main]
+/// # async fn main() -> Result<()> {
+/// let lua = Lua::new();
+/// let fetch = lua.create_promise_function(|_, n: u64| async move {
+///     tokio::time::sleep(Duration::from_millis(n)).await;
+///     Ok(n * 2)
+/// })?;
+/// lua.globals().set("fetch", fetch)?;
+/// lua.globals().set("Promise", Promise::create_library(&lua)?)?;
+///
+/// let sum: u64 = lua.load(r#"
+///     -- Both futures run concurrently
+///     local a, b = fetch(20), fetch(10)
+///     local results = Promise.all({a, b}):await()
+///     return results[1] + results[2]
+/// "#).eval_async().await?;
+/// assert_eq!(sum, 60);
+/// # Ok(())
+/// # }
+/// ```
+#[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+#[derive(Clone)]
+pub struct Promise(Arc<Mutex<PromiseInner>>);
+
+struct PromiseInner {
+    lua: WeakLua,
+    state: PromiseState,
+    // Taken out while being polled
+    future: Option<BoxFuture<'static, Result<MultiValue>>>,
+    wakers: Vec<Waker>,
+}
+
+#[derive(Clone)]
+enum PromiseState {
+    Pending,
+    Fulfilled(MultiValue),
+    Rejected(Error),
+    Cancelled,
+}
+
+/// Status of a [`Promise`].
+#[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PromiseStatus {
+    /// The promise is not settled yet.
+    Pending,
+    /// The promise is resolved with values.
+    Fulfilled,
+    /// The promise is rejected with an error.
+    Rejected,
+    /// The promise is cancelled.
+    Cancelled,
+}
+
+impl PromiseStatus {
+    fn as_str(self) -> &'static str {
+        match self {
+            PromiseStatus::Pending => "pending",
+            PromiseStatus::Fulfilled => "fulfilled",
+            PromiseStatus::Rejected => "rejected",
+            PromiseStatus::Cancelled => "cancelled",
+        }
+    }
+}
+
+// Rust-backed promises that are not settled yet (and not dropped)
+#[derive(Default)]
+struct PromisePool {
+    pending: Vec<Weak<Mutex<PromiseInner>>>,
+    driving: bool,
+}
+
+type SharedPromisePool = Arc<Mutex<PromisePool>>;
+
+impl Promise {
+    fn new(lua: &Lua, future: Option<BoxFuture<'static, Result<MultiValue>>>) -> Self {
+        let inner = PromiseInner {
+            lua: lua.weak(),
+            state: PromiseState::Pending,
+            future,
+            wakers: Vec::new(),
+        };
+        Promise(Arc::new(Mutex::new(inner)))
+    }
+
+    fn from_future(lua: &Lua, fut: impl Future<Output = Result<MultiValue>> + MaybeSend + 'static) -> Self {
+        let promise = Promise::new(lua, Some(Box::pin(fut)));
+        let pool = promise_pool(lua);
+        let mut pool = pool.lock();
+        // Forget dropped promises that were never driven (amortized by the vector growth)
+        if pool.pending.len() == pool.pending.capacity() {
+            pool.pending.retain(|inner| inner.strong_count() > 0);
+        }
+        pool.pending.push(Arc::downgrade(&promise.0));
+        drop(pool);
+        promise
+    }
+
+    fn settled(lua: &Lua, state: PromiseState) -> Self {
+        let promise = Promise::new(lua, None);
+        promise.settle(state);
+        promise
+    }
+
+    /// Returns the current status of the promise.
+    pub fn status(&self) -> PromiseStatus {
+        match self.0.lock().state {
+            PromiseState::Pending => PromiseStatus::Pending,
+            PromiseState::Fulfilled(_) => PromiseStatus::Fulfilled,
+            PromiseState::Rejected(_) => PromiseStatus::Rejected,
+            PromiseState::Cancelled => PromiseStatus::Cancelled,
+        }
+    }
+
+    /// Cancels the promise, dropping the underlying future (if any).
+    ///
+    /// Awaiting a cancelled promise results in [`Error::Cancelled`].
+    /// Returns `false` if the promise is already settled.
+    pub fn cancel(&self) -> bool {
+        self.settle(PromiseState::Cancelled)
+    }
+
+    /// Waits for the promise to settle and converts its values to `R`.
+    pub async fn value<R: FromLuaMulti>(self) -> Result<R> {
+        let lua = self.lua()?;
+        let values = self.await?;
+        R::from_lua_multi(values, &lua)
+    }
+
+    /// Returns a promise that is fulfilled with a table of the first values of all promises once
+    /// they are fulfilled, or rejected as soon as any of them is rejected.
+    pub fn all(lua: &Lua, promises: impl IntoIterator<Item = Promise>) -> Promise {
+        let promises = promises.into_iter().collect::<Vec<_>>();
+        let weak_lua = lua.weak();
+        Promise::from_future(lua, async move {
+            let results = future::poll_fn(|cx| {
+                let (mut results, mut pending) = (Vec::with_capacity(promises.len()), false);
+                for promise in &promises {
+                    match promise.poll_result(cx) {
+                        Poll::Ready(Ok(values)) => {
+                            results.push(values.into_iter().next().unwrap_or(Value::Nil))
+                        }
+                        Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                        Poll::Pending => pending = true,
+                    }
+                }
+                if pending {
+                    return Poll::Pending;
+                }
+                Poll::Ready(Ok(results))
+            })
+            .await?;
+            let lua = upgrade(&weak_lua)?;
+            lua.create_sequence_from(results)?.into_lua_multi(&lua)
+        })
+    }
+
+    /// Returns a promise that is settled the same way as the first settled promise.
+    pub fn race(lua: &Lua, promises: impl IntoIterator<Item = Promise>) -> Promise {
+        let promises = promises.into_iter().collect::<Vec<_>>();
+        Promise::from_future(lua, async move {
+            future::poll_fn(|cx| {
+                for promise in &promises {
+                    if let Poll::Ready(result) = promise.poll_result(cx) {
+                        return Poll::Ready(result);
+                    }
+                }
+                Poll::Pending
+            })
+            .await
+        })
+    }
+
+    /// Returns a promise that is fulfilled with the values of the first fulfilled promise, or
+    /// rejected if all of the promises are rejected.
+    pub fn any(lua: &Lua, promises: impl IntoIterator<Item = Promise>) -> Promise {
+        let promises = promises.into_iter().collect::<Vec<_>>();
+        Promise::from_future(lua, async move {
+            future::poll_fn(|cx| {
+                let mut rejected = 0;
+                for promise in &promises {
+                    match promise.poll_result(cx) {
+                        Poll::Ready(Ok(values)) => return Poll::Ready(Ok(values)),
+                        Poll::Ready(Err(_)) => rejected += 1,
+                        Poll::Pending => {}
+                    }
+                }
+                if rejected == promises.len() {
+                    return Poll::Ready(Err(Error::runtime("all promises were rejected")));
+                }
+                Poll::Pending
+            })
+            .await
+        })
+    }
+
+    /// Creates a Lua table with functions to create and combine promises from Lua code.
+    ///
+    /// The table contains the following functions:
+    /// - `new(executor)`: creates a new promise and calls `executor(resolve, reject)`, where
+    ///   `resolve(...)` fulfills the promise with the given values and `reject(err)` rejects it
+    /// - `resolve(...)`: returns a promise fulfilled with the given values
+    /// - `reject(err)`: returns a promise rejected with the given error
+    /// - `all(promises)`, `race(promises)`, `any(promises)`: combine a list of promises as
+    ///   [`Promise::all`], [`Promise::race`] and [`Promise::any`] do
+    pub fn create_library(lua: &Lua) -> Result<Table> {
+        let library = lua.create_table()?;
+        library.raw_set(
+            "new",
+            lua.create_function(|lua, executor: Function| {
+                let promise = Promise::new(lua, None);
+                let this = promise.clone();
+                let resolve = lua.create_function(move |_, values: MultiValue| {
+                    Ok(this.settle(PromiseState::Fulfilled(values)))
+                })?;
+                let this = promise.clone();
+                let reject =
+                    lua.create_function(move |_, err: Error| Ok(this.settle(PromiseState::Rejected(err))))?;
+                if let Err(err) = executor.call::<()>((resolve, reject)) {
+                    promise.settle(PromiseState::Rejected(err));
+                }
+                Ok(promise)
+            })?,
+        )?;
+        library.raw_set(
+            "resolve",
+            lua.create_function(|lua, values: MultiValue| {
+                if let Some(promise) = as_promise(&values) {
+                    return Ok(promise);
+                }
+                Ok(Promise::settled(lua, PromiseState::Fulfilled(values)))
+            })?,
+        )?;
+        library.raw_set(
+            "reject",
+            lua.create_function(|lua, err: Error| Ok(Promise::settled(lua, PromiseState::Rejected(err))))?,
+        )?;
+        library.raw_set(
+            "all",
+            lua.create_function(|lua, promises: Vec<Promise>| Ok(Promise::all(lua, promises)))?,
+        )?;
+        library.raw_set(
+            "race",
+            lua.create_function(|lua, promises: Vec<Promise>| Ok(Promise::race(lua, promises)))?,
+        )?;
+        library.raw_set(
+            "any",
+            lua.create_function(|lua, promises: Vec<Promise>| Ok(Promise::any(lua, promises)))?,
+        )?;
+        Ok(library)
+    }
+
+    fn next(&self, lua: &Lua, on_fulfilled: Option<Function>, on_rejected: Option<Function>) -> Promise {
+        let promise = self.clone();
+        Promise::from_future(lua, async move {
+            let (handler, args) = match promise.await {
+                Ok(values) => match on_fulfilled {
+                    Some(handler) => (handler, values),
+                    None => return Ok(values),
+                },
+                Err(err) => match on_rejected {
+                    Some(handler) => (handler, MultiValue::from_vec(vec![Value::Error(Box::new(err))])),
+                    None => return Err(err),
+                },
+            };
+            let results = handler.call_async::<MultiValue>(args).await?;
+            // Promises returned from handlers are flattened
+            match as_promise(&results) {
+                Some(promise) => promise.await,
+                None => Ok(results),
+            }
+        })
+    }
+
+    fn is_pending(&self) -> bool {
+        matches!(self.0.lock().state, PromiseState::Pending)
+    }
+
+    // Settles the promise (if it is pending), waking up the tasks awaiting it
+    fn settle(&self, state: PromiseState) -> bool {
+        let (future, wakers) = {
+            let mut inner = self.0.lock();
+            if !matches!(inner.state, PromiseState::Pending) {
+                return false;
+            }
+            inner.state = state;
+            (inner.future.take(), std::mem::take(&mut inner.wakers))
+        };
+        drop(future);
+        wakers.into_iter().for_each(Waker::wake);
+        true
+    }
+
+    fn poll_result(&self, cx: &mut Context<'_>) -> Poll<Result<MultiValue>> {
+        self.poll_future(cx);
+        match self.lua() {
+            Ok(lua) => drive_promises(&lua, cx),
+            Err(err) => return Poll::Ready(Err(err)),
+        }
+
+        let mut inner = self.0.lock();
+        match &inner.state {
+            PromiseState::Pending => {
+                if !inner.wakers.iter().any(|waker| waker.will_wake(cx.waker())) {
+                    inner.wakers.push(cx.waker().clone());
+                }
+                Poll::Pending
+            }
+            PromiseState::Fulfilled(values) => Poll::Ready(Ok(values.clone())),
+            PromiseState::Rejected(err) => Poll::Ready(Err(err.clone())),
+            PromiseState::Cancelled => Poll::Ready(Err(Error::Cancelled)),
+        }
+    }
+
+    // Polls the underlying future (unless it is already being polled up the stack)
+    fn poll_future(&self, cx: &mut Context<'_>) {
+        let Some(mut future) = self.0.lock().future.take() else {
+            return;
+        };
+        match future.as_mut().poll(cx) {
+            Poll::Pending => {
+                let mut inner = self.0.lock();
+                // The promise could be cancelled while being polled
+                if matches!(inner.state, PromiseState::Pending) {
+                    inner.future = Some(future);
+                }
+            }
+            Poll::Ready(Ok(values)) => {
+                self.settle(PromiseState::Fulfilled(values));
+            }
+            Poll::Ready(Err(err)) => {
+                self.settle(PromiseState::Rejected(err));
+            }
+        }
+    }
+
+    fn lua(&self) -> Result<Lua> {
+        upgrade(&self.0.lock().lua)
+    }
+}
+
+impl Future for Promise {
+    type Output = Result<MultiValue>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.poll_result(cx)
+    }
+}
+
+impl fmt::Debug for Promise {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Promise").field(&self.status()).finish()
+    }
+}
+
+impl UserData for Promise {
+    fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+        methods.add_async_method("await", |_, this, ()| Promise::clone(&this));
+        for name in ["next", "then"] {
+            methods.add_method(
+                name,
+                |lua, this, (on_fulfilled, on_rejected): (Option<Function>, Option<Function>)| {
+                    Ok(this.next(lua, on_fulfilled, on_rejected))
+                },
+            );
+        }
+        methods.add_method("catch", |lua, this, on_rejected: Function| {
+            Ok(this.next(lua, None, Some(on_rejected)))
+        });
+        methods.add_method("cancel", |_, this, ()| Ok(this.cancel()));
+        methods.add_method("status", |_, this, ()| Ok(this.status().as_str()));
+        methods.add_meta_method(MetaMethod::ToString, |_, this, ()| {
+            Ok(format!("Promise: {}", this.status().as_str()))
+        });
+    }
+}
+
+impl FromLua for Promise {
+    fn from_lua(value: Value, _: &Lua) -> Result<Self> {
+        match value {
+            Value::UserData(ud) => Ok(ud.borrow::<Promise>()?.clone()),
+            _ => Err(Error::from_lua_conversion(value.type_name(), "Promise", None)),
+        }
+    }
+}
+
+impl Lua {
+    /// Creates a [`Promise`] backed by the given future.
+    ///
+    /// The future is driven while any promise of this Lua state is awaited.
+    #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+    pub fn create_promise<FR, R>(&self, fut: FR) -> Promise
+    where
+        FR: Future<Output = Result<R>> + MaybeSend + 'static,
+        R: IntoLuaMulti,
+    {
+        let weak_lua = self.weak();
+        Promise::from_future(self, async move {
+            let values = fut.await?;
+            values.into_lua_multi(&upgrade(&weak_lua)?)
+        })
+    }
+
+    /// Wraps a Rust async function or closure, creating a callable Lua function that returns a
+    /// [`Promise`] of the function result.
+    ///
+    /// Unlike [`Lua::create_async_function`], the function does not suspend the calling coroutine
+    /// and can be called outside of coroutines.
+    #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+    pub fn create_promise_function<F, A, FR, R>(&self, func: F) -> Result<Function>
+    where
+        F: Fn(Lua, A) -> FR + MaybeSend + 'static,
+        A: FromLuaMulti,
+        FR: Future<Output = Result<R>> + MaybeSend + 'static,
+        R: IntoLuaMulti,
+    {
+        self.create_function(move |lua, args: A| Ok(lua.create_promise(func(lua.clone(), args))))
+    }
+}
+
+// Returns the promise if the values consist of a single promise
+fn as_promise(values: &MultiValue) -> Option<Promise> {
+    match values.front() {
+        Some(Value::UserData(ud)) if values.len() == 1 => ud.borrow::<Promise>().ok().map(|p| p.clone()),
+        _ => None,
+    }
+}
+
+fn upgrade(lua: &WeakLua) -> Result<Lua> {
+    lua.try_upgrade()
+        .ok_or_else(|| Error::runtime("Lua instance is destroyed"))
+}
+
+fn promise_pool(lua: &Lua) -> SharedPromisePool {
+    let rawlua = lua.lock();
+    if let Some(pool) = rawlua.priv_app_data_ref::<SharedPromisePool>() {
+        return pool.clone();
+    }
+    let pool = SharedPromisePool::default();
+    rawlua.set_priv_app_data(pool.clone());
+    pool
+}
+
+// Polls futures of all pending promises of the Lua state
+fn drive_promises(lua: &Lua, cx: &mut Context<'_>) {
+    struct DrivingGuard(SharedPromisePool);
+
+    impl Drop for DrivingGuard {
+        fn drop(&mut self) {
+            let mut pool = self.0.lock();
+            pool.driving = false;
+            let is_pending = |inner: &Weak<_>| Weak::upgrade(inner).is_some_and(|p| Promise(p).is_pending());
+            pool.pending.retain(is_pending);
+        }
+    }
+
+    let pool = promise_pool(lua);
+    let promises = {
+        let mut pool = pool.lock();
+        // Promises awaited while driving are polled directly
+        if pool.driving {
+            return;
+        }
+        pool.driving = true;
+        pool.pending
+            .iter()
+            .filter_map(Weak::upgrade)
+            .map(Promise)
+            .collect::<Vec<_>>()
+    };
+    let _guard = DrivingGuard(pool);
+    for promise in promises {
+        promise.poll_future(cx);
+    }
+}
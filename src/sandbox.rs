@@ -0,0 +1,336 @@
+//! Fine-grained sandboxing of Lua code.
+
+use std::string::String as StdString;
+
+use crate::chunk::ChunkMode;
+use crate::error::{Error, Result};
+use crate::function::Function;
+use crate::state::Lua;
+use crate::string::String as LuaString;
+use crate::table::Table;
+use crate::traits::IntoLua;
+use crate::types::MaybeSend;
+use crate::value::Value;
+
+#[cfg(feature = "send")]
+type GlobalsFn = Box<dyn Fn(&Lua, &Table) -> Result<()> + Send>;
+
+#[cfg(not(feature = "send"))]
+type GlobalsFn = Box<dyn Fn(&Lua, &Table) -> Result<()>>;
+
+/// A set of capabilities granted to sandboxed Lua code.
+///
+/// The policy starts empty and explicitly allowlists the global values available in the sandbox,
+/// either whole libraries (e.g. `"string"`) or individual functions (e.g. `"os.time"`). Allowed
+/// libraries are shallow copies of the original tables, so changes made in the sandbox do not
+/// affect the host environment.
+///
+/// The policy can be applied either by creating a new [`Lua`] instance ([`SandboxPolicy::build`])
+/// or by creating an environment table for running untrusted chunks in an existing instance
+/// ([`SandboxPolicy::create_environment`] and [`Chunk::set_environment`]).
+///
+/// Unlike [`Lua::sandbox`], which is Luau-only, the policy works with every Lua version.
+///
+/// # Environments share the string metatable
+///
+/// Lua has a single metatable for all string values per instance, so string methods (e.g.
+/// `("").dump` or `("").upper`) are looked up in the **host** `string` library, whatever the
+/// environment is. An environment created by [`SandboxPolicy::create_environment`] therefore does
+/// not enforce `deny` or `freeze` for the `string` library when accessed through string values.
+///
+/// Only [`SandboxPolicy::build`] is safe in this regard, as it creates a separate Lua instance
+/// with the string metatable pointing to the sandboxed library. Use environments only when the
+/// host `string` library is safe to expose, or remove the dangerous functions from it.
+///
+/// Please note that some functions allow escaping the sandbox and should not be allowed for
+/// untrusted code, for example `getfenv`, `setfenv`, `rawset` (on frozen libraries), `require`,
+/// `dofile`, `loadfile` and the `debug` library.
+///
+/// # Examples
+///
+/// ```
+/// # use mlua::{Lua, Result, SandboxPolicy};
+/// # fn main() -> Result<()> {
+/// let policy = SandboxPolicy::new()
+///     .allow("print")
+///     .allow("string")
+///     .deny("string.dump")
+///     .allow("os.time")
+///     .freeze(true);
+///
+/// let lua = policy.build()?;
+/// let chunk = lua.load(r#"return os.time() > 0, os.execute == nil, string.dump == nil, ("").dump == nil"#);
+/// assert_eq!(chunk.eval::<(bool, bool, bool, bool)>()?, (true, true, true, true));
+///
+/// // Libraries are read-only
+/// assert!(lua.load("string.upper = nil").exec().is_err());
+///
+/// // Environments for running chunks in an existing instance
+/// let lua = Lua::new();
+/// let env = policy.create_environment(&lua)?;
+/// assert!(lua.load("os.exit()").set_environment(env).exec().is_err());
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`Chunk::set_environment`]: crate::Chunk::set_environment
+#[derive(Default)]
+pub struct SandboxPolicy {
+    allowed: Vec<StdString>,
+    denied: Vec<StdString>,
+    freeze: bool,
+    binary_chunks: bool,
+    globals: Vec<GlobalsFn>,
+}
+
+impl SandboxPolicy {
+    /// Creates a new policy that does not allow anything.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Allows a global value at the given path, for example `"print"`, `"string"` or `"os.time"`.
+    ///
+    /// Paths that do not exist in the Lua instance are ignored.
+    pub fn allow(mut self, path: impl Into<StdString>) -> Self {
+        self.allowed.push(path.into());
+        self
+    }
+
+    /// Removes a value previously allowed (e.g. as part of a library), for example
+    /// `"string.dump"`.
+    pub fn deny(mut self, path: impl Into<StdString>) -> Self {
+        self.denied.push(path.into());
+        self
+    }
+
+    /// Makes allowed libraries read-only.
+    ///
+    /// Libraries are replaced with proxies that have a protected metatable and reject any
+    /// modifications. In Luau the native read-only tables are used instead.
+    ///
+    /// Default: **false**
+    pub fn freeze(mut self, enabled: bool) -> Self {
+        self.freeze = enabled;
+        self
+    }
+
+    /// Allows `load` (and `loadstring`) to load binary chunks (bytecode).
+    ///
+    /// Loading malicious bytecode can crash the interpreter, so only text chunks are allowed by
+    /// default.
+    ///
+    /// Default: **false**
+    pub fn binary_chunks(mut self, enabled: bool) -> Self {
+        self.binary_chunks = enabled;
+        self
+    }
+
+    /// Adds a function to install extra globals into every sandbox environment.
+    ///
+    /// The function is called with the environment table after applying the rest of the policy.
+    pub fn globals<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&Lua, &Table) -> Result<()> + MaybeSend + 'static,
+    {
+        self.globals.push(Box::new(f));
+        self
+    }
+
+    /// Creates a new Lua instance with the safe subset of the standard library, restricted by this
+    /// policy.
+    ///
+    /// The globals table of the returned instance is replaced with the sandbox environment and
+    /// the `string` type metatable refers to the sandboxed `string` library (and is protected
+    /// if the policy freezes libraries).
+    pub fn build(&self) -> Result<Lua> {
+        let lua = Lua::new();
+        let env = self.create_environment(&lua)?;
+        let string_mt = match env.raw_get::<Value>("string")? {
+            Value::Table(string) => {
+                let mt = lua.create_table()?;
+                mt.raw_set("__index", string)?;
+                if self.freeze {
+                    mt.raw_set("__metatable", false)?;
+                }
+                Some(mt)
+            }
+            _ => None,
+        };
+        lua.set_type_metatable::<LuaString>(string_mt);
+        lua.set_globals(env)?;
+        Ok(lua)
+    }
+
+    /// Creates a new environment table in the given Lua instance, to be used with
+    /// [`Chunk::set_environment`].
+    ///
+    /// Values are taken from the current globals table of the instance.
+    ///
+    /// **Warning**: string methods are still resolved through the host `string` library, so the
+    /// policy is not enforced for them. See the [type-level documentation](SandboxPolicy) for
+    /// details and use [`SandboxPolicy::build`] if this matters.
+    ///
+    /// [`Chunk::set_environment`]: crate::Chunk::set_environment
+    pub fn create_environment(&self, lua: &Lua) -> Result<Table> {
+        let globals = lua.globals();
+        let env = lua.create_table()?;
+        // Copied library tables along with their parents
+        let mut libs = Vec::new();
+
+        for path in &self.allowed {
+            let keys = path.split('.').collect::<Vec<_>>();
+            let (last, parents) = keys.split_last().expect("split returns at least one item");
+            let Some(source) = find_table(&globals, parents)? else {
+                continue;
+            };
+            let mut target = env.clone();
+            for &key in parents {
+                target = match target.raw_get(key)? {
+                    Value::Table(t) => t,
+                    _ => {
+                        let t = lua.create_table()?;
+                        target.raw_set(key, &t)?;
+                        libs.push((target.clone(), key, t.clone()));
+                        t
+                    }
+                };
+            }
+            match source.raw_get(*last)? {
+                Value::Nil => {}
+                Value::Table(t) if t == globals => target.raw_set(*last, &env)?,
+                Value::Table(t) => {
+                    let copy = match target.raw_get(*last)? {
+                        Value::Table(copy) => copy,
+                        _ => lua.create_table()?,
+                    };
+                    for pair in t.pairs::<Value, Value>() {
+                        let (key, value) = pair?;
+                        copy.raw_set(key, value)?;
+                    }
+                    target.raw_set(*last, &copy)?;
+                    libs.push((target, *last, copy));
+                }
+                value => target.raw_set(*last, value)?,
+            }
+        }
+
+        for path in &self.denied {
+            let keys = path.split('.').collect::<Vec<_>>();
+            let (last, parents) = keys.split_last().expect("split returns at least one item");
+            if let Some(target) = find_table(&env, parents)? {
+                target.raw_set(*last, Value::Nil)?;
+            }
+        }
+
+        // Replace chunk loaders to use the sandbox environment by default
+        for name in ["load", "loadstring"] {
+            if let Value::Function(_) = env.raw_get(name)? {
+                let loader = lua
+                    .load("local load, env = ... return function(s, n, m, e) return load(s, n, m, e or env) end")
+                    .call::<Function>((self.create_loader(lua)?, &env))?;
+                env.raw_set(name, loader)?;
+            }
+        }
+
+        if self.freeze {
+            for (parent, key, lib) in libs.into_iter().rev() {
+                parent.raw_set(key, freeze_table(lua, lib)?)?;
+            }
+        }
+
+        for f in &self.globals {
+            f(lua, &env)?;
+        }
+        Ok(env)
+    }
+
+    // Creates a `load` function that respects the binary chunks policy
+    fn create_loader(&self, lua: &Lua) -> Result<Function> {
+        let binary_chunks = self.binary_chunks;
+        lua.create_function(
+            move |lua, (chunk, name, mode, env): (Value, Option<StdString>, Option<StdString>, Table)| {
+                let source = match chunk {
+                    Value::String(s) => s.as_bytes().to_vec(),
+                    Value::Function(reader) => {
+                        let mut source = Vec::new();
+                        loop {
+                            match reader.call::<Option<LuaString>>(())? {
+                                Some(piece) if !piece.as_bytes().is_empty() => {
+                                    source.extend_from_slice(&piece.as_bytes())
+                                }
+                                _ => break,
+                            }
+                        }
+                        source
+                    }
+                    value => {
+                        let msg = format!(
+                            "bad argument #1 to 'load' (string expected, got {})",
+                            value.type_name()
+                        );
+                        return Err(Error::runtime(msg));
+                    }
+                };
+                let mode = match (mode.as_deref(), binary_chunks) {
+                    (Some("t"), _) | (_, false) => Some(ChunkMode::Text),
+                    (Some("b"), true) => Some(ChunkMode::Binary),
+                    _ => None,
+                };
+                let mut chunk = lua.load(source).set_environment(env);
+                chunk = chunk.set_name(name.unwrap_or_else(|| "=(load)".to_string()));
+                if let Some(mode) = mode {
+                    chunk = chunk.set_mode(mode);
+                }
+                match chunk.into_function() {
+                    Ok(func) => Ok((Value::Function(func), Value::Nil)),
+                    Err(Error::SyntaxError { message, .. }) => Ok((Value::Nil, message.into_lua(lua)?)),
+                    Err(err) => Ok((Value::Nil, err.to_string().into_lua(lua)?)),
+                }
+            },
+        )
+    }
+}
+
+// Returns a table at the given path of keys, if exists
+fn find_table(table: &Table, keys: &[&str]) -> Result<Option<Table>> {
+    let mut table = table.clone();
+    for &key in keys {
+        table = match table.raw_get(key)? {
+            Value::Table(t) => t,
+            _ => return Ok(None),
+        };
+    }
+    Ok(Some(table))
+}
+
+// Returns a read-only view of the table
+fn freeze_table(lua: &Lua, table: Table) -> Result<Table> {
+    #[cfg(feature = "luau")]
+    {
+        table.set_readonly(true);
+        let _ = lua;
+        Ok(table)
+    }
+
+    #[cfg(not(feature = "luau"))]
+    {
+        let proxy = lua.create_table()?;
+        let mt = lua.create_table()?;
+        mt.raw_set("__index", &table)?;
+        let newindex = lua
+            .load("return function() error('attempt to modify a read-only table', 2) end")
+            .eval::<Function>()?;
+        mt.raw_set("__newindex", newindex)?;
+        mt.raw_set("__metatable", false)?;
+        #[cfg(any(feature = "lua54", feature = "lua53", feature = "lua52"))]
+        {
+            let pairs = lua
+                .load("local next, t = ... return function() return next, t, nil end")
+                .call::<Function>((lua.globals().raw_get::<Value>("next")?, &table))?;
+            mt.raw_set("__pairs", pairs)?;
+        }
+        proxy.set_metatable(Some(mt))?;
+        Ok(proxy)
+    }
+}
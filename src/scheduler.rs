@@ -0,0 +1,506 @@
+//! Cooperative scheduler for Lua threads.
+
+use std::collections::{BTreeSet, VecDeque};
+use std::os::raw::c_void;
+use std::string::String as StdString;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+use rustc_hash::FxHashMap;
+
+use crate::error::{Error, Result};
+use crate::function::Function;
+use crate::multi::MultiValue;
+use crate::state::Lua;
+use crate::table::Table;
+use crate::thread::{Thread, ThreadStatus};
+use crate::traits::IntoLuaMulti;
+use crate::types::{LightUserData, MaybeSend};
+use crate::value::Value;
+
+#[cfg(any(feature = "lua54", feature = "lua53", feature = "luau"))]
+use crate::budget::{self, ExecutionBudget};
+
+/// A source of time for [`Scheduler`].
+pub trait Clock: MaybeSend + 'static {
+    /// Returns the time elapsed since some fixed point in the past.
+    fn now(&self) -> Duration;
+}
+
+/// A [`Clock`] that measures real (monotonic) time.
+#[derive(Clone, Copy, Debug)]
+pub struct SystemClock {
+    origin: Instant,
+}
+
+impl SystemClock {
+    /// Creates a new clock starting at zero.
+    pub fn new() -> Self {
+        SystemClock {
+            origin: Instant::now(),
+        }
+    }
+}
+
+impl Default for SystemClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        self.origin.elapsed()
+    }
+}
+
+/// A [`Clock`] that only moves when advanced manually.
+///
+/// Useful to make scheduling deterministic (e.g. in tests). Clones of the clock share the same
+/// time.
+#[derive(Clone, Debug, Default)]
+pub struct ManualClock {
+    now: Arc<Mutex<Duration>>,
+}
+
+impl ManualClock {
+    /// Creates a new clock starting at zero.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Moves the clock forward by the given amount of time.
+    pub fn advance(&self, duration: Duration) {
+        *self.now.lock() += duration;
+    }
+
+    /// Sets the current time of the clock.
+    pub fn set(&self, now: Duration) {
+        *self.now.lock() = now;
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Duration {
+        *self.now.lock()
+    }
+}
+
+/// Identifier of a task managed by [`Scheduler`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct TaskId(u64);
+
+/// A cooperative scheduler of Lua threads (coroutines).
+///
+/// Each task runs in its own Lua thread until it finishes or yields. Tasks suspend themselves
+/// using functions from the [library] provided by the scheduler, for example to wait for some
+/// time or for a signal. The host application drives execution by calling [`Scheduler::tick`]
+/// periodically (e.g. once per frame), which resumes all tasks that are ready to continue.
+///
+/// Time is measured by a [`Clock`], which can be replaced by [`ManualClock`] to make scheduling
+/// deterministic.
+///
+/// `Scheduler` is cheap to clone; all clones share the same tasks.
+///
+/// # Example
+///
+/// ```
+/// # use std::time::Duration;
+/// # use mlua::{Lua, ManualClock, Result, Scheduler};
+/// # fn main() -> Result<()> {
+/// let lua = Lua::new();
+/// let clock = ManualClock::new();
+/// let scheduler = Scheduler::with_clock(clock.clone());
+/// lua.globals().set("task", scheduler.create_library(&lua)?)?;
+///
+/// let func = lua.load(r#"
+///     state = "waiting"
+///     task.wait(1.5)
+///     state = "done"
+/// "#).into_function()?;
+/// scheduler.spawn(func, ())?;
+/// assert_eq!(lua.globals().get::<String>("state")?, "waiting");
+///
+/// clock.advance(Duration::from_secs(2));
+/// scheduler.tick();
+/// assert_eq!(lua.globals().get::<String>("state")?, "done");
+/// assert!(scheduler.is_empty());
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [library]: Scheduler::create_library
+#[derive(Clone)]
+pub struct Scheduler {
+    state: Arc<Mutex<SchedulerState>>,
+}
+
+struct SchedulerState {
+    clock: Box<dyn Clock>,
+    next_id: u64,
+    tasks: FxHashMap<TaskId, Task>,
+    // Queues can contain stale entries (e.g. for cancelled tasks) that are skipped
+    ready: VecDeque<TaskId>,
+    timers: BTreeSet<(Duration, TaskId)>,
+    signals: FxHashMap<StdString, Vec<TaskId>>,
+    errors: Vec<(TaskId, Error)>,
+    #[cfg(any(feature = "lua54", feature = "lua53", feature = "luau"))]
+    budget: Option<ExecutionBudget>,
+}
+
+struct Task {
+    thread: Thread,
+    wait: Wait,
+}
+
+enum Wait {
+    // Queued with the arguments to resume the task with
+    Ready(MultiValue),
+    Sleep { since: Duration, until: Duration },
+    Signal(StdString),
+    // Sleeping for a time that cannot be represented (e.g. `math.huge`)
+    Forever,
+    Running,
+}
+
+impl Default for Scheduler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Scheduler {
+    /// Creates a new scheduler using [`SystemClock`].
+    pub fn new() -> Self {
+        Self::with_clock(SystemClock::new())
+    }
+
+    /// Creates a new scheduler using the given clock.
+    pub fn with_clock(clock: impl Clock) -> Self {
+        let state = SchedulerState {
+            clock: Box::new(clock),
+            next_id: 1,
+            tasks: FxHashMap::default(),
+            ready: VecDeque::new(),
+            timers: BTreeSet::new(),
+            signals: FxHashMap::default(),
+            errors: Vec::new(),
+            #[cfg(any(feature = "lua54", feature = "lua53", feature = "luau"))]
+            budget: None,
+        };
+        Scheduler {
+            state: Arc::new(Mutex::new(state)),
+        }
+    }
+
+    /// Sets the execution budget for every resumption of a task.
+    ///
+    /// A task that exhausts its budget is preempted and continues on the next tick.
+    /// Pass `None` to remove the limit (default).
+    #[cfg(any(feature = "lua54", feature = "lua53", feature = "luau"))]
+    #[cfg_attr(docsrs, doc(cfg(any(feature = "lua54", feature = "lua53", feature = "luau"))))]
+    pub fn set_budget(&self, budget: Option<ExecutionBudget>) {
+        self.state.lock().budget = budget;
+    }
+
+    /// Creates a Lua table with functions to interact with this scheduler from Lua code.
+    ///
+    /// The table contains the following functions:
+    /// - `wait([seconds])`: suspends the current task for the given number of seconds (or until
+    ///   the next tick), returns the actual elapsed time. The task never wakes up if the time is
+    ///   too large to be represented (e.g. `math.huge`). Negative numbers and NaN are errors.
+    /// - `wait_signal(name)`: suspends the current task until the signal is fired, returns the
+    ///   signal arguments
+    /// - `signal(name, ...)`: fires a signal, returns the number of woken tasks
+    /// - `spawn(func, ...)`: creates a new task and runs it immediately until it yields
+    /// - `defer(func, ...)`: creates a new task that starts on the next tick
+    ///
+    /// The `coroutine` standard library must be loaded.
+    pub fn create_library(&self, lua: &Lua) -> Result<Table> {
+        let yield_fn = (lua.globals().get::<Table>("coroutine"))
+            .and_then(|co| co.get::<Function>("yield"))
+            .map_err(|_| Error::runtime("coroutine library is not loaded"))?;
+        let (wait, wait_signal) = lua
+            .load(
+                r#"
+                local token, yield = ...
+                local function wait(seconds)
+                    if seconds ~= nil and (type(seconds) ~= "number" or not (seconds >= 0)) then
+                        error("bad argument #1 to 'wait' (non-negative number expected)", 2)
+                    end
+                    return yield(token, "wait", seconds)
+                end
+                local function wait_signal(name)
+                    return yield(token, "signal", name)
+                end
+                return wait, wait_signal
+                "#,
+            )
+            .set_name("=__mlua_scheduler")
+            .call::<(Function, Function)>((self.token(), yield_fn))?;
+
+        let library = lua.create_table()?;
+        library.raw_set("wait", wait)?;
+        library.raw_set("wait_signal", wait_signal)?;
+        let this = self.clone();
+        let signal =
+            lua.create_function(move |_, (name, args): (StdString, MultiValue)| this.signal(&name, args));
+        library.raw_set("signal", signal?)?;
+        let this = self.clone();
+        let spawn = lua.create_function(move |_, (func, args): (Function, MultiValue)| {
+            this.spawn(func, args).map(|_| ())
+        });
+        library.raw_set("spawn", spawn?)?;
+        let this = self.clone();
+        let defer = lua.create_function(move |_, (func, args): (Function, MultiValue)| {
+            this.defer(func, args).map(|_| ())
+        });
+        library.raw_set("defer", defer?)?;
+        Ok(library)
+    }
+
+    /// Creates a new task and runs it immediately until it yields or finishes.
+    ///
+    /// Errors raised by the task are reported by the next [`Scheduler::tick`].
+    pub fn spawn(&self, func: Function, args: impl IntoLuaMulti) -> Result<TaskId> {
+        let lua = func.0.lua.upgrade();
+        let args = args.into_lua_multi(&lua)?;
+        let id = self.add_task(lua.create_thread(func)?, Wait::Running);
+        self.resume_task(id, args);
+        Ok(id)
+    }
+
+    /// Creates a new task that starts on the next [`Scheduler::tick`].
+    pub fn defer(&self, func: Function, args: impl IntoLuaMulti) -> Result<TaskId> {
+        let lua = func.0.lua.upgrade();
+        let args = args.into_lua_multi(&lua)?;
+        let id = self.add_task(lua.create_thread(func)?, Wait::Ready(args));
+        self.state.lock().ready.push_back(id);
+        Ok(id)
+    }
+
+    /// Fires a signal, waking all tasks waiting for it.
+    ///
+    /// The woken tasks receive the given arguments and continue on the next [`Scheduler::tick`].
+    /// Returns the number of woken tasks.
+    pub fn signal(&self, name: &str, args: impl IntoLuaMulti) -> Result<usize> {
+        let mut state = self.state.lock();
+        let waiting = (state.signals.remove(name).unwrap_or_default().into_iter())
+            .filter(|id| matches!(state.tasks.get(id), Some(Task { wait: Wait::Signal(s), .. }) if s == name))
+            .collect::<Vec<_>>();
+        let Some(first) = waiting.first() else {
+            return Ok(0);
+        };
+        let args = args.into_lua_multi(&state.tasks[first].thread.0.lua.upgrade())?;
+        for &id in &waiting {
+            state.set_ready(id, args.clone());
+        }
+        Ok(waiting.len())
+    }
+
+    /// Cancels a task.
+    ///
+    /// Returns `false` if the task does not exist (e.g. it has already finished).
+    pub fn cancel(&self, id: TaskId) -> bool {
+        self.state.lock().tasks.remove(&id).is_some()
+    }
+
+    /// Returns `true` if the task exists (has not finished and was not cancelled).
+    pub fn contains(&self, id: TaskId) -> bool {
+        self.state.lock().tasks.contains_key(&id)
+    }
+
+    /// Returns the number of tasks.
+    pub fn len(&self) -> usize {
+        self.state.lock().tasks.len()
+    }
+
+    /// Returns `true` if there are no tasks.
+    pub fn is_empty(&self) -> bool {
+        self.state.lock().tasks.is_empty()
+    }
+
+    /// Returns the earliest (clock) time when a task is ready to continue.
+    ///
+    /// Returns `None` if no tasks are ready or sleeping (all are waiting for signals or sleeping
+    /// forever).
+    pub fn next_wakeup(&self) -> Option<Duration> {
+        let state = self.state.lock();
+        if (state.ready.iter()).any(|id| {
+            matches!(
+                state.tasks.get(id),
+                Some(Task {
+                    wait: Wait::Ready(_),
+                    ..
+                })
+            )
+        }) {
+            return Some(state.clock.now());
+        }
+        (state.timers.iter())
+            .find(|&&(until, id)| state.is_sleeping(id, until))
+            .map(|&(until, _)| until)
+    }
+
+    /// Resumes all tasks that are ready to continue.
+    ///
+    /// Tasks that become ready while the tick is running (e.g. deferred or woken by a signal)
+    /// continue on the next tick.
+    ///
+    /// Returns errors raised by tasks since the previous tick. Tasks that raised errors are
+    /// removed.
+    pub fn tick(&self) -> Vec<(TaskId, Error)> {
+        let count = {
+            let mut state = self.state.lock();
+            let now = state.clock.now();
+            // Wake up sleeping tasks
+            while let Some(&(until, id)) = state.timers.first() {
+                if until > now {
+                    break;
+                }
+                state.timers.pop_first();
+                if let Some(Task {
+                    wait: Wait::Sleep { since, until: u },
+                    ..
+                }) = state.tasks.get(&id)
+                {
+                    if *u == until {
+                        let elapsed = (now - *since).as_secs_f64();
+                        state.set_ready(id, MultiValue::from_iter([Value::Number(elapsed)]));
+                    }
+                }
+            }
+            state.ready.len()
+        };
+
+        for _ in 0..count {
+            let mut state = self.state.lock();
+            let Some(id) = state.ready.pop_front() else {
+                break;
+            };
+            let args = match state.tasks.get_mut(&id) {
+                Some(task) => match std::mem::replace(&mut task.wait, Wait::Running) {
+                    Wait::Ready(args) => args,
+                    wait => {
+                        task.wait = wait;
+                        continue;
+                    }
+                },
+                None => continue,
+            };
+            drop(state);
+            self.resume_task(id, args);
+        }
+
+        std::mem::take(&mut self.state.lock().errors)
+    }
+
+    fn token(&self) -> LightUserData {
+        LightUserData(Arc::as_ptr(&self.state) as *mut c_void)
+    }
+
+    fn add_task(&self, thread: Thread, wait: Wait) -> TaskId {
+        let mut state = self.state.lock();
+        let id = TaskId(state.next_id);
+        state.next_id += 1;
+        state.tasks.insert(id, Task { thread, wait });
+        id
+    }
+
+    fn resume_task(&self, id: TaskId, args: MultiValue) {
+        let (thread, result) = {
+            let mut state = self.state.lock();
+            let Some(task) = state.tasks.get_mut(&id) else {
+                return;
+            };
+            task.wait = Wait::Running;
+            let thread = task.thread.clone();
+            #[cfg(any(feature = "lua54", feature = "lua53", feature = "luau"))]
+            let budget = state.budget;
+            drop(state);
+
+            #[cfg(any(feature = "lua54", feature = "lua53", feature = "luau"))]
+            let result = match budget {
+                Some(budget) => budget::resume(&thread, args, budget),
+                None => thread.resume::<MultiValue>(args).map(|values| (values, false)),
+            };
+            #[cfg(not(any(feature = "lua54", feature = "lua53", feature = "luau")))]
+            let result = thread.resume::<MultiValue>(args).map(|values| (values, false));
+            (thread, result)
+        };
+
+        let mut state = self.state.lock();
+        if !state.tasks.contains_key(&id) {
+            // Cancelled while running
+            return;
+        }
+        match result {
+            Err(err) => {
+                state.tasks.remove(&id);
+                state.errors.push((id, err));
+            }
+            Ok(_) if thread.status() != ThreadStatus::Resumable => {
+                state.tasks.remove(&id);
+            }
+            Ok((_, true)) => state.set_ready(id, MultiValue::new()),
+            Ok((values, false)) => {
+                let now = state.clock.now();
+                let wait = match values.front() {
+                    Some(Value::LightUserData(token)) if *token == self.token() => {
+                        match (values.get(1), values.get(2)) {
+                            (Some(Value::String(kind)), seconds) if kind == "wait" => {
+                                let seconds = match seconds {
+                                    Some(Value::Integer(i)) => *i as f64,
+                                    Some(Value::Number(n)) => *n,
+                                    _ => 0.0,
+                                };
+                                let delay = Duration::try_from_secs_f64(seconds).ok();
+                                match delay.and_then(|delay| now.checked_add(delay)) {
+                                    Some(until) => Wait::Sleep { since: now, until },
+                                    None => Wait::Forever,
+                                }
+                            }
+                            (Some(Value::String(kind)), Some(Value::String(name))) if kind == "signal" => {
+                                Wait::Signal(name.to_string_lossy())
+                            }
+                            _ => {
+                                let err = Error::runtime("invalid scheduler request");
+                                state.tasks.remove(&id);
+                                state.errors.push((id, err));
+                                return;
+                            }
+                        }
+                    }
+                    // Plain `coroutine.yield()` continues on the next tick
+                    _ => Wait::Ready(MultiValue::new()),
+                };
+                state.set_wait(id, wait);
+            }
+        }
+    }
+}
+
+impl SchedulerState {
+    fn is_sleeping(&self, id: TaskId, until: Duration) -> bool {
+        matches!(self.tasks.get(&id), Some(Task { wait: Wait::Sleep { until: u, .. }, .. }) if *u == until)
+    }
+
+    fn set_ready(&mut self, id: TaskId, args: MultiValue) {
+        self.set_wait(id, Wait::Ready(args));
+    }
+
+    fn set_wait(&mut self, id: TaskId, wait: Wait) {
+        match &wait {
+            Wait::Ready(_) => self.ready.push_back(id),
+            Wait::Sleep { until, .. } => {
+                self.timers.insert((*until, id));
+            }
+            Wait::Signal(name) => self.signals.entry(name.clone()).or_default().push(id),
+            Wait::Forever | Wait::Running => {}
+        }
+        if let Some(task) = self.tasks.get_mut(&id) {
+            task.wait = wait;
+        }
+    }
+}
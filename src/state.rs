@@ -5,13 +5,14 @@ use std::ops::Deref;
 use std::os::raw::{c_char, c_int};
 use std::panic::Location;
 use std::result::Result as StdResult;
+use std::string::String as StdString;
 use std::{fmt, mem, ptr};
 
 use crate::chunk::{AsChunk, Chunk};
-use crate::debug::Debug;
+use crate::debug::{Debug, Frame, MemoryProfile};
 use crate::error::{Error, Result};
 use crate::function::Function;
-use crate::memory::MemoryState;
+use crate::memory::{AllocationTracker, MemoryProfilerHook, MemoryState};
 use crate::multi::MultiValue;
 use crate::scope::Scope;
 use crate::stdlib::StdLib;
@@ -19,6 +20,8 @@ use crate::string::String;
 use crate::table::Table;
 use crate::thread::Thread;
 use crate::traits::{FromLua, FromLuaMulti, IntoLua, IntoLuaMulti};
+use crate::transfer::{self, CloneAcrossStates, TransferRegistry};
+use crate::typedef;
 use crate::types::{
     AppDataRef, AppDataRefMut, ArcReentrantMutexGuard, Integer, LuaType, MaybeSend, Number, ReentrantMutex,
     ReentrantMutexGuard, RegistryKey, VmState, XRc, XWeak,
@@ -28,11 +31,17 @@ use crate::util::{assert_stack, check_stack, protect_lua_closure, push_string, r
 use crate::value::{Nil, Value};
 
 #[cfg(not(feature = "luau"))]
-use crate::{debug::HookTriggers, types::HookKind};
+use crate::{
+    debug::{DebugEvent, HookTriggers},
+    types::{HookCallback, HookKind},
+};
 
 #[cfg(any(feature = "luau", doc))]
 use crate::{buffer::Buffer, chunk::Compiler};
 
+#[cfg(feature = "luau")]
+use crate::types::InterruptCallback;
+
 #[cfg(feature = "async")]
 use {
     crate::types::LightUserData,
@@ -44,6 +53,8 @@ use serde::Serialize;
 
 pub(crate) use extra::ExtraData;
 pub use raw::RawLua;
+#[cfg(all(not(feature = "luau"), any(feature = "lua54", feature = "lua53")))]
+pub(crate) use raw::ThreadHook;
 pub(crate) use util::callback_error_ext;
 
 /// Top level Lua struct which represents an instance of Lua VM.
@@ -103,6 +114,14 @@ pub struct LuaOptions {
     #[cfg(feature = "async")]
     #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
     pub thread_pool_size: usize,
+
+    /// Record type information of the Rust functions and userdata types.
+    ///
+    /// Recorded information is used to generate type definitions for scripts, see
+    /// [`Lua::generate_type_definitions`].
+    ///
+    /// Default: **false**
+    pub type_definitions: bool,
 }
 
 impl Default for LuaOptions {
@@ -118,6 +137,7 @@ impl LuaOptions {
             catch_rust_panics: true,
             #[cfg(feature = "async")]
             thread_pool_size: 0,
+            type_definitions: false,
         }
     }
 
@@ -140,6 +160,15 @@ impl LuaOptions {
         self.thread_pool_size = size;
         self
     }
+
+    /// Sets [`type_definitions`] option.
+    ///
+    /// [`type_definitions`]: #structfield.type_definitions
+    #[must_use]
+    pub const fn type_definitions(mut self, enabled: bool) -> Self {
+        self.type_definitions = enabled;
+        self
+    }
 }
 
 impl Drop for Lua {
@@ -552,7 +581,7 @@ impl Lua {
         unsafe {
             (*lua.extra.get()).hook_triggers = triggers;
             (*lua.extra.get()).hook_callback = Some(XRc::new(callback));
-            lua.set_thread_hook(lua.state(), HookKind::Global)
+            lua.set_thread_hook(lua.state(), None, HookKind::Global)
         }
     }
 
@@ -599,7 +628,7 @@ impl Lua {
         F: Fn(&Lua, &Debug) -> Result<VmState> + MaybeSend + 'static,
     {
         let lua = self.lock();
-        unsafe { lua.set_thread_hook(lua.state(), HookKind::Thread(triggers, XRc::new(callback))) }
+        unsafe { lua.set_thread_hook(lua.state(), None, HookKind::Thread(triggers, XRc::new(callback))) }
     }
 
     /// Removes a global hook previously set by [`Lua::set_global_hook`].
@@ -615,6 +644,35 @@ impl Lua {
         }
     }
 
+    /// Returns the current global hook (if any).
+    #[cfg(not(feature = "luau"))]
+    pub(crate) fn global_hook(&self) -> Option<(HookTriggers, HookCallback)> {
+        let lua = self.lock();
+        unsafe {
+            let extra = lua.extra.get();
+            ((*extra).hook_callback.clone()).map(|callback| ((*extra).hook_triggers, callback))
+        }
+    }
+
+    /// Restores a global hook previously returned by [`Lua::global_hook`].
+    #[cfg(not(feature = "luau"))]
+    pub(crate) fn restore_global_hook(&self, hook: Option<(HookTriggers, HookCallback)>) -> Result<()> {
+        match hook {
+            Some((triggers, callback)) => {
+                let lua = self.lock();
+                unsafe {
+                    (*lua.extra.get()).hook_triggers = triggers;
+                    (*lua.extra.get()).hook_callback = Some(callback);
+                    lua.set_thread_hook(lua.state(), None, HookKind::Global)
+                }
+            }
+            None => {
+                self.remove_global_hook();
+                Ok(())
+            }
+        }
+    }
+
     /// Removes any hook from the current thread.
     ///
     /// This function has no effect if a hook was not previously set.
@@ -721,6 +779,27 @@ impl Lua {
         }
     }
 
+    /// Returns the current interrupt callback (if any).
+    #[cfg(feature = "luau")]
+    pub(crate) fn interrupt_callback(&self) -> Option<InterruptCallback> {
+        let lua = self.lock();
+        unsafe { (*lua.extra.get()).interrupt_callback.clone() }
+    }
+
+    /// Restores an interrupt callback previously returned by [`Lua::interrupt_callback`].
+    ///
+    /// An interrupt function set by [`Lua::set_interrupt`] must be installed.
+    #[cfg(feature = "luau")]
+    pub(crate) fn restore_interrupt_callback(&self, callback: Option<InterruptCallback>) {
+        match callback {
+            Some(callback) => {
+                let lua = self.lock();
+                unsafe { (*lua.extra.get()).interrupt_callback = Some(callback) };
+            }
+            None => self.remove_interrupt(),
+        }
+    }
+
     /// Sets a thread creation callback that will be called when a thread is created.
     #[cfg(any(feature = "luau", doc))]
     #[cfg_attr(docsrs, doc(cfg(feature = "luau")))]
@@ -818,7 +897,6 @@ impl Lua {
     {
         use std::ffi::CStr;
         use std::os::raw::{c_char, c_void};
-        use std::string::String as StdString;
 
         unsafe extern "C-unwind" fn warn_proc(ud: *mut c_void, msg: *const c_char, tocont: c_int) {
             let extra = ud as *mut ExtraData;
@@ -929,6 +1007,122 @@ impl Lua {
         }
     }
 
+    /// Enables or disables memory profiling.
+    ///
+    /// When enabled, every allocation made by this Lua state is attributed to the Lua function and
+    /// line that was executing at that moment (and, for Lua 5.2+, to the type of the allocated
+    /// object). Use [`Lua::memory_profile`] to get a snapshot of the collected statistics.
+    ///
+    /// Enabling profiling again discards the collected statistics. Only allocations made while
+    /// profiling is enabled are tracked.
+    ///
+    /// For Lua 5.x and LuaJIT, the profiler is installed as a global hook (see
+    /// [`Lua::set_global_hook`]), replacing the current global hook while profiling is enabled.
+    /// For Luau, it replaces the interrupt function (see [`Lua::set_interrupt`]) in the same way.
+    /// Disabling profiling restores the replaced hook, unless the profiler hook has been replaced
+    /// in the meantime (eg. by attaching a debugger), in which case the new hook is left in place.
+    ///
+    /// Does not work in module mode where Lua state is managed externally.
+    pub fn set_memory_profiling(&self, enabled: bool) -> Result<()> {
+        unsafe {
+            let mem_state = MemoryState::get(self.lock().main_state());
+            if mem_state.is_null() {
+                return Err(Error::MemoryControlNotAvailable);
+            }
+            (*mem_state).tracker = enabled.then(|| Box::new(AllocationTracker::new()));
+        }
+
+        let hook = unsafe { (*self.lock().extra.get()).memory_profiler_hook.take() };
+        let hook = hook.filter(|hook| hook.is_current(self));
+        if !enabled {
+            if let Some(hook) = hook {
+                hook.restore(self)?;
+            }
+            return Ok(());
+        }
+        if let Some(hook) = hook {
+            // The profiler hook is still installed
+            unsafe { (*self.lock().extra.get()).memory_profiler_hook = Some(hook) };
+            return Ok(());
+        }
+
+        // Updates the current allocation site
+        fn set_site(lua: &Lua, frame: Option<Frame>) {
+            unsafe {
+                let mem_state = MemoryState::get(lua.lock().main_state());
+                if let Some(tracker) = (*mem_state).tracker.as_deref_mut() {
+                    tracker.set_site(frame);
+                }
+            }
+        }
+
+        #[cfg(not(feature = "luau"))]
+        {
+            let previous = self.global_hook();
+            let triggers = HookTriggers::EVERY_LINE | HookTriggers::ON_RETURNS;
+            self.set_global_hook(triggers, |lua, debug| {
+                let frame = match debug.event() {
+                    DebugEvent::Line => Some(Frame::new(debug)),
+                    // Attribute allocations to the first Lua function the control returns to
+                    _ => (1..)
+                        .map_while(|level| lua.inspect_stack(level, Frame::new))
+                        .find(|frame| frame.line.is_some()),
+                };
+                set_site(lua, frame);
+                Ok(VmState::Continue)
+            })?;
+            let installed = mlua_expect!(self.global_hook(), "memory profiler hook is not set");
+            let hook = MemoryProfilerHook { installed, previous };
+            unsafe { (*self.lock().extra.get()).memory_profiler_hook = Some(hook) };
+        }
+        #[cfg(feature = "luau")]
+        {
+            let previous = self.interrupt_callback();
+            self.set_interrupt(|lua| {
+                set_site(lua, lua.inspect_stack(0, Frame::new));
+                Ok(VmState::Continue)
+            });
+            let installed = mlua_expect!(self.interrupt_callback(), "memory profiler interrupt is not set");
+            let hook = MemoryProfilerHook { installed, previous };
+            unsafe { (*self.lock().extra.get()).memory_profiler_hook = Some(hook) };
+        }
+        Ok(())
+    }
+
+    /// Returns a snapshot of the memory statistics collected by the memory profiler.
+    ///
+    /// Memory profiling must be enabled first using [`Lua::set_memory_profiling`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use mlua::{Lua, Result};
+    /// # fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    /// lua.set_memory_profiling(true)?;
+    /// let before = lua.memory_profile()?;
+    /// lua.load("cache = {} for i = 1, 100 do cache[i] = {} end").exec()?;
+    /// let after = lua.memory_profile()?;
+    /// for change in after.diff(&before) {
+    ///     println!("{:?} {}: {} bytes", change.frame, change.kind, change.bytes);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn memory_profile(&self) -> Result<MemoryProfile> {
+        let lua = self.lock();
+        unsafe {
+            let mem_state = MemoryState::get(lua.main_state());
+            if mem_state.is_null() {
+                return Err(Error::MemoryControlNotAvailable);
+            }
+            match (*mem_state).tracker.as_deref() {
+                Some(tracker) => Ok(tracker.snapshot()),
+                None => Err(Error::runtime("memory profiling is not enabled")),
+            }
+        }
+    }
+
     /// Returns `true` if the garbage collector is currently running automatically.
     #[cfg(any(feature = "lua54", feature = "lua53", feature = "lua52", feature = "luau"))]
     pub fn gc_is_running(&self) -> bool {
@@ -1262,10 +1456,12 @@ impl Lua {
         A: FromLuaMulti,
         R: IntoLuaMulti,
     {
-        (self.lock()).create_callback(Box::new(move |rawlua, nargs| unsafe {
+        let func = (self.lock()).create_callback(Box::new(move |rawlua, nargs| unsafe {
             let args = A::from_stack_args(nargs, 1, None, rawlua)?;
             func(rawlua.lua(), args)?.push_into_stack_multi(rawlua)
-        }))
+        }))?;
+        typedef::record_function::<A, R>(self, &func)?;
+        Ok(func)
     }
 
     /// Wraps a Rust mutable closure, creating a callable Lua function handle to it.
@@ -1356,7 +1552,7 @@ impl Lua {
     {
         // In future we should switch to async closures when they are stable to capture `&Lua`
         // See https://rust-lang.github.io/rfcs/3668-async-closures.html
-        (self.lock()).create_async_callback(Box::new(move |rawlua, nargs| unsafe {
+        let func = (self.lock()).create_async_callback(Box::new(move |rawlua, nargs| unsafe {
             let args = match A::from_stack_args(nargs, 1, None, rawlua) {
                 Ok(args) => args,
                 Err(e) => return Box::pin(future::ready(Err(e))),
@@ -1364,7 +1560,9 @@ impl Lua {
             let lua = rawlua.lua();
             let fut = func(lua.clone(), args);
             Box::pin(async move { fut.await?.push_into_stack_multi(lua.raw_lua()) })
-        }))
+        }))?;
+        typedef::record_function::<A, R>(self, &func)?;
+        Ok(func)
     }
 
     /// Wraps a Lua function into a new thread (or coroutine).
@@ -1445,6 +1643,59 @@ impl Lua {
         Ok(())
     }
 
+    /// Allows userdata of type `T` created in this Lua state to be copied to other states.
+    ///
+    /// See [`Lua::transfer`] for details.
+    pub fn register_transferable<T>(&self)
+    where
+        T: UserData + CloneAcrossStates + MaybeSend + 'static,
+    {
+        let lua = self.lock();
+        if let Some(mut registry) = lua.priv_app_data_mut::<TransferRegistry>() {
+            registry.register::<T>();
+            return;
+        }
+        let mut registry = TransferRegistry::default();
+        registry.register::<T>();
+        lua.set_priv_app_data(registry);
+    }
+
+    /// Makes a deep copy of a value from this Lua state in the `other` state.
+    ///
+    /// The following values can be copied:
+    /// - `nil`, booleans, numbers, strings, light userdata, vectors and buffers
+    /// - tables, together with their metatables; tables referenced more than once (including
+    ///   cycles) are copied once, preserving the structure
+    /// - C functions without upvalues (e.g. functions from the standard library)
+    /// - Lua functions, by loading their bytecode (see [`Function::dump`]) and copying their
+    ///   upvalues; upvalues shared between functions remain shared (Lua 5.2+ only). Not available
+    ///   in Luau.
+    /// - userdata of types registered using [`Lua::register_transferable`]
+    ///
+    /// The globals table of this state is not copied, references to it are replaced with the
+    /// globals table of the `other` state.
+    ///
+    /// Returns an error if the value (or any value reachable from it) cannot be copied.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Lua, Result, Table};
+    /// # fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    /// let other = Lua::new();
+    ///
+    /// let config: Table = lua.load("{ name = 'app', limits = { 1, 2, 3 } }").eval()?;
+    /// let copy = lua.transfer(&other, config)?;
+    /// other.globals().set("config", copy)?;
+    /// assert_eq!(other.load("#config.limits").eval::<i64>()?, 3);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn transfer(&self, other: &Lua, value: impl IntoLua) -> Result<Value> {
+        transfer::deep_copy(&value.into_lua(self)?, other)
+    }
+
     /// Create a Lua userdata "proxy" object from a custom userdata type.
     ///
     /// Proxy object is an empty userdata object that has `T` metatable attached.
@@ -1486,6 +1737,42 @@ impl Lua {
         unsafe { self.lock().make_userdata(UserDataStorage::new(ud)) }
     }
 
+    /// Generates type definitions for the Rust functions and userdata types exposed to Lua.
+    ///
+    /// Definitions include all registered userdata types, global functions and values (including
+    /// nested tables) and loaded modules. Only functions created using [`Lua::create_function`]
+    /// (and its variants) are described, other functions are skipped.
+    ///
+    /// For Luau this produces `.d.luau` declarations, for other Lua versions [LuaLS] annotations
+    /// (`---@class`, `---@param`, etc.).
+    ///
+    /// Types are recorded only when the [`LuaOptions::type_definitions`] option is enabled,
+    /// otherwise an error is returned. Argument names are not known and generated as `arg1`,
+    /// `arg2`, etc.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Lua, LuaOptions, Result, StdLib};
+    /// # fn main() -> Result<()> {
+    /// let lua = Lua::new_with(StdLib::ALL_SAFE, LuaOptions::new().type_definitions(true))?;
+    /// let add = lua.create_function(|_, (a, b): (i64, i64)| Ok(a + b))?;
+    /// lua.globals().set("add", add)?;
+    ///
+    /// let defs = lua.generate_type_definitions()?;
+    /// # #[cfg(feature = "luau")]
+    /// assert!(defs.contains("declare function add(arg1: number, arg2: number): number"));
+    /// # #[cfg(not(feature = "luau"))]
+    /// assert!(defs.contains("---@param arg1 integer"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [LuaLS]: https://luals.github.io/wiki/annotations/
+    pub fn generate_type_definitions(&self) -> Result<StdString> {
+        typedef::generate(self)
+    }
+
     /// Sets the metatable for a Lua builtin type.
     ///
     /// The metatable will be shared by all values of the given type.
@@ -2082,6 +2369,13 @@ impl Lua {
         LightUserData(&ASYNC_POLL_TERMINATE as *const u8 as *mut std::os::raw::c_void)
     }
 
+    #[cfg(feature = "async")]
+    #[inline(always)]
+    pub(crate) fn poll_cancel() -> LightUserData {
+        static ASYNC_POLL_CANCEL: u8 = 0;
+        LightUserData(&ASYNC_POLL_CANCEL as *const u8 as *mut std::os::raw::c_void)
+    }
+
     /// Returns a weak reference to the Lua instance.
     ///
     /// This is useful for creating a reference to the Lua instance that does not prevent it from
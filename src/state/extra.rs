@@ -87,6 +87,8 @@ pub(crate) struct ExtraData {
     pub(super) thread_creation_callback: Option<crate::types::ThreadCreationCallback>,
     #[cfg(feature = "luau")]
     pub(super) thread_collection_callback: Option<crate::types::ThreadCollectionCallback>,
+    // Hook installed by the memory profiler (if enabled)
+    pub(super) memory_profiler_hook: Option<crate::memory::MemoryProfilerHook>,
 
     #[cfg(feature = "luau")]
     pub(crate) running_gc: bool,
@@ -200,6 +202,7 @@ impl ExtraData {
             thread_creation_callback: None,
             #[cfg(feature = "luau")]
             thread_collection_callback: None,
+            memory_profiler_hook: None,
             #[cfg(feature = "luau")]
             sandboxed: false,
             #[cfg(feature = "luau")]
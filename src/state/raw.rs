@@ -17,6 +17,7 @@ use crate::string::String;
 use crate::table::Table;
 use crate::thread::Thread;
 use crate::traits::IntoLua;
+use crate::typedef::TypeRegistry;
 use crate::types::{
     AppDataRef, AppDataRefMut, Callback, CallbackUpvalue, DestructedUserdata, Integer, LightUserData,
     MaybeSend, ReentrantMutex, RegistryKey, ValueRef, XRc,
@@ -42,6 +43,9 @@ use crate::{
     types::{HookCallback, HookKind, VmState},
 };
 
+#[cfg(any(feature = "lua54", feature = "lua53"))]
+use crate::debug::HookTriggers;
+
 #[cfg(feature = "async")]
 use {
     crate::multi::MultiValue,
@@ -50,6 +54,23 @@ use {
     std::task::{Context, Poll, Waker},
 };
 
+/// A hook of a thread saved by [`RawLua::thread_hook`].
+#[cfg(any(feature = "lua54", feature = "lua53"))]
+pub(crate) struct ThreadHook {
+    func: Option<ffi::lua_Hook>,
+    mask: c_int,
+    count: c_int,
+    /// The hook function, if the hook was set by mlua.
+    pub(crate) callback: Option<HookCallback>,
+}
+
+#[cfg(any(feature = "lua54", feature = "lua53"))]
+impl ThreadHook {
+    pub(crate) fn triggers(&self) -> HookTriggers {
+        HookTriggers::from_mask(self.mask, self.count)
+    }
+}
+
 /// An inner Lua struct which holds a raw Lua state.
 #[doc(hidden)]
 pub struct RawLua {
@@ -190,6 +211,10 @@ impl RawLua {
             (*extra).thread_pool.reserve_exact(options.thread_pool_size);
         }
 
+        if options.type_definitions {
+            rawlua.lock().set_priv_app_data(TypeRegistry::default());
+        }
+
         rawlua
     }
 
@@ -328,6 +353,14 @@ impl RawLua {
         extra.app_data_priv.insert(data)
     }
 
+    /// Private version of [`Lua::remove_app_data`]
+    #[track_caller]
+    #[inline]
+    pub(crate) fn remove_priv_app_data<T: 'static>(&self) -> Option<T> {
+        let extra = unsafe { &*self.extra.get() };
+        extra.app_data_priv.remove()
+    }
+
     /// Private version of [`Lua::app_data_ref`]
     #[track_caller]
     #[inline]
@@ -416,82 +449,16 @@ impl RawLua {
     }
 
     /// Sets a hook for a thread (coroutine).
+    ///
+    /// `thread_ref` must be provided unless the thread is the current one. A suspended thread
+    /// stack is not safe to push to (e.g. after yielding from a hook).
     #[cfg(not(feature = "luau"))]
     pub(crate) unsafe fn set_thread_hook(
         &self,
         thread_state: *mut ffi::lua_State,
+        thread_ref: Option<&ValueRef>,
         hook: HookKind,
     ) -> Result<()> {
-        // Key to store hooks in the registry
-        const HOOKS_KEY: *const c_char = cstr!("__mlua_hooks");
-
-        unsafe fn process_status(state: *mut ffi::lua_State, event: c_int, status: VmState) {
-            match status {
-                VmState::Continue => {}
-                VmState::Yield => {
-                    // Only count and line events can yield
-                    if event == ffi::LUA_HOOKCOUNT || event == ffi::LUA_HOOKLINE {
-                        #[cfg(any(feature = "lua54", feature = "lua53"))]
-                        if ffi::lua_isyieldable(state) != 0 {
-                            ffi::lua_yield(state, 0);
-                        }
-                        #[cfg(any(
-                            feature = "lua52",
-                            feature = "lua51",
-                            feature = "lua51-wasi",
-                            feature = "luajit"
-                        ))]
-                        {
-                            ffi::lua_pushliteral(state, c"attempt to yield from a hook");
-                            ffi::lua_error(state);
-                        }
-                    }
-                }
-            }
-        }
-
-        unsafe extern "C-unwind" fn global_hook_proc(state: *mut ffi::lua_State, ar: *mut ffi::lua_Debug) {
-            let status = callback_error_ext(state, ptr::null_mut(), false, move |extra, _| {
-                match (*extra).hook_callback.clone() {
-                    Some(hook_callback) => {
-                        let rawlua = (*extra).raw_lua();
-                        let debug = Debug::new(rawlua, 0, ar);
-                        hook_callback((*extra).lua(), &debug)
-                    }
-                    None => {
-                        ffi::lua_sethook(state, None, 0, 0);
-                        Ok(VmState::Continue)
-                    }
-                }
-            });
-            process_status(state, (*ar).event, status);
-        }
-
-        unsafe extern "C-unwind" fn hook_proc(state: *mut ffi::lua_State, ar: *mut ffi::lua_Debug) {
-            let top = ffi::lua_gettop(state);
-            let mut hook_callback_ptr = ptr::null();
-            ffi::luaL_checkstack(state, 3, ptr::null());
-            if ffi::lua_getfield(state, ffi::LUA_REGISTRYINDEX, HOOKS_KEY) == ffi::LUA_TTABLE {
-                ffi::lua_pushthread(state);
-                if ffi::lua_rawget(state, -2) == ffi::LUA_TUSERDATA {
-                    hook_callback_ptr = get_internal_userdata::<HookCallback>(state, -1, ptr::null());
-                }
-            }
-            ffi::lua_settop(state, top);
-            if hook_callback_ptr.is_null() {
-                ffi::lua_sethook(state, None, 0, 0);
-                return;
-            }
-
-            let status = callback_error_ext(state, ptr::null_mut(), false, |extra, _| {
-                let rawlua = (*extra).raw_lua();
-                let debug = Debug::new(rawlua, 0, ar);
-                let hook_callback = (*hook_callback_ptr).clone();
-                hook_callback((*extra).lua(), &debug)
-            });
-            process_status(state, (*ar).event, status)
-        }
-
         let (triggers, callback) = match hook {
             HookKind::Global if (*self.extra.get()).hook_callback.is_none() => {
                 return Ok(());
@@ -508,8 +475,16 @@ impl RawLua {
         // Hooks for threads stored in the registry (in a weak table)
         let state = self.state();
         let _sg = StackGuard::new(state);
-        check_stack(state, 3)?;
-        protect_lua!(state, 0, 0, |state| {
+        check_stack(state, 4)?;
+        match thread_ref {
+            Some(thread_ref) => self.push_ref(thread_ref), // key (thread)
+            None => {
+                ffi::lua_pushthread(thread_state);
+                ffi::lua_xmove(thread_state, state, 1); // key (thread)
+            }
+        }
+        push_internal_userdata(state, callback, true)?; // value (hook callback)
+        protect_lua!(state, 2, 0, |state| {
             if ffi::luaL_getsubtable(state, ffi::LUA_REGISTRYINDEX, HOOKS_KEY) == 0 {
                 // Table just created, initialize it
                 ffi::lua_pushliteral(state, c"k");
@@ -518,9 +493,7 @@ impl RawLua {
                 ffi::lua_setmetatable(state, -2); // metatable(hooktable) = hooktable
             }
 
-            ffi::lua_pushthread(thread_state);
-            ffi::lua_xmove(thread_state, state, 1); // key (thread)
-            let _ = push_internal_userdata(state, callback, false); // value (hook callback)
+            ffi::lua_insert(state, -3);
             ffi::lua_rawset(state, -3); // hooktable[thread] = hook callback
         })?;
 
@@ -529,6 +502,65 @@ impl RawLua {
         Ok(())
     }
 
+    /// Returns the hook currently set for a thread (coroutine), to be put back later using
+    /// [`RawLua::restore_thread_hook`].
+    #[cfg(any(feature = "lua54", feature = "lua53"))]
+    pub(crate) unsafe fn thread_hook(
+        &self,
+        thread_state: *mut ffi::lua_State,
+        thread_ref: &ValueRef,
+    ) -> ThreadHook {
+        let func = ffi::lua_gethook(thread_state);
+        let mask = ffi::lua_gethookmask(thread_state);
+        let count = ffi::lua_gethookcount(thread_state);
+        let callback = match func {
+            Some(func) if is_same_hook(func, global_hook_proc) => (*self.extra.get()).hook_callback.clone(),
+            Some(func) if is_same_hook(func, hook_proc) => {
+                let state = self.state();
+                let _sg = StackGuard::new(state);
+                let mut callback = None;
+                if ffi::lua_checkstack(state, 2) != 0
+                    && ffi::lua_getfield(state, ffi::LUA_REGISTRYINDEX, HOOKS_KEY) == ffi::LUA_TTABLE
+                {
+                    self.push_ref(thread_ref);
+                    if ffi::lua_rawget(state, -2) == ffi::LUA_TUSERDATA {
+                        let ptr = get_internal_userdata::<HookCallback>(state, -1, ptr::null());
+                        callback = ptr.as_ref().cloned();
+                    }
+                }
+                callback
+            }
+            _ => None,
+        };
+        ThreadHook {
+            func,
+            mask,
+            count,
+            callback,
+        }
+    }
+
+    /// Puts back a hook previously returned by [`RawLua::thread_hook`].
+    #[cfg(any(feature = "lua54", feature = "lua53"))]
+    pub(crate) unsafe fn restore_thread_hook(
+        &self,
+        thread_state: *mut ffi::lua_State,
+        thread_ref: &ValueRef,
+        hook: ThreadHook,
+    ) -> Result<()> {
+        let triggers = hook.triggers();
+        match (hook.func, hook.callback) {
+            (Some(func), Some(callback)) if is_same_hook(func, hook_proc) => {
+                let hook = HookKind::Thread(triggers, callback);
+                self.set_thread_hook(thread_state, Some(thread_ref), hook)
+            }
+            (func, _) => {
+                ffi::lua_sethook(thread_state, func, hook.mask, hook.count);
+                Ok(())
+            }
+        }
+    }
+
     /// See [`Lua::create_string`]
     pub(crate) unsafe fn create_string(&self, s: impl AsRef<[u8]>) -> Result<String> {
         let state = self.state();
@@ -633,7 +665,7 @@ impl RawLua {
 
         // Inherit global hook if set
         #[cfg(not(feature = "luau"))]
-        self.set_thread_hook(thread_state, HookKind::Global)?;
+        self.set_thread_hook(thread_state, None, HookKind::Global)?;
 
         let thread = Thread(self.pop_ref(), thread_state);
         ffi::lua_xpush(self.ref_thread(), thread_state, func.0.index);
@@ -985,6 +1017,11 @@ impl RawLua {
 
     pub(crate) unsafe fn push_userdata_metatable(&self, mut registry: RawUserDataRegistry) -> Result<()> {
         let state = self.state();
+        if let Some(class) = registry.type_def.take() {
+            if let Some(mut types) = self.priv_app_data_mut::<TypeRegistry>() {
+                types.add_class(class);
+            }
+        }
         let mut stack_guard = StackGuard::new(state);
         check_stack(state, 13)?;
 
@@ -1290,6 +1327,11 @@ impl RawLua {
                     ffi::lua_pushinteger(state, -1);
                     return Ok(1);
                 }
+                if nargs == 1 && ffi::lua_tolightuserdata(state, -1) == Lua::poll_cancel().0 {
+                    // Destroy the future and raise the cancellation error at the await point
+                    (*upvalue).data.take();
+                    return Err(Error::Cancelled);
+                }
 
                 let fut = &mut (*upvalue).data;
                 let mut ctx = Context::from_waker(rawlua.waker());
@@ -1407,6 +1449,85 @@ impl RawLua {
 }
 
 // Uses 3 stack spaces
+#[cfg(any(feature = "lua54", feature = "lua53"))]
+fn is_same_hook(func: ffi::lua_Hook, other: ffi::lua_Hook) -> bool {
+    func as usize == other as usize
+}
+
+// Key to store hooks in the registry
+#[cfg(not(feature = "luau"))]
+const HOOKS_KEY: *const c_char = cstr!("__mlua_hooks");
+
+#[cfg(not(feature = "luau"))]
+unsafe fn process_status(state: *mut ffi::lua_State, event: c_int, status: VmState) {
+    match status {
+        VmState::Continue => {}
+        VmState::Yield => {
+            // Only count and line events can yield
+            if event == ffi::LUA_HOOKCOUNT || event == ffi::LUA_HOOKLINE {
+                #[cfg(any(feature = "lua54", feature = "lua53"))]
+                if ffi::lua_isyieldable(state) != 0 {
+                    ffi::lua_yield(state, 0);
+                }
+                #[cfg(any(
+                    feature = "lua52",
+                    feature = "lua51",
+                    feature = "lua51-wasi",
+                    feature = "luajit"
+                ))]
+                {
+                    ffi::lua_pushliteral(state, c"attempt to yield from a hook");
+                    ffi::lua_error(state);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(not(feature = "luau"))]
+unsafe extern "C-unwind" fn global_hook_proc(state: *mut ffi::lua_State, ar: *mut ffi::lua_Debug) {
+    let status = callback_error_ext(state, ptr::null_mut(), false, move |extra, _| {
+        match (*extra).hook_callback.clone() {
+            Some(hook_callback) => {
+                let rawlua = (*extra).raw_lua();
+                let debug = Debug::new(rawlua, 0, ar);
+                hook_callback((*extra).lua(), &debug)
+            }
+            None => {
+                ffi::lua_sethook(state, None, 0, 0);
+                Ok(VmState::Continue)
+            }
+        }
+    });
+    process_status(state, (*ar).event, status);
+}
+
+#[cfg(not(feature = "luau"))]
+unsafe extern "C-unwind" fn hook_proc(state: *mut ffi::lua_State, ar: *mut ffi::lua_Debug) {
+    let top = ffi::lua_gettop(state);
+    let mut hook_callback_ptr = ptr::null();
+    ffi::luaL_checkstack(state, 3, ptr::null());
+    if ffi::lua_getfield(state, ffi::LUA_REGISTRYINDEX, HOOKS_KEY) == ffi::LUA_TTABLE {
+        ffi::lua_pushthread(state);
+        if ffi::lua_rawget(state, -2) == ffi::LUA_TUSERDATA {
+            hook_callback_ptr = get_internal_userdata::<HookCallback>(state, -1, ptr::null());
+        }
+    }
+    ffi::lua_settop(state, top);
+    if hook_callback_ptr.is_null() {
+        ffi::lua_sethook(state, None, 0, 0);
+        return;
+    }
+
+    let status = callback_error_ext(state, ptr::null_mut(), false, |extra, _| {
+        let rawlua = (*extra).raw_lua();
+        let debug = Debug::new(rawlua, 0, ar);
+        let hook_callback = (*hook_callback_ptr).clone();
+        hook_callback((*extra).lua(), &debug)
+    });
+    process_status(state, (*ar).event, status)
+}
+
 unsafe fn load_std_libs(state: *mut ffi::lua_State, libs: StdLib) -> Result<()> {
     #[cfg(feature = "lua51-wasi")]
     {
@@ -3,6 +3,7 @@ use std::panic::{catch_unwind, AssertUnwindSafe};
 use std::ptr;
 use std::sync::Arc;
 
+use crate::debug::StackTrace;
 use crate::error::{Error, Result};
 use crate::state::{ExtraData, RawLua};
 use crate::util::{self, get_internal_metatable, WrappedFailure};
@@ -132,9 +133,14 @@ where
                 "<not enough stack space for traceback>".to_string()
             };
             let cause = Arc::new(err);
+            let stack_trace = Arc::new(StackTrace::capture(state, 0));
             ptr::write(
                 wrapped_error,
-                WrappedFailure::Error(Error::CallbackError { traceback, cause }),
+                WrappedFailure::Error(Error::CallbackError {
+                    traceback,
+                    cause,
+                    stack_trace,
+                }),
             );
             get_internal_metatable::<WrappedFailure>(state);
             ffi::lua_setmetatable(state, -2);
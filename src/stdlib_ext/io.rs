@@ -0,0 +1,186 @@
+use std::string::String as StdString;
+use std::sync::Arc;
+
+use crate::error::{Error, Result};
+use crate::multi::MultiValue;
+use crate::state::Lua;
+use crate::table::Table;
+use crate::traits::{IntoLua, IntoLuaMulti};
+use crate::value::Value;
+use crate::vfs::{FileHandle, MemoryVfs, Vfs};
+
+/// A Rust implementation of the `io` library.
+///
+/// Files are opened in a virtual filesystem: the one set by [`IoLib::vfs`], the one set by
+/// [`Lua::set_vfs`] or an empty read-only filesystem otherwise. Opening files for writing and
+/// access to the standard streams are capabilities that must be granted explicitly.
+#[derive(Debug, Clone)]
+pub struct IoLib {
+    vfs: Option<Arc<dyn Vfs>>,
+    writable: bool,
+    stdin: bool,
+    stdout: bool,
+}
+
+impl Default for IoLib {
+    fn default() -> Self {
+        IoLib {
+            vfs: None,
+            writable: false,
+            stdin: false,
+            stdout: true,
+        }
+    }
+}
+
+impl IoLib {
+    /// Creates a new `io` library with the default capabilities.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets a virtual filesystem to open files in.
+    pub fn vfs(mut self, vfs: impl Vfs) -> Self {
+        self.vfs = Some(Arc::new(vfs));
+        self
+    }
+
+    /// Allows opening files for writing.
+    ///
+    /// Default: **false**
+    pub fn writable(mut self, enabled: bool) -> Self {
+        self.writable = enabled;
+        self
+    }
+
+    /// Provides access to the standard input (`io.stdin` and `io.read`).
+    ///
+    /// Default: **false**
+    pub fn stdin(mut self, enabled: bool) -> Self {
+        self.stdin = enabled;
+        self
+    }
+
+    /// Provides access to the standard output and error (`io.stdout`, `io.stderr` and
+    /// `io.write`).
+    ///
+    /// Default: **true**
+    pub fn stdout(mut self, enabled: bool) -> Self {
+        self.stdout = enabled;
+        self
+    }
+
+    /// Creates the library table.
+    pub fn create(&self, lua: &Lua) -> Result<Table> {
+        let vfs = (self.vfs.clone())
+            .or_else(|| lua.vfs())
+            .unwrap_or_else(|| Arc::new(MemoryVfs::new().read_only(true)));
+
+        let (open_vfs, writable) = (vfs.clone(), self.writable);
+        let open = lua.create_function(move |lua, (path, mode): (StdString, Option<StdString>)| {
+            let mode = mode.as_deref().unwrap_or("r");
+            match FileHandle::open(open_vfs.clone(), &path, mode, writable)? {
+                Ok(file) => (file, Value::Nil).into_lua_multi(lua),
+                Err(err) => (Value::Nil, format!("{path}: {err}")).into_lua_multi(lua),
+            }
+        })?;
+
+        let lines = lua.create_function(move |lua, (path, formats): (StdString, MultiValue)| {
+            let file = FileHandle::open(vfs.clone(), &path, "r", false)?
+                .map_err(|err| Error::runtime(format!("{path}: {err}")))?;
+            FileHandle::lines(lua, lua.create_userdata(file)?, formats, true)
+        })?;
+
+        let io_type = lua.create_function(|lua, value: Value| match value {
+            Value::UserData(ud) => match ud.borrow::<FileHandle>() {
+                Ok(file) if file.is_closed() => "closed file".into_lua(lua),
+                Ok(_) => "file".into_lua(lua),
+                Err(_) => Ok(Value::Nil),
+            },
+            _ => Ok(Value::Nil),
+        })?;
+
+        let stdin = match self.stdin {
+            true => Value::UserData(lua.create_userdata(FileHandle::stdin())?),
+            false => Value::Nil,
+        };
+        let (stdout, stderr) = match self.stdout {
+            true => (
+                Value::UserData(lua.create_userdata(FileHandle::stdout())?),
+                Value::UserData(lua.create_userdata(FileHandle::stderr())?),
+            ),
+            false => (Value::Nil, Value::Nil),
+        };
+
+        lua.load(IO_LIB)
+            .set_name("=__mlua_io")
+            .call::<Table>((open, lines, io_type, stdin, stdout, stderr))
+    }
+
+    /// Creates the library and sets it as the global `io` table.
+    pub fn install(&self, lua: &Lua) -> Result<()> {
+        super::install_lib(lua, "io", self.create(lua)?)
+    }
+}
+
+// Functions working with the default input and output files
+const IO_LIB: &str = r#"
+local open, lines, io_type, stdin, stdout, stderr = ...
+local input, output = stdin, stdout
+local io = { open = open, type = io_type, stdin = stdin, stdout = stdout, stderr = stderr }
+
+local function default_file(file, name)
+    if file == nil then
+        error("default " .. name .. " file is not available", 3)
+    end
+    return file
+end
+
+local function set_default(file, mode)
+    if type(file) == "string" then
+        return assert(open(file, mode))
+    elseif io_type(file) == nil then
+        error("bad argument #1 (file expected)", 3)
+    end
+    return file
+end
+
+function io.input(file)
+    if file ~= nil then
+        input = set_default(file, "r")
+    end
+    return input
+end
+
+function io.output(file)
+    if file ~= nil then
+        output = set_default(file, "w")
+    end
+    return output
+end
+
+function io.read(...)
+    return default_file(input, "input"):read(...)
+end
+
+function io.write(...)
+    return default_file(output, "output"):write(...)
+end
+
+function io.lines(path, ...)
+    if path == nil then
+        return default_file(input, "input"):lines(...)
+    end
+    return lines(path, ...)
+end
+
+function io.close(file)
+    return (file or default_file(output, "output")):close()
+end
+
+function io.flush()
+    return default_file(output, "output"):flush()
+end
+
+return io
+"#;
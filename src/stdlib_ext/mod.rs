@@ -0,0 +1,50 @@
+//! Rust implementations of the `io` and `os` standard libraries.
+//!
+//! Unlike the C libraries loaded using [`StdLib::IO`] and [`StdLib::OS`], these libraries work the
+//! same way across all supported Lua versions (including Luau) and only provide capabilities that
+//! are explicitly granted: files are opened in a virtual filesystem and environment variables
+//! are filtered by an allowlist.
+//!
+//! # Examples
+//!
+//! ```
+//! # use mlua::{Lua, Result};
+//! # use mlua::stdlib_ext::{IoLib, OsLib};
+//! # use mlua::MemoryVfs;
+//! # fn main() -> Result<()> {
+//! let lua = Lua::new();
+//! let vfs = MemoryVfs::new().with_file("data.txt", "hello");
+//! IoLib::new().vfs(vfs).install(&lua)?;
+//! OsLib::new().allow_env("HOME").install(&lua)?;
+//!
+//! let data: String = lua.load("return io.open('data.txt'):read('a')").eval()?;
+//! assert_eq!(data, "hello");
+//! assert_eq!(lua.load("return os.date('!%Y-%m-%d', 0)").eval::<String>()?, "1970-01-01");
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [`StdLib::IO`]: crate::StdLib::IO
+//! [`StdLib::OS`]: crate::StdLib::OS
+
+pub use io::IoLib;
+pub use os::OsLib;
+
+use crate::error::Result;
+use crate::state::Lua;
+use crate::table::Table;
+use crate::value::Value;
+
+mod io;
+mod os;
+
+// Sets the library as a global and marks it as loaded in `package.loaded`
+fn install_lib(lua: &Lua, name: &str, lib: Table) -> Result<()> {
+    let globals = lua.globals();
+    if let Value::Table(package) = globals.raw_get("package")? {
+        if let Value::Table(loaded) = package.raw_get("loaded")? {
+            loaded.raw_set(name, &lib)?;
+        }
+    }
+    globals.raw_set(name, lib)
+}
@@ -0,0 +1,278 @@
+use std::string::String as StdString;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use crate::error::{Error, Result};
+use crate::state::Lua;
+use crate::table::Table;
+use crate::types::Integer;
+use crate::value::Value;
+
+/// A Rust implementation of a restricted `os` library.
+///
+/// Provides `os.clock`, `os.time`, `os.date`, `os.difftime` and `os.getenv`. Functions that
+/// affect the host process or the filesystem (`os.execute`, `os.exit`, `os.remove`, etc.) are
+/// not available.
+///
+/// Time zones are not supported: local time is always the same as UTC. `os.clock` returns the
+/// time elapsed since the library was created, rather than the processor time.
+#[derive(Debug, Default, Clone)]
+pub struct OsLib {
+    env: Vec<StdString>,
+}
+
+impl OsLib {
+    /// Creates a new `os` library that does not expose any environment variables.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Allows reading the environment variable with the given name using `os.getenv`.
+    pub fn allow_env(mut self, name: impl Into<StdString>) -> Self {
+        self.env.push(name.into());
+        self
+    }
+
+    /// Creates the library table.
+    pub fn create(&self, lua: &Lua) -> Result<Table> {
+        let os = lua.create_table()?;
+
+        let started = Instant::now();
+        os.raw_set(
+            "clock",
+            lua.create_function(move |_, ()| Ok(started.elapsed().as_secs_f64()))?,
+        )?;
+
+        os.raw_set(
+            "time",
+            lua.create_function(|_, table: Option<Table>| match table {
+                Some(table) => time_from_table(&table),
+                None => {
+                    let now = SystemTime::now().duration_since(UNIX_EPOCH);
+                    Ok(now.map(|d| d.as_secs() as Integer).unwrap_or(0))
+                }
+            })?,
+        )?;
+
+        os.raw_set(
+            "date",
+            lua.create_function(|lua, (format, time): (Option<StdString>, Option<Integer>)| {
+                let time = match time {
+                    Some(time) => time,
+                    None => {
+                        let now = SystemTime::now().duration_since(UNIX_EPOCH);
+                        now.map(|d| d.as_secs() as Integer).unwrap_or(0)
+                    }
+                };
+                let format = format.as_deref().unwrap_or("%c");
+                let format = format.strip_prefix('!').unwrap_or(format);
+                let date = DateTime::from_timestamp(time);
+                if format.starts_with("*t") {
+                    return date.to_table(lua).map(Value::Table);
+                }
+                let date = date.format(format)?;
+                lua.create_string(date).map(Value::String)
+            })?,
+        )?;
+
+        os.raw_set(
+            "difftime",
+            lua.create_function(|_, (t2, t1): (f64, Option<f64>)| Ok(t2 - t1.unwrap_or(0.0)))?,
+        )?;
+
+        let env = self.env.clone();
+        os.raw_set(
+            "getenv",
+            lua.create_function(move |_, name: StdString| match env.contains(&name) {
+                true => Ok(std::env::var(&name).ok()),
+                false => Ok(None),
+            })?,
+        )?;
+
+        Ok(os)
+    }
+
+    /// Creates the library and sets it as the global `os` table.
+    pub fn install(&self, lua: &Lua) -> Result<()> {
+        super::install_lib(lua, "os", self.create(lua)?)
+    }
+}
+
+// Converts a date table (as accepted by `os.time`) to a timestamp
+fn time_from_table(table: &Table) -> Result<Integer> {
+    // Like in C Lua, fields must fit into C `int` (after subtracting `delta`)
+    let field = |name: &str, default: Option<Integer>, delta: Integer| -> Result<Integer> {
+        match table.get::<Option<Integer>>(name)? {
+            Some(value)
+                if value
+                    .checked_sub(delta)
+                    .and_then(|v| i32::try_from(v).ok())
+                    .is_none() =>
+            {
+                Err(Error::runtime(format!("field '{name}' is out-of-bound")))
+            }
+            Some(value) => Ok(value),
+            None => default.ok_or_else(|| Error::runtime(format!("field '{name}' missing in date table"))),
+        }
+    };
+    let year = field("year", None, 1900)?;
+    let (month, day) = (field("month", None, 1)?, field("day", None, 0)?);
+    let (hour, min, sec) = (
+        field("hour", Some(12), 0)?,
+        field("min", Some(0), 0)?,
+        field("sec", Some(0), 0)?,
+    );
+
+    // Out of range values are normalized
+    let year = year + (month - 1).div_euclid(12);
+    let month = (month - 1).rem_euclid(12) + 1;
+    let days = days_from_civil(year, month, 1) + day - 1;
+    (days.checked_mul(86400))
+        .and_then(|time| time.checked_add(hour * 3600 + min * 60 + sec))
+        .ok_or_else(|| Error::runtime("time result cannot be represented in this installation"))
+}
+
+// Number of days since 1970-01-01 for the given date of the proleptic Gregorian calendar
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let doy = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+// Date of the proleptic Gregorian calendar for the given number of days since 1970-01-01
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let doe = days.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+struct DateTime {
+    year: i64,
+    month: i64,
+    day: i64,
+    hour: i64,
+    min: i64,
+    sec: i64,
+    // Days since Sunday (0-6)
+    wday: i64,
+    // Days since January 1 (0-365)
+    yday: i64,
+}
+
+const WEEKDAYS: [&str; 7] = [
+    "Sunday",
+    "Monday",
+    "Tuesday",
+    "Wednesday",
+    "Thursday",
+    "Friday",
+    "Saturday",
+];
+
+const MONTHS: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+
+impl DateTime {
+    fn from_timestamp(time: Integer) -> Self {
+        let days = time.div_euclid(86400);
+        let secs = time.rem_euclid(86400);
+        let (year, month, day) = civil_from_days(days);
+        DateTime {
+            year,
+            month,
+            day,
+            hour: secs / 3600,
+            min: secs / 60 % 60,
+            sec: secs % 60,
+            wday: (days + 4).rem_euclid(7),
+            yday: days - days_from_civil(year, 1, 1),
+        }
+    }
+
+    fn to_table(&self, lua: &Lua) -> Result<Table> {
+        let table = lua.create_table_with_capacity(0, 9)?;
+        table.raw_set("year", self.year)?;
+        table.raw_set("month", self.month)?;
+        table.raw_set("day", self.day)?;
+        table.raw_set("hour", self.hour)?;
+        table.raw_set("min", self.min)?;
+        table.raw_set("sec", self.sec)?;
+        table.raw_set("wday", self.wday + 1)?;
+        table.raw_set("yday", self.yday + 1)?;
+        table.raw_set("isdst", false)?;
+        Ok(table)
+    }
+
+    // Formats the date using a subset of `strftime` conversion specifiers
+    fn format(&self, format: &str) -> Result<StdString> {
+        let mut result = StdString::new();
+        let mut chars = format.chars();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                result.push(c);
+                continue;
+            }
+            let hour12 = if self.hour % 12 == 0 { 12 } else { self.hour % 12 };
+            let weekday = WEEKDAYS[self.wday as usize];
+            let month = MONTHS[self.month as usize - 1];
+            match chars.next() {
+                Some('a') => result.push_str(&weekday[..3]),
+                Some('A') => result.push_str(weekday),
+                Some('b') | Some('h') => result.push_str(&month[..3]),
+                Some('B') => result.push_str(month),
+                Some('c') => result.push_str(&self.format("%a %b %e %H:%M:%S %Y")?),
+                Some('C') => result.push_str(&format!("{:02}", self.year.div_euclid(100))),
+                Some('d') => result.push_str(&format!("{:02}", self.day)),
+                Some('D') | Some('x') => result.push_str(&self.format("%m/%d/%y")?),
+                Some('e') => result.push_str(&format!("{:2}", self.day)),
+                Some('F') => result.push_str(&self.format("%Y-%m-%d")?),
+                Some('H') => result.push_str(&format!("{:02}", self.hour)),
+                Some('I') => result.push_str(&format!("{hour12:02}")),
+                Some('j') => result.push_str(&format!("{:03}", self.yday + 1)),
+                Some('m') => result.push_str(&format!("{:02}", self.month)),
+                Some('M') => result.push_str(&format!("{:02}", self.min)),
+                Some('n') => result.push('\n'),
+                Some('p') => result.push_str(if self.hour < 12 { "AM" } else { "PM" }),
+                Some('r') => result.push_str(&self.format("%I:%M:%S %p")?),
+                Some('R') => result.push_str(&self.format("%H:%M")?),
+                Some('S') => result.push_str(&format!("{:02}", self.sec)),
+                Some('t') => result.push('\t'),
+                Some('T') | Some('X') => result.push_str(&self.format("%H:%M:%S")?),
+                Some('u') => result.push_str(&format!("{}", if self.wday == 0 { 7 } else { self.wday })),
+                Some('w') => result.push_str(&format!("{}", self.wday)),
+                Some('y') => result.push_str(&format!("{:02}", self.year.rem_euclid(100))),
+                Some('Y') => result.push_str(&format!("{}", self.year)),
+                Some('z') => result.push_str("+0000"),
+                Some('Z') => result.push_str("UTC"),
+                Some('%') => result.push('%'),
+                c => {
+                    let spec = c.map(|c| format!("%{c}")).unwrap_or_else(|| "%".to_string());
+                    let msg = format!("bad argument #1 to 'date' (invalid conversion specifier '{spec}')");
+                    return Err(Error::runtime(msg));
+                }
+            }
+        }
+        Ok(result)
+    }
+}
@@ -933,7 +933,7 @@ impl ObjectLike for Table {
             Ok(Value::Function(func)) => func.call_async(args),
             Ok(val) => {
                 let msg = format!("attempt to call a {} value (function '{name}')", val.type_name());
-                AsyncCallFuture::error(Error::RuntimeError(msg))
+                AsyncCallFuture::error(Error::runtime(msg))
             }
             Err(err) => AsyncCallFuture::error(err),
         }
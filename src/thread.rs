@@ -16,6 +16,7 @@ use crate::{
 
 #[cfg(feature = "async")]
 use {
+    crate::util::{start_timer, wake_at, TimerEntry},
     futures_util::stream::Stream,
     std::{
         future::Future,
@@ -23,9 +24,19 @@ use {
         pin::Pin,
         ptr::NonNull,
         task::{Context, Poll, Waker},
+        time::{Duration, Instant},
     },
 };
 
+#[cfg(all(
+    feature = "async",
+    any(feature = "lua54", feature = "lua53", feature = "luau")
+))]
+use {
+    crate::budget::{AsyncBudget, ExecutionBudget, Meter},
+    std::sync::Arc,
+};
+
 /// Status of a Lua thread (coroutine).
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum ThreadStatus {
@@ -61,7 +72,6 @@ impl ThreadStatusInner {
         matches!(self, ThreadStatusInner::New(_) | ThreadStatusInner::Yielded(_))
     }
 
-    #[cfg(feature = "async")]
     #[inline(always)]
     fn is_yielded(self) -> bool {
         matches!(self, ThreadStatusInner::Yielded(_))
@@ -88,6 +98,23 @@ pub struct AsyncThread<R> {
     thread: Thread,
     ret: PhantomData<fn() -> R>,
     recycle: bool,
+    // The thread is suspended while awaiting a Rust future
+    awaiting: bool,
+    cancel: CancelState,
+    deadline: Option<Instant>,
+    // Wake up registered at the deadline
+    timer: Option<TimerEntry>,
+    #[cfg(any(feature = "lua54", feature = "lua53", feature = "luau"))]
+    meter: Option<Arc<Meter>>,
+}
+
+#[cfg(feature = "async")]
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum CancelState {
+    Active,
+    Cancelled,
+    // Cancellation error is returned to the caller
+    Reported,
 }
 
 impl Thread {
@@ -156,7 +183,7 @@ impl Thread {
         let thread_state = self.state();
         unsafe {
             let _sg = StackGuard::new(state);
-            let _thread_sg = StackGuard::with_top(thread_state, 0);
+            let mut thread_sg = StackGuard::with_top(thread_state, 0);
 
             let nargs = args.push_into_stack_multi(&lua)?;
             if nargs > 0 {
@@ -165,7 +192,11 @@ impl Thread {
                 pushed_nargs += nargs;
             }
 
-            let (_, nresults) = self.resume_inner(&lua, pushed_nargs)?;
+            let (status, nresults) = self.resume_inner(&lua, pushed_nargs)?;
+            if status.is_yielded() && nresults == 0 {
+                // The thread could yield from a hook, keep the stack of the interrupted function
+                thread_sg.keep(ffi::lua_gettop(thread_state));
+            }
             check_stack(state, nresults + 1)?;
             ffi::lua_xmove(thread_state, state, nresults);
 
@@ -277,6 +308,7 @@ impl Thread {
         unsafe {
             lua.set_thread_hook(
                 self.state(),
+                Some(&self.0),
                 HookKind::Thread(triggers, crate::types::XRc::new(callback)),
             )
         }
@@ -435,6 +467,12 @@ impl Thread {
                 thread: self,
                 ret: PhantomData,
                 recycle: false,
+                awaiting: false,
+                cancel: CancelState::Active,
+                deadline: None,
+                timer: None,
+                #[cfg(any(feature = "lua54", feature = "lua53", feature = "luau"))]
+                meter: (lua.priv_app_data_ref::<AsyncBudget>()).map(|budget| Meter::new(budget.0)),
             })
         }
     }
@@ -518,6 +556,150 @@ impl<R> AsyncThread<R> {
     pub(crate) fn set_recyclable(&mut self, recyclable: bool) {
         self.recycle = recyclable;
     }
+
+    /// Cancels execution of the thread.
+    ///
+    /// If the thread is suspended while awaiting a Rust future (e.g. called an async function),
+    /// the future is dropped and [`Error::Cancelled`] is raised inside the coroutine at that
+    /// point, so Lua code can catch it and clean up. The thread then runs until it finishes or
+    /// yields again, in which case it's terminated. In Lua 5.4 and Luau, pending to-be-closed
+    /// variables are closed (calling their `__close` metamethods).
+    ///
+    /// The cancelled thread resolves to [`Error::Cancelled`] regardless of how it finishes.
+    ///
+    /// Returns `false` if the thread is already finished or cancelled.
+    pub fn cancel(&mut self) -> bool {
+        let lua = self.thread.0.lua.lock();
+        let status = self.thread.status_inner(&lua);
+        if self.cancel != CancelState::Active || !status.is_resumable() {
+            return false;
+        }
+        self.cancel = CancelState::Cancelled;
+        unsafe { self.thread.cancel_inner(&lua, status, self.awaiting) };
+        true
+    }
+
+    /// Sets a time limit for the thread execution, counting from now.
+    ///
+    /// When the time is up, the thread is cancelled as [`AsyncThread::cancel`] does.
+    ///
+    /// The time limit is checked only when the thread is polled (which happens at the deadline
+    /// even if the awaited future is not ready). CPU-bound Lua code that runs without awaiting is
+    /// **not** interrupted by the time limit alone: combine it with an execution budget
+    /// ([`AsyncThread::with_budget`] or [`Lua::set_async_budget`], available in Lua 5.3, 5.4 and
+    /// Luau), so that the thread is preempted regularly and the deadline is checked in between.
+    ///
+    /// # Background thread
+    ///
+    /// To be woken up at the deadline independently of the async runtime, the first call to this
+    /// method spawns a global `mlua-timer` OS thread, which then lives until the process exits.
+    /// Threads without a time limit never spawn it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the timer thread cannot be spawned (e.g. on targets without thread
+    /// support).
+    ///
+    /// [`Lua::set_async_budget`]: crate::Lua::set_async_budget
+    pub fn with_timeout(mut self, timeout: Duration) -> Result<Self> {
+        if !start_timer() {
+            return Err(Error::runtime(
+                "cannot set a time limit: failed to spawn the timer thread",
+            ));
+        }
+        self.deadline = Some(Instant::now() + timeout);
+        Ok(self)
+    }
+
+    /// Returns `true` if the thread is cancelled (explicitly or by timeout).
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel != CancelState::Active
+    }
+
+    /// Sets the execution budget for every poll of the thread.
+    ///
+    /// Once the budget is exhausted, the thread is preempted and gives control back to the async
+    /// executor, to continue on the next poll (which is scheduled immediately). Pass `None` to
+    /// disable preemption.
+    ///
+    /// Overrides the default budget set by [`Lua::set_async_budget`].
+    ///
+    /// [`Lua::set_async_budget`]: crate::Lua::set_async_budget
+    #[cfg(any(feature = "lua54", feature = "lua53", feature = "luau"))]
+    #[cfg_attr(docsrs, doc(cfg(any(feature = "lua54", feature = "lua53", feature = "luau"))))]
+    pub fn with_budget(mut self, budget: Option<ExecutionBudget>) -> Self {
+        self.meter = budget.map(Meter::new);
+        self
+    }
+
+    /// Returns the execution budget for every poll of the thread (if any).
+    #[cfg(any(feature = "lua54", feature = "lua53", feature = "luau"))]
+    #[cfg_attr(docsrs, doc(cfg(any(feature = "lua54", feature = "lua53", feature = "luau"))))]
+    pub fn budget(&self) -> Option<ExecutionBudget> {
+        self.meter.as_ref().map(|meter| meter.budget())
+    }
+
+    // Resumes the thread within the execution budget.
+    //
+    // Returns `true` as the last value if the thread was preempted.
+    unsafe fn resume(&self, lua: &RawLua, nargs: c_int) -> Result<(ThreadStatusInner, c_int, bool)> {
+        #[cfg(any(feature = "lua54", feature = "lua53", feature = "luau"))]
+        if let Some(meter) = &self.meter {
+            let (status, nresults) = meter.run(&self.thread, || self.thread.resume_inner(lua, nargs))??;
+            let preempted = meter.is_exhausted() && status.is_yielded() && nresults == 0;
+            return Ok((status, nresults, preempted));
+        }
+        let (status, nresults) = self.thread.resume_inner(lua, nargs)?;
+        Ok((status, nresults, false))
+    }
+
+    // Cancels the thread if the deadline is reached, otherwise arranges a wake up at the deadline
+    fn check_deadline(&mut self, cx: &Context<'_>) {
+        let Some(deadline) = self.deadline else {
+            return;
+        };
+        if Instant::now() >= deadline {
+            self.deadline = None;
+            self.timer = None;
+            self.cancel();
+        } else if !(self.timer.as_ref()).is_some_and(|timer| timer.will_wake(cx.waker())) {
+            self.timer = Some(wake_at(deadline, cx.waker()));
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+impl Thread {
+    unsafe fn cancel_inner(&self, lua: &RawLua, mut status: ThreadStatusInner, awaiting: bool) {
+        let thread_state = self.state();
+        let _sg = StackGuard::new(lua.state());
+        let _thread_sg = StackGuard::with_top(thread_state, 0);
+
+        if awaiting && status.is_yielded() {
+            // Raise the cancellation error at the await point
+            ffi::lua_settop(thread_state, 0);
+            ffi::lua_pushlightuserdata(thread_state, crate::Lua::poll_cancel().0);
+            status = match self.resume_inner(lua, 1) {
+                Ok((ThreadStatusInner::Yielded(_), nresults)) => {
+                    // Lua code caught the error and yielded again
+                    let awaiting = nresults == 1 && is_poll_pending(thread_state);
+                    ffi::lua_settop(thread_state, 0);
+                    if awaiting {
+                        // Drop the new future, the thread stays suspended forever
+                        ffi::lua_pushlightuserdata(thread_state, crate::Lua::poll_terminate().0);
+                        let _ = self.resume_inner(lua, 1);
+                        ffi::lua_settop(thread_state, 0);
+                    }
+                    self.status_inner(lua)
+                }
+                Ok((status, _)) => status,
+                Err(_) => ThreadStatusInner::Error,
+            };
+        }
+
+        // For Lua 5.4 and Luau this also closes all pending to-be-closed variables
+        let _ = self.reset_inner(status);
+    }
 }
 
 #[cfg(feature = "async")]
@@ -551,23 +733,43 @@ impl<R: FromLuaMulti> Stream for AsyncThread<R> {
     type Item = Result<R>;
 
     fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        let lua = self.thread.0.lua.lock();
-        let nargs = match self.thread.status_inner(&lua) {
+        let this = self.get_mut();
+        this.check_deadline(cx);
+        match this.cancel {
+            CancelState::Active => {}
+            CancelState::Cancelled => {
+                this.cancel = CancelState::Reported;
+                return Poll::Ready(Some(Err(Error::Cancelled)));
+            }
+            CancelState::Reported => return Poll::Ready(None),
+        }
+
+        let lua = this.thread.0.lua.lock();
+        let nargs = match this.thread.status_inner(&lua) {
             ThreadStatusInner::New(nargs) | ThreadStatusInner::Yielded(nargs) => nargs,
             _ => return Poll::Ready(None),
         };
 
         let state = lua.state();
-        let thread_state = self.thread.state();
+        let thread_state = this.thread.state();
         unsafe {
             let _sg = StackGuard::new(state);
-            let _thread_sg = StackGuard::with_top(thread_state, 0);
+            let mut thread_sg = StackGuard::with_top(thread_state, 0);
             let _wg = WakerGuard::new(&lua, cx.waker());
 
-            let (status, nresults) = (self.thread).resume_inner(&lua, nargs)?;
+            let (status, nresults, preempted) = this.resume(&lua, nargs)?;
+            if preempted {
+                // Keep the stack of the interrupted function
+                thread_sg.keep(ffi::lua_gettop(thread_state));
+            }
 
+            this.awaiting = status.is_yielded() && nresults == 1 && is_poll_pending(thread_state);
             if status.is_yielded() {
-                if nresults == 1 && is_poll_pending(thread_state) {
+                if this.awaiting {
+                    return Poll::Pending;
+                }
+                if preempted {
+                    cx.waker().wake_by_ref();
                     return Poll::Pending;
                 }
                 // Continue polling
@@ -587,24 +789,36 @@ impl<R: FromLuaMulti> Future for AsyncThread<R> {
     type Output = Result<R>;
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        let lua = self.thread.0.lua.lock();
-        let nargs = match self.thread.status_inner(&lua) {
+        let this = self.get_mut();
+        this.check_deadline(cx);
+        if this.cancel != CancelState::Active {
+            this.cancel = CancelState::Reported;
+            return Poll::Ready(Err(Error::Cancelled));
+        }
+
+        let lua = this.thread.0.lua.lock();
+        let nargs = match this.thread.status_inner(&lua) {
             ThreadStatusInner::New(nargs) | ThreadStatusInner::Yielded(nargs) => nargs,
             _ => return Poll::Ready(Err(Error::CoroutineUnresumable)),
         };
 
         let state = lua.state();
-        let thread_state = self.thread.state();
+        let thread_state = this.thread.state();
         unsafe {
             let _sg = StackGuard::new(state);
-            let _thread_sg = StackGuard::with_top(thread_state, 0);
+            let mut thread_sg = StackGuard::with_top(thread_state, 0);
             let _wg = WakerGuard::new(&lua, cx.waker());
 
-            let (status, nresults) = self.thread.resume_inner(&lua, nargs)?;
+            let (status, nresults, preempted) = this.resume(&lua, nargs)?;
+            if preempted {
+                // Keep the stack of the interrupted function
+                thread_sg.keep(ffi::lua_gettop(thread_state));
+            }
 
+            this.awaiting = status.is_yielded() && nresults == 1 && is_poll_pending(thread_state);
             if status.is_yielded() {
-                if !(nresults == 1 && is_poll_pending(thread_state)) {
-                    // Ignore value returned via yield()
+                if !this.awaiting {
+                    // Ignore value returned via yield() (or the thread was preempted)
                     cx.waker().wake_by_ref();
                 }
                 return Poll::Pending;
@@ -2,10 +2,12 @@ use std::os::raw::c_int;
 use std::string::String as StdString;
 use std::sync::Arc;
 
+use crate::derive::FieldPath;
 use crate::error::{Error, Result};
 use crate::multi::MultiValue;
 use crate::private::Sealed;
 use crate::state::{Lua, RawLua};
+use crate::typedef::TypeInfo;
 use crate::types::MaybeSend;
 use crate::util::{check_stack, short_type_name};
 use crate::value::Value;
@@ -18,6 +20,14 @@ pub trait IntoLua: Sized {
     /// Performs the conversion.
     fn into_lua(self, lua: &Lua) -> Result<Value>;
 
+    /// Returns type information of the converted value.
+    ///
+    /// It's used to generate type definitions, see [`Lua::generate_type_definitions`].
+    #[inline]
+    fn type_info() -> TypeInfo {
+        TypeInfo::Any
+    }
+
     /// Pushes the value into the Lua stack.
     ///
     /// # Safety
@@ -34,6 +44,14 @@ pub trait FromLua: Sized {
     /// Performs the conversion.
     fn from_lua(value: Value, lua: &Lua) -> Result<Self>;
 
+    /// Returns type information of the converted value.
+    ///
+    /// It's used to generate type definitions, see [`Lua::generate_type_definitions`].
+    #[inline]
+    fn type_info() -> TypeInfo {
+        TypeInfo::Any
+    }
+
     /// Performs the conversion for an argument (eg. function argument).
     ///
     /// `i` is the argument index (position),
@@ -49,6 +67,15 @@ pub trait FromLua: Sized {
         })
     }
 
+    /// Performs the conversion for a field of a type with derived `FromLua`.
+    ///
+    /// `path` is the location of the field within the outermost converted type.
+    #[doc(hidden)]
+    #[inline]
+    fn from_lua_field(value: Value, path: &FieldPath, lua: &Lua) -> Result<Self> {
+        Self::from_lua(value, lua).map_err(|err| path.error(err))
+    }
+
     /// Performs the conversion for a value in the Lua stack at index `idx`.
     #[doc(hidden)]
     #[inline]
@@ -77,6 +104,14 @@ pub trait IntoLuaMulti: Sized {
     /// Performs the conversion.
     fn into_lua_multi(self, lua: &Lua) -> Result<MultiValue>;
 
+    /// Returns type information of the converted values.
+    ///
+    /// It's used to generate type definitions, see [`Lua::generate_type_definitions`].
+    #[inline]
+    fn type_info_multi() -> Vec<TypeInfo> {
+        vec![TypeInfo::Variadic(Box::new(TypeInfo::Any))]
+    }
+
     /// Pushes the values into the Lua stack.
     ///
     /// Returns number of pushed values.
@@ -109,6 +144,14 @@ pub trait FromLuaMulti: Sized {
     /// any missing values are nil.
     fn from_lua_multi(values: MultiValue, lua: &Lua) -> Result<Self>;
 
+    /// Returns type information of the converted values.
+    ///
+    /// It's used to generate type definitions, see [`Lua::generate_type_definitions`].
+    #[inline]
+    fn type_info_multi() -> Vec<TypeInfo> {
+        vec![TypeInfo::Variadic(Box::new(TypeInfo::Any))]
+    }
+
     /// Performs the conversion for a list of arguments.
     ///
     /// `i` is an index (position) of the first argument,
@@ -0,0 +1,207 @@
+//! Deep copying of values between Lua states.
+
+use std::any::TypeId;
+use std::os::raw::c_void;
+
+use rustc_hash::FxHashMap;
+
+use crate::error::{Error, Result};
+use crate::function::Function;
+use crate::state::Lua;
+use crate::table::Table;
+use crate::userdata::{AnyUserData, UserData};
+use crate::util::{check_stack, StackGuard};
+use crate::value::Value;
+
+#[cfg(not(feature = "luau"))]
+use crate::{
+    chunk::ChunkMode,
+    persist::{get_upvalues, set_upvalue},
+};
+
+#[cfg(any(feature = "lua54", feature = "lua53", feature = "lua52"))]
+use crate::persist::join_upvalues;
+
+/// Trait for userdata types that can be copied to another Lua state.
+///
+/// Types implementing this trait must be registered using [`Lua::register_transferable`] in the
+/// source state to be copied by [`Lua::transfer`] and [`Value::deep_copy_into`].
+pub trait CloneAcrossStates: Sized {
+    /// Returns a copy of the userdata to be placed into the `to` Lua state.
+    fn clone_across_states(&self, to: &Lua) -> Result<Self>;
+}
+
+type CloneFn = fn(&AnyUserData, &Lua) -> Result<AnyUserData>;
+
+// Userdata types registered to be copied across states
+#[derive(Default)]
+pub(crate) struct TransferRegistry {
+    types: FxHashMap<TypeId, CloneFn>,
+}
+
+impl TransferRegistry {
+    pub(crate) fn register<T>(&mut self)
+    where
+        T: UserData + CloneAcrossStates + crate::types::MaybeSend + 'static,
+    {
+        fn clone_userdata<T>(ud: &AnyUserData, to: &Lua) -> Result<AnyUserData>
+        where
+            T: UserData + CloneAcrossStates + crate::types::MaybeSend + 'static,
+        {
+            let copy = ud.borrow::<T>()?.clone_across_states(to)?;
+            to.create_userdata(copy)
+        }
+        self.types.insert(TypeId::of::<T>(), clone_userdata::<T>);
+    }
+}
+
+/// Copies `value` into the `to` Lua state.
+///
+/// Values that are referenced more than once (including cycles) are copied only once.
+pub(crate) fn deep_copy(value: &Value, to: &Lua) -> Result<Value> {
+    Copier {
+        to,
+        copies: FxHashMap::default(),
+        upvalues: FxHashMap::default(),
+    }
+    .copy(value)
+}
+
+struct Copier<'a> {
+    to: &'a Lua,
+    copies: FxHashMap<*const c_void, Value>,
+    #[cfg_attr(feature = "luau", allow(unused))]
+    upvalues: FxHashMap<usize, (Function, usize)>,
+}
+
+impl Copier<'_> {
+    fn copy(&mut self, value: &Value) -> Result<Value> {
+        let ptr = value.to_pointer();
+        if let (false, Some(copy)) = (value.is_light_userdata(), self.copies.get(&ptr)) {
+            return Ok(copy.clone());
+        }
+        Ok(match value {
+            Value::Nil => Value::Nil,
+            Value::Boolean(b) => Value::Boolean(*b),
+            Value::LightUserData(ud) => Value::LightUserData(*ud),
+            Value::Integer(i) => Value::Integer(*i),
+            Value::Number(n) => Value::Number(*n),
+            #[cfg(feature = "luau")]
+            Value::Vector(v) => Value::Vector(*v),
+            Value::String(s) => Value::String(self.to.create_string(s.as_bytes())?),
+            #[cfg(feature = "luau")]
+            Value::Buffer(buf) => {
+                let copy = Value::Buffer(self.to.create_buffer(buf.to_vec())?);
+                self.copies.insert(ptr, copy.clone());
+                copy
+            }
+            Value::Table(t) => Value::Table(self.copy_table(t)?),
+            Value::Function(f) => Value::Function(self.copy_function(f)?),
+            Value::UserData(ud) => Value::UserData(self.copy_userdata(ud)?),
+            Value::Error(err) => Value::Error(err.clone()),
+            _ => {
+                let type_name = value.type_name();
+                return Err(Error::runtime(format!("cannot copy value of type {type_name}")));
+            }
+        })
+    }
+
+    fn copy_table(&mut self, table: &Table) -> Result<Table> {
+        if let Some(Value::Table(copy)) = self.copies.get(&table.to_pointer()) {
+            return Ok(copy.clone());
+        }
+        // Globals table of the source state maps to globals of the target state
+        let from = table.0.lua.upgrade();
+        let copy = match from.globals() == *table {
+            true => return Ok(self.to.globals()),
+            false => self.to.create_table()?,
+        };
+        self.copies.insert(table.to_pointer(), Value::Table(copy.clone()));
+
+        for pair in table.pairs::<Value, Value>() {
+            let (key, value) = pair?;
+            copy.raw_set(self.copy(&key)?, self.copy(&value)?)?;
+        }
+        if let Some(mt) = table.metatable() {
+            copy.set_metatable(Some(self.copy_table(&mt)?))?;
+        }
+        #[cfg(feature = "luau")]
+        if table.is_readonly() {
+            copy.set_readonly(true);
+        }
+        Ok(copy)
+    }
+
+    fn copy_function(&mut self, func: &Function) -> Result<Function> {
+        match unsafe { c_function(func)? } {
+            Some(Some(cfunc)) => {
+                let copy = unsafe { self.to.create_c_function(cfunc)? };
+                self.copies
+                    .insert(func.to_pointer(), Value::Function(copy.clone()));
+                Ok(copy)
+            }
+            Some(None) => Err(Error::runtime("cannot copy C function with upvalues")),
+            #[cfg(feature = "luau")]
+            None => Err(Error::runtime("cannot copy Lua function in Luau")),
+            #[cfg(not(feature = "luau"))]
+            None => self.copy_lua_function(func),
+        }
+    }
+
+    #[cfg(not(feature = "luau"))]
+    fn copy_lua_function(&mut self, func: &Function) -> Result<Function> {
+        let copy = (self.to.load(func.dump(false)))
+            .set_mode(ChunkMode::Binary)
+            .into_function()?;
+        self.copies
+            .insert(func.to_pointer(), Value::Function(copy.clone()));
+
+        let upvalues = unsafe { get_upvalues(func)? };
+        for (n, (value, id)) in upvalues.into_iter().enumerate() {
+            let n = n + 1;
+            // Shared upvalues remain shared
+            #[cfg(any(feature = "lua54", feature = "lua53", feature = "lua52"))]
+            if let Some((other, m)) = id.and_then(|id| self.upvalues.get(&id)) {
+                unsafe { join_upvalues(&copy, n, other, *m)? };
+                continue;
+            }
+            if let Some(id) = id {
+                self.upvalues.insert(id, (copy.clone(), n));
+            }
+            let value = self.copy(&value)?;
+            unsafe { set_upvalue(&copy, n, &value)? };
+        }
+        Ok(copy)
+    }
+
+    fn copy_userdata(&mut self, ud: &AnyUserData) -> Result<AnyUserData> {
+        let clone = (ud.type_id()).and_then(|type_id| {
+            let from = ud.0.lua.lock();
+            let registry = from.priv_app_data_ref::<TransferRegistry>()?;
+            registry.types.get(&type_id).copied()
+        });
+        let clone = clone.ok_or_else(|| Error::runtime("cannot copy userdata of unregistered type"))?;
+        let copy = clone(ud, self.to)?;
+        self.copies.insert(ud.to_pointer(), Value::UserData(copy.clone()));
+        Ok(copy)
+    }
+}
+
+// Returns `Some` if the function is a C function, along with its pointer if it has no upvalues
+// (and therefore does not depend on the state it was created in).
+unsafe fn c_function(func: &Function) -> Result<Option<Option<ffi::lua_CFunction>>> {
+    let lua = func.0.lua.lock();
+    let state = lua.state();
+    let _sg = StackGuard::new(state);
+    check_stack(state, 2)?;
+
+    lua.push_ref(&func.0);
+    if ffi::lua_iscfunction(state, -1) == 0 {
+        return Ok(None);
+    }
+    let cfunc = ffi::lua_tocfunction(state, -1);
+    if !ffi::lua_getupvalue(state, -1, 1).is_null() {
+        return Ok(Some(None));
+    }
+    Ok(Some(cfunc))
+}
@@ -0,0 +1,642 @@
+//! Type information of Rust functions and userdata types, used to generate type definitions
+//! (`.d.luau` declarations or LuaLS annotations) for scripts.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::Write as _;
+use std::os::raw::c_void;
+use std::string::String as StdString;
+
+use crate::error::{Error, Result};
+use crate::function::Function;
+use crate::state::Lua;
+use crate::table::Table;
+use crate::traits::{FromLuaMulti, IntoLuaMulti};
+use crate::types::RegistryKey;
+use crate::value::Value;
+
+/// Describes the Lua type of a value produced by [`IntoLua`] or accepted by [`FromLua`].
+///
+/// Type information is used to generate type definitions for the registered Rust functions and
+/// userdata types. See [`Lua::generate_type_definitions`] for details.
+///
+/// [`IntoLua`]: crate::IntoLua
+/// [`FromLua`]: crate::FromLua
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum TypeInfo {
+    /// Any Lua value.
+    Any,
+    /// The `nil` value.
+    Nil,
+    /// Boolean value.
+    Boolean,
+    /// Light userdata value.
+    LightUserData,
+    /// Integer number.
+    Integer,
+    /// Floating point number.
+    Number,
+    /// Lua string.
+    String,
+    /// Lua table of unknown shape.
+    Table,
+    /// Lua function of unknown signature.
+    Function,
+    /// Lua thread (coroutine).
+    Thread,
+    /// Userdata of unknown type.
+    UserData,
+    /// Luau vector.
+    Vector,
+    /// Luau buffer.
+    Buffer,
+    /// Named type, such as a registered userdata type.
+    Named(StdString),
+    /// Value of the inner type or `nil`.
+    Optional(Box<TypeInfo>),
+    /// Sequence of values of the inner type.
+    Array(Box<TypeInfo>),
+    /// Table mapping keys to values.
+    Map(Box<TypeInfo>, Box<TypeInfo>),
+    /// One of the listed types.
+    Union(Vec<TypeInfo>),
+    /// Any number of values of the inner type.
+    ///
+    /// Only meaningful as the last argument or return value.
+    Variadic(Box<TypeInfo>),
+}
+
+impl TypeInfo {
+    /// Renders the type using Luau syntax.
+    fn luau(&self) -> StdString {
+        match self {
+            TypeInfo::Any | TypeInfo::LightUserData | TypeInfo::UserData => "any".into(),
+            TypeInfo::Nil => "nil".into(),
+            TypeInfo::Boolean => "boolean".into(),
+            TypeInfo::Integer | TypeInfo::Number => "number".into(),
+            TypeInfo::String => "string".into(),
+            TypeInfo::Table => "{ [any]: any }".into(),
+            TypeInfo::Function => "(...any) -> ...any".into(),
+            TypeInfo::Thread => "thread".into(),
+            TypeInfo::Vector => "vector".into(),
+            TypeInfo::Buffer => "buffer".into(),
+            TypeInfo::Named(name) => ident(name),
+            TypeInfo::Optional(ty) => match &**ty {
+                TypeInfo::Any | TypeInfo::Nil | TypeInfo::Optional(_) => ty.luau(),
+                TypeInfo::Function | TypeInfo::Union(_) => format!("({})?", ty.luau()),
+                _ => format!("{}?", ty.luau()),
+            },
+            TypeInfo::Array(ty) => format!("{{ {} }}", ty.luau()),
+            TypeInfo::Map(key, value) => format!("{{ [{}]: {} }}", key.luau(), value.luau()),
+            TypeInfo::Union(types) => (types.iter())
+                .map(|ty| match ty {
+                    TypeInfo::Function => format!("({})", ty.luau()),
+                    _ => ty.luau(),
+                })
+                .collect::<Vec<_>>()
+                .join(" | "),
+            TypeInfo::Variadic(ty) => format!("...{}", ty.luau()),
+        }
+    }
+
+    /// Renders the type using LuaLS annotations syntax.
+    fn luals(&self) -> StdString {
+        match self {
+            TypeInfo::Any | TypeInfo::Vector | TypeInfo::Buffer => "any".into(),
+            TypeInfo::Nil => "nil".into(),
+            TypeInfo::Boolean => "boolean".into(),
+            TypeInfo::LightUserData => "lightuserdata".into(),
+            TypeInfo::Integer => "integer".into(),
+            TypeInfo::Number => "number".into(),
+            TypeInfo::String => "string".into(),
+            TypeInfo::Table => "table".into(),
+            TypeInfo::Function => "function".into(),
+            TypeInfo::Thread => "thread".into(),
+            TypeInfo::UserData => "userdata".into(),
+            TypeInfo::Named(name) => ident(name),
+            TypeInfo::Optional(ty) => match &**ty {
+                TypeInfo::Any | TypeInfo::Nil | TypeInfo::Optional(_) => ty.luals(),
+                TypeInfo::Union(_) => format!("({})?", ty.luals()),
+                _ => format!("{}?", ty.luals()),
+            },
+            TypeInfo::Array(ty) => match &**ty {
+                TypeInfo::Optional(_) | TypeInfo::Union(_) => format!("({})[]", ty.luals()),
+                _ => format!("{}[]", ty.luals()),
+            },
+            TypeInfo::Map(key, value) => format!("table<{}, {}>", key.luals(), value.luals()),
+            TypeInfo::Union(types) => (types.iter().map(|ty| ty.luals())).collect::<Vec<_>>().join("|"),
+            TypeInfo::Variadic(ty) => format!("...{}", ty.luals()),
+        }
+    }
+}
+
+/// Argument and return types of a function.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub(crate) struct Signature {
+    pub(crate) params: Vec<TypeInfo>,
+    pub(crate) returns: Vec<TypeInfo>,
+}
+
+impl Signature {
+    pub(crate) fn new<A: FromLuaMulti, R: IntoLuaMulti>() -> Self {
+        Signature {
+            params: A::type_info_multi(),
+            returns: R::type_info_multi(),
+        }
+    }
+
+    /// Renders parameters list, `decl` selects the syntax of declarations over function types.
+    fn luau_params(&self, self_param: bool, decl: bool) -> StdString {
+        let params = self.params.iter().enumerate().map(|(i, ty)| match ty {
+            TypeInfo::Variadic(ty) if decl => format!("...: {}", ty.luau()),
+            TypeInfo::Variadic(_) => ty.luau(),
+            _ => format!("arg{}: {}", i + 1, ty.luau()),
+        });
+        let params = self_param.then(|| "self".to_string()).into_iter().chain(params);
+        params.collect::<Vec<_>>().join(", ")
+    }
+
+    fn luau_returns(&self) -> StdString {
+        match &*self.returns {
+            [] => "()".into(),
+            [ty @ (TypeInfo::Function | TypeInfo::Optional(_) | TypeInfo::Union(_))] => {
+                format!("({})", ty.luau())
+            }
+            [ty] => ty.luau(),
+            types => {
+                let types = types.iter().map(|ty| ty.luau()).collect::<Vec<_>>();
+                format!("({})", types.join(", "))
+            }
+        }
+    }
+
+    /// Renders the signature as Luau function type.
+    fn luau_type(&self) -> StdString {
+        format!("({}) -> {}", self.luau_params(false, false), self.luau_returns())
+    }
+
+    fn luals_params(&self) -> Vec<(StdString, StdString)> {
+        (self.params.iter().enumerate())
+            .map(|(i, ty)| match ty {
+                TypeInfo::Variadic(ty) => ("...".to_string(), ty.luals()),
+                TypeInfo::Optional(inner) if !matches!(**inner, TypeInfo::Any | TypeInfo::Nil) => {
+                    (format!("arg{}?", i + 1), inner.luals())
+                }
+                _ => (format!("arg{}", i + 1), ty.luals()),
+            })
+            .collect()
+    }
+
+    fn luals_returns(&self) -> Vec<StdString> {
+        (self.returns.iter())
+            .map(|ty| match ty {
+                TypeInfo::Variadic(ty) => format!("{} ...", ty.luals()),
+                _ => ty.luals(),
+            })
+            .collect()
+    }
+
+    /// Writes `---@param` and `---@return` annotations.
+    fn write_luals_annotations(&self, out: &mut StdString) {
+        for (name, ty) in self.luals_params() {
+            _ = writeln!(out, "---@param {name} {ty}");
+        }
+        for ty in self.luals_returns() {
+            _ = writeln!(out, "---@return {ty}");
+        }
+    }
+
+    fn luals_param_names(&self) -> StdString {
+        (self.luals_params().into_iter())
+            .map(|(name, _)| name.trim_end_matches('?').to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum MethodKind {
+    /// Method receiving userdata as `self`.
+    Method,
+    /// Function stored in the userdata type.
+    Function,
+    /// Metamethod receiving userdata as `self`.
+    MetaMethod,
+    /// Metamethod receiving all arguments explicitly.
+    MetaFunction,
+}
+
+#[derive(Clone, Debug)]
+struct MethodDef {
+    name: StdString,
+    is_method: bool,
+    signature: Signature,
+}
+
+/// Recorded fields and methods of a userdata type.
+#[derive(Clone, Debug)]
+pub(crate) struct ClassDef {
+    name: StdString,
+    fields: Vec<(StdString, TypeInfo)>,
+    methods: Vec<MethodDef>,
+    meta_methods: Vec<MethodDef>,
+}
+
+impl ClassDef {
+    pub(crate) fn new(name: StdString) -> Self {
+        ClassDef {
+            name,
+            fields: Vec::new(),
+            methods: Vec::new(),
+            meta_methods: Vec::new(),
+        }
+    }
+
+    pub(crate) fn add_field(&mut self, name: &str, ty: TypeInfo) {
+        // Getter and setter of the same field are recorded separately
+        if !self.fields.iter().any(|(n, _)| n == name) {
+            self.fields.push((name.to_string(), ty));
+        }
+    }
+
+    pub(crate) fn add_method(&mut self, name: &str, kind: MethodKind, mut signature: Signature) {
+        let (is_method, list) = match kind {
+            MethodKind::Method => (true, &mut self.methods),
+            MethodKind::Function => (false, &mut self.methods),
+            MethodKind::MetaMethod => (true, &mut self.meta_methods),
+            MethodKind::MetaFunction => {
+                // The first argument is the userdata itself
+                if !signature.params.is_empty() {
+                    signature.params.remove(0);
+                }
+                (true, &mut self.meta_methods)
+            }
+        };
+        list.push(MethodDef {
+            name: name.to_string(),
+            is_method,
+            signature,
+        });
+    }
+
+    pub(crate) fn extend(&mut self, other: ClassDef) {
+        for (name, ty) in other.fields {
+            self.add_field(&name, ty);
+        }
+        self.methods.extend(other.methods);
+        self.meta_methods.extend(other.meta_methods);
+    }
+}
+
+/// Type definitions recorded in the Lua state.
+///
+/// Present in the private app data only when recording is enabled in [`LuaOptions`].
+///
+/// [`LuaOptions`]: crate::LuaOptions
+#[derive(Default)]
+pub(crate) struct TypeRegistry {
+    classes: BTreeMap<StdString, ClassDef>,
+    // Distinct function signatures, referenced by index
+    signatures: Vec<Signature>,
+    signature_ids: HashMap<Signature, usize>,
+    // Weak-keyed table mapping functions to their signature index
+    functions: Option<RegistryKey>,
+}
+
+impl TypeRegistry {
+    pub(crate) fn add_class(&mut self, class: ClassDef) {
+        self.classes.insert(class.name.clone(), class);
+    }
+
+    fn add_signature(&mut self, signature: Signature) -> usize {
+        if let Some(&id) = self.signature_ids.get(&signature) {
+            return id;
+        }
+        let id = self.signatures.len();
+        self.signatures.push(signature.clone());
+        self.signature_ids.insert(signature, id);
+        id
+    }
+}
+
+/// Records signature of the function created from Rust callback.
+pub(crate) fn record_function<A: FromLuaMulti, R: IntoLuaMulti>(lua: &Lua, func: &Function) -> Result<()> {
+    let rawlua = lua.lock();
+    let (id, functions) = match rawlua.priv_app_data_mut::<TypeRegistry>() {
+        Some(mut registry) => {
+            let functions = match &registry.functions {
+                Some(key) => Some(lua.registry_value::<Table>(key)?),
+                None => None,
+            };
+            (registry.add_signature(Signature::new::<A, R>()), functions)
+        }
+        None => return Ok(()),
+    };
+    let functions = match functions {
+        Some(functions) => functions,
+        None => {
+            let functions = lua.create_table()?;
+            functions.set_metatable(Some(lua.create_table_from([("__mode", "k")])?))?;
+            let key = lua.create_registry_value(&functions)?;
+            if let Some(mut registry) = rawlua.priv_app_data_mut::<TypeRegistry>() {
+                registry.functions = Some(key);
+            }
+            functions
+        }
+    };
+    functions.raw_set(func, id)
+}
+
+/// Item found while traversing globals and modules.
+enum Item {
+    Function(Signature),
+    Table(Vec<(StdString, Item)>),
+    Value(TypeInfo),
+}
+
+struct Collector<'a> {
+    classes: &'a BTreeMap<StdString, ClassDef>,
+    signatures: &'a [Signature],
+    functions: Option<Table>,
+    visited: HashSet<*const c_void>,
+}
+
+impl Collector<'_> {
+    /// Collects recorded functions, userdata values and nested tables containing them.
+    fn collect(&mut self, table: &Table) -> Result<Vec<(StdString, Item)>> {
+        let mut items = Vec::new();
+        for pair in table.pairs::<Value, Value>() {
+            let (key, value) = pair?;
+            let key = match key {
+                Value::String(key) => match key.to_str() {
+                    Ok(key) if is_identifier(&key) => key.to_string(),
+                    _ => continue,
+                },
+                _ => continue,
+            };
+            let item = match value {
+                Value::Function(func) => match self.signature(&func)? {
+                    Some(signature) => Item::Function(signature),
+                    None => continue,
+                },
+                Value::Table(table) => match self.collect_table(&table)? {
+                    Some(items) => Item::Table(items),
+                    None => continue,
+                },
+                // Userdata not created by us (eg. files from `io` library) is skipped as well
+                Value::UserData(ud) => match ud.type_name() {
+                    Ok(Some(name)) if self.classes.contains_key(&name) => Item::Value(TypeInfo::Named(name)),
+                    _ => continue,
+                },
+                _ => continue,
+            };
+            items.push((key, item));
+        }
+        items.sort_by(|(a, _), (b, _)| a.cmp(b));
+        Ok(items)
+    }
+
+    fn collect_table(&mut self, table: &Table) -> Result<Option<Vec<(StdString, Item)>>> {
+        if !self.visited.insert(table.to_pointer()) {
+            return Ok(None);
+        }
+        let items = self.collect(table)?;
+        Ok(Some(items).filter(|items| !items.is_empty()))
+    }
+
+    fn signature(&self, func: &Function) -> Result<Option<Signature>> {
+        let Some(functions) = &self.functions else {
+            return Ok(None);
+        };
+        let id = functions.raw_get::<Option<usize>>(func)?;
+        Ok(id.and_then(|id| self.signatures.get(id).cloned()))
+    }
+}
+
+/// Generates type definitions for recorded userdata types, globals and modules.
+pub(crate) fn generate(lua: &Lua) -> Result<StdString> {
+    let rawlua = lua.lock();
+    let registry = (rawlua.priv_app_data_ref::<TypeRegistry>()).ok_or_else(|| {
+        Error::runtime("recording of type definitions is not enabled (see `LuaOptions::type_definitions`)")
+    })?;
+
+    let functions = match &registry.functions {
+        Some(key) => Some(lua.registry_value::<Table>(key)?),
+        None => None,
+    };
+    let mut collector = Collector {
+        classes: &registry.classes,
+        signatures: &registry.signatures,
+        functions,
+        visited: HashSet::new(),
+    };
+
+    let globals_table = lua.globals();
+    collector.visited.insert(globals_table.to_pointer());
+    // Skip the `package` library, modules are reported separately
+    if let Some(package) = globals_table.raw_get::<Option<Table>>("package")? {
+        collector.visited.insert(package.to_pointer());
+    }
+    let globals = collector.collect(&globals_table)?;
+
+    #[cfg(not(feature = "luau"))]
+    const LOADED_MODULES_KEY: &str = "_LOADED";
+    #[cfg(feature = "luau")]
+    const LOADED_MODULES_KEY: &str = "_REGISTEREDMODULES";
+    let mut modules = Vec::new();
+    if let Some(loaded) = lua.named_registry_value::<Option<Table>>(LOADED_MODULES_KEY)? {
+        for pair in loaded.pairs::<StdString, Value>() {
+            if let (name, Value::Table(module)) = pair? {
+                if let Some(items) = collector.collect_table(&module)? {
+                    modules.push((name, items));
+                }
+            }
+        }
+    }
+    modules.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    let mut out = StdString::new();
+    if cfg!(feature = "luau") {
+        write_luau(&mut out, &registry.classes, &globals, &modules);
+    } else {
+        write_luals(&mut out, &registry.classes, &globals, &modules);
+    }
+    Ok(out)
+}
+
+fn write_luau(
+    out: &mut StdString,
+    classes: &BTreeMap<StdString, ClassDef>,
+    globals: &[(StdString, Item)],
+    modules: &[(StdString, Vec<(StdString, Item)>)],
+) {
+    for class in classes.values() {
+        _ = writeln!(out, "declare class {}", ident(&class.name));
+        for (name, ty) in &class.fields {
+            _ = writeln!(out, "\t{name}: {}", ty.luau());
+        }
+        for method in class.methods.iter().chain(&class.meta_methods) {
+            let sig = &method.signature;
+            match method.is_method {
+                true => {
+                    let params = sig.luau_params(true, true);
+                    _ = writeln!(
+                        out,
+                        "\tfunction {}({params}): {}",
+                        method.name,
+                        sig.luau_returns()
+                    );
+                }
+                false => _ = writeln!(out, "\t{}: {}", method.name, sig.luau_type()),
+            }
+        }
+        out.push_str("end\n\n");
+    }
+
+    for (name, item) in globals {
+        match item {
+            Item::Function(sig) => {
+                let params = sig.luau_params(false, true);
+                _ = writeln!(out, "declare function {name}({params}): {}", sig.luau_returns());
+            }
+            item => _ = writeln!(out, "declare {name}: {}", luau_item_type(item, 0)),
+        }
+        out.push('\n');
+    }
+
+    for (name, items) in modules {
+        let ty = luau_table_type(items, 0);
+        _ = writeln!(out, "-- module \"{name}\"\ntype {} = {ty}\n", ident(name));
+    }
+
+    out.truncate(out.trim_end().len());
+    out.push('\n');
+}
+
+fn luau_item_type(item: &Item, indent: usize) -> StdString {
+    match item {
+        Item::Function(sig) => sig.luau_type(),
+        Item::Value(ty) => ty.luau(),
+        Item::Table(items) => luau_table_type(items, indent),
+    }
+}
+
+fn luau_table_type(items: &[(StdString, Item)], indent: usize) -> StdString {
+    let mut out = StdString::from("{\n");
+    for (name, item) in items {
+        let ty = luau_item_type(item, indent + 1);
+        _ = writeln!(out, "{}{name}: {ty},", "\t".repeat(indent + 1));
+    }
+    out.push_str(&"\t".repeat(indent));
+    out.push('}');
+    out
+}
+
+fn write_luals(
+    out: &mut StdString,
+    classes: &BTreeMap<StdString, ClassDef>,
+    globals: &[(StdString, Item)],
+    modules: &[(StdString, Vec<(StdString, Item)>)],
+) {
+    out.push_str("---@meta\n\n");
+
+    for class in classes.values() {
+        let class_name = ident(&class.name);
+        _ = writeln!(out, "---@class {class_name}");
+        for (name, ty) in &class.fields {
+            _ = writeln!(out, "---@field {name} {}", ty.luals());
+        }
+        for method in &class.meta_methods {
+            if let Some(operator) = luals_operator(&method.name, &method.signature) {
+                _ = writeln!(out, "---@operator {operator}");
+            }
+        }
+        _ = writeln!(out, "local {class_name} = {{}}\n");
+
+        for method in &class.methods {
+            let sig = &method.signature;
+            sig.write_luals_annotations(out);
+            let sep = if method.is_method { ":" } else { "." };
+            let params = sig.luals_param_names();
+            _ = writeln!(out, "function {class_name}{sep}{}({params}) end\n", method.name);
+        }
+    }
+
+    for (name, item) in globals {
+        write_luals_item(out, name, item);
+    }
+
+    for (name, items) in modules {
+        let module = ident(name);
+        _ = writeln!(
+            out,
+            "-- module \"{name}\"\n---@class {module}\nlocal {module} = {{}}\n"
+        );
+        for (key, item) in items {
+            write_luals_item(out, &format!("{module}.{key}"), item);
+        }
+    }
+
+    out.truncate(out.trim_end().len());
+    out.push('\n');
+}
+
+fn write_luals_item(out: &mut StdString, path: &str, item: &Item) {
+    match item {
+        Item::Function(sig) => {
+            sig.write_luals_annotations(out);
+            _ = writeln!(out, "function {path}({}) end\n", sig.luals_param_names());
+        }
+        Item::Value(ty) => _ = writeln!(out, "---@type {}\n{path} = nil\n", ty.luals()),
+        Item::Table(items) => {
+            _ = writeln!(out, "{path} = {{}}\n");
+            for (key, item) in items {
+                write_luals_item(out, &format!("{path}.{key}"), item);
+            }
+        }
+    }
+}
+
+/// Converts metamethod to LuaLS `---@operator` annotation (if supported).
+fn luals_operator(name: &str, sig: &Signature) -> Option<StdString> {
+    let name = name.strip_prefix("__")?;
+    let ret = sig
+        .returns
+        .first()
+        .map(|ty| ty.luals())
+        .unwrap_or_else(|| "nil".into());
+    match name {
+        "unm" | "bnot" | "len" => Some(format!("{name}: {ret}")),
+        "add" | "sub" | "mul" | "div" | "mod" | "pow" | "idiv" | "band" | "bor" | "bxor" | "shl" | "shr"
+        | "concat" | "call" => {
+            let params = (sig.params.iter().map(|ty| ty.luals())).collect::<Vec<_>>();
+            Some(format!("{name}({}): {ret}", params.join(", ")))
+        }
+        _ => None,
+    }
+}
+
+/// Converts Rust type name to a valid identifier.
+fn ident(name: &str) -> StdString {
+    let name = (name.chars())
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect::<StdString>();
+    name.trim_matches('_').to_string()
+}
+
+fn is_identifier(s: &str) -> bool {
+    const KEYWORDS: &[&str] = &[
+        "and", "break", "do", "else", "elseif", "end", "false", "for", "function", "goto", "if", "in",
+        "local", "nil", "not", "or", "repeat", "return", "then", "true", "until", "while",
+    ];
+    let mut chars = s.chars();
+    (chars.next()).is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !KEYWORDS.contains(&s)
+}
@@ -66,7 +66,7 @@ impl ObjectLike for AnyUserData {
             Value::Function(func) => func.call(args),
             val => {
                 let msg = format!("attempt to call a {} value (function '{name}')", val.type_name());
-                Err(Error::RuntimeError(msg))
+                Err(Error::runtime(msg))
             }
         }
     }
@@ -80,7 +80,7 @@ impl ObjectLike for AnyUserData {
             Ok(Value::Function(func)) => func.call_async(args),
             Ok(val) => {
                 let msg = format!("attempt to call a {} value (function '{name}')", val.type_name());
-                AsyncCallFuture::error(Error::RuntimeError(msg))
+                AsyncCallFuture::error(Error::runtime(msg))
             }
             Err(err) => AsyncCallFuture::error(err),
         }
@@ -6,8 +6,9 @@ use std::{fmt, mem};
 use crate::error::{Error, Result};
 use crate::state::{Lua, RawLua};
 use crate::traits::FromLua;
+use crate::typedef::TypeInfo;
 use crate::userdata::AnyUserData;
-use crate::util::get_userdata;
+use crate::util::{get_userdata, short_type_name};
 use crate::value::Value;
 
 use super::cell::{UserDataStorage, UserDataVariant};
@@ -79,6 +80,11 @@ impl<T: 'static> FromLua for UserDataRef<T> {
         try_value_to_userdata::<T>(value)?.borrow()
     }
 
+    #[inline]
+    fn type_info() -> TypeInfo {
+        TypeInfo::Named(short_type_name::<T>())
+    }
+
     #[inline]
     unsafe fn from_stack(idx: c_int, lua: &RawLua) -> Result<Self> {
         Self::borrow_from_stack(lua, lua.state(), idx)
@@ -295,6 +301,11 @@ impl<T: 'static> FromLua for UserDataRefMut<T> {
         try_value_to_userdata::<T>(value)?.borrow_mut()
     }
 
+    #[inline]
+    fn type_info() -> TypeInfo {
+        TypeInfo::Named(short_type_name::<T>())
+    }
+
     unsafe fn from_stack(idx: c_int, lua: &RawLua) -> Result<Self> {
         Self::borrow_from_stack(lua, lua.state(), idx)
     }
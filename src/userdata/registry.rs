@@ -9,6 +9,7 @@ use std::string::String as StdString;
 use crate::error::{Error, Result};
 use crate::state::{Lua, LuaGuard};
 use crate::traits::{FromLua, FromLuaMulti, IntoLua, IntoLuaMulti};
+use crate::typedef::{ClassDef, MethodKind, Signature, TypeInfo, TypeRegistry};
 use crate::types::{Callback, MaybeSend};
 use crate::userdata::{
     borrow_userdata_scoped, borrow_userdata_scoped_mut, AnyUserData, MetaMethod, TypeIdHints, UserData,
@@ -56,6 +57,7 @@ pub(crate) struct RawUserDataRegistry {
     pub(crate) destructor: ffi::lua_CFunction,
     pub(crate) type_id: Option<TypeId>,
     pub(crate) type_name: StdString,
+    pub(crate) type_def: Option<ClassDef>,
 }
 
 impl UserDataType {
@@ -86,6 +88,7 @@ impl<T> UserDataRegistry<T> {
 
     #[inline(always)]
     fn with_type(lua: &Lua, r#type: UserDataType) -> Self {
+        let record_types = lua.lock().priv_app_data_ref::<TypeRegistry>().is_some();
         let raw = RawUserDataRegistry {
             fields: Vec::new(),
             field_getters: Vec::new(),
@@ -100,6 +103,7 @@ impl<T> UserDataRegistry<T> {
             destructor: super::util::destroy_userdata_storage::<T>,
             type_id: r#type.type_id(),
             type_name: short_type_name::<T>(),
+            type_def: record_types.then(|| ClassDef::new(short_type_name::<T>())),
         };
 
         UserDataRegistry {
@@ -353,6 +357,18 @@ impl<T> UserDataRegistry<T> {
         value.into_lua(lua)
     }
 
+    fn record_field(&mut self, name: &str, ty: TypeInfo) {
+        if let Some(class) = &mut self.raw.type_def {
+            class.add_field(name, ty);
+        }
+    }
+
+    fn record_method<A: FromLuaMulti, R: IntoLuaMulti>(&mut self, name: &str, kind: MethodKind) {
+        if let Some(class) = &mut self.raw.type_def {
+            class.add_method(name, kind, Signature::new::<A, R>());
+        }
+    }
+
     #[inline(always)]
     pub(crate) fn into_raw(self) -> RawUserDataRegistry {
         self.raw
@@ -370,6 +386,7 @@ impl<T> UserDataFields<T> for UserDataRegistry<T> {
         V: IntoLua + 'static,
     {
         let name = name.into();
+        self.record_field(&name, V::type_info());
         self.raw.fields.push((name, value.into_lua(self.lua.lua())));
     }
 
@@ -380,6 +397,7 @@ impl<T> UserDataFields<T> for UserDataRegistry<T> {
     {
         let name = name.into();
         let callback = self.box_method(&name, move |lua, data, ()| method(lua, data));
+        self.record_field(&name, R::type_info());
         self.raw.field_getters.push((name, callback));
     }
 
@@ -390,6 +408,7 @@ impl<T> UserDataFields<T> for UserDataRegistry<T> {
     {
         let name = name.into();
         let callback = self.box_method_mut(&name, method);
+        self.record_field(&name, A::type_info());
         self.raw.field_setters.push((name, callback));
     }
 
@@ -400,6 +419,7 @@ impl<T> UserDataFields<T> for UserDataRegistry<T> {
     {
         let name = name.into();
         let callback = self.box_function(&name, function);
+        self.record_field(&name, R::type_info());
         self.raw.field_getters.push((name, callback));
     }
 
@@ -410,6 +430,7 @@ impl<T> UserDataFields<T> for UserDataRegistry<T> {
     {
         let name = name.into();
         let callback = self.box_function_mut(&name, move |lua, (data, val)| function(lua, data, val));
+        self.record_field(&name, A::type_info());
         self.raw.field_setters.push((name, callback));
     }
 
@@ -444,6 +465,7 @@ impl<T> UserDataMethods<T> for UserDataRegistry<T> {
     {
         let name = name.into();
         let callback = self.box_method(&name, method);
+        self.record_method::<A, R>(&name, MethodKind::Method);
         self.raw.methods.push((name, callback));
     }
 
@@ -455,6 +477,7 @@ impl<T> UserDataMethods<T> for UserDataRegistry<T> {
     {
         let name = name.into();
         let callback = self.box_method_mut(&name, method);
+        self.record_method::<A, R>(&name, MethodKind::Method);
         self.raw.methods.push((name, callback));
     }
 
@@ -469,6 +492,7 @@ impl<T> UserDataMethods<T> for UserDataRegistry<T> {
     {
         let name = name.into();
         let callback = self.box_async_method(&name, method);
+        self.record_method::<A, R>(&name, MethodKind::Method);
         self.raw.async_methods.push((name, callback));
     }
 
@@ -483,6 +507,7 @@ impl<T> UserDataMethods<T> for UserDataRegistry<T> {
     {
         let name = name.into();
         let callback = self.box_async_method_mut(&name, method);
+        self.record_method::<A, R>(&name, MethodKind::Method);
         self.raw.async_methods.push((name, callback));
     }
 
@@ -494,6 +519,7 @@ impl<T> UserDataMethods<T> for UserDataRegistry<T> {
     {
         let name = name.into();
         let callback = self.box_function(&name, function);
+        self.record_method::<A, R>(&name, MethodKind::Function);
         self.raw.methods.push((name, callback));
     }
 
@@ -505,6 +531,7 @@ impl<T> UserDataMethods<T> for UserDataRegistry<T> {
     {
         let name = name.into();
         let callback = self.box_function_mut(&name, function);
+        self.record_method::<A, R>(&name, MethodKind::Function);
         self.raw.methods.push((name, callback));
     }
 
@@ -518,6 +545,7 @@ impl<T> UserDataMethods<T> for UserDataRegistry<T> {
     {
         let name = name.into();
         let callback = self.box_async_function(&name, function);
+        self.record_method::<A, R>(&name, MethodKind::Function);
         self.raw.async_methods.push((name, callback));
     }
 
@@ -529,6 +557,7 @@ impl<T> UserDataMethods<T> for UserDataRegistry<T> {
     {
         let name = name.into();
         let callback = self.box_method(&name, method);
+        self.record_method::<A, R>(&name, MethodKind::MetaMethod);
         self.raw.meta_methods.push((name, callback));
     }
 
@@ -540,6 +569,7 @@ impl<T> UserDataMethods<T> for UserDataRegistry<T> {
     {
         let name = name.into();
         let callback = self.box_method_mut(&name, method);
+        self.record_method::<A, R>(&name, MethodKind::MetaMethod);
         self.raw.meta_methods.push((name, callback));
     }
 
@@ -557,6 +587,7 @@ impl<T> UserDataMethods<T> for UserDataRegistry<T> {
     {
         let name = name.into();
         let callback = self.box_async_method(&name, method);
+        self.record_method::<A, R>(&name, MethodKind::MetaMethod);
         self.raw.async_meta_methods.push((name, callback));
     }
 
@@ -574,6 +605,7 @@ impl<T> UserDataMethods<T> for UserDataRegistry<T> {
     {
         let name = name.into();
         let callback = self.box_async_method_mut(&name, method);
+        self.record_method::<A, R>(&name, MethodKind::MetaMethod);
         self.raw.async_meta_methods.push((name, callback));
     }
 
@@ -585,6 +617,7 @@ impl<T> UserDataMethods<T> for UserDataRegistry<T> {
     {
         let name = name.into();
         let callback = self.box_function(&name, function);
+        self.record_method::<A, R>(&name, MethodKind::MetaFunction);
         self.raw.meta_methods.push((name, callback));
     }
 
@@ -596,6 +629,7 @@ impl<T> UserDataMethods<T> for UserDataRegistry<T> {
     {
         let name = name.into();
         let callback = self.box_function_mut(&name, function);
+        self.record_method::<A, R>(&name, MethodKind::MetaFunction);
         self.raw.meta_methods.push((name, callback));
     }
 
@@ -612,6 +646,7 @@ impl<T> UserDataMethods<T> for UserDataRegistry<T> {
     {
         let name = name.into();
         let callback = self.box_async_function(&name, function);
+        self.record_method::<A, R>(&name, MethodKind::MetaFunction);
         self.raw.async_meta_methods.push((name, callback));
     }
 }
@@ -634,6 +669,11 @@ macro_rules! lua_userdata_impl {
                 (registry.raw.meta_methods).extend(orig_registry.raw.meta_methods);
                 #[cfg(feature = "async")]
                 (registry.raw.async_meta_methods).extend(orig_registry.raw.async_meta_methods);
+                if let (Some(class), Some(orig_class)) =
+                    (&mut registry.raw.type_def, orig_registry.raw.type_def)
+                {
+                    class.extend(orig_class);
+                }
             }
         }
     };
@@ -6,6 +6,7 @@ use std::panic::{catch_unwind, resume_unwind, AssertUnwindSafe};
 use std::ptr;
 use std::sync::Arc;
 
+use crate::debug::StackTrace;
 use crate::error::{Error, Result};
 use crate::memory::MemoryState;
 use crate::util::{
@@ -83,7 +84,12 @@ where
                 "<not enough stack space for traceback>".to_string()
             };
             let cause = Arc::new(err);
-            let wrapped_error = WrappedFailure::Error(Error::CallbackError { traceback, cause });
+            let stack_trace = Arc::new(StackTrace::capture(state, 0));
+            let wrapped_error = WrappedFailure::Error(Error::CallbackError {
+                traceback,
+                cause,
+                stack_trace,
+            });
             ptr::write(ud, wrapped_error);
             ffi::lua_error(state)
         }
@@ -124,7 +130,7 @@ pub(crate) unsafe fn pop_error(state: *mut ffi::lua_State, err_code: c_int) -> E
             ffi::lua_pop(state, 1);
 
             match err_code {
-                ffi::LUA_ERRRUN => Error::RuntimeError(err_string),
+                ffi::LUA_ERRRUN => Error::RuntimeError(err_string, None),
                 ffi::LUA_ERRSYNTAX => {
                     Error::SyntaxError {
                         // This seems terrible, but as far as I can tell, this is exactly what the
@@ -138,7 +144,7 @@ pub(crate) unsafe fn pop_error(state: *mut ffi::lua_State, err_code: c_int) -> E
                     // recursively, and continuing to trigger the error handler would cause a stack
                     // overflow. It is not very useful to differentiate between this and "ordinary"
                     // runtime errors, so we handle them the same way.
-                    Error::RuntimeError(err_string)
+                    Error::RuntimeError(err_string, None)
                 }
                 ffi::LUA_ERRMEM => Error::MemoryError(err_string),
                 #[cfg(any(feature = "lua53", feature = "lua52"))]
@@ -270,6 +276,8 @@ pub(crate) unsafe extern "C-unwind" fn error_traceback(state: *mut ffi::lua_Stat
             ffi::luaL_traceback(state, state, s, 0);
             ffi::lua_remove(state, -2);
         }
+        // Skip the message handler itself
+        wrap_runtime_error(state, state, 1);
     }
 
     1
@@ -286,9 +294,28 @@ pub(crate) unsafe fn error_traceback_thread(state: *mut ffi::lua_State, thread:
             ffi::luaL_traceback(state, thread, s, 0);
             ffi::lua_remove(state, -2);
         }
+        wrap_runtime_error(state, thread, 0);
     }
 }
 
+// Replaces the error message on top of the stack with `RuntimeError` carrying the call stack of
+// the given thread, starting from the given level.
+//
+// Lua errors can be raised only before the Rust values are created.
+unsafe fn wrap_runtime_error(state: *mut ffi::lua_State, thread: *mut ffi::lua_State, level: c_int) {
+    if ffi::lua_checkstack(state, 3) == 0 {
+        return;
+    }
+    let ud = WrappedFailure::new_userdata(state);
+    let message = to_string(state, -2);
+    let stack_trace = Arc::new(StackTrace::capture(thread, level));
+    ptr::write(
+        ud,
+        WrappedFailure::Error(Error::RuntimeError(message, Some(stack_trace))),
+    );
+    ffi::lua_remove(state, -2);
+}
+
 // Initialize the error, panic, and destructed userdata metatables.
 pub(crate) unsafe fn init_error_registry(state: *mut ffi::lua_State) -> Result<()> {
     check_stack(state, 7)?;
@@ -20,6 +20,9 @@ pub(crate) use userdata::{
 #[cfg(not(feature = "luau"))]
 pub(crate) use userdata::push_uninit_userdata;
 
+#[cfg(feature = "async")]
+pub(crate) use timer::{start_timer, wake_at, TimerEntry};
+
 // Checks that Lua has enough free stack space for future stack operations. On failure, this will
 // panic with an internal error message.
 #[inline]
@@ -330,5 +333,7 @@ pub(crate) fn linenumber_to_usize(n: c_int) -> Option<usize> {
 
 mod error;
 mod short_names;
+#[cfg(feature = "async")]
+mod timer;
 mod types;
 mod userdata;
@@ -0,0 +1,97 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+use std::task::Waker;
+use std::thread;
+use std::time::Instant;
+
+use parking_lot::{Condvar, Mutex, MutexGuard};
+
+// A background thread waking up tasks at the given time, independent of the async runtime.
+//
+// The thread is spawned on first use and lives until the process exits.
+#[derive(Default)]
+struct Timer {
+    entries: Mutex<Vec<(u64, Instant, Waker)>>,
+    cond: Condvar,
+}
+
+impl Timer {
+    fn get() -> Option<&'static Timer> {
+        static TIMER: OnceLock<Option<&'static Timer>> = OnceLock::new();
+
+        *TIMER.get_or_init(|| {
+            let timer: &'static Timer = Box::leak(Box::default());
+            let builder = thread::Builder::new().name("mlua-timer".to_string());
+            builder.spawn(|| timer.run()).ok().map(|_| timer)
+        })
+    }
+
+    fn run(&self) {
+        let mut entries = self.entries.lock();
+        loop {
+            let now = Instant::now();
+            let mut expired = Vec::new();
+            entries.retain(|(_, deadline, waker)| {
+                if *deadline <= now {
+                    expired.push(waker.clone());
+                }
+                *deadline > now
+            });
+            if !expired.is_empty() {
+                MutexGuard::unlocked(&mut entries, || expired.into_iter().for_each(Waker::wake));
+                continue;
+            }
+            match entries.iter().map(|(_, deadline, _)| *deadline).min() {
+                Some(deadline) => drop(self.cond.wait_until(&mut entries, deadline)),
+                None => self.cond.wait(&mut entries),
+            }
+        }
+    }
+}
+
+// A registered wake up, cancelled when dropped
+pub(crate) struct TimerEntry {
+    timer: Option<&'static Timer>,
+    id: u64,
+    waker: Waker,
+}
+
+impl TimerEntry {
+    // Returns `true` if the entry wakes the same task as the given waker
+    pub(crate) fn will_wake(&self, waker: &Waker) -> bool {
+        self.waker.will_wake(waker)
+    }
+}
+
+impl Drop for TimerEntry {
+    fn drop(&mut self) {
+        if let Some(timer) = self.timer {
+            timer.entries.lock().retain(|(id, ..)| *id != self.id);
+        }
+    }
+}
+
+// Spawns the timer thread if needed, returns `false` if it cannot be spawned (e.g. on targets
+// without threads).
+pub(crate) fn start_timer() -> bool {
+    Timer::get().is_some()
+}
+
+// Wakes the task at the given time, unless the returned entry is dropped before.
+//
+// Does nothing if the timer thread is not available (see `start_timer`).
+pub(crate) fn wake_at(deadline: Instant, waker: &Waker) -> TimerEntry {
+    static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+    let timer = Timer::get();
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    if let Some(timer) = timer {
+        timer.entries.lock().push((id, deadline, waker.clone()));
+        timer.cond.notify_one();
+    }
+    TimerEntry {
+        timer,
+        id,
+        waker: waker.clone(),
+    }
+}
@@ -8,6 +8,7 @@ use num_traits::FromPrimitive;
 
 use crate::error::{Error, Result};
 use crate::function::Function;
+use crate::state::Lua;
 use crate::string::{BorrowedStr, String};
 use crate::table::Table;
 use crate::thread::Thread;
@@ -185,6 +186,13 @@ impl Value {
         }
     }
 
+    /// Makes a deep copy of the value in another Lua state.
+    ///
+    /// See [`Lua::transfer`] for the list of values that can be copied.
+    pub fn deep_copy_into(&self, lua: &Lua) -> Result<Value> {
+        crate::transfer::deep_copy(self, lua)
+    }
+
     /// Returns `true` if the value is a [`Nil`].
     #[inline]
     pub fn is_nil(&self) -> bool {
@@ -0,0 +1,594 @@
+//! Virtual filesystem support.
+
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::string::String as StdString;
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+use rustc_hash::{FxHashMap, FxHashSet};
+
+use crate::error::Result;
+use crate::state::Lua;
+use crate::types::{MaybeSend, MaybeSync};
+
+#[cfg(not(feature = "luau"))]
+use {
+    crate::chunk::ChunkMode,
+    crate::error::Error,
+    crate::function::Function,
+    crate::multi::MultiValue,
+    crate::table::Table,
+    crate::traits::{IntoLua, IntoLuaMulti},
+    crate::value::Value,
+};
+
+#[cfg(any(not(feature = "luau"), feature = "stdlib-ext"))]
+pub(crate) use file::FileHandle;
+
+#[cfg(any(not(feature = "luau"), feature = "stdlib-ext"))]
+mod file;
+mod zip;
+
+/// A virtual filesystem used by Lua code.
+///
+/// When installed using [`Lua::set_vfs`], it backs `io.open`, `io.lines`, `loadfile`, `dofile`
+/// and the module searchers used by `require` (or the Luau [`TextRequirer`]), so scripts never
+/// touch the real disk.
+///
+/// The following implementations are provided:
+/// - [`DirVfs`]: files inside a directory, which acts as the filesystem root (chroot-style)
+/// - [`MemoryVfs`]: files stored in memory
+/// - [`ArchiveVfs`]: files from a tar (uncompressed) or zip archive
+///
+/// Paths are always interpreted relative to the root of the filesystem, `..` components cannot
+/// escape it.
+///
+/// [`TextRequirer`]: crate::TextRequirer
+pub trait Vfs: MaybeSend + MaybeSync + 'static {
+    /// Reads the whole contents of a file.
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>>;
+
+    /// Returns `true` if the path points at a file.
+    fn is_file(&self, path: &Path) -> bool;
+
+    /// Returns `true` if the path points at a directory.
+    fn is_dir(&self, path: &Path) -> bool;
+
+    /// Replaces the contents of a file, creating it if it does not exist.
+    ///
+    /// The default implementation returns an error as the filesystem is read-only.
+    fn write(&self, path: &Path, data: &[u8]) -> io::Result<()> {
+        let _ = (path, data);
+        Err(read_only_error())
+    }
+}
+
+impl std::fmt::Debug for dyn Vfs {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<dyn Vfs>")
+    }
+}
+
+impl<T: Vfs + ?Sized> Vfs for Arc<T> {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        (**self).read(path)
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        (**self).is_file(path)
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        (**self).is_dir(path)
+    }
+
+    fn write(&self, path: &Path, data: &[u8]) -> io::Result<()> {
+        (**self).write(path, data)
+    }
+}
+
+/// A filesystem rooted at a directory on disk.
+///
+/// Files outside of the directory are not accessible, including through symbolic links.
+#[derive(Debug, Clone)]
+pub struct DirVfs {
+    root: PathBuf,
+}
+
+impl DirVfs {
+    /// Creates a new filesystem rooted at the given (existing) directory.
+    pub fn new(root: impl AsRef<Path>) -> io::Result<Self> {
+        let root = fs::canonicalize(root)?;
+        if !root.is_dir() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "not a directory"));
+        }
+        Ok(DirVfs { root })
+    }
+
+    /// Returns the root directory of the filesystem.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    // Maps a virtual path to a physical path inside the root directory
+    fn resolve(&self, path: &Path) -> io::Result<PathBuf> {
+        let path = self.root.join(normalize_path(path));
+        // Symbolic links must not point outside of the root
+        let mut existing = path.as_path();
+        while fs::symlink_metadata(existing).is_err() {
+            existing = existing.parent().unwrap_or(&self.root);
+        }
+        if !fs::canonicalize(existing)?.starts_with(&self.root) {
+            let msg = "path is outside of the filesystem root";
+            return Err(io::Error::new(io::ErrorKind::PermissionDenied, msg));
+        }
+        Ok(path)
+    }
+}
+
+impl Vfs for DirVfs {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        fs::read(self.resolve(path)?)
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        self.resolve(path).map(|p| p.is_file()).unwrap_or(false)
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        self.resolve(path).map(|p| p.is_dir()).unwrap_or(false)
+    }
+
+    fn write(&self, path: &Path, data: &[u8]) -> io::Result<()> {
+        fs::write(self.resolve(path)?, data)
+    }
+}
+
+/// A filesystem that keeps files in memory.
+///
+/// Directories are implied by the file paths.
+///
+/// # Examples
+///
+/// ```
+/// # use mlua::{Lua, Result};
+/// # use mlua::MemoryVfs;
+/// # fn main() -> Result<()> {
+/// let lua = Lua::new();
+/// let vfs = MemoryVfs::new().with_file("config.lua", "return { answer = 42 }");
+/// lua.set_vfs(vfs)?;
+/// # #[cfg(not(feature = "luau"))]
+/// assert_eq!(lua.load("return dofile('config.lua').answer").eval::<i64>()?, 42);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Default)]
+pub struct MemoryVfs {
+    files: RwLock<FxHashMap<PathBuf, Vec<u8>>>,
+    read_only: bool,
+}
+
+impl MemoryVfs {
+    /// Creates a new empty filesystem.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a file to the filesystem.
+    pub fn with_file(self, path: impl AsRef<Path>, contents: impl Into<Vec<u8>>) -> Self {
+        self.insert(path, contents);
+        self
+    }
+
+    /// Makes the filesystem read-only for Lua code.
+    ///
+    /// Files still can be added or removed using [`MemoryVfs::insert`] and [`MemoryVfs::remove`].
+    ///
+    /// Default: **false**
+    pub fn read_only(mut self, enabled: bool) -> Self {
+        self.read_only = enabled;
+        self
+    }
+
+    /// Adds or replaces a file.
+    pub fn insert(&self, path: impl AsRef<Path>, contents: impl Into<Vec<u8>>) {
+        let path = normalize_path(path.as_ref());
+        self.files.write().insert(path, contents.into());
+    }
+
+    /// Removes a file, returning its contents.
+    pub fn remove(&self, path: impl AsRef<Path>) -> Option<Vec<u8>> {
+        self.files.write().remove(&normalize_path(path.as_ref()))
+    }
+}
+
+impl Vfs for MemoryVfs {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        let files = self.files.read();
+        files
+            .get(&normalize_path(path))
+            .cloned()
+            .ok_or_else(not_found_error)
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        self.files.read().contains_key(&normalize_path(path))
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        let path = normalize_path(path);
+        let files = self.files.read();
+        path.as_os_str().is_empty() || files.keys().any(|file| file != &path && file.starts_with(&path))
+    }
+
+    fn write(&self, path: &Path, data: &[u8]) -> io::Result<()> {
+        if self.read_only {
+            return Err(read_only_error());
+        }
+        let path = normalize_path(path);
+        if path.as_os_str().is_empty() || self.is_dir(&path) {
+            return Err(io::Error::other("is a directory"));
+        }
+        self.files.write().insert(path, data.to_vec());
+        Ok(())
+    }
+}
+
+/// A read-only filesystem backed by an archive.
+///
+/// The following formats are supported:
+/// - uncompressed tar archives, including GNU and PAX extensions for long names
+/// - zip archives with stored or deflated entries; encrypted entries, other compression methods
+///   and ZIP64 archives are rejected with an error
+///
+/// Links and special files in the archive are ignored.
+#[derive(Debug, Default)]
+pub struct ArchiveVfs {
+    files: FxHashMap<PathBuf, Vec<u8>>,
+    dirs: FxHashSet<PathBuf>,
+}
+
+impl ArchiveVfs {
+    /// Reads a tar archive from the given file on disk.
+    pub fn open_tar(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::from_tar(&fs::read(path)?)
+    }
+
+    /// Reads a zip archive from the given file on disk.
+    pub fn open_zip(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::from_zip(&fs::read(path)?)
+    }
+
+    /// Creates a filesystem from the contents of a tar archive.
+    pub fn from_tar(data: &[u8]) -> io::Result<Self> {
+        let mut vfs = ArchiveVfs::default();
+        let mut long_name = None;
+        let mut offset = 0;
+        while offset < data.len() {
+            let header = (data.get(offset..offset + 512))
+                .ok_or_else(|| invalid_data_error("unexpected end of archive"))?;
+            if header.iter().all(|&b| b == 0) {
+                break;
+            }
+            let size = parse_octal(&header[124..136])?;
+            let start = offset + 512;
+            let end = (start.checked_add(size))
+                .filter(|&end| end <= data.len())
+                .ok_or_else(|| invalid_data_error("unexpected end of archive"))?;
+            let contents = &data[start..end];
+
+            let mut name = StdString::from_utf8_lossy(until_nul(&header[..100])).into_owned();
+            if &header[257..262] == b"ustar" {
+                let prefix = until_nul(&header[345..500]);
+                if !prefix.is_empty() {
+                    name = format!("{}/{name}", StdString::from_utf8_lossy(prefix));
+                }
+            }
+            if let Some(long_name) = long_name.take() {
+                name = long_name;
+            }
+
+            match header[156] {
+                b'0' | b'7' | 0 => vfs.add_file(&name, contents.to_vec()),
+                b'5' => vfs.add_dir(&name),
+                // GNU long name
+                b'L' => long_name = Some(StdString::from_utf8_lossy(until_nul(contents)).into_owned()),
+                // PAX extended header
+                b'x' => long_name = parse_pax_path(contents),
+                _ => {}
+            }
+            offset = start + size.div_ceil(512) * 512;
+        }
+        vfs.dirs.insert(PathBuf::new());
+        Ok(vfs)
+    }
+
+    /// Creates a filesystem from the contents of a zip archive.
+    pub fn from_zip(data: &[u8]) -> io::Result<Self> {
+        let mut vfs = ArchiveVfs::default();
+        for entry in zip::read_entries(data)? {
+            match entry {
+                zip::Entry::File(name, contents) => vfs.add_file(&name, contents),
+                zip::Entry::Dir(name) => vfs.add_dir(&name),
+            }
+        }
+        vfs.dirs.insert(PathBuf::new());
+        Ok(vfs)
+    }
+
+    fn add_file(&mut self, name: &str, contents: Vec<u8>) {
+        let path = normalize_path(name.as_ref());
+        self.dirs.extend(path.ancestors().skip(1).map(Path::to_path_buf));
+        self.files.insert(path, contents);
+    }
+
+    fn add_dir(&mut self, name: &str) {
+        let path = normalize_path(name.as_ref());
+        self.dirs.extend(path.ancestors().map(Path::to_path_buf));
+    }
+}
+
+impl Vfs for ArchiveVfs {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        let file = self.files.get(&normalize_path(path));
+        file.cloned().ok_or_else(not_found_error)
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        self.files.contains_key(&normalize_path(path))
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        self.dirs.contains(&normalize_path(path))
+    }
+}
+
+impl Lua {
+    /// Sets a virtual filesystem to be used by Lua code instead of the real one.
+    ///
+    /// Replaces `io.open`, `io.lines`, `io.type`, `loadfile` and `dofile` functions (when
+    /// loaded) and the module searchers used by `require`. Modules are searched in the
+    /// filesystem using `package.path`, which is reset to `./?.lua;./?/init.lua`; native modules
+    /// are not supported. In Luau, the `require` function is replaced with the one using
+    /// [`TextRequirer`] on top of the filesystem.
+    ///
+    /// Other functions that access the disk (`io.input`, `io.output`, `os.remove`, `os.rename`,
+    /// etc.) are not affected and should be removed for untrusted code, for example using
+    /// [`SandboxPolicy`].
+    ///
+    /// [`TextRequirer`]: crate::TextRequirer
+    /// [`SandboxPolicy`]: crate::SandboxPolicy
+    pub fn set_vfs(&self, vfs: impl Vfs) -> Result<()> {
+        let vfs: Arc<dyn Vfs> = Arc::new(vfs);
+        self.lock().set_priv_app_data(vfs.clone());
+
+        #[cfg(feature = "luau")]
+        {
+            let require = self.create_require_function(crate::TextRequirer::with_vfs(vfs))?;
+            self.globals().raw_set("require", require)?;
+        }
+        #[cfg(not(feature = "luau"))]
+        install_vfs(self, vfs)?;
+
+        Ok(())
+    }
+
+    /// Returns the virtual filesystem set by [`Lua::set_vfs`].
+    #[cfg(any(not(feature = "luau"), feature = "stdlib-ext"))]
+    pub(crate) fn vfs(&self) -> Option<Arc<dyn Vfs>> {
+        let lua = self.lock();
+        lua.priv_app_data_ref::<Arc<dyn Vfs>>().map(|vfs| vfs.clone())
+    }
+}
+
+// Converts a path to a relative one without `.` and `..` components, clamped at the root
+pub(crate) fn normalize_path(path: &Path) -> PathBuf {
+    let mut result = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(name) => result.push(name),
+            Component::ParentDir => {
+                result.pop();
+            }
+            Component::Prefix(..) | Component::RootDir | Component::CurDir => {}
+        }
+    }
+    result
+}
+
+fn not_found_error() -> io::Error {
+    io::Error::new(io::ErrorKind::NotFound, "No such file or directory")
+}
+
+fn read_only_error() -> io::Error {
+    io::Error::new(io::ErrorKind::PermissionDenied, "Read-only file system")
+}
+
+fn invalid_data_error(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn until_nul(bytes: &[u8]) -> &[u8] {
+    let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    &bytes[..end]
+}
+
+fn parse_octal(bytes: &[u8]) -> io::Result<usize> {
+    let digits = StdString::from_utf8_lossy(until_nul(bytes));
+    let digits = digits.trim_matches(' ');
+    if digits.is_empty() {
+        return Ok(0);
+    }
+    usize::from_str_radix(digits, 8).map_err(|_| invalid_data_error("invalid number in archive header"))
+}
+
+// Extracts the `path` record from PAX extended header
+fn parse_pax_path(mut data: &[u8]) -> Option<StdString> {
+    let mut path = None;
+    while let Some(space) = data.iter().position(|&b| b == b' ') {
+        let len = std::str::from_utf8(&data[..space]).ok()?.parse::<usize>().ok()?;
+        let record = data.get(space + 1..len)?.strip_suffix(b"\n")?;
+        if let Some(value) = record.strip_prefix(b"path=") {
+            path = Some(StdString::from_utf8_lossy(value).into_owned());
+        }
+        data = &data[len..];
+    }
+    path
+}
+
+#[cfg(not(feature = "luau"))]
+fn install_vfs(lua: &Lua, vfs: Arc<dyn Vfs>) -> Result<()> {
+    let globals = lua.globals();
+
+    if let Value::Function(_) = globals.raw_get("loadfile")? {
+        globals.raw_set("loadfile", create_loadfile(lua, vfs.clone())?)?;
+    }
+    if let Value::Function(_) = globals.raw_get("dofile")? {
+        let dofile = lua
+            .load("local loadfile = ... return function(path) return assert(loadfile(path))() end")
+            .set_name("=dofile")
+            .call::<Function>(create_loadfile(lua, vfs.clone())?)?;
+        globals.raw_set("dofile", dofile)?;
+    }
+
+    if let Value::Table(io) = globals.raw_get("io")? {
+        let open_vfs = vfs.clone();
+        let open = lua.create_function(move |lua, (path, mode): (StdString, Option<StdString>)| {
+            let mode = mode.as_deref().unwrap_or("r");
+            match FileHandle::open(open_vfs.clone(), &path, mode, true)? {
+                Ok(file) => (file, Value::Nil).into_lua_multi(lua),
+                Err(err) => (Value::Nil, format!("{path}: {err}")).into_lua_multi(lua),
+            }
+        })?;
+        io.raw_set("open", open)?;
+
+        let lines_vfs = vfs.clone();
+        let default_lines = io.raw_get::<Option<Function>>("lines")?;
+        let lines = lua.create_function(move |lua, (path, formats): (Option<StdString>, MultiValue)| {
+            let Some(path) = path else {
+                let default_lines =
+                    (default_lines.as_ref()).ok_or_else(|| Error::runtime("no default input file"))?;
+                return default_lines.call::<MultiValue>(formats);
+            };
+            let file = FileHandle::open(lines_vfs.clone(), &path, "r", true)?
+                .map_err(|err| Error::runtime(format!("{path}: {err}")))?;
+            let file = lua.create_userdata(file)?;
+            Ok(MultiValue::from_vec(vec![Value::Function(FileHandle::lines(
+                lua, file, formats, true,
+            )?)]))
+        })?;
+        io.raw_set("lines", lines)?;
+
+        let default_type = io.raw_get::<Option<Function>>("type")?;
+        let io_type = lua.create_function(move |lua, value: Value| {
+            if let Value::UserData(ud) = &value {
+                if let Ok(file) = ud.borrow::<FileHandle>() {
+                    return (if file.is_closed() { "closed file" } else { "file" }).into_lua(lua);
+                }
+            }
+            match &default_type {
+                Some(default_type) => default_type.call(value),
+                None => Ok(Value::Nil),
+            }
+        })?;
+        io.raw_set("type", io_type)?;
+    }
+
+    if let Value::Table(package) = globals.raw_get("package")? {
+        package.raw_set("path", "./?.lua;./?/init.lua")?;
+        package.raw_set("cpath", "")?;
+        #[cfg(any(feature = "lua54", feature = "lua53", feature = "lua52"))]
+        let searchers = package.raw_get::<Option<Table>>("searchers")?;
+        #[cfg(any(feature = "lua51", feature = "luajit"))]
+        let searchers = package.raw_get::<Option<Table>>("loaders")?;
+        if let Some(searchers) = searchers {
+            // Keep the `package.preload` searcher and replace the rest
+            for i in (3..=searchers.raw_len()).rev() {
+                searchers.raw_set(i, Value::Nil)?;
+            }
+            searchers.raw_set(2, create_searcher(lua, vfs)?)?;
+        }
+    }
+
+    Ok(())
+}
+
+// Loads a Lua chunk from the virtual filesystem
+#[cfg(not(feature = "luau"))]
+fn load_file(
+    lua: &Lua,
+    vfs: &dyn Vfs,
+    path: &str,
+    mode: Option<&str>,
+    env: Option<Table>,
+) -> Result<Function> {
+    let mut source =
+        (vfs.read(path.as_ref())).map_err(|err| Error::runtime(format!("cannot open {path}: {err}")))?;
+    // Skip the first line if it starts with '#' (e.g. shebang), keeping line numbers intact
+    if source.starts_with(b"#") {
+        let end = source.iter().position(|&b| b == b'\n').unwrap_or(source.len());
+        source.drain(..end);
+    }
+    let mut chunk = lua.load(source).set_name(format!("@{path}"));
+    if let Some(env) = env {
+        chunk = chunk.set_environment(env);
+    }
+    match mode {
+        Some("t") => chunk = chunk.set_mode(ChunkMode::Text),
+        Some("b") => chunk = chunk.set_mode(ChunkMode::Binary),
+        _ => {}
+    }
+    chunk.into_function()
+}
+
+#[cfg(not(feature = "luau"))]
+fn create_loadfile(lua: &Lua, vfs: Arc<dyn Vfs>) -> Result<Function> {
+    lua.create_function(
+        move |lua, (path, mode, env): (Option<StdString>, Option<StdString>, Option<Table>)| {
+            let path = path.ok_or_else(|| Error::runtime("loading from standard input is not supported"))?;
+            match load_file(lua, &*vfs, &path, mode.as_deref(), env) {
+                Ok(func) => (func, Value::Nil).into_lua_multi(lua),
+                Err(Error::SyntaxError { message, .. }) => (Value::Nil, message).into_lua_multi(lua),
+                Err(err) => (Value::Nil, err.to_string()).into_lua_multi(lua),
+            }
+        },
+    )
+}
+
+// Creates a `require` searcher for Lua modules in the virtual filesystem
+#[cfg(not(feature = "luau"))]
+fn create_searcher(lua: &Lua, vfs: Arc<dyn Vfs>) -> Result<Function> {
+    lua.create_function(move |lua, name: StdString| {
+        let package = lua.globals().raw_get::<Table>("package")?;
+        let template = package.raw_get::<StdString>("path")?;
+        let name_path = name.replace('.', "/");
+        let mut not_found = Vec::new();
+        for template in template.split(';').filter(|t| !t.is_empty()) {
+            let path = template.replace('?', &name_path);
+            if !vfs.is_file(path.as_ref()) {
+                not_found.push(format!("no file '{path}'"));
+                continue;
+            }
+            let loader = load_file(lua, &*vfs, &path, None, None).map_err(|err| {
+                let msg = match err {
+                    Error::SyntaxError { message, .. } => message,
+                    err => err.to_string(),
+                };
+                Error::runtime(format!(
+                    "error loading module '{name}' from file '{path}':\n\t{msg}"
+                ))
+            })?;
+            return (loader, path).into_lua_multi(lua);
+        }
+        #[cfg(feature = "lua54")]
+        let msg = not_found.join("\n\t");
+        #[cfg(not(feature = "lua54"))]
+        let msg = not_found
+            .iter()
+            .map(|s| format!("\n\t{s}"))
+            .collect::<StdString>();
+        msg.into_lua_multi(lua)
+    })
+}
@@ -0,0 +1,405 @@
+use std::io::{self, BufRead, Read, Write};
+use std::path::PathBuf;
+use std::string::String as StdString;
+use std::sync::Arc;
+
+use crate::error::{Error, Result};
+use crate::function::Function;
+use crate::multi::MultiValue;
+use crate::state::Lua;
+use crate::traits::IntoLuaMulti;
+use crate::types::Integer;
+use crate::userdata::{AnyUserData, MetaMethod, UserData, UserDataMethods};
+use crate::value::Value;
+
+use super::Vfs;
+
+#[cfg_attr(not(feature = "stdlib-ext"), allow(dead_code))]
+enum Backend {
+    File(Arc<dyn Vfs>, PathBuf),
+    Stdin,
+    Stdout,
+    Stderr,
+}
+
+// Amount of data required by a read format
+#[derive(Clone, Copy)]
+enum Need {
+    Line,
+    Number,
+    Count(usize),
+    All,
+}
+
+/// A file handle compatible with Lua file objects.
+///
+/// Files in a virtual filesystem are buffered in memory and written back on `flush`, `close` or
+/// when the handle is garbage collected. Standard streams are read and written directly.
+pub(crate) struct FileHandle {
+    backend: Backend,
+    data: Vec<u8>,
+    pos: usize,
+    readable: bool,
+    writable: bool,
+    append: bool,
+    dirty: bool,
+    closed: bool,
+}
+
+impl FileHandle {
+    /// Opens a file using the C `fopen` mode string.
+    ///
+    /// Returns an outer error for invalid mode and an inner error for failures to open the file.
+    /// Opening for writing fails with "permission denied" unless `allow_write` is set.
+    pub(crate) fn open(
+        vfs: Arc<dyn Vfs>,
+        path: &str,
+        mode: &str,
+        allow_write: bool,
+    ) -> Result<io::Result<Self>> {
+        let (readable, writable, append, truncate) = match mode.trim_end_matches('b') {
+            "r" => (true, false, false, false),
+            "r+" => (true, true, false, false),
+            "w" => (false, true, false, true),
+            "w+" => (true, true, false, true),
+            "a" => (false, true, true, false),
+            "a+" => (true, true, true, false),
+            _ => return Err(Error::runtime("bad argument #2 to 'open' (invalid mode)")),
+        };
+        if writable && !allow_write {
+            return Ok(Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "Permission denied",
+            )));
+        }
+        let path = PathBuf::from(path);
+        let data = match vfs.read(&path) {
+            Ok(_) if truncate => Vec::new(),
+            Ok(data) => data,
+            Err(err) if err.kind() == io::ErrorKind::NotFound && (truncate || append) => Vec::new(),
+            Err(err) => return Ok(Err(err)),
+        };
+        // Check that the file can be written
+        if truncate || (append && !vfs.is_file(&path)) {
+            if let Err(err) = vfs.write(&path, &data) {
+                return Ok(Err(err));
+            }
+        }
+        let pos = if append && !readable { data.len() } else { 0 };
+        let mut file = Self::new(Backend::File(vfs, path), readable, writable);
+        (file.data, file.pos, file.append) = (data, pos, append);
+        Ok(Ok(file))
+    }
+
+    #[cfg(feature = "stdlib-ext")]
+    pub(crate) fn stdin() -> Self {
+        Self::new(Backend::Stdin, true, false)
+    }
+
+    #[cfg(feature = "stdlib-ext")]
+    pub(crate) fn stdout() -> Self {
+        Self::new(Backend::Stdout, false, true)
+    }
+
+    #[cfg(feature = "stdlib-ext")]
+    pub(crate) fn stderr() -> Self {
+        Self::new(Backend::Stderr, false, true)
+    }
+
+    fn new(backend: Backend, readable: bool, writable: bool) -> Self {
+        #[rustfmt::skip]
+        let file = FileHandle {
+            backend, data: Vec::new(), pos: 0, readable, writable, append: false, dirty: false, closed: false,
+        };
+        file
+    }
+
+    pub(crate) fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    fn check_open(&self) -> Result<()> {
+        if self.closed {
+            return Err(Error::runtime("attempt to use a closed file"));
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &self.backend {
+            Backend::File(vfs, path) if self.dirty => {
+                vfs.write(path, &self.data)?;
+                self.dirty = false;
+            }
+            Backend::Stdout => io::stdout().flush()?,
+            Backend::Stderr => io::stderr().flush()?,
+            _ => {}
+        }
+        Ok(())
+    }
+
+    // Reads more data from the standard input, if needed
+    fn fill(&mut self, need: Need) -> io::Result<()> {
+        if !matches!(self.backend, Backend::Stdin) {
+            return Ok(());
+        }
+        // Discard consumed input
+        self.data.drain(..self.pos.min(self.data.len()));
+        self.pos = 0;
+
+        let mut stdin = io::stdin().lock();
+        if let Need::All = need {
+            stdin.read_to_end(&mut self.data)?;
+            return Ok(());
+        }
+        loop {
+            let done = match need {
+                Need::Line => self.data.contains(&b'\n'),
+                Need::Number => (self.data.iter())
+                    .skip_while(|b| b.is_ascii_whitespace())
+                    .any(|&b| b == b'\n'),
+                Need::Count(n) => self.data.len() >= n,
+                Need::All => unreachable!(),
+            };
+            if done || stdin.read_until(b'\n', &mut self.data)? == 0 {
+                return Ok(());
+            }
+        }
+    }
+
+    fn read(&mut self, lua: &Lua, formats: MultiValue) -> Result<MultiValue> {
+        self.check_open()?;
+        if !self.readable {
+            return (Value::Nil, "Bad file descriptor").into_lua_multi(lua);
+        }
+        let mut results = MultiValue::new();
+        if formats.is_empty() {
+            results.push_back(self.read_line(lua, false)?);
+        }
+        for format in formats {
+            let value = match format {
+                Value::Integer(n) => self.read_count(lua, n.max(0) as usize)?,
+                Value::Number(n) => self.read_count(lua, n.max(0.0) as usize)?,
+                Value::String(s) => match s.to_str()?.trim_start_matches('*').chars().next() {
+                    Some('n') => self.read_number()?,
+                    Some('l') => self.read_line(lua, false)?,
+                    Some('L') => self.read_line(lua, true)?,
+                    Some('a') => {
+                        self.fill(Need::All)?;
+                        let rest = lua.create_string(&self.data[self.pos.min(self.data.len())..])?;
+                        self.pos = self.data.len();
+                        Value::String(rest)
+                    }
+                    _ => return Err(Error::runtime("bad argument #1 to 'read' (invalid format)")),
+                },
+                _ => return Err(Error::runtime("bad argument #1 to 'read' (invalid format)")),
+            };
+            let eof = value.is_nil();
+            results.push_back(value);
+            if eof {
+                break;
+            }
+        }
+        Ok(results)
+    }
+
+    fn read_count(&mut self, lua: &Lua, count: usize) -> Result<Value> {
+        self.fill(Need::Count(count.max(1)))?;
+        if self.pos >= self.data.len() {
+            return Ok(Value::Nil);
+        }
+        let end = self.pos.saturating_add(count).min(self.data.len());
+        let bytes = lua.create_string(&self.data[self.pos..end])?;
+        self.pos = end;
+        Ok(Value::String(bytes))
+    }
+
+    fn read_line(&mut self, lua: &Lua, keep_newline: bool) -> Result<Value> {
+        self.fill(Need::Line)?;
+        if self.pos >= self.data.len() {
+            return Ok(Value::Nil);
+        }
+        let rest = &self.data[self.pos..];
+        let (line, consumed) = match rest.iter().position(|&b| b == b'\n') {
+            Some(i) if keep_newline => (&rest[..=i], i + 1),
+            Some(i) => (&rest[..i], i + 1),
+            None => (rest, rest.len()),
+        };
+        let line = lua.create_string(line)?;
+        self.pos += consumed;
+        Ok(Value::String(line))
+    }
+
+    fn read_number(&mut self) -> Result<Value> {
+        self.fill(Need::Number)?;
+        let rest = &self.data[self.pos.min(self.data.len())..];
+        let start = (rest.iter())
+            .position(|b| !b.is_ascii_whitespace())
+            .unwrap_or(rest.len());
+        let len = (rest[start..].iter())
+            .take(200)
+            .take_while(|b| b.is_ascii_hexdigit() || b"+-.xXpP".contains(b))
+            .count();
+        let text = StdString::from_utf8_lossy(&rest[start..start + len]).into_owned();
+        self.pos += start + len;
+        let (negative, digits) = match text.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, text.strip_prefix('+').unwrap_or(&text)),
+        };
+        let int = match digits.strip_prefix("0x").or_else(|| digits.strip_prefix("0X")) {
+            Some(hex) => u64::from_str_radix(hex, 16).ok().map(|i| i as Integer),
+            None => digits.parse::<Integer>().ok(),
+        };
+        Ok(match (int, text.parse::<f64>()) {
+            (Some(i), _) => Value::Integer(if negative { i.wrapping_neg() } else { i }),
+            (None, Ok(n)) => Value::Number(n),
+            (None, Err(_)) => Value::Nil,
+        })
+    }
+
+    fn write(&mut self, lua: &Lua, args: MultiValue) -> Result<io::Result<()>> {
+        self.check_open()?;
+        if !self.writable {
+            let err = io::Error::new(io::ErrorKind::PermissionDenied, "Bad file descriptor");
+            return Ok(Err(err));
+        }
+        for (i, arg) in args.into_iter().enumerate() {
+            let bytes = match lua.coerce_string(arg)? {
+                Some(s) => s.as_bytes().to_vec(),
+                None => {
+                    let msg = format!("bad argument #{} to 'write' (string expected)", i + 1);
+                    return Err(Error::runtime(msg));
+                }
+            };
+            let result = match self.backend {
+                Backend::Stdout => io::stdout().write_all(&bytes),
+                Backend::Stderr => io::stderr().write_all(&bytes),
+                _ => {
+                    self.write_buffered(&bytes);
+                    Ok(())
+                }
+            };
+            if let Err(err) = result {
+                return Ok(Err(err));
+            }
+        }
+        Ok(Ok(()))
+    }
+
+    fn write_buffered(&mut self, bytes: &[u8]) {
+        if self.append {
+            self.pos = self.data.len();
+        }
+        let end = self.pos + bytes.len();
+        if self.data.len() < end {
+            self.data.resize(end, 0);
+        }
+        self.data[self.pos..end].copy_from_slice(bytes);
+        self.pos = end;
+        self.dirty = true;
+    }
+
+    fn seek(&mut self, whence: Option<StdString>, offset: Option<Integer>) -> Result<io::Result<Integer>> {
+        self.check_open()?;
+        if !matches!(self.backend, Backend::File(..)) {
+            return Ok(Err(io::Error::other("Illegal seek")));
+        }
+        let base = match whence.as_deref().unwrap_or("cur") {
+            "set" => 0,
+            "cur" => self.pos as Integer,
+            "end" => self.data.len() as Integer,
+            _ => return Err(Error::runtime("bad argument #1 to 'seek' (invalid option)")),
+        };
+        let pos = base.saturating_add(offset.unwrap_or(0));
+        if pos < 0 {
+            return Ok(Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Invalid argument",
+            )));
+        }
+        self.pos = pos as usize;
+        Ok(Ok(pos))
+    }
+
+    fn close(&mut self) -> io::Result<()> {
+        if !matches!(self.backend, Backend::File(..)) {
+            return Err(io::Error::other("cannot close standard file"));
+        }
+        self.closed = true;
+        self.flush()
+    }
+
+    /// Creates an iterator over the file contents, optionally closing the file at the end.
+    pub(crate) fn lines(lua: &Lua, file: AnyUserData, formats: MultiValue, close: bool) -> Result<Function> {
+        let formats = formats.into_vec();
+        lua.create_function(move |lua, ()| {
+            let mut this = file.borrow_mut::<FileHandle>()?;
+            if this.closed {
+                return Err(Error::runtime("file is already closed"));
+            }
+            let values = this.read(lua, MultiValue::from_vec(formats.clone()))?;
+            if close && values.front().map(Value::is_nil).unwrap_or(true) {
+                this.close().map_err(Error::external)?;
+            }
+            Ok(values)
+        })
+    }
+}
+
+impl Drop for FileHandle {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+impl UserData for FileHandle {
+    fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+        fn result<T: IntoLuaMulti>(lua: &Lua, result: io::Result<T>) -> Result<MultiValue> {
+            match result {
+                Ok(value) => value.into_lua_multi(lua),
+                Err(err) => (Value::Nil, err.to_string()).into_lua_multi(lua),
+            }
+        }
+
+        methods.add_method_mut("read", |lua, this, formats: MultiValue| this.read(lua, formats));
+        methods.add_function("lines", |lua, (ud, formats): (AnyUserData, MultiValue)| {
+            ud.borrow::<FileHandle>()?.check_open()?;
+            FileHandle::lines(lua, ud, formats, false)
+        });
+        methods.add_function("write", |lua, (ud, args): (AnyUserData, MultiValue)| {
+            let written = ud.borrow_mut::<FileHandle>()?.write(lua, args)?;
+            result(lua, written.map(|_| ud))
+        });
+        methods.add_method_mut(
+            "seek",
+            |lua, this, (whence, offset): (Option<StdString>, Option<Integer>)| {
+                let pos = this.seek(whence, offset)?;
+                result(lua, pos)
+            },
+        );
+        methods.add_method_mut("flush", |lua, this, ()| {
+            this.check_open()?;
+            result(lua, this.flush().map(|_| true))
+        });
+        methods.add_method_mut("close", |lua, this, ()| {
+            this.check_open()?;
+            result(lua, this.close().map(|_| true))
+        });
+        methods.add_method("setvbuf", |_, this, _: MultiValue| {
+            this.check_open()?;
+            Ok(true)
+        });
+
+        methods.add_meta_method(MetaMethod::ToString, |_, this, ()| match this.closed {
+            true => Ok("file (closed)".to_string()),
+            false => Ok(format!("file ({:p})", this)),
+        });
+        #[cfg(feature = "lua54")]
+        methods.add_meta_method_mut(MetaMethod::Close, |_, this, _: MultiValue| {
+            if !this.closed && matches!(this.backend, Backend::File(..)) {
+                this.close().map_err(Error::external)?;
+            }
+            Ok(())
+        });
+    }
+}
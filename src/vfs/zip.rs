@@ -0,0 +1,376 @@
+//! Reading of zip archives.
+//!
+//! Only the features needed for a read-only filesystem are implemented: stored and deflated
+//! entries with CRC-32 validation. Encrypted entries, other compression methods and ZIP64
+//! archives are rejected with an error.
+
+use std::io;
+use std::string::String as StdString;
+
+use super::invalid_data_error;
+
+const LOCAL_HEADER_SIGNATURE: u32 = 0x04034b50;
+const CENTRAL_HEADER_SIGNATURE: u32 = 0x02014b50;
+const END_OF_CENTRAL_DIR_SIGNATURE: u32 = 0x06054b50;
+
+const METHOD_STORED: u16 = 0;
+const METHOD_DEFLATED: u16 = 8;
+
+/// An entry of a zip archive.
+pub(super) enum Entry {
+    File(StdString, Vec<u8>),
+    Dir(StdString),
+}
+
+/// Reads all entries of a zip archive using its central directory.
+///
+/// Symbolic links are skipped.
+pub(super) fn read_entries(data: &[u8]) -> io::Result<Vec<Entry>> {
+    let eocd = find_end_of_central_dir(data)?;
+    let count = read_u16(data, eocd + 10)? as usize;
+    let mut offset = read_u32(data, eocd + 16)? as usize;
+    if count == 0xFFFF || offset == 0xFFFFFFFF {
+        return Err(unsupported_error("ZIP64 archives are not supported"));
+    }
+
+    let mut entries = Vec::with_capacity(count);
+    for _ in 0..count {
+        if read_u32(data, offset)? != CENTRAL_HEADER_SIGNATURE {
+            return Err(invalid_data_error("invalid zip central directory"));
+        }
+        let host = read_u16(data, offset + 4)? >> 8;
+        let flags = read_u16(data, offset + 8)?;
+        let method = read_u16(data, offset + 10)?;
+        let crc = read_u32(data, offset + 16)?;
+        let compressed_size = read_u32(data, offset + 20)? as usize;
+        let size = read_u32(data, offset + 24)? as usize;
+        let name_len = read_u16(data, offset + 28)? as usize;
+        let extra_len = read_u16(data, offset + 30)? as usize;
+        let comment_len = read_u16(data, offset + 32)? as usize;
+        let mode = read_u32(data, offset + 38)? >> 16;
+        let header_offset = read_u32(data, offset + 42)? as usize;
+        let name = slice(data, offset + 46, name_len)?;
+        let name = StdString::from_utf8_lossy(name).into_owned();
+        offset += 46 + name_len + extra_len + comment_len;
+
+        // Unix symbolic link
+        if host == 3 && mode & 0o170000 == 0o120000 {
+            continue;
+        }
+        if name.ends_with('/') {
+            entries.push(Entry::Dir(name));
+            continue;
+        }
+        if flags & 1 != 0 {
+            return Err(unsupported_error("encrypted zip entries are not supported"));
+        }
+        if compressed_size == 0xFFFFFFFF || size == 0xFFFFFFFF {
+            return Err(unsupported_error("ZIP64 archives are not supported"));
+        }
+
+        if read_u32(data, header_offset)? != LOCAL_HEADER_SIGNATURE {
+            return Err(invalid_data_error("invalid zip local header"));
+        }
+        let local_name_len = read_u16(data, header_offset + 26)? as usize;
+        let local_extra_len = read_u16(data, header_offset + 28)? as usize;
+        let start = header_offset + 30 + local_name_len + local_extra_len;
+        let compressed = slice(data, start, compressed_size)?;
+
+        let contents = match method {
+            METHOD_STORED => compressed.to_vec(),
+            METHOD_DEFLATED => inflate(compressed, size)?,
+            _ => {
+                let msg = format!("unsupported zip compression method {method}");
+                return Err(unsupported_error(&msg));
+            }
+        };
+        if contents.len() != size || crc32(&contents) != crc {
+            return Err(invalid_data_error("zip entry checksum mismatch"));
+        }
+        entries.push(Entry::File(name, contents));
+    }
+    Ok(entries)
+}
+
+// Locates the "end of central directory" record, which is followed by a comment of variable size
+fn find_end_of_central_dir(data: &[u8]) -> io::Result<usize> {
+    let last = data.len().checked_sub(22);
+    let first = last.map(|last| last.saturating_sub(0xFFFF));
+    if let (Some(first), Some(last)) = (first, last) {
+        for offset in (first..=last).rev() {
+            if read_u32(data, offset)? == END_OF_CENTRAL_DIR_SIGNATURE {
+                return Ok(offset);
+            }
+        }
+    }
+    Err(invalid_data_error("not a zip archive"))
+}
+
+fn slice(data: &[u8], offset: usize, len: usize) -> io::Result<&[u8]> {
+    (offset.checked_add(len).and_then(|end| data.get(offset..end)))
+        .ok_or_else(|| invalid_data_error("unexpected end of archive"))
+}
+
+fn read_u16(data: &[u8], offset: usize) -> io::Result<u16> {
+    Ok(u16::from_le_bytes(slice(data, offset, 2)?.try_into().unwrap()))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> io::Result<u32> {
+    Ok(u32::from_le_bytes(slice(data, offset, 4)?.try_into().unwrap()))
+}
+
+fn unsupported_error(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::Unsupported, msg)
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    const TABLE: [u32; 256] = {
+        let mut table = [0u32; 256];
+        let mut i = 0;
+        while i < 256 {
+            let mut crc = i as u32;
+            let mut j = 0;
+            while j < 8 {
+                crc = if crc & 1 != 0 {
+                    0xEDB88320 ^ (crc >> 1)
+                } else {
+                    crc >> 1
+                };
+                j += 1;
+            }
+            table[i] = crc;
+            i += 1;
+        }
+        table
+    };
+
+    let crc = data.iter().fold(!0u32, |crc, &b| {
+        TABLE[((crc ^ b as u32) & 0xFF) as usize] ^ (crc >> 8)
+    });
+    !crc
+}
+
+//
+// Decompression of raw DEFLATE streams (RFC 1951)
+//
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195,
+    227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049, 3073,
+    4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13,
+];
+// Order of code length codes in a dynamic block header
+const CODE_LENGTH_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    bit: u32,
+}
+
+impl BitReader<'_> {
+    fn bits(&mut self, count: u32) -> io::Result<u32> {
+        let mut value = 0;
+        for i in 0..count {
+            let byte = (self.data.get(self.pos))
+                .ok_or_else(|| invalid_data_error("unexpected end of deflate stream"))?;
+            value |= ((*byte as u32 >> self.bit) & 1) << i;
+            self.bit += 1;
+            if self.bit == 8 {
+                self.bit = 0;
+                self.pos += 1;
+            }
+        }
+        Ok(value)
+    }
+
+    fn align_to_byte(&mut self) {
+        if self.bit != 0 {
+            self.bit = 0;
+            self.pos += 1;
+        }
+    }
+}
+
+// Canonical Huffman code, decoded one bit at a time
+struct Huffman {
+    counts: [u16; 16],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> io::Result<Self> {
+        let mut counts = [0u16; 16];
+        for &len in lengths {
+            counts[len as usize] += 1;
+        }
+        counts[0] = 0;
+
+        // Reject over-subscribed codes (incomplete codes are allowed)
+        let mut left = 1i32;
+        for &count in &counts[1..] {
+            left = (left << 1) - count as i32;
+            if left < 0 {
+                return Err(invalid_data_error("invalid deflate Huffman code"));
+            }
+        }
+
+        let mut offsets = [0u16; 16];
+        for len in 1..15 {
+            offsets[len + 1] = offsets[len] + counts[len];
+        }
+        let mut symbols = vec![0; lengths.len()];
+        for (symbol, &len) in lengths.iter().enumerate() {
+            if len != 0 {
+                symbols[offsets[len as usize] as usize] = symbol as u16;
+                offsets[len as usize] += 1;
+            }
+        }
+        Ok(Huffman { counts, symbols })
+    }
+
+    fn decode(&self, reader: &mut BitReader) -> io::Result<u16> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for &count in &self.counts[1..] {
+            code |= reader.bits(1)? as i32;
+            let count = count as i32;
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(invalid_data_error("invalid deflate Huffman code"))
+    }
+}
+
+// Decompresses a raw DEFLATE stream, which must produce exactly `size` bytes
+fn inflate(data: &[u8], size: usize) -> io::Result<Vec<u8>> {
+    let mut reader = BitReader { data, pos: 0, bit: 0 };
+    let mut output = Vec::with_capacity(size);
+    loop {
+        let last = reader.bits(1)? == 1;
+        match reader.bits(2)? {
+            0 => {
+                reader.align_to_byte();
+                let header = slice(data, reader.pos, 4)?;
+                let len = u16::from_le_bytes([header[0], header[1]]);
+                if len != !u16::from_le_bytes([header[2], header[3]]) {
+                    return Err(invalid_data_error("invalid deflate stored block"));
+                }
+                let block = slice(data, reader.pos + 4, len as usize)?;
+                if output.len() + block.len() > size {
+                    return Err(invalid_data_error("zip entry is larger than declared"));
+                }
+                output.extend_from_slice(block);
+                reader.pos += 4 + len as usize;
+            }
+            1 => {
+                let mut lengths = [0u8; 288];
+                lengths[..144].fill(8);
+                lengths[144..256].fill(9);
+                lengths[256..280].fill(7);
+                lengths[280..].fill(8);
+                let lit = Huffman::new(&lengths)?;
+                let dist = Huffman::new(&[5; 30])?;
+                inflate_block(&mut reader, &mut output, size, &lit, &dist)?;
+            }
+            2 => {
+                let (lit, dist) = read_dynamic_codes(&mut reader)?;
+                inflate_block(&mut reader, &mut output, size, &lit, &dist)?;
+            }
+            _ => return Err(invalid_data_error("invalid deflate block type")),
+        }
+        if last {
+            return Ok(output);
+        }
+    }
+}
+
+fn read_dynamic_codes(reader: &mut BitReader) -> io::Result<(Huffman, Huffman)> {
+    let nlit = reader.bits(5)? as usize + 257;
+    let ndist = reader.bits(5)? as usize + 1;
+    let ncode = reader.bits(4)? as usize + 4;
+    if nlit > 286 || ndist > 30 {
+        return Err(invalid_data_error("invalid deflate block header"));
+    }
+
+    let mut code_lengths = [0u8; 19];
+    for &i in &CODE_LENGTH_ORDER[..ncode] {
+        code_lengths[i] = reader.bits(3)? as u8;
+    }
+    let code = Huffman::new(&code_lengths)?;
+
+    let mut lengths = vec![0u8; nlit + ndist];
+    let mut i = 0;
+    while i < lengths.len() {
+        let (value, repeat) = match code.decode(reader)? {
+            symbol @ 0..=15 => (symbol as u8, 1),
+            16 if i > 0 => (lengths[i - 1], 3 + reader.bits(2)? as usize),
+            17 => (0, 3 + reader.bits(3)? as usize),
+            18 => (0, 11 + reader.bits(7)? as usize),
+            _ => return Err(invalid_data_error("invalid deflate code lengths")),
+        };
+        if i + repeat > lengths.len() {
+            return Err(invalid_data_error("invalid deflate code lengths"));
+        }
+        lengths[i..i + repeat].fill(value);
+        i += repeat;
+    }
+    if lengths[256] == 0 {
+        return Err(invalid_data_error("missing deflate end-of-block code"));
+    }
+    Ok((Huffman::new(&lengths[..nlit])?, Huffman::new(&lengths[nlit..])?))
+}
+
+fn inflate_block(
+    reader: &mut BitReader,
+    output: &mut Vec<u8>,
+    size: usize,
+    lit: &Huffman,
+    dist: &Huffman,
+) -> io::Result<()> {
+    loop {
+        let symbol = lit.decode(reader)? as usize;
+        let len = match symbol {
+            0..=255 => 1,
+            256 => return Ok(()),
+            257..=285 => {
+                let i = symbol - 257;
+                LENGTH_BASE[i] as usize + reader.bits(LENGTH_EXTRA[i] as u32)? as usize
+            }
+            _ => return Err(invalid_data_error("invalid deflate length code")),
+        };
+        if output.len() + len > size {
+            return Err(invalid_data_error("zip entry is larger than declared"));
+        }
+        if symbol < 256 {
+            output.push(symbol as u8);
+            continue;
+        }
+
+        let i = dist.decode(reader)? as usize;
+        if i >= DIST_BASE.len() {
+            return Err(invalid_data_error("invalid deflate distance code"));
+        }
+        let distance = DIST_BASE[i] as usize + reader.bits(DIST_EXTRA[i] as u32)? as usize;
+        if distance > output.len() {
+            return Err(invalid_data_error("invalid deflate distance"));
+        }
+        // Copy byte by byte, as the source may overlap with the copied data
+        let start = output.len() - distance;
+        for j in 0..len {
+            output.push(output[start + j]);
+        }
+    }
+}
@@ -114,7 +114,7 @@ async fn test_async_call() -> Result<()> {
     })?;
 
     match hello.call::<()>("alex") {
-        Err(Error::RuntimeError(_)) => {}
+        Err(Error::RuntimeError(..)) => {}
         err => panic!("expected `RuntimeError`, got {err:?}"),
     };
 
@@ -390,7 +390,7 @@ async fn test_async_table_object_like() -> Result<()> {
     assert_eq!(table.call_async::<i64>(()).await.unwrap(), 15);
 
     match table.call_async_method::<()>("non_existent", ()).await {
-        Err(Error::RuntimeError(err)) => {
+        Err(Error::RuntimeError(err, _)) => {
             assert!(err.contains("attempt to call a nil value (function 'non_existent')"))
         }
         r => panic!("expected RuntimeError, got {r:?}"),
@@ -538,7 +538,7 @@ async fn test_async_thread_error() -> Result<()> {
         .call_async::<()>(MyUserData)
         .await;
     assert!(
-        matches!(result, Err(Error::RuntimeError(cause)) if cause.contains("myuserdata error")),
+        matches!(result, Err(Error::RuntimeError(cause, _)) if cause.contains("myuserdata error")),
         "improper error traceback from dead thread"
     );
 
@@ -580,6 +580,257 @@ async fn test_async_terminate() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn test_async_thread_cancel() -> Result<()> {
+    let lua = Lua::new();
+
+    let sleep = lua.create_async_function(move |_lua, n: u64| async move {
+        sleep_ms(n).await;
+        Ok(())
+    })?;
+    lua.globals().set("sleep", sleep)?;
+
+    // Lua 5.1 cannot yield across pcall
+    #[cfg(not(feature = "lua51"))]
+    let code = r#"
+        local ok, err = pcall(sleep, 1000)
+        caught = not ok and tostring(err)
+        sleep(1000)
+        finished = true
+    "#;
+    #[cfg(feature = "lua51")]
+    let code = "sleep(1000) finished = true";
+    let thread = lua.create_thread(lua.load(code).into_function()?)?;
+    let mut fut = thread.clone().into_async::<()>(())?;
+    assert!(tokio::time::timeout(Duration::from_millis(10), &mut fut)
+        .await
+        .is_err());
+    assert!(!fut.is_cancelled());
+    assert!(fut.cancel());
+    assert!(!fut.cancel());
+    assert!(fut.is_cancelled());
+    assert!(matches!(fut.await, Err(Error::Cancelled)));
+    #[cfg(not(feature = "lua51"))]
+    assert!(lua
+        .globals()
+        .get::<String>("caught")?
+        .contains("operation was cancelled"));
+    assert_eq!(lua.globals().get::<Value>("finished")?, Value::Nil);
+
+    // Not started thread
+    let mut fut = (lua.load("finished = true").into_function()?).call_async::<()>(());
+    assert!(fut.cancel());
+    assert!(matches!(fut.await, Err(Error::Cancelled)));
+    assert_eq!(lua.globals().get::<Value>("finished")?, Value::Nil);
+
+    // Streams report cancellation once
+    let thread = lua.create_thread(lua.load("coroutine.yield(1) sleep(1000)").into_function()?)?;
+    let mut stream = thread.into_async::<Option<i64>>(())?;
+    assert_eq!(stream.try_next().await?, Some(Some(1)));
+    assert!(tokio::time::timeout(Duration::from_millis(10), stream.try_next())
+        .await
+        .is_err());
+    assert!(stream.cancel());
+    assert!(matches!(stream.try_next().await, Err(Error::Cancelled)));
+    assert!(stream.try_next().await?.is_none());
+
+    Ok(())
+}
+
+#[cfg(feature = "lua54")]
+#[tokio::test]
+async fn test_async_thread_cancel_close() -> Result<()> {
+    let lua = Lua::new();
+
+    let sleep = lua.create_async_function(move |_lua, n: u64| async move {
+        sleep_ms(n).await;
+        Ok(())
+    })?;
+    lua.globals().set("sleep", sleep)?;
+
+    let func = lua
+        .load(
+            r#"
+            local guard <close> = setmetatable({}, {__close = function(_, err) closed_with = tostring(err) end})
+            sleep(1000)
+            "#,
+        )
+        .into_function()?;
+    let mut fut = func.call_async::<()>(());
+    assert!(tokio::time::timeout(Duration::from_millis(10), &mut fut)
+        .await
+        .is_err());
+    assert!(fut.cancel());
+    assert!(lua
+        .globals()
+        .get::<String>("closed_with")?
+        .contains("operation was cancelled"));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_async_thread_timeout() -> Result<()> {
+    let lua = Lua::new();
+
+    let sleep = lua.create_async_function(move |_lua, n: u64| async move {
+        sleep_ms(n).await;
+        Ok(())
+    })?;
+    lua.globals().set("sleep", sleep)?;
+
+    let func = lua.load("sleep(...) return 'done'").into_function()?;
+    let start = std::time::Instant::now();
+    let res = func
+        .call_async::<String>(5000)
+        .with_timeout(Duration::from_millis(50))
+        .await;
+    assert!(matches!(res, Err(Error::Cancelled)), "{res:?}");
+    assert!(start.elapsed() < Duration::from_millis(1000));
+
+    let res = func
+        .call_async::<String>(10)
+        .with_timeout(Duration::from_secs(5))
+        .await?;
+    assert_eq!(res, "done");
+
+    // Dropping the thread releases the waker registered for the deadline
+    struct NoopWaker;
+    impl std::task::Wake for NoopWaker {
+        fn wake(self: Arc<Self>) {}
+    }
+    let waker = Arc::new(NoopWaker);
+    let task_waker = std::task::Waker::from(waker.clone());
+    let mut fut = Box::pin(
+        func.call_async::<String>(5000)
+            .with_timeout(Duration::from_secs(60)),
+    );
+    let mut cx = std::task::Context::from_waker(&task_waker);
+    assert!(std::future::Future::poll(fut.as_mut(), &mut cx).is_pending());
+    drop(task_waker);
+    assert!(Arc::strong_count(&waker) > 1);
+    drop(fut);
+    assert_eq!(Arc::strong_count(&waker), 1);
+
+    Ok(())
+}
+
+#[cfg(any(feature = "lua54", feature = "lua53", feature = "luau"))]
+#[tokio::test]
+async fn test_async_budget() -> Result<()> {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use mlua::ExecutionBudget;
+
+    let lua = Lua::new();
+
+    // Background task making progress only when the executor gets control
+    let ticks = Arc::new(AtomicUsize::new(0));
+    let ticker = tokio::spawn({
+        let ticks = ticks.clone();
+        async move {
+            loop {
+                ticks.fetch_add(1, Ordering::Relaxed);
+                tokio::task::yield_now().await;
+            }
+        }
+    });
+    let ticks2 = ticks.clone();
+    let get_ticks = lua.create_function(move |_, ()| Ok(ticks2.load(Ordering::Relaxed)))?;
+    lua.globals().set("ticks", get_ticks)?;
+
+    let code = r#"
+        local start = ticks()
+        local sum = 0
+        for i = 1, 100000 do sum = sum + i end
+        return sum, ticks() - start
+    "#;
+    let func = lua.load(code).into_function()?;
+
+    // No preemption by default
+    let (sum, progress) = func.call_async::<(i64, usize)>(()).await?;
+    assert_eq!(sum, 5_000_050_000);
+    assert_eq!(progress, 0);
+
+    lua.set_async_budget(Some(ExecutionBudget::instructions(1000)));
+    let (sum, progress) = func.call_async::<(i64, usize)>(()).await?;
+    assert_eq!(sum, 5_000_050_000);
+    assert!(progress > 0);
+    let (_, progress) = lua.load(code).eval_async::<(i64, usize)>().await?;
+    assert!(progress > 0);
+
+    // Per-thread budget overrides the default one
+    let (_, progress) = func.call_async::<(i64, usize)>(()).with_budget(None).await?;
+    assert_eq!(progress, 0);
+    let thread = lua.create_thread(func.clone())?;
+    let fut = thread.into_async::<(i64, usize)>(())?;
+    assert_eq!(fut.budget(), Some(ExecutionBudget::instructions(1000)));
+    let fut = fut.with_budget(Some(ExecutionBudget::time(Duration::from_micros(100))));
+    assert!(fut.await?.1 > 0);
+    lua.set_async_budget(None);
+    let (_, progress) = func.call_async::<(i64, usize)>(()).await?;
+    assert_eq!(progress, 0);
+
+    // Preemption is not visible as yielded values
+    let thread = lua.create_thread(
+        lua.load("for i = 1, 3 do for _ = 1, 10000 do end coroutine.yield(i) end return 4")
+            .into_function()?,
+    )?;
+    let stream = (thread.into_async::<i64>(())?).with_budget(Some(ExecutionBudget::instructions(100)));
+    assert_eq!(stream.try_collect::<Vec<_>>().await?, [1, 2, 3, 4]);
+
+    // Combined with a time limit, CPU-bound code is interrupted
+    let res = (lua.load("while true do end").into_function()?)
+        .call_async::<()>(())
+        .with_budget(Some(ExecutionBudget::instructions(1000)))
+        .with_timeout(Duration::from_millis(50))
+        .await;
+    assert!(matches!(res, Err(Error::Cancelled)), "{res:?}");
+
+    ticker.abort();
+
+    Ok(())
+}
+
+// Lua 5.1 cannot yield from the generic `for` iterator function
+#[cfg(not(feature = "lua51"))]
+#[tokio::test]
+async fn test_async_iterator() -> Result<()> {
+    use futures_util::stream::StreamExt;
+
+    let lua = Lua::new();
+
+    let ticks = lua.create_function(|lua, n: u64| {
+        let stream = futures_util::stream::iter(1..=n).then(|i| async move {
+            sleep_ms(5).await;
+            (i, format!("tick{i}"))
+        });
+        lua.create_async_iterator(stream)
+    })?;
+    lua.globals().set("ticks", ticks)?;
+
+    let res = lua
+        .load(
+            r#"
+            local res = {}
+            for i, name in ticks(5) do
+                if i > 3 then break end
+                res[i] = name
+            end
+            return table.concat(res, ",")
+            "#,
+        )
+        .eval_async::<String>()
+        .await?;
+    assert_eq!(res, "tick1,tick2,tick3");
+
+    // Streams can only be iterated in async context
+    let err = lua.load("for _ in ticks(1) do end").exec().unwrap_err();
+    assert!(err.to_string().contains("attempt to yield"), "{err}");
+
+    Ok(())
+}
+
 #[tokio::test]
 async fn test_async_task() -> Result<()> {
     let lua = Lua::new();
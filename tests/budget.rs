@@ -0,0 +1,241 @@
+#![cfg(any(feature = "lua54", feature = "lua53", feature = "luau"))]
+
+use std::time::{Duration, Instant};
+
+use mlua::{Budget, Error, ExecutionBudget, Function, Lua, Result, ThreadStatus};
+
+#[test]
+fn test_budget_instructions() -> Result<()> {
+    let lua = Lua::new();
+
+    let sum: Function = lua
+        .load(
+            r#"
+            function(n)
+                local sum = 0
+                for i = 1, n do
+                    sum = sum + i
+                end
+                return sum, "done"
+            end
+        "#,
+        )
+        .eval()?;
+
+    // Enough budget
+    let result = sum.call_with_budget::<(i64, String)>(10, ExecutionBudget::instructions(1_000_000))?;
+    assert_eq!(result.completed(), Some((55, "done".into())));
+
+    // Small budget
+    let budget = ExecutionBudget::instructions(100);
+    let mut state = sum.call_with_budget::<(i64, String)>(10_000, budget)?;
+    let mut slices = 1;
+    let result = loop {
+        match state {
+            Budget::Completed(result) => break result,
+            Budget::Exhausted(preempted) => {
+                assert_eq!(preempted.thread().status(), ThreadStatus::Resumable);
+                state = preempted.resume(budget)?;
+                slices += 1;
+            }
+        }
+    };
+    assert_eq!(result, (50_005_000, "done".into()));
+    assert!(slices > 10);
+
+    Ok(())
+}
+
+#[test]
+fn test_budget_gc_between_slices() -> Result<()> {
+    let lua = Lua::new();
+
+    let sum: Function = lua
+        .load(
+            r#"
+            function(n)
+                local t = { x = 1 }
+                local sum = 0
+                for i = 1, n do
+                    sum = sum + t.x
+                end
+                return sum
+            end
+        "#,
+        )
+        .eval()?;
+
+    // Locals of the preempted function must survive garbage collection
+    let budget = ExecutionBudget::instructions(100);
+    let mut state = sum.call_with_budget::<i64>(10_000, budget)?;
+    let result = loop {
+        match state {
+            Budget::Completed(result) => break result,
+            Budget::Exhausted(preempted) => {
+                lua.gc_collect()?;
+                state = preempted.resume(budget)?;
+            }
+        }
+    };
+    assert_eq!(result, 10_000);
+
+    Ok(())
+}
+
+#[test]
+fn test_budget_time() -> Result<()> {
+    let lua = Lua::new();
+
+    let start = Instant::now();
+    let budget = ExecutionBudget::time(Duration::from_millis(20));
+    let state = lua.load("while true do end").exec_with_budget(budget)?;
+    assert!(start.elapsed() >= Duration::from_millis(20));
+    let Budget::Exhausted(preempted) = state else {
+        panic!("expected budget to be exhausted");
+    };
+    let state = preempted.resume(budget)?;
+    assert!(!state.is_completed());
+
+    // Combined limits
+    let budget = ExecutionBudget::time(Duration::from_secs(60)).with_instructions(1000);
+    let state = lua.load("while true do end").exec_with_budget(budget)?;
+    assert!(!state.is_completed());
+
+    Ok(())
+}
+
+#[test]
+fn test_budget_interleaving() -> Result<()> {
+    let lua = Lua::new();
+    lua.globals().set("log", lua.create_table()?)?;
+
+    let chunk = |name: &str| {
+        lua.load(format!(
+            r#"
+            for i = 1, 3 do
+                table.insert(log, "{name}")
+                local x = 0
+                for j = 1, 10000 do x = x + j end
+            end
+        "#
+        ))
+    };
+
+    // Run two scripts in turns
+    let budget = ExecutionBudget::instructions(10000);
+    let mut tasks = vec![
+        chunk("a").exec_with_budget(budget)?,
+        chunk("b").exec_with_budget(budget)?,
+    ];
+    while !tasks.is_empty() {
+        tasks = (tasks.into_iter())
+            .map(|task| match task {
+                Budget::Completed(()) => Ok(None),
+                Budget::Exhausted(preempted) => preempted.resume(budget).map(Some),
+            })
+            .filter_map(Result::transpose)
+            .collect::<Result<_>>()?;
+    }
+
+    let log = lua.globals().get::<Vec<String>>("log")?;
+    assert_eq!(log.len(), 6);
+    assert_ne!(log[..3], ["a", "a", "a"]);
+
+    Ok(())
+}
+
+#[test]
+fn test_budget_errors() -> Result<()> {
+    let lua = Lua::new();
+
+    let budget = ExecutionBudget::instructions(1_000_000);
+    match lua.load("error('boom')").exec_with_budget(budget) {
+        Err(Error::RuntimeError(msg, _)) => assert!(msg.contains("boom")),
+        r => panic!("expected RuntimeError, got {r:?}"),
+    }
+
+    match lua.load("coroutine.yield(1)").exec_with_budget(budget) {
+        Err(Error::RuntimeError(msg, _)) => assert!(msg.contains("attempt to yield")),
+        r => panic!("expected RuntimeError, got {r:?}"),
+    }
+
+    Ok(())
+}
+
+#[cfg(not(feature = "luau"))]
+#[test]
+fn test_budget_keeps_hooks() -> Result<()> {
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Arc;
+
+    use mlua::{HookTriggers, VmState};
+
+    let lua = Lua::new();
+    let lines = Arc::new(AtomicU64::new(0));
+    let lines2 = lines.clone();
+    lua.set_global_hook(HookTriggers::EVERY_LINE, move |_, _| {
+        lines2.fetch_add(1, Ordering::Relaxed);
+        Ok(VmState::Continue)
+    })?;
+
+    let func: Function = lua
+        .load("function()\n local n = 0\n for i = 1, 1000 do\n n = n + i\n end\n return n\n end")
+        .eval()?;
+    let state = func.call_with_budget::<i64>((), ExecutionBudget::instructions(100))?;
+    let Budget::Exhausted(preempted) = state else {
+        panic!("budget must be exhausted");
+    };
+    assert!(lines.load(Ordering::Relaxed) > 0);
+
+    // Thread hooks are called while running with a budget and restored afterwards
+    let thread_lines = Arc::new(AtomicU64::new(0));
+    let thread_lines2 = thread_lines.clone();
+    preempted
+        .thread()
+        .set_hook(HookTriggers::EVERY_LINE, move |_, _| {
+            thread_lines2.fetch_add(1, Ordering::Relaxed);
+            Ok(VmState::Continue)
+        })?;
+    let preempted = match preempted.resume(ExecutionBudget::instructions(100))? {
+        Budget::Exhausted(preempted) => preempted,
+        Budget::Completed(_) => panic!("budget must be exhausted"),
+    };
+    let during_budget = thread_lines.load(Ordering::Relaxed);
+    assert!(during_budget > 0);
+    assert_eq!(preempted.into_thread().resume::<i64>(())?, 500500);
+    assert!(thread_lines.load(Ordering::Relaxed) > during_budget);
+
+    // The global hook is still set
+    let before = lines.load(Ordering::Relaxed);
+    lua.load("local x = 1\nlocal y = 2").exec()?;
+    assert!(lines.load(Ordering::Relaxed) > before);
+
+    Ok(())
+}
+
+#[cfg(feature = "luau")]
+#[test]
+fn test_budget_restores_interrupt() -> Result<()> {
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Arc;
+
+    use mlua::VmState;
+
+    let lua = Lua::new();
+    let count = Arc::new(AtomicU64::new(0));
+    let count2 = count.clone();
+    lua.set_interrupt(move |_| {
+        count2.fetch_add(1, Ordering::Relaxed);
+        Ok(VmState::Continue)
+    });
+
+    let budget = ExecutionBudget::instructions(10);
+    let state = lua.load("while true do end").exec_with_budget(budget)?;
+    assert!(!state.is_completed());
+    assert_eq!(count.load(Ordering::Relaxed), 0);
+
+    lua.load("for i = 1, 10 do end").exec()?;
+    assert!(count.load(Ordering::Relaxed) > 0);
+
+    Ok(())
+}
@@ -0,0 +1,215 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use mlua::{ChunkCache, ChunkCacheKey, DirChunkCache, Lua, Result};
+
+#[derive(Default)]
+struct CountingCache {
+    entries: Mutex<HashMap<ChunkCacheKey, Vec<u8>>>,
+    hits: AtomicUsize,
+    misses: AtomicUsize,
+}
+
+impl ChunkCache for CountingCache {
+    fn get(&self, key: &ChunkCacheKey) -> io::Result<Option<Vec<u8>>> {
+        let bytecode = self.entries.lock().unwrap().get(key).cloned();
+        match bytecode {
+            Some(_) => self.hits.fetch_add(1, Ordering::Relaxed),
+            None => self.misses.fetch_add(1, Ordering::Relaxed),
+        };
+        Ok(bytecode)
+    }
+
+    fn put(&self, key: &ChunkCacheKey, bytecode: &[u8]) -> io::Result<()> {
+        self.entries
+            .lock()
+            .unwrap()
+            .insert(key.clone(), bytecode.to_vec());
+        Ok(())
+    }
+
+    fn remove(&self, key: &ChunkCacheKey) -> io::Result<()> {
+        self.entries.lock().unwrap().remove(key);
+        Ok(())
+    }
+
+    fn clear(&self) -> io::Result<()> {
+        self.entries.lock().unwrap().clear();
+        Ok(())
+    }
+}
+
+impl CountingCache {
+    fn stats(&self) -> (usize, usize) {
+        (
+            self.hits.load(Ordering::Relaxed),
+            self.misses.load(Ordering::Relaxed),
+        )
+    }
+}
+
+#[test]
+fn test_chunk_cache() -> Result<()> {
+    let lua = Lua::new();
+    let cache = Arc::new(CountingCache::default());
+    lua.set_chunk_cache(cache.clone());
+
+    let source = "local a, b = ... return a + b";
+    for _ in 0..3 {
+        let sum: i64 = lua.load(source).set_name("sum").call((1, 2))?;
+        assert_eq!(sum, 3);
+    }
+    assert_eq!(cache.stats(), (2, 1));
+
+    // Different name or source produce different keys
+    lua.load(source).set_name("sum2").call::<i64>((1, 2))?;
+    lua.load("local a, b = ... return a * b")
+        .set_name("sum")
+        .call::<i64>((1, 2))?;
+    assert_eq!(cache.stats(), (2, 3));
+    assert_eq!(cache.entries.lock().unwrap().len(), 3);
+
+    // Expressions are cached as well
+    assert_eq!(lua.load("1 + 2").set_name("expr").eval::<i64>()?, 3);
+    assert_eq!(lua.load("1 + 2").set_name("expr").eval::<i64>()?, 3);
+    assert_eq!(cache.stats(), (3, 4));
+
+    // Environment is applied to cached chunks
+    let env = lua.create_table_from([("x", 10)])?;
+    let chunk = lua.load("return x").set_name("env").set_environment(env);
+    let key = chunk.cache_key().unwrap();
+    assert_eq!(key.name(), "env");
+    assert_eq!(chunk.call::<i64>(())?, 10);
+    lua.globals().set("x", 20)?;
+    assert_eq!(lua.load("return x").set_name("env").call::<i64>(())?, 20);
+    assert_eq!(cache.stats(), (4, 5));
+
+    // Errors keep the chunk name
+    for _ in 0..2 {
+        let err = lua.load("error('boom')").set_name("=failing").exec().unwrap_err();
+        assert!(err.to_string().contains("failing:1: boom"), "{err}");
+    }
+
+    // Syntax errors are not cached
+    let err = lua.load("return +").set_name("=invalid").exec().unwrap_err();
+    assert!(matches!(err, mlua::Error::SyntaxError { .. }), "{err:?}");
+
+    cache.remove(&key)?;
+    assert!(cache.get(&key)?.is_none());
+
+    // Binary chunks are never cached
+    lua.remove_chunk_cache();
+    #[cfg(not(feature = "luau"))]
+    {
+        let bytecode = lua.load("return 1").into_function()?.dump(false);
+        lua.set_chunk_cache(cache.clone());
+        assert!(lua.load(&bytecode).cache_key().is_none());
+        assert_eq!(lua.load(&bytecode).eval::<i64>()?, 1);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_chunk_cache_key() -> Result<()> {
+    let lua = Lua::new();
+    let key = lua.load("return 1").set_name("a").cache_key().unwrap();
+    assert_eq!(key, lua.load("return 1").set_name("a").cache_key().unwrap());
+    assert_ne!(
+        key.source_hash(),
+        lua.load("return 2").cache_key().unwrap().source_hash()
+    );
+    assert!(key
+        .version()
+        .contains(if cfg!(feature = "luau") { "Luau" } else { "Lua" }));
+
+    #[cfg(feature = "luau")]
+    {
+        use mlua::Compiler;
+
+        let key2 = (lua.load("return 1").set_name("a"))
+            .set_compiler(Compiler::new().set_optimization_level(2))
+            .cache_key()
+            .unwrap();
+        assert_ne!(key.options_hash(), key2.options_hash());
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_dir_chunk_cache() -> Result<()> {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let dir = temp_dir.path().join("cache");
+    let cache = DirChunkCache::new(&dir)?;
+    assert_eq!(cache.dir(), dir);
+
+    let lua = Lua::new();
+    lua.set_chunk_cache(cache.clone());
+    assert_eq!(
+        lua.load("return 'v1'").set_name("=script").eval::<String>()?,
+        "v1"
+    );
+    let key = lua.load("return 'v1'").set_name("=script").cache_key().unwrap();
+    assert!(cache.get(&key)?.is_some());
+    let files = || fs::read_dir(&dir).unwrap().count();
+    assert_eq!(files(), 1);
+
+    // Cached bytecode is used by another Lua instance
+    let lua2 = Lua::new();
+    lua2.set_chunk_cache(DirChunkCache::new(&dir)?);
+    assert_eq!(
+        lua2.load("return 'v1'").set_name("=script").eval::<String>()?,
+        "v1"
+    );
+    assert_eq!(files(), 1);
+
+    // Changing the source keeps the outdated entry until it's pruned
+    assert_eq!(
+        lua.load("return 'v2'").set_name("=script").eval::<String>()?,
+        "v2"
+    );
+    assert_eq!(files(), 2);
+    assert!(cache.get(&key)?.is_some());
+    let key_v2 = lua.load("return 'v2'").set_name("=script").cache_key().unwrap();
+    cache.prune(&key_v2)?;
+    assert_eq!(files(), 1);
+    assert!(cache.get(&key)?.is_none());
+    assert!(cache.get(&key_v2)?.is_some());
+
+    // Chunks loaded by a shared helper (without a name) do not evict each other
+    let run = |source: &str| lua.load(source).eval::<i64>();
+    assert_eq!(run("return 10")?, 10);
+    assert_eq!(run("return 20")?, 20);
+    assert_eq!(files(), 3);
+    cache.clear()?;
+    lua.load("return 'v2'").set_name("=script").exec()?;
+
+    lua.load("return 1").set_name("=other").exec()?;
+    assert_eq!(files(), 2);
+
+    // Damaged entries are ignored and replaced
+    let key = lua.load("return 1").set_name("=other").cache_key().unwrap();
+    for entry in fs::read_dir(&dir).unwrap() {
+        let path = entry.unwrap().path();
+        let data = fs::read(&path).unwrap();
+        fs::write(&path, &data[..data.len() - 1]).unwrap();
+    }
+    assert!(cache.get(&key)?.is_none());
+    assert_eq!(lua.load("return 1").set_name("=other").eval::<i64>()?, 1);
+    assert!(cache.get(&key)?.is_some());
+
+    cache.remove(&key)?;
+    assert!(cache.get(&key)?.is_none());
+    assert_eq!(files(), 1);
+
+    // Clearing keeps unrelated files
+    fs::write(dir.join("README"), "keep me").unwrap();
+    cache.clear()?;
+    assert_eq!(files(), 1);
+
+    Ok(())
+}
@@ -243,15 +243,15 @@ fn test_error_conversion() -> Result<()> {
         res => panic!("expected `Error::ExternalError`, got {res:?}"),
     }
     match lua.convert::<Error>("abc") {
-        Ok(Error::RuntimeError(msg)) => assert_eq!(msg, "abc"),
+        Ok(Error::RuntimeError(msg, _)) => assert_eq!(msg, "abc"),
         res => panic!("expected `Error::RuntimeError`, got {res:?}"),
     }
     match lua.convert::<Error>(true) {
-        Ok(Error::RuntimeError(msg)) => assert_eq!(msg, "true"),
+        Ok(Error::RuntimeError(msg, _)) => assert_eq!(msg, "true"),
         res => panic!("expected `Error::RuntimeError`, got {res:?}"),
     }
     match lua.convert::<Error>(lua.globals()) {
-        Ok(Error::RuntimeError(msg)) => assert!(msg.starts_with("table:")),
+        Ok(Error::RuntimeError(msg, _)) => assert!(msg.starts_with("table:")),
         res => panic!("expected `Error::RuntimeError`, got {res:?}"),
     }
 
@@ -748,3 +748,217 @@ fn test_char_from_lua() -> Result<()> {
 
     Ok(())
 }
+
+#[cfg(feature = "macros")]
+#[test]
+fn test_derive_struct_table() -> Result<()> {
+    use mlua::FromLua;
+
+    #[derive(Debug, Default, PartialEq, FromLua, IntoLua)]
+    #[lua(table)]
+    struct Limits {
+        max_conn: u32,
+    }
+
+    #[derive(Debug, PartialEq, FromLua, IntoLua)]
+    #[lua(table)]
+    struct Server {
+        host: String,
+        #[lua(rename = "portNumber")]
+        port: u16,
+        #[lua(default)]
+        debug: bool,
+        #[lua(default = "default_name")]
+        name: String,
+        #[lua(skip)]
+        cache: Vec<u8>,
+        #[lua(flatten)]
+        limits: Limits,
+        tags: Option<Vec<String>>,
+    }
+
+    fn default_name() -> String {
+        "server".into()
+    }
+
+    #[derive(Debug, PartialEq, FromLua, IntoLua)]
+    #[lua(table)]
+    struct Config {
+        server: Server,
+        point: Point,
+    }
+
+    #[derive(Debug, PartialEq, FromLua, IntoLua)]
+    #[lua(table)]
+    struct Meters(f64);
+
+    #[derive(Debug, PartialEq, FromLua, IntoLua)]
+    #[lua(table)]
+    struct Point(i32, Option<i32>, i32);
+
+    let lua = Lua::new();
+
+    let server = Server {
+        host: "localhost".into(),
+        port: 8080,
+        debug: true,
+        name: "main".into(),
+        cache: vec![1, 2, 3],
+        limits: Limits { max_conn: 10 },
+        tags: None,
+    };
+    let table = lua.convert::<Table>(server)?;
+    assert_eq!(table.get::<String>("host")?, "localhost");
+    assert_eq!(table.get::<u16>("portNumber")?, 8080);
+    assert_eq!(table.get::<u32>("max_conn")?, 10);
+    assert!(!table.contains_key("port")?);
+    assert!(!table.contains_key("cache")?);
+    assert!(!table.contains_key("limits")?);
+
+    let server: Server = lua.convert(table)?;
+    assert_eq!(server.cache, Vec::<u8>::new());
+    assert_eq!(server.limits.max_conn, 10);
+    assert!(server.debug);
+
+    let config: Config = lua
+        .load(r#"{server = {host = "h", portNumber = 1, max_conn = 2, tags = {"a"}}, point = {1, nil, 3}}"#)
+        .eval()?;
+    assert_eq!(config.server.name, "server");
+    assert!(!config.server.debug);
+    assert_eq!(config.server.tags, Some(vec!["a".to_string()]));
+    assert_eq!(config.point, Point(1, None, 3));
+
+    // Newtype and tuple structs
+    assert_eq!(lua.convert::<Value>(Meters(1.5))?, Value::Number(1.5));
+    assert_eq!(lua.convert::<Meters>(2)?, Meters(2.0));
+    let point = lua.convert::<Table>(Point(1, None, 3))?;
+    assert_eq!(point.get::<i32>(3)?, 3);
+    assert_eq!(lua.convert::<Point>(point)?, Point(1, None, 3));
+
+    // Errors name the failing field path
+    let err = lua
+        .load(r#"{server = {host = "h", portNumber = "x", max_conn = 2}, point = {1, 2, 3}}"#)
+        .eval::<Config>()
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "error converting Lua string to Config (field `server.portNumber`: expected number or string coercible to number)"
+    );
+    let err = lua
+        .load(r#"{host = "h", portNumber = 1}"#)
+        .eval::<Server>()
+        .unwrap_err();
+    assert!(err.to_string().contains("to Limits (field `max_conn`"), "{err}");
+    let err = lua.convert::<Point>(vec![1, 2]).unwrap_err();
+    assert!(err.to_string().contains("field `[3]`"), "{err}");
+    let err = Server::from_lua(Value::Integer(1), &lua).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "error converting Lua integer to Server (expected table)"
+    );
+
+    Ok(())
+}
+
+#[cfg(feature = "macros")]
+#[test]
+fn test_derive_enum_table() -> Result<()> {
+    use mlua::FromLua;
+
+    #[derive(Debug, PartialEq, FromLua, IntoLua)]
+    #[lua(table)]
+    enum Level {
+        Low,
+        #[lua(rename = "high")]
+        High,
+    }
+
+    #[derive(Debug, Clone, PartialEq, FromLua, IntoLua)]
+    #[lua(table)]
+    enum Message {
+        Quit,
+        Move { x: i32, y: i32 },
+        Write(String),
+        Color(u8, u8, u8),
+    }
+
+    #[derive(Debug, Clone, PartialEq, FromLua, IntoLua)]
+    #[lua(table, tag = "kind")]
+    enum Shape {
+        Empty,
+        Circle {
+            r: f64,
+        },
+        #[lua(rename = "square")]
+        Square {
+            side: f64,
+        },
+        Wrapped(Inner),
+    }
+
+    #[derive(Debug, Clone, PartialEq, FromLua, IntoLua)]
+    #[lua(table)]
+    struct Inner {
+        value: i32,
+    }
+
+    let lua = Lua::new();
+
+    // String constants
+    assert_eq!(lua.convert::<String>(Level::High)?, "high");
+    assert_eq!(lua.convert::<Level>("Low")?, Level::Low);
+    let err = lua.convert::<Level>("Mid").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "error converting Lua string to Level (unknown variant `Mid`, expected one of `Low`, `high`)"
+    );
+
+    // Externally tagged
+    assert_eq!(lua.convert::<String>(Message::Quit)?, "Quit");
+    let mv = lua.convert::<Table>(Message::Move { x: 1, y: 2 })?;
+    assert_eq!(mv.get::<Table>("Move")?.get::<i32>("y")?, 2);
+    for msg in [
+        Message::Quit,
+        Message::Move { x: 1, y: 2 },
+        Message::Write("hi".into()),
+        Message::Color(1, 2, 3),
+    ] {
+        let value = lua.convert::<Value>(msg.clone())?;
+        assert_eq!(lua.convert::<Message>(value)?, msg);
+    }
+    let msg: Message = lua.load("{Write = 'hello'}").eval()?;
+    assert_eq!(msg, Message::Write("hello".into()));
+    let err = lua
+        .load("{Move = {x = 1, y = 'a'}}")
+        .eval::<Message>()
+        .unwrap_err();
+    assert!(err.to_string().contains("to Message::Move (field `y`"), "{err}");
+    let err = lua
+        .load("{Move = {}, Quit = true}")
+        .eval::<Message>()
+        .unwrap_err();
+    assert!(
+        err.to_string().contains("expected table with a single key"),
+        "{err}"
+    );
+
+    // Internally tagged
+    let circle = lua.convert::<Table>(Shape::Circle { r: 1.0 })?;
+    assert_eq!(circle.get::<String>("kind")?, "Circle");
+    assert_eq!(circle.get::<f64>("r")?, 1.0);
+    for shape in [
+        Shape::Empty,
+        Shape::Circle { r: 1.0 },
+        Shape::Square { side: 2.0 },
+        Shape::Wrapped(Inner { value: 3 }),
+    ] {
+        let value = lua.convert::<Value>(shape.clone())?;
+        assert_eq!(lua.convert::<Shape>(value)?, shape);
+    }
+    let shape: Shape = lua.load("{kind = 'square', side = 4}").eval()?;
+    assert_eq!(shape, Shape::Square { side: 4.0 });
+    let err = lua.load("{kind = 'Triangle'}").eval::<Shape>().unwrap_err();
+    assert!(err.to_string().contains("unknown variant `Triangle`"), "{err}");
+
+    Ok(())
+}
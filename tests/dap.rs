@@ -0,0 +1,227 @@
+#![cfg(all(feature = "dap", not(feature = "luau")))]
+
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc;
+use std::thread;
+
+use mlua::{DapServer, Lua, Result};
+use serde_json::{json, Value as JsonValue};
+
+// A scripted DAP client
+struct Client {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+    seq: i64,
+    events: VecDeque<JsonValue>,
+}
+
+impl Client {
+    fn connect(addr: std::net::SocketAddr) -> Self {
+        let stream = TcpStream::connect(addr).unwrap();
+        Client {
+            reader: BufReader::new(stream.try_clone().unwrap()),
+            writer: stream,
+            seq: 0,
+            events: VecDeque::new(),
+        }
+    }
+
+    fn read_message(&mut self) -> JsonValue {
+        let mut content_length = 0;
+        loop {
+            let mut line = String::new();
+            self.reader.read_line(&mut line).unwrap();
+            let line = line.trim_end();
+            if line.is_empty() {
+                break;
+            }
+            if let Some(len) = line.strip_prefix("Content-Length: ") {
+                content_length = len.parse().unwrap();
+            }
+        }
+        let mut body = vec![0; content_length];
+        self.reader.read_exact(&mut body).unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    fn request(&mut self, command: &str, arguments: JsonValue) -> JsonValue {
+        self.seq += 1;
+        let message = json!({
+            "seq": self.seq,
+            "type": "request",
+            "command": command,
+            "arguments": arguments,
+        })
+        .to_string();
+        write!(self.writer, "Content-Length: {}\r\n\r\n{message}", message.len()).unwrap();
+        loop {
+            let message = self.read_message();
+            if message["type"] == "event" {
+                self.events.push_back(message);
+                continue;
+            }
+            assert_eq!(message["request_seq"], self.seq);
+            assert_eq!(message["command"], command);
+            return message;
+        }
+    }
+
+    fn wait_event(&mut self, event: &str) -> JsonValue {
+        loop {
+            let message = match self.events.pop_front() {
+                Some(message) => message,
+                None => self.read_message(),
+            };
+            if message["type"] == "event" && message["event"] == event {
+                return message["body"].clone();
+            }
+        }
+    }
+
+    fn variables(&mut self, reference: &JsonValue) -> Vec<(String, String)> {
+        let response = self.request("variables", json!({ "variablesReference": reference }));
+        (response["body"]["variables"].as_array().unwrap().iter())
+            .map(|var| {
+                let name = var["name"].as_str().unwrap().to_string();
+                (name, var["value"].as_str().unwrap().to_string())
+            })
+            .collect()
+    }
+}
+
+const SOURCE: &str = r#"
+local function add(a, b)
+    local s = a + b
+    return s
+end
+local x = 1
+local t = { key = "value" }
+local y = add(x, 2)
+return y * 2
+"#;
+
+#[test]
+fn test_dap_session() -> Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let addr = listener.local_addr()?;
+    let (configured_tx, configured_rx) = mpsc::channel();
+
+    let client = thread::spawn(move || {
+        let mut client = Client::connect(addr);
+
+        let response = client.request(
+            "initialize",
+            json!({ "adapterID": "mlua", "linesStartAt1": true }),
+        );
+        assert_eq!(response["success"], true);
+        assert_eq!(response["body"]["supportsFunctionBreakpoints"], true);
+        client.wait_event("initialized");
+
+        let response = client.request(
+            "setBreakpoints",
+            json!({
+                "source": { "path": "/project/main.lua" },
+                "breakpoints": [{ "line": 8 }],
+            }),
+        );
+        assert_eq!(response["body"]["breakpoints"][0]["verified"], true);
+        client.request("configurationDone", json!({}));
+        // Inspection is not available while running
+        let response = client.request("stackTrace", json!({ "threadId": 1 }));
+        assert_eq!(response["success"], false);
+        configured_tx.send(()).unwrap();
+
+        let stopped = client.wait_event("stopped");
+        assert_eq!(stopped["reason"], "breakpoint");
+
+        let response = client.request("threads", json!({}));
+        assert_eq!(response["body"]["threads"][0]["id"], 1);
+
+        let response = client.request("stackTrace", json!({ "threadId": 1 }));
+        let frame = &response["body"]["stackFrames"][0];
+        assert_eq!(frame["line"], 8);
+        assert_eq!(frame["name"], "main chunk");
+        assert_eq!(frame["source"]["path"], "/project/main.lua");
+        assert_eq!(frame["source"]["name"], "main.lua");
+
+        let response = client.request("scopes", json!({ "frameId": frame["id"] }));
+        let scopes = response["body"]["scopes"].as_array().unwrap();
+        assert_eq!(scopes[0]["name"], "Locals");
+        let locals_ref = scopes[0]["variablesReference"].clone();
+        let locals = client.variables(&locals_ref);
+        assert_eq!(locals[1], ("x".into(), "1".into()));
+        assert_eq!(locals[2].0, "t");
+
+        // Expand table
+        let response = client.request("variables", json!({ "variablesReference": locals_ref }));
+        let table_ref = response["body"]["variables"][2]["variablesReference"].clone();
+        assert_eq!(
+            client.variables(&table_ref),
+            vec![("key".into(), "\"value\"".into())]
+        );
+
+        let response = client.request(
+            "setVariable",
+            json!({ "variablesReference": locals_ref, "name": "x", "value": "10" }),
+        );
+        assert_eq!(response["body"]["value"], "10");
+
+        let response = client.request(
+            "evaluate",
+            json!({ "expression": "x + #t.key", "frameId": frame["id"] }),
+        );
+        assert_eq!(response["body"]["result"], "15");
+        let response = client.request("evaluate", json!({ "expression": "x +", "frameId": frame["id"] }));
+        assert_eq!(response["success"], false);
+
+        client.request("stepIn", json!({ "threadId": 1 }));
+        let stopped = client.wait_event("stopped");
+        assert_eq!(stopped["reason"], "step");
+        let response = client.request("stackTrace", json!({ "threadId": 1 }));
+        let frames = response["body"]["stackFrames"].as_array().unwrap();
+        assert_eq!(frames[0]["line"], 3);
+        assert_eq!(frames[0]["name"], "add");
+        assert_eq!(frames[1]["line"], 8);
+
+        let response = client.request("scopes", json!({ "frameId": frames[0]["id"] }));
+        let locals_ref = response["body"]["scopes"][0]["variablesReference"].clone();
+        let locals = client.variables(&locals_ref);
+        assert_eq!(locals, vec![("a".into(), "10".into()), ("b".into(), "2".into())]);
+
+        client.request("continue", json!({ "threadId": 1 }));
+        client.wait_event("terminated");
+        client.request("disconnect", json!({}));
+    });
+
+    let (stream, _) = listener.accept()?;
+    let server = DapServer::tcp(stream)?;
+    server.map_source("main", "/project/main.lua");
+    configured_rx.recv().unwrap();
+
+    let lua = Lua::new();
+    server.attach(&lua)?;
+    let result: i64 = lua.load(SOURCE).set_name("main").eval()?;
+    assert_eq!(result, 24);
+    server.detach(&lua);
+
+    client.join().unwrap();
+
+    Ok(())
+}
+
+#[test]
+fn test_dap_server_drop() -> Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let mut client = TcpStream::connect(listener.local_addr()?)?;
+    let (stream, _) = listener.accept()?;
+    let server = DapServer::tcp(stream)?;
+
+    // Dropping the server closes the connection and stops the reader thread
+    drop(server);
+    let mut buf = Vec::new();
+    assert_eq!(client.read_to_end(&mut buf)?, 0);
+
+    Ok(())
+}
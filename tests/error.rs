@@ -1,7 +1,7 @@
 use std::error::Error as _;
 use std::{fmt, io};
 
-use mlua::{Error, ErrorContext, Lua, Result};
+use mlua::{Error, ErrorContext, FrameKind, Function, Lua, Result};
 
 #[test]
 fn test_error_context() -> Result<()> {
@@ -77,6 +77,184 @@ fn test_error_chain() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_syntax_error_details() -> Result<()> {
+    let lua = Lua::new();
+
+    let syntax_error_details = |source: &str, name: &str| {
+        let err = lua.load(source).set_name(name).exec().unwrap_err();
+        assert!(
+            matches!(err, Error::SyntaxError { .. }),
+            "expected SyntaxError, got {err:?}"
+        );
+        err.syntax_error_details().expect("expected syntax error details")
+    };
+
+    let source = "x = 1 +* 2";
+    let details = syntax_error_details(source, "a:1: b");
+    assert_eq!(details.chunk_id(), r#"[string "a:1: b"]"#);
+    assert_eq!(details.line(), 1);
+    assert_eq!(details.column(source), Some(8));
+    assert_eq!(details.token(), Some("*"));
+
+    let source = "local a = 1\nlocal b = a +\nreturn b";
+    let details = syntax_error_details(source, "=example");
+    assert_eq!(details.chunk_id(), "example");
+    assert_eq!(details.line(), 3);
+    assert_eq!(details.column(source), Some(1));
+    assert_eq!(details.token(), Some("return"));
+    #[cfg(not(feature = "luau"))]
+    {
+        assert_eq!(details.message(), "unexpected symbol near 'return'");
+        assert_eq!(
+            details.code_frame(source),
+            "  1 | local a = 1\n  2 | local b = a +\n> 3 | return b\n    | ^^^^^^ unexpected symbol near 'return'"
+        );
+    }
+
+    // Errors at the end of the source
+    let source = "if x then\n  y = 1\n";
+    let details = syntax_error_details(source, "=eof");
+    assert_eq!(details.line(), 3);
+    assert_eq!(details.column(source), Some(1));
+    assert_eq!(details.token(), Some("<eof>"));
+
+    // Column is not known without the source
+    assert_eq!(details.column(""), None);
+
+    // Errors without location
+    let err = (lua.load(&[0x1bu8, 0x4c][..]))
+        .set_mode(mlua::ChunkMode::Text)
+        .exec()
+        .unwrap_err();
+    assert!(matches!(err, Error::SyntaxError { .. }));
+    assert_eq!(err.syntax_error_details(), None);
+    assert_eq!(Error::runtime("x:1: y").syntax_error_details(), None);
+
+    #[cfg(feature = "luau")]
+    {
+        let err = mlua::Compiler::new().compile("x = 1 +* 2").unwrap_err();
+        let details = err.syntax_error_details().expect("expected syntax error details");
+        assert_eq!(details.chunk_id(), "");
+        assert_eq!(details.line(), 1);
+        assert_eq!(details.token(), Some("*"));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_error_stack_trace() -> Result<()> {
+    let lua = Lua::new();
+
+    // Errors returned by Rust callbacks
+    let rust_fail = lua.create_function(|_, ()| Err::<(), _>(Error::runtime("rust error")))?;
+    lua.globals().set("rust_fail", rust_fail)?;
+    lua.load("function inner() rust_fail() end\nfunction outer() inner() return 1 end")
+        .set_name("@trace.lua")
+        .exec()?;
+    let outer = lua.globals().get::<Function>("outer")?;
+    let err = outer.call::<()>(()).unwrap_err();
+    let stack_trace = err.stack_trace().expect("stack trace must be present");
+    let frames = stack_trace.frames();
+    assert_eq!(frames.len(), 3);
+    assert_eq!(frames[0].kind, FrameKind::C);
+    assert_eq!(frames[0].line, None);
+    assert_eq!(frames[0].function.as_deref(), Some("rust_fail"));
+    assert_eq!(frames[1].kind, FrameKind::Lua);
+    assert_eq!(frames[1].function.as_deref(), Some("inner"));
+    assert_eq!(frames[1].source.as_deref(), Some("@trace.lua"));
+    assert_eq!(frames[1].short_src.as_deref(), Some("trace.lua"));
+    assert_eq!(frames[1].line, Some(1));
+    assert_eq!(frames[1].line_defined, Some(1));
+    assert_eq!(frames[2].line, Some(2));
+    assert!(!frames[2].is_tail_call);
+    let trace = stack_trace.to_string();
+    assert!(trace.starts_with("stack traceback:\n\t"), "{trace}");
+    assert!(trace.contains("\n\ttrace.lua:1: in function 'inner'"), "{trace}");
+    assert!(trace.contains("\n\ttrace.lua:2: in function "), "{trace}");
+    assert_eq!(err.clone().context("context").stack_trace(), Some(stack_trace));
+
+    // Errors raised by Lua code
+    let err = lua
+        .load("function lua_fail() error('lua error') end\nfunction tail() return lua_fail() end\ntail()")
+        .set_name("=main")
+        .exec()
+        .unwrap_err();
+    assert!(matches!(err, Error::RuntimeError(..)));
+    let stack_trace = err.stack_trace().expect("stack trace must be present");
+    let frames = stack_trace.frames();
+    let error = frames
+        .iter()
+        .position(|f| f.function.as_deref() == Some("error"))
+        .unwrap();
+    assert_eq!(frames[error].kind, FrameKind::C);
+    assert_eq!(frames[error + 1].line, Some(1));
+    #[cfg(any(feature = "lua54", feature = "lua53", feature = "lua52", feature = "lua51"))]
+    assert!(frames[error + 1].is_tail_call);
+    let main = frames.last().unwrap();
+    #[cfg(not(feature = "luau"))]
+    assert_eq!((main.kind, main.line), (FrameKind::Main, Some(3)));
+    #[cfg(not(feature = "luau"))]
+    assert!(stack_trace.to_string().ends_with("\n\tmain:3: in main chunk"));
+    assert_eq!(main.source.as_deref(), Some("=main"));
+    assert_eq!(main.short_src.as_deref(), Some("main"));
+    assert_eq!(stack_trace.skipped_frames(), 0);
+    assert_eq!(stack_trace.skipped_at(), None);
+
+    // Sources with special characters
+    let err = lua.load("error('x')").set_name("a:1: in b").exec().unwrap_err();
+    let frames = err.stack_trace().unwrap().frames().to_vec();
+    let main = frames.last().unwrap();
+    assert_eq!(main.short_src.as_deref(), Some(r#"[string "a:1: in b"]"#));
+    assert_eq!(main.line, Some(1));
+
+    // Messages looking like a traceback do not affect the stack trace
+    let err = lua
+        .load("error('fake\\nstack traceback:\\n\\tfake.lua:1: in main chunk')")
+        .set_name("=real")
+        .exec()
+        .unwrap_err();
+    let frames = err.stack_trace().unwrap().frames().to_vec();
+    assert!(frames.iter().all(|f| f.short_src.as_deref() != Some("fake.lua")));
+    assert_eq!(frames.last().unwrap().short_src.as_deref(), Some("real"));
+
+    // Errors raised in coroutines
+    let thread = lua.create_thread(
+        lua.load("error('in thread')")
+            .set_name("=thread")
+            .into_function()?,
+    )?;
+    let err = thread.resume::<()>(()).unwrap_err();
+    let frames = err.stack_trace().unwrap().frames().to_vec();
+    assert_eq!(frames.last().unwrap().short_src.as_deref(), Some("thread"));
+
+    // Deep stacks are truncated
+    let err = lua
+        .load("local function f(n) if n == 0 then error('deep') end f(n - 1) end f(100)")
+        .set_name("=deep")
+        .exec()
+        .unwrap_err();
+    let stack_trace = err.stack_trace().unwrap();
+    assert_eq!(stack_trace.frames().len(), 32);
+    assert!(stack_trace.skipped_frames() > 50);
+    assert_eq!(stack_trace.skipped_at(), Some(16));
+    assert!(stack_trace.to_string().contains("levels)"));
+    // The outermost frames are kept
+    #[cfg(not(feature = "luau"))]
+    assert_eq!(stack_trace.frames().last().unwrap().kind, FrameKind::Main);
+    assert_eq!(
+        stack_trace.frames().last().unwrap().short_src.as_deref(),
+        Some("deep")
+    );
+
+    // Errors without traceback
+    assert_eq!(Error::runtime("error").stack_trace(), None);
+    assert_eq!(Error::MemoryControlNotAvailable.stack_trace(), None);
+
+    Ok(())
+}
+
 #[cfg(feature = "anyhow")]
 #[test]
 fn test_error_anyhow() -> Result<()> {
@@ -95,7 +273,7 @@ fn test_error_anyhow() -> Result<()> {
     let err = err.into_lua(&lua)?;
     assert!(err.is_error());
     let err = err.as_error().unwrap();
-    assert!(matches!(err, Error::RuntimeError(msg) if msg == "runtime error"));
+    assert!(matches!(err, Error::RuntimeError(msg, _) if msg == "runtime error"));
 
     Ok(())
 }
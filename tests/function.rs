@@ -20,7 +20,7 @@ fn test_function_call_error() -> Result<()> {
         .load(r#"function(arg1, arg2) error("concat error") end"#)
         .eval::<Function>()?;
     match concat_err.call::<String>(("foo", "bar")) {
-        Err(Error::RuntimeError(msg)) if msg.contains("concat error") => {}
+        Err(Error::RuntimeError(msg, _)) if msg.contains("concat error") => {}
         other => panic!("unexpected result: {other:?}"),
     }
 
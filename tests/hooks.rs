@@ -3,7 +3,10 @@
 use std::sync::atomic::{AtomicI64, Ordering};
 use std::sync::{Arc, Mutex};
 
-use mlua::{DebugEvent, Error, HookTriggers, Lua, Result, ThreadStatus, Value, VmState};
+use mlua::{
+    Breakpoint, DebugEvent, Debugger, Error, HookTriggers, Lua, PauseReason, Result, StepAction,
+    ThreadStatus, Value, VmState,
+};
 
 #[test]
 fn test_hook_triggers() {
@@ -104,7 +107,7 @@ fn test_error_within_hook() -> Result<()> {
 
     let err = lua.load("x = 1").exec().expect_err("panic didn't propagate");
     match err {
-        Error::RuntimeError(msg) => assert_eq!(msg, "Something happened in there!"),
+        Error::RuntimeError(msg, _) => assert_eq!(msg, "Something happened in there!"),
         err => panic!("expected `RuntimeError` with a specific message, got {err:?}"),
     }
 
@@ -285,7 +288,7 @@ fn test_hook_yield() -> Result<()> {
     #[cfg(any(feature = "lua51", feature = "lua52", feature = "luajit"))]
     {
         assert!(
-            matches!(co.resume::<()>(()), Err(Error::RuntimeError(err)) if err.contains("attempt to yield from a hook"))
+            matches!(co.resume::<()>(()), Err(Error::RuntimeError(err, _)) if err.contains("attempt to yield from a hook"))
         );
         assert!(co.status() == ThreadStatus::Error);
     }
@@ -325,3 +328,188 @@ fn test_global_hook() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_debug_locals_upvalues() -> Result<()> {
+    let lua = Lua::new();
+
+    let output = Arc::new(Mutex::new(Vec::new()));
+    let hook_output = output.clone();
+    lua.set_hook(HookTriggers::EVERY_LINE, move |_lua, debug| {
+        if debug.current_line() == Some(5) {
+            let locals = debug.locals().map(|(n, v)| (n, v.as_i64())).collect::<Vec<_>>();
+            let upvalues = debug.upvalues().map(|(n, v)| (n, v.as_i64())).collect::<Vec<_>>();
+            hook_output.lock().unwrap().push((locals, upvalues));
+            debug.set_local("c", 100)?;
+            debug.set_upvalue("up", 20)?;
+            assert!(debug.set_local("missing", 0).is_err());
+            assert!(debug.set_upvalue("missing", 0).is_err());
+        }
+        Ok(VmState::Continue)
+    })?;
+
+    let (r, up): (i64, i64) = lua
+        .load(
+            r#"
+            local up = 10
+            local function f(a, b)
+                local c = a + b + up
+                return c
+            end
+            local r = f(1, 2)
+            return r, up
+        "#,
+        )
+        .eval()?;
+    lua.remove_hook();
+    assert_eq!((r, up), (100, 20));
+
+    let output = output.lock().unwrap();
+    assert_eq!(output.len(), 1);
+    let (locals, upvalues) = &output[0];
+    let locals = (locals.iter()).map(|(n, v)| (n.as_str(), *v)).collect::<Vec<_>>();
+    assert_eq!(locals, vec![("a", Some(1)), ("b", Some(2)), ("c", Some(13))]);
+    let upvalues = (upvalues.iter())
+        .map(|(n, v)| (n.as_str(), *v))
+        .collect::<Vec<_>>();
+    assert_eq!(upvalues, vec![("up", Some(10))]);
+
+    Ok(())
+}
+
+const DEBUGGER_CHUNK: &str = r#"
+local function add(a, b)
+    local s = a + b
+    return s
+end
+local x = 1
+local y = add(x, 2)
+local z = y * 2
+return z
+"#;
+
+#[test]
+fn test_debugger_breakpoints() -> Result<()> {
+    let lua = Lua::new();
+    let debugger = Debugger::new();
+
+    assert!(debugger.add_breakpoint(Breakpoint::line("@main.lua", 7)));
+    assert!(!debugger.add_breakpoint(Breakpoint::line("main.lua", 7)));
+    assert!(debugger.add_breakpoint(Breakpoint::function("add")));
+    assert!(debugger.add_breakpoint(Breakpoint::line("other.lua", 1)));
+    debugger.clear_chunk_breakpoints("other.lua");
+    assert_eq!(
+        debugger.breakpoints(),
+        vec![Breakpoint::line("main.lua", 7), Breakpoint::function("add")]
+    );
+
+    let output = Arc::new(Mutex::new(Vec::new()));
+    let hook_output = output.clone();
+    let hook_debugger = debugger.clone();
+    debugger.attach(&lua, move |_lua, debug, reason| {
+        let locals = (debug.locals()).map(|(name, _)| name).collect::<Vec<_>>();
+        // Breakpoints can be changed while paused
+        if let PauseReason::Breakpoint(bp @ Breakpoint::Function(_)) = &reason {
+            assert!(hook_debugger.remove_breakpoint(bp));
+        }
+        hook_output
+            .lock()
+            .unwrap()
+            .push((reason, debug.current_line(), locals));
+        Ok(StepAction::Continue)
+    })?;
+
+    let z: i64 = lua.load(DEBUGGER_CHUNK).set_name("main.lua").eval()?;
+    assert_eq!(z, 6);
+    // Function breakpoint was removed when hit
+    lua.load(DEBUGGER_CHUNK).set_name("main.lua").exec()?;
+    debugger.detach(&lua);
+    lua.load(DEBUGGER_CHUNK).set_name("main.lua").exec()?;
+
+    let output = output.lock().unwrap();
+    let line_bp = PauseReason::Breakpoint(Breakpoint::line("main.lua", 7));
+    let func_bp = PauseReason::Breakpoint(Breakpoint::function("add"));
+    assert_eq!(
+        *output,
+        vec![
+            (line_bp.clone(), Some(7), vec!["add".into(), "x".into()]),
+            (func_bp, Some(3), vec!["a".into(), "b".into()]),
+            (line_bp, Some(7), vec!["add".into(), "x".into()]),
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_debugger_stepping() -> Result<()> {
+    let lua = Lua::new();
+    let debugger = Debugger::new();
+    debugger.add_breakpoint(Breakpoint::line("main.lua", 7));
+
+    let output = Arc::new(Mutex::new(Vec::new()));
+    let hook_output = output.clone();
+    let mut actions = vec![
+        StepAction::StepIn,
+        StepAction::StepOver,
+        StepAction::StepOut,
+        StepAction::StepOver,
+        StepAction::Continue,
+    ]
+    .into_iter();
+    let actions = Mutex::new(move || actions.next().unwrap());
+    debugger.attach(&lua, move |_lua, debug, reason| {
+        let what = debug.source().what;
+        hook_output
+            .lock()
+            .unwrap()
+            .push((reason, debug.current_line(), what));
+        Ok((actions.lock().unwrap())())
+    })?;
+
+    let z: i64 = lua.load(DEBUGGER_CHUNK).set_name("main.lua").eval()?;
+    debugger.detach(&lua);
+    assert_eq!(z, 6);
+
+    let output = output.lock().unwrap();
+    assert_eq!(output.len(), 5);
+    let line_bp = PauseReason::Breakpoint(Breakpoint::line("main.lua", 7));
+    assert_eq!(output[0], (line_bp, Some(7), "main"));
+    assert_eq!(output[1], (PauseReason::Step, Some(3), "Lua"));
+    assert_eq!(output[2], (PauseReason::Step, Some(4), "Lua"));
+    // Stepping out returns to the caller
+    assert_eq!((&output[3].0, output[3].2), (&PauseReason::Step, "main"));
+    assert_eq!((&output[4].0, output[4].2), (&PauseReason::Step, "main"));
+
+    Ok(())
+}
+
+#[test]
+fn test_debugger_pause() -> Result<()> {
+    let lua = Lua::new();
+    let debugger = Debugger::new();
+
+    let output = Arc::new(Mutex::new(Vec::new()));
+    let hook_output = output.clone();
+    debugger.attach(&lua, move |_lua, debug, reason| {
+        hook_output.lock().unwrap().push((reason, debug.current_line()));
+        Ok(StepAction::Continue)
+    })?;
+
+    debugger.pause();
+    lua.load(DEBUGGER_CHUNK).set_name("main.lua").exec()?;
+    debugger.detach(&lua);
+
+    // Handler errors are propagated
+    debugger.attach(&lua, |_, _, _| Err(Error::runtime("terminated")))?;
+    debugger.pause();
+    let err = lua.load(DEBUGGER_CHUNK).exec().unwrap_err();
+    assert!(err.to_string().contains("terminated"));
+    debugger.detach(&lua);
+
+    let output = output.lock().unwrap();
+    assert_eq!(output.len(), 1);
+    assert_eq!(output[0].0, PauseReason::Pause);
+
+    Ok(())
+}
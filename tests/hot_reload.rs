@@ -0,0 +1,133 @@
+#![cfg(not(feature = "luau"))]
+
+use std::fs;
+use std::sync::Arc;
+
+use mlua::{Error, Lua, MemoryVfs, ModuleRegistry, Result};
+
+#[test]
+fn test_hot_reload() -> Result<()> {
+    let lua = Lua::new();
+    let vfs = Arc::new(
+        MemoryVfs::new()
+            .with_file("config.lua", "return { value = 1 }")
+            .with_file(
+                "service.lua",
+                "local config = require('config') return { get = function() return config.value end }",
+            )
+            .with_file("app/init.lua", "require('service') return { started = true }")
+            .with_file("other.lua", "return {}"),
+    );
+    lua.set_vfs(vfs.clone())?;
+    let registry = ModuleRegistry::new(&lua)?;
+
+    lua.load("require('app') require('other')").exec()?;
+    assert_eq!(registry.modules(), ["app", "config", "other", "service"]);
+    assert_eq!(registry.dependencies("service"), ["config"]);
+    assert_eq!(registry.dependents("config"), ["service"]);
+    assert_eq!(registry.dependents("service"), ["app"]);
+    assert_eq!(
+        registry.source_path("app").unwrap(),
+        std::path::Path::new("app/init.lua")
+    );
+
+    // Nothing changed
+    assert!(registry.poll()?.is_empty());
+
+    // The changed module is reloaded together with its dependents, dependencies first
+    vfs.insert(
+        "config.lua",
+        r#"
+        local M = { value = 2 }
+        function M.__reload(old)
+            previous_value = old.value
+        end
+        return M
+        "#,
+    );
+    assert_eq!(registry.poll()?, ["config", "service", "app"]);
+    assert_eq!(lua.load("return require('service').get()").eval::<i64>()?, 2);
+    assert_eq!(lua.globals().get::<i64>("previous_value")?, 1);
+    assert!(registry.poll()?.is_empty());
+
+    // Failed module keeps the previous version and is not reloaded again until changed
+    vfs.insert("config.lua", "return {");
+    match registry.poll() {
+        Err(Error::WithContext { context, cause }) => {
+            assert_eq!(context, "failed to reload module 'config'");
+            assert!(
+                cause.to_string().contains("error loading module 'config'"),
+                "{cause}"
+            );
+        }
+        res => panic!("expected error, got {res:?}"),
+    }
+    assert_eq!(lua.load("return require('service').get()").eval::<i64>()?, 2);
+    assert_eq!(registry.dependents("config"), ["service"]);
+    assert!(registry.poll()?.is_empty());
+
+    // Change notifications
+    registry.notify_changed("other");
+    assert_eq!(registry.reload_changed()?, ["other"]);
+    vfs.insert("config.lua", "return { value = 3 }");
+    assert!(registry.notify_file_changed("./config.lua"));
+    assert!(!registry.notify_file_changed("missing.lua"));
+    assert_eq!(registry.reload_changed()?, ["config", "service", "app"]);
+    assert_eq!(lua.load("return require('service').get()").eval::<i64>()?, 3);
+
+    // Explicit reloading
+    assert_eq!(registry.reload("service")?, ["service", "app"]);
+
+    Ok(())
+}
+
+#[test]
+fn test_hot_reload_files() -> Result<()> {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let path = temp_dir.path().join("greeting.lua");
+    fs::write(&path, "return { text = 'hello' }").unwrap();
+
+    let lua = Lua::new();
+    let package = lua.globals().get::<mlua::Table>("package")?;
+    package.set("path", format!("{}/?.lua", temp_dir.path().display()))?;
+    let registry = ModuleRegistry::new(&lua)?;
+
+    let text = "return require('greeting').text";
+    assert_eq!(lua.load(text).eval::<String>()?, "hello");
+    assert_eq!(
+        registry.source_path("greeting").unwrap(),
+        fs::canonicalize(&path).unwrap()
+    );
+
+    fs::write(&path, "return { text = 'bye' }").unwrap();
+    assert_eq!(registry.poll()?, ["greeting"]);
+    assert_eq!(lua.load(text).eval::<String>()?, "bye");
+
+    // Modules without source files are reloaded only on request
+    let preload = package.get::<mlua::Table>("preload")?;
+    preload.set("counter", lua.create_function(|lua, ()| lua.create_table())?)?;
+    lua.load("require('counter').n = 1").exec()?;
+    assert!(registry.source_path("counter").is_none());
+    assert!(registry.poll()?.is_empty());
+    assert_eq!(registry.reload("counter")?, ["counter"]);
+    assert!(lua
+        .load("return require('counter').n")
+        .eval::<Option<i64>>()?
+        .is_none());
+
+    Ok(())
+}
+
+#[test]
+fn test_module_registry_lua_destroyed() -> Result<()> {
+    let lua = Lua::new();
+    let registry = ModuleRegistry::new(&lua)?;
+    drop(lua);
+
+    assert!(!registry.notify_file_changed("module.lua"));
+    let err = registry.poll().unwrap_err();
+    assert!(err.to_string().contains("Lua instance is destroyed"));
+    assert!(registry.reload_changed().is_err());
+
+    Ok(())
+}
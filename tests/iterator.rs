@@ -0,0 +1,64 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use mlua::{Lua, Result, Value};
+
+// Counts drops of the iterator it is moved into
+struct DropCounter(Arc<AtomicUsize>);
+
+impl Drop for DropCounter {
+    fn drop(&mut self) {
+        self.0.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+#[test]
+fn test_iterator() -> Result<()> {
+    let lua = Lua::new();
+
+    let drops = Arc::new(AtomicUsize::new(0));
+    let drops2 = drops.clone();
+    let range = lua.create_function(move |lua, n: i64| {
+        let counter = DropCounter(drops2.clone());
+        lua.create_iterator((1..=n).map(move |i| {
+            let _ = &counter;
+            (i, i * i)
+        }))
+    })?;
+    lua.globals().set("range", range)?;
+
+    let sum = lua
+        .load("local sum = 0 for i, sq in range(4) do sum = sum + i + sq end return sum")
+        .eval::<i64>()?;
+    assert_eq!(sum, 40);
+    // Exhausted iterator is dropped immediately
+    assert_eq!(drops.load(Ordering::Relaxed), 1);
+
+    // The iterator function keeps returning nothing after the end
+    let (func, state, control) =
+        lua.load("local f, s, c = range(1) f() return f, s, c")
+            .eval::<(mlua::Function, Value, Value)>()?;
+    assert_eq!((state, control), (Value::Nil, Value::Nil));
+    assert_eq!(func.call::<mlua::MultiValue>(())?.len(), 0);
+
+    // Early exit from the loop drops the iterator in Lua 5.4
+    lua.load("for i in range(100) do if i == 2 then break end end")
+        .exec()?;
+    #[cfg(feature = "lua54")]
+    assert_eq!(drops.load(Ordering::Relaxed), 3);
+    let err = lua
+        .load("for i in range(100) do error('stop') end")
+        .exec()
+        .unwrap_err();
+    assert!(err.to_string().contains("stop"));
+    #[cfg(feature = "lua54")]
+    assert_eq!(drops.load(Ordering::Relaxed), 4);
+
+    // Abandoned iterators are dropped on garbage collection
+    lua.load("local f = range(100) f()").exec()?;
+    lua.gc_collect()?;
+    lua.gc_collect()?;
+    assert_eq!(drops.load(Ordering::Relaxed), 5);
+
+    Ok(())
+}
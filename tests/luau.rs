@@ -153,7 +153,7 @@ fn test_readonly_table() -> Result<()> {
     #[track_caller]
     fn check_readonly_error<T: Debug>(res: Result<T>) {
         match res {
-            Err(Error::RuntimeError(e)) if e.contains("attempt to modify a readonly table") => {}
+            Err(Error::RuntimeError(e, _)) if e.contains("attempt to modify a readonly table") => {}
             r => panic!("expected RuntimeError(...) with a specific message, got {r:?}"),
         }
     }
@@ -169,7 +169,7 @@ fn test_readonly_table() -> Result<()> {
 
     // Special case
     match t.set_metatable(None) {
-        Err(Error::RuntimeError(e)) if e.contains("attempt to modify a readonly table") => {}
+        Err(Error::RuntimeError(e, _)) if e.contains("attempt to modify a readonly table") => {}
         r => panic!("expected RuntimeError(...) with a specific message, got {r:?}"),
     }
 
@@ -335,7 +335,7 @@ fn test_interrupts() -> Result<()> {
     //
     lua.set_interrupt(|_| Err(Error::runtime("error from interrupt")));
     match f.call::<()>(()) {
-        Err(Error::RuntimeError(ref msg)) => assert_eq!(msg, "error from interrupt"),
+        Err(Error::RuntimeError(ref msg, _)) => assert_eq!(msg, "error from interrupt"),
         res => panic!("expected `RuntimeError` with a specific message, got {res:?}"),
     }
 
@@ -405,7 +405,7 @@ fn test_thread_events() -> Result<()> {
     let result = lua.create_thread(lua.load("return 123").into_function()?);
     assert!(result.is_err());
     assert!(
-        matches!(result, Err(Error::RuntimeError(err)) if err.contains("error when processing thread event"))
+        matches!(result, Err(Error::RuntimeError(err, _)) if err.contains("error when processing thread event"))
     );
 
     // Test context switch when running Lua script
@@ -426,7 +426,7 @@ fn test_thread_events() -> Result<()> {
         )
         .exec();
     assert!(result.is_err());
-    assert!(matches!(result, Err(Error::RuntimeError(err)) if err.contains("thread limit exceeded")));
+    assert!(matches!(result, Err(Error::RuntimeError(err, _)) if err.contains("thread limit exceeded")));
 
     Ok(())
 }
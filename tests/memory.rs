@@ -131,3 +131,111 @@ fn test_gc_error() {
         Ok(()) => panic!("__gc error did not result in error"),
     }
 }
+
+#[test]
+fn test_memory_profiling() -> Result<()> {
+    let lua = Lua::new();
+
+    match lua.memory_profile() {
+        Err(Error::RuntimeError(msg, _)) => assert!(msg.contains("not enabled")),
+        r => panic!("expected RuntimeError, got {r:?}"),
+    }
+
+    lua.set_memory_profiling(true)?;
+    let leak = lua
+        .load(
+            r#"
+            cache = {}
+            return function(n)
+                for i = 1, n do
+                    cache[#cache + 1] = { i }
+                end
+            end
+        "#,
+        )
+        .set_name("leak.lua")
+        .eval::<mlua::Function>()?;
+
+    leak.call::<()>(10)?;
+    lua.gc_collect()?;
+    let before = lua.memory_profile()?;
+    leak.call::<()>(1000)?;
+    lua.gc_collect()?;
+    let after = lua.memory_profile()?;
+    assert!(after.live_bytes() > before.live_bytes());
+
+    let diff = after.diff(&before);
+    let top = &diff[0];
+    let frame = top.frame.as_ref().expect("allocation site has no frame");
+    assert_eq!(frame.source, "leak.lua");
+    assert_eq!(frame.line, Some(5));
+    assert!(top.bytes > 0 && top.count >= 1000);
+    #[cfg(any(feature = "lua54", feature = "lua53", feature = "lua52"))]
+    assert_eq!(top.kind, "table");
+
+    let site = (after.sites().iter())
+        .find(|site| site.frame == top.frame && site.kind == top.kind)
+        .unwrap();
+    assert!(site.live_count >= 1010);
+    assert!(site.allocated_bytes >= site.live_bytes);
+
+    // Freed memory is reflected in the diff
+    lua.load("cache = nil").exec()?;
+    lua.gc_collect()?;
+    lua.gc_collect()?;
+    let freed = lua.memory_profile()?;
+    assert!(freed
+        .diff(&after)
+        .iter()
+        .any(|change| change.frame == top.frame && change.bytes < 0));
+
+    lua.set_memory_profiling(false)?;
+    assert!(lua.memory_profile().is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_memory_profiling_hooks() -> Result<()> {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let lua = Lua::new();
+    let calls = Arc::new(AtomicUsize::new(0));
+
+    // Install a user hook, which is replaced while profiling and restored afterwards
+    let calls2 = calls.clone();
+    let user_hook = move |_: &Lua| {
+        calls2.fetch_add(1, Ordering::Relaxed);
+        Ok(mlua::VmState::Continue)
+    };
+    #[cfg(not(feature = "luau"))]
+    lua.set_global_hook(mlua::HookTriggers::EVERY_LINE, move |lua, _| user_hook(lua))?;
+    #[cfg(feature = "luau")]
+    lua.set_interrupt(user_hook);
+    let run = || lua.load("local x = 0\nfor i = 1, 10 do x = x + i end").exec();
+
+    lua.set_memory_profiling(true)?;
+    run()?;
+    assert_eq!(calls.load(Ordering::Relaxed), 0);
+    lua.set_memory_profiling(false)?;
+    run()?;
+    assert!(calls.load(Ordering::Relaxed) > 0);
+
+    // A hook installed while profiling is kept
+    lua.set_memory_profiling(true)?;
+    let calls3 = calls.clone();
+    let new_hook = move |_: &Lua| {
+        calls3.store(usize::MAX, Ordering::Relaxed);
+        Ok(mlua::VmState::Continue)
+    };
+    #[cfg(not(feature = "luau"))]
+    lua.set_global_hook(mlua::HookTriggers::EVERY_LINE, move |lua, _| new_hook(lua))?;
+    #[cfg(feature = "luau")]
+    lua.set_interrupt(new_hook);
+    lua.set_memory_profiling(false)?;
+    calls.store(0, Ordering::Relaxed);
+    run()?;
+    assert_eq!(calls.load(Ordering::Relaxed), usize::MAX);
+
+    Ok(())
+}
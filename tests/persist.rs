@@ -0,0 +1,188 @@
+use mlua::{
+    AnyUserData, Error, Function, Lua, ObjectLike, PersistUserData, Persister, Result, Table, UserData,
+    UserDataMethods, Value,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Point {
+    x: i64,
+    y: i64,
+}
+
+impl UserData for Point {
+    fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+        methods.add_method("sum", |_, this, ()| Ok(this.x + this.y));
+    }
+}
+
+impl PersistUserData for Point {
+    fn persist(&self, lua: &Lua) -> Result<Value> {
+        Ok(Value::Table(lua.create_sequence_from([self.x, self.y])?))
+    }
+
+    fn unpersist(_: &Lua, value: Value) -> Result<Self> {
+        let table = value.as_table().ok_or_else(|| Error::runtime("expected table"))?;
+        Ok(Point {
+            x: table.get(1)?,
+            y: table.get(2)?,
+        })
+    }
+}
+
+#[test]
+fn test_persist_tables() -> Result<()> {
+    let lua = Lua::new();
+    lua.load(
+        r#"
+        data = { name = "data", list = {1, 2.5, true, "four"}, [10] = false }
+        data.self = data
+        data.list.parent = data
+        setmetatable(data, { __index = function(_, key) return "missing " .. key end })
+        "#,
+    )
+    .exec()?;
+
+    let persister = Persister::new();
+    let bytes = persister.persist(&lua, lua.globals().get::<Table>("data")?)?;
+
+    let lua2 = Lua::new();
+    let data: Table = persister.unpersist(&lua2, &bytes)?;
+    assert_eq!(data.get::<String>("name")?, "data");
+    assert!(!data.get::<bool>(10)?);
+    assert_eq!(data.get::<Table>("self")?, data);
+    let list: Table = data.get("list")?;
+    assert_eq!(list.get::<Table>("parent")?, data);
+    assert_eq!(list.raw_len(), 4);
+    assert_eq!(list.get::<f64>(2)?, 2.5);
+    assert_eq!(list.get::<String>(4)?, "four");
+    assert_eq!(data.get::<String>("foo")?, "missing foo");
+
+    Ok(())
+}
+
+#[cfg(not(feature = "luau"))]
+#[test]
+fn test_persist_functions() -> Result<()> {
+    let lua = Lua::new();
+    lua.load(
+        r#"
+        local count = 10
+        counter = {
+            inc = function() count = count + 1 return count end,
+            get = function() return count end,
+        }
+        function describe(x)
+            return string.format("<%s>", tostring(x))
+        end
+        "#,
+    )
+    .exec()?;
+    lua.load("counter.inc()").exec()?;
+
+    let persister = Persister::new();
+    let bytes = persister.persist(&lua, lua.globals())?;
+
+    let lua2 = Lua::new();
+    persister.unpersist::<()>(&lua2, &bytes)?;
+    assert_eq!(lua2.load("counter.inc()").eval::<i64>()?, 12);
+    #[cfg(not(any(feature = "lua51", feature = "luajit")))]
+    assert_eq!(lua2.load("counter.get()").eval::<i64>()?, 12);
+    // Standard library functions are restored by reference
+    assert_eq!(lua2.load("describe(1)").eval::<String>()?, "<1>");
+    let string_format = lua2.load("string.format").eval::<Value>()?;
+    assert_eq!(
+        lua2.globals().get::<Table>("string")?.get::<Value>("format")?,
+        string_format
+    );
+
+    // Source instance is unaffected
+    assert_eq!(lua.load("counter.get()").eval::<i64>()?, 11);
+
+    Ok(())
+}
+
+#[test]
+fn test_persist_permanents() -> Result<()> {
+    let lua = Lua::new();
+    let api = lua.create_table()?;
+    api.set("answer", lua.create_function(|_, ()| Ok(42))?)?;
+    lua.globals().set("api", api)?;
+    let config = lua.create_table()?;
+    config.set("level", 1)?;
+    lua.globals().set("config", config)?;
+    lua.load("state = { answer = api.answer, config = config, nested = { config = config } }")
+        .exec()?;
+
+    let persister = Persister::new().permanent("config");
+    let bytes = persister.persist(&lua, lua.globals().get::<Table>("state")?)?;
+
+    let lua2 = Lua::new();
+    let api = lua2.create_table()?;
+    api.set("answer", lua2.create_function(|_, ()| Ok(43))?)?;
+    lua2.globals().set("api", api)?;
+    let config = lua2.create_table()?;
+    config.set("level", 2)?;
+    lua2.globals().set("config", config.clone())?;
+
+    let state: Table = persister.unpersist(&lua2, &bytes)?;
+    assert_eq!(state.get::<Function>("answer")?.call::<i64>(())?, 43);
+    assert_eq!(state.get::<Table>("config")?, config);
+    assert_eq!(state.get::<Table>("nested")?.get::<Table>("config")?, config);
+
+    Ok(())
+}
+
+#[test]
+fn test_persist_userdata() -> Result<()> {
+    let lua = Lua::new();
+    let point = lua.create_userdata(Point { x: 1, y: 2 })?;
+    let data = lua.create_table()?;
+    data.set("a", &point)?;
+    data.set("b", &point)?;
+
+    let persister = Persister::new().register_userdata::<Point>("Point");
+    let bytes = persister.persist(&lua, (data, lua.create_userdata(Point { x: 3, y: 4 })?))?;
+
+    let lua2 = Lua::new();
+    let (data, other): (Table, AnyUserData) = persister.unpersist(&lua2, &bytes)?;
+    let a: AnyUserData = data.get("a")?;
+    assert_eq!(*a.borrow::<Point>()?, Point { x: 1, y: 2 });
+    assert_eq!(a.call_method::<i64>("sum", ())?, 3);
+    assert_eq!(data.get::<AnyUserData>("b")?, a);
+    assert_eq!(*other.borrow::<Point>()?, Point { x: 3, y: 4 });
+
+    // Unregistered type
+    match Persister::new().persist(&lua, point) {
+        Err(Error::RuntimeError(msg, _)) => assert!(msg.contains("cannot persist userdata")),
+        r => panic!("expected RuntimeError, got {r:?}"),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_persist_errors() -> Result<()> {
+    let lua = Lua::new();
+    let persister = Persister::new();
+
+    let thread = lua.create_thread(lua.load("return").into_function()?)?;
+    match persister.persist(&lua, thread) {
+        Err(Error::RuntimeError(msg, _)) => assert!(msg.contains("cannot persist value of type thread")),
+        r => panic!("expected RuntimeError, got {r:?}"),
+    }
+
+    // C function not reachable from globals
+    let func = lua.create_function(|_, ()| Ok(()))?;
+    match persister.persist(&lua, func) {
+        Err(Error::RuntimeError(msg, _)) => assert!(msg.contains("cannot persist C function")),
+        r => panic!("expected RuntimeError, got {r:?}"),
+    }
+
+    // Invalid data
+    assert!(persister.unpersist::<Value>(&lua, b"garbage").is_err());
+    let mut bytes = persister.persist(&lua, "hello")?;
+    bytes.truncate(bytes.len() - 1);
+    assert!(persister.unpersist::<Value>(&lua, &bytes).is_err());
+
+    Ok(())
+}
@@ -0,0 +1,225 @@
+#![cfg(feature = "send")]
+
+use std::time::Duration;
+
+use mlua::{
+    AnyUserData, Error, Lua, LuaOptions, LuaPool, PersistUserData, Result, StdLib, Table, UserData, Value,
+};
+
+#[test]
+fn test_pool_execute() -> Result<()> {
+    let pool = LuaPool::new(3, |lua| {
+        lua.load("function fib(n) if n < 2 then return n end return fib(n - 1) + fib(n - 2) end")
+            .exec()
+    })?;
+    assert_eq!(pool.size(), 3);
+
+    let jobs = (0..10)
+        .map(|n| pool.execute(move |lua| lua.globals().get::<mlua::Function>("fib")?.call::<i64>(n)))
+        .collect::<Vec<_>>();
+    let results = jobs
+        .into_iter()
+        .map(|job| job.join())
+        .collect::<Result<Vec<_>>>()?;
+    assert_eq!(results, [0, 1, 1, 2, 3, 5, 8, 13, 21, 34]);
+
+    // Every worker has its own state
+    let workers = pool.broadcast(|lua| {
+        lua.load("counter = (counter or 0) + 1").exec()?;
+        lua.load("return pool.worker, counter").eval::<(usize, i64)>()
+    });
+    let workers = workers
+        .into_iter()
+        .map(|job| job.join())
+        .collect::<Result<Vec<_>>>()?;
+    assert_eq!(workers, [(0, 1), (1, 1), (2, 1)]);
+
+    let worker = pool.execute_on(2, |lua| {
+        lua.load("return pool.worker, pool.size").eval::<(usize, usize)>()
+    });
+    assert_eq!(worker.join()?, (2, 3));
+
+    // Errors are returned to the caller
+    match pool.execute(|lua| lua.load("error('boom')").exec()).join() {
+        Err(Error::RuntimeError(msg, _)) => assert!(msg.contains("boom")),
+        r => panic!("expected RuntimeError, got {r:?}"),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_pool_setup_error() {
+    match LuaPool::new(2, |lua| lua.load("error('setup failed')").exec()) {
+        Err(Error::RuntimeError(msg, _)) => assert!(msg.contains("setup failed")),
+        r => panic!("expected RuntimeError, got {:?}", r.map(|_| ())),
+    }
+    assert!(LuaPool::new(0, |_| Ok(())).is_err());
+
+    // Panics in the setup function are reported as errors
+    match LuaPool::new(2, |_| panic!("setup panicked")) {
+        Err(Error::RuntimeError(msg, _)) => assert!(msg.contains("setup panicked"), "{msg}"),
+        r => panic!("expected RuntimeError, got {:?}", r.map(|_| ())),
+    }
+}
+
+#[test]
+fn test_pool_libs_and_options() -> Result<()> {
+    let pool = LuaPool::builder()
+        .size(1)
+        .libs(StdLib::MATH)
+        .options(LuaOptions::new())
+        .build()?;
+    let libs = pool.execute(|lua| {
+        lua.load("return type(math), type(string)")
+            .eval::<(String, String)>()
+    });
+    assert_eq!(libs.join()?, ("table".to_string(), "nil".to_string()));
+
+    // Unsafe libraries are rejected
+    #[cfg(not(feature = "luau"))]
+    assert!(LuaPool::builder().size(1).libs(StdLib::DEBUG).build().is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_pool_channels() -> Result<()> {
+    let pool = LuaPool::new(2, |_| Ok(()))?;
+
+    // Producer and consumer running in different states
+    let consumer = pool.execute_on(1, |lua| {
+        lua.load(
+            r#"
+            local input = pool.channel("input")
+            local sum = 0
+            while true do
+                local item = input:recv()
+                if item == nil then break end
+                sum = sum + item.value * #item.tags
+            end
+            return sum
+        "#,
+        )
+        .eval::<i64>()
+    });
+    pool.execute_on(0, |lua| {
+        lua.load(
+            r#"
+            local input = pool.channel("input")
+            for i = 1, 10 do
+                input:send({ value = i, tags = {"a", "b"} })
+            end
+            input:close()
+        "#,
+        )
+        .exec()
+    })
+    .join()?;
+    assert_eq!(consumer.join()?, 110);
+
+    // Rust side
+    let lua = Lua::new();
+    let channel = pool.channel("rust");
+    assert_eq!(channel.name(), "rust");
+    channel.send(&lua, (1, "two", lua.create_sequence_from([3, 4])?))?;
+    assert_eq!(channel.len(), 1);
+    let job = pool.execute(|lua| {
+        lua.load(
+            r#"
+            local a, b, c = pool.channel("rust"):recv()
+            pool.channel("back"):send({ a = a, b = b, c = c })
+            return a .. b .. table.concat(c, ",")
+        "#,
+        )
+        .eval::<String>()
+    });
+    assert_eq!(job.join()?, "1two3,4");
+    let back = pool.channel("back").recv::<Table>(&lua)?.unwrap();
+    assert_eq!(back.get::<i64>("a")?, 1);
+    assert_eq!(back.get::<String>("b")?, "two");
+    assert_eq!(back.get::<Vec<i64>>("c")?, [3, 4]);
+
+    assert!(channel.is_empty());
+    assert_eq!(channel.try_recv::<Value>(&lua)?, None);
+    let timeout = Duration::from_millis(10);
+    assert_eq!(channel.recv_timeout::<Value>(&lua, timeout)?, None);
+    channel.close();
+    assert!(channel.is_closed());
+    assert!(channel.send(&lua, 1).is_err());
+
+    Ok(())
+}
+
+#[derive(Debug, PartialEq)]
+struct Point(f64, f64);
+
+impl UserData for Point {}
+
+impl PersistUserData for Point {
+    fn persist(&self, lua: &Lua) -> Result<Value> {
+        Ok(Value::Table(lua.create_sequence_from([self.0, self.1])?))
+    }
+
+    fn unpersist(_: &Lua, value: Value) -> Result<Self> {
+        let table = value.as_table().ok_or_else(|| Error::runtime("expected table"))?;
+        Ok(Point(table.get(1)?, table.get(2)?))
+    }
+}
+
+#[test]
+fn test_pool_transfer() -> Result<()> {
+    let pool = LuaPool::builder().size(1).register_userdata::<Point>().build()?;
+    let lua = Lua::new();
+    let channel = pool.channel("points");
+
+    channel.send(&lua, Point(1.5, 2.5))?;
+    let point = pool
+        .execute(|lua| {
+            let point = lua.load("pool.channel('points'):recv()").eval::<AnyUserData>()?;
+            let point = point.take::<Point>()?;
+            Ok((point.0, point.1))
+        })
+        .join()?;
+    assert_eq!(point, (1.5, 2.5));
+
+    // Cycles and shared references are preserved
+    let cyclic = lua.create_table()?;
+    cyclic.set("self", &cyclic)?;
+    channel.send(&lua, lua.create_sequence_from([&cyclic, &cyclic])?)?;
+    let list = channel.recv::<Table>(&lua)?.unwrap();
+    let copy = list.get::<Table>(1)?;
+    assert_eq!(copy, list.get::<Table>(2)?);
+    assert_eq!(copy, copy.get::<Table>("self")?);
+
+    // Lua functions are sent with their upvalues
+    #[cfg(not(feature = "luau"))]
+    {
+        let func = lua
+            .load("local n = 40; return function() return n + 2 end")
+            .eval::<mlua::Function>()?;
+        channel.send(&lua, func)?;
+        let func = channel.recv::<mlua::Function>(&lua)?.unwrap();
+        assert_eq!(func.call::<i64>(())?, 42);
+    }
+
+    // Unsupported values
+    match channel.send(&lua, lua.create_function(|_, ()| Ok(()))?) {
+        Err(Error::RuntimeError(msg, _)) => assert!(msg.contains("cannot persist C function")),
+        r => panic!("expected RuntimeError, got {r:?}"),
+    }
+    struct Unregistered;
+    impl UserData for Unregistered {}
+    assert!(channel.send(&lua, Unregistered).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_pool_drop_closes_channels() -> Result<()> {
+    let pool = LuaPool::new(1, |_| Ok(()))?;
+    let waiting = pool.execute(|lua| lua.load("return pool.channel('never'):recv()").eval::<Value>());
+    drop(pool);
+    assert_eq!(waiting.join()?, Value::Nil);
+    Ok(())
+}
@@ -0,0 +1,77 @@
+use mlua::{Lua, Profiler, Result};
+
+const SOURCE: &str = r#"
+local function busy(n)
+    local x = 0
+    for i = 1, n do
+        x = x + i % 7
+    end
+    return x
+end
+
+local function work()
+    local total = 0
+    for _ = 1, 20 do
+        total = total + busy(1000)
+    end
+    return total
+end
+
+local result = work()
+return result
+"#;
+
+#[test]
+fn test_profiler() -> Result<()> {
+    let lua = Lua::new();
+    let profiler = Profiler::new().sample_interval(100);
+
+    profiler.start(&lua)?;
+    lua.load(SOURCE).set_name("busy.lua").exec()?;
+    let profile = profiler.stop(&lua);
+
+    assert!(profile.samples() > 10);
+    for (stack, count) in profile.stacks() {
+        assert!(count > 0);
+        assert_eq!(stack[0].function, "main");
+        assert_eq!(stack[0].source, "busy.lua");
+    }
+
+    // Most of the time is spent in `busy`
+    let functions = profile.functions();
+    let busy = &functions[0];
+    assert_eq!(busy.function, "busy");
+    assert_eq!(busy.source, "busy.lua");
+    assert_eq!(busy.line_defined, Some(2));
+    assert!(busy.self_samples > profile.samples() / 2);
+    let main = functions.iter().find(|f| f.function == "main").unwrap();
+    assert_eq!(main.total_samples, profile.samples());
+
+    let lines = profile.lines();
+    assert!(lines.iter().all(|l| l.source == "busy.lua"));
+    assert!((4..=6).contains(&lines[0].line));
+
+    // Folded stacks
+    let folded = profile.to_folded();
+    let mut total = 0;
+    for line in folded.lines() {
+        let (stack, count) = line.rsplit_once(' ').unwrap();
+        assert!(stack.starts_with("busy.lua:main"));
+        total += count.parse::<u64>().unwrap();
+    }
+    assert_eq!(total, profile.samples());
+    assert!(folded.contains("busy.lua:main;busy.lua:work;busy.lua:busy "));
+
+    // pprof
+    let pprof = profile.to_pprof();
+    assert_eq!(pprof[0], (1 << 3) | 2); // sample_type
+    let contains = |s: &[u8]| pprof.windows(s.len()).any(|w| w == s);
+    assert!(contains(b"busy.lua"));
+    assert!(contains(b"nanoseconds"));
+
+    // Profiler is removed after stopping
+    lua.load(SOURCE).exec()?;
+    assert_eq!(profiler.stop(&lua).samples(), 0);
+
+    Ok(())
+}
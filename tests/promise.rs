@@ -0,0 +1,246 @@
+#![cfg(feature = "async")]
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use mlua::{Error, Function, Lua, Promise, PromiseStatus, Result};
+
+fn setup() -> Result<Lua> {
+    let lua = Lua::new();
+    let sleep = lua.create_promise_function(|_, (ms, value): (u64, mlua::Value)| async move {
+        tokio::time::sleep(Duration::from_millis(ms)).await;
+        Ok(value)
+    })?;
+    lua.globals().set("sleep", sleep)?;
+    let fail = lua.create_promise_function(|_, ms: u64| async move {
+        tokio::time::sleep(Duration::from_millis(ms)).await;
+        Err::<(), _>(Error::runtime("failed"))
+    })?;
+    lua.globals().set("fail", fail)?;
+    lua.globals().set("Promise", Promise::create_library(&lua)?)?;
+    Ok(lua)
+}
+
+#[tokio::test]
+async fn test_promise_await() -> Result<()> {
+    let lua = setup()?;
+
+    // Promises started together run concurrently
+    let start = std::time::Instant::now();
+    let sum = lua
+        .load(
+            r#"
+            local a, b, c = sleep(100, 1), sleep(100, 2), sleep(100, 3)
+            assert(a:status() == "pending")
+            return a:await() + b:await() + c:await()
+            "#,
+        )
+        .eval_async::<i64>()
+        .await?;
+    assert_eq!(sum, 6);
+    assert!(start.elapsed() < Duration::from_millis(250));
+
+    // Errors are raised by `await`
+    let err = lua.load("fail(10):await()").exec_async().await.unwrap_err();
+    assert!(err.to_string().contains("failed"), "{err}");
+    // Lua 5.1 cannot yield across pcall
+    #[cfg(not(feature = "lua51"))]
+    {
+        let ok = lua
+            .load("local ok, err = pcall(function() return fail(10):await() end) return not ok and tostring(err)")
+            .eval_async::<String>()
+            .await?;
+        assert!(ok.contains("failed"), "{ok}");
+    }
+
+    // Promises created in Lua
+    let value = lua
+        .load(
+            r#"
+            local resolve_later
+            local p = Promise.new(function(resolve) resolve_later = resolve end)
+            sleep(10):next(function() resolve_later("later") end)
+            return p:await()
+            "#,
+        )
+        .eval_async::<String>()
+        .await?;
+    assert_eq!(value, "later");
+    let err = lua
+        .load("Promise.new(function() error('executor error') end):await()")
+        .exec_async()
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("executor error"), "{err}");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_promise_chaining() -> Result<()> {
+    let lua = setup()?;
+
+    let value = lua
+        .load(
+            r#"
+            return sleep(10, 1)
+                :next(function(x) return x + 1 end)
+                :next(function(x) return sleep(10, x * 10) end)
+                :next(function(x) return sleep(10, x):await() + 1 end)
+                :await()
+            "#,
+        )
+        .eval_async::<i64>()
+        .await?;
+    assert_eq!(value, 21);
+
+    let value = lua
+        .load(
+            r#"
+            local p = fail(10)
+            return p["then"](p, function() return "unreachable" end)
+                :catch(function(err) return "caught: " .. tostring(err) end)
+                :await()
+            "#,
+        )
+        .eval_async::<String>()
+        .await?;
+    assert!(
+        value.starts_with("caught: ") && value.contains("failed"),
+        "{value}"
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_promise_combinators() -> Result<()> {
+    let lua = setup()?;
+
+    let (a, b) = lua
+        .load("local t = Promise.all({sleep(20, 'a'), sleep(10, 'b')}):await() return t[1], t[2]")
+        .eval_async::<(String, String)>()
+        .await?;
+    assert_eq!((a.as_str(), b.as_str()), ("a", "b"));
+    let err = lua
+        .load("Promise.all({sleep(100, 1), fail(10)}):await()")
+        .exec_async()
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("failed"), "{err}");
+
+    let first = lua
+        .load("return Promise.race({sleep(100, 'slow'), sleep(10, 'fast')}):await()")
+        .eval_async::<String>()
+        .await?;
+    assert_eq!(first, "fast");
+
+    let first = lua
+        .load("return Promise.any({fail(10), sleep(30, 'ok')}):await()")
+        .eval_async::<String>()
+        .await?;
+    assert_eq!(first, "ok");
+    let err = lua
+        .load("Promise.any({fail(10), Promise.reject('no')}):await()")
+        .exec_async()
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("all promises were rejected"), "{err}");
+
+    let value = lua
+        .load("return Promise.resolve(Promise.resolve(5)):await()")
+        .eval_async::<i64>()
+        .await?;
+    assert_eq!(value, 5);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_promise_rust_side() -> Result<()> {
+    let lua = setup()?;
+
+    // Lua promises as Rust futures
+    let promise = lua
+        .load("return Promise.new(function(resolve) sleep(10):next(function() resolve(1, 2) end) end)")
+        .eval::<Promise>()?;
+    assert_eq!(promise.status(), PromiseStatus::Pending);
+    assert_eq!(promise.clone().value::<(i64, i64)>().await?, (1, 2));
+    assert_eq!(promise.status(), PromiseStatus::Fulfilled);
+
+    // Rust futures as Lua promises
+    let promise = lua.create_promise(async { Ok("from rust") });
+    let func: Function = lua.load("return function(p) return p:await() end").eval()?;
+    assert_eq!(func.call_async::<String>(promise).await?, "from rust");
+
+    // Cancellation
+    let promise = lua.load("return sleep(1000, 1)").eval::<Promise>()?;
+    let waiter = lua
+        .load("return function(p) return p:await() end")
+        .eval::<Function>()?;
+    let fut = waiter.call_async::<i64>(promise.clone());
+    tokio::pin!(fut);
+    assert!(tokio::time::timeout(Duration::from_millis(10), &mut fut)
+        .await
+        .is_err());
+    assert!(promise.cancel());
+    assert!(!promise.cancel());
+    assert_eq!(promise.status(), PromiseStatus::Cancelled);
+    let err = fut.await.unwrap_err();
+    assert!(err.to_string().contains("cancelled"), "{err}");
+    let cancelled = lua
+        .load("local p = sleep(1000) p:cancel() return p:status()")
+        .eval::<String>()?;
+    assert_eq!(cancelled, "cancelled");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_promise_dropped() -> Result<()> {
+    struct DropFlag(Arc<AtomicBool>);
+
+    impl Drop for DropFlag {
+        fn drop(&mut self) {
+            self.0.store(true, Ordering::Relaxed);
+        }
+    }
+
+    let lua = setup()?;
+
+    // A promise without owners is removed from the pool and its future is dropped
+    let dropped = Arc::new(AtomicBool::new(false));
+    let flag = DropFlag(dropped.clone());
+    let promise = lua.create_promise(async move {
+        let _flag = flag;
+        tokio::time::sleep(Duration::from_millis(1000)).await;
+        Ok(())
+    });
+    drop(promise);
+    assert!(dropped.load(Ordering::Relaxed));
+
+    // Including promises created and discarded by Lua
+    let dropped = Arc::new(AtomicBool::new(false));
+    let flag = Arc::new(std::sync::Mutex::new(Some(DropFlag(dropped.clone()))));
+    let forget = lua.create_promise_function(move |_, ()| {
+        let flag = flag.lock().unwrap().take();
+        async move {
+            let _flag = flag;
+            tokio::time::sleep(Duration::from_millis(1000)).await;
+            Ok(())
+        }
+    })?;
+    lua.globals().set("forget", forget)?;
+    lua.load("forget() collectgarbage() collectgarbage()").exec()?;
+    assert!(dropped.load(Ordering::Relaxed));
+
+    // Other pending promises are still driven
+    let value = lua
+        .load("return sleep(10, 1):await()")
+        .eval_async::<i64>()
+        .await?;
+    assert_eq!(value, 1);
+
+    Ok(())
+}
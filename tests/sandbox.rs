@@ -0,0 +1,184 @@
+use mlua::{Error, Function, Lua, Result, SandboxPolicy, Table, Value};
+
+#[test]
+fn test_sandbox_policy_allow_deny() -> Result<()> {
+    let lua = Lua::new();
+    let policy = SandboxPolicy::new()
+        .allow("print")
+        .allow("string")
+        .allow("os.time")
+        .allow("_G")
+        .deny("string.dump");
+    let env = policy.create_environment(&lua)?;
+
+    assert!(env.get::<Function>("print").is_ok());
+    assert_eq!(env.get::<Value>("pairs")?, Value::Nil);
+    assert_eq!(env.get::<Table>("_G")?, env);
+    let os = env.get::<Table>("os")?;
+    assert!(os.get::<Function>("time").is_ok());
+    assert_eq!(os.get::<Value>("execute")?, Value::Nil);
+    let string = env.get::<Table>("string")?;
+    assert!(string.get::<Function>("upper").is_ok());
+    assert_eq!(string.get::<Value>("dump")?, Value::Nil);
+
+    // Host environment is not affected
+    let globals = lua.globals();
+    assert!(globals.get::<Table>("string")?.get::<Function>("dump").is_ok());
+    assert!(globals.get::<Table>("os")?.get::<Function>("execute").is_ok());
+
+    // Changes in the sandbox do not leak to the host
+    lua.load("string.upper = nil; x = 1")
+        .set_environment(env)
+        .exec()?;
+    assert!(globals.get::<Table>("string")?.get::<Function>("upper").is_ok());
+    assert_eq!(globals.get::<Value>("x")?, Value::Nil);
+
+    // Unknown paths are ignored
+    let env = SandboxPolicy::new()
+        .allow("nonexistent.path")
+        .create_environment(&lua)?;
+    assert_eq!(env.get::<Value>("nonexistent")?, Value::Nil);
+
+    Ok(())
+}
+
+#[test]
+fn test_sandbox_policy_freeze() -> Result<()> {
+    let lua = Lua::new();
+    let policy = SandboxPolicy::new().allow("string").allow("pairs").freeze(true);
+
+    let env = policy.create_environment(&lua)?;
+    match lua.load("string.upper = nil").set_environment(env).exec() {
+        Err(Error::RuntimeError(msg, _)) => assert!(msg.contains("read")),
+        r => panic!("expected RuntimeError, got {r:?}"),
+    }
+
+    let env = policy.create_environment(&lua)?;
+    let (upper, count) = lua
+        .load(
+            r#"
+            local count = 0
+            for _ in pairs(string) do count = count + 1 end
+            return string.upper("abc"), count
+        "#,
+        )
+        .set_environment(env)
+        .eval::<(String, i64)>()?;
+    assert_eq!(upper, "ABC");
+    #[cfg(any(feature = "lua54", feature = "lua53", feature = "lua52", feature = "luau"))]
+    assert!(count > 0);
+    #[cfg(not(any(feature = "lua54", feature = "lua53", feature = "lua52", feature = "luau")))]
+    let _ = count;
+
+    // Top-level environment is still writable
+    let env = policy.create_environment(&lua)?;
+    lua.load("x = 1").set_environment(env.clone()).exec()?;
+    assert_eq!(env.get::<i64>("x")?, 1);
+
+    Ok(())
+}
+
+#[test]
+fn test_sandbox_policy_load() -> Result<()> {
+    let lua = Lua::new();
+    let policy = SandboxPolicy::new().allow("load").allow("tostring");
+    let env = policy.create_environment(&lua)?;
+
+    // Chunks loaded in the sandbox use the sandbox environment
+    let (tostring_ok, os) = lua
+        .load(r#"return load("return tostring ~= nil, os")()"#)
+        .set_environment(env.clone())
+        .eval::<(bool, Value)>()?;
+    assert!(tostring_ok);
+    assert_eq!(os, Value::Nil);
+
+    // Syntax errors are returned as messages
+    let (func, msg) = lua
+        .load(r#"return load("return +")"#)
+        .set_environment(env.clone())
+        .eval::<(Value, String)>()?;
+    assert_eq!(func, Value::Nil);
+    assert!(!msg.is_empty());
+
+    // Binary chunks are rejected by default
+    #[cfg(not(feature = "luau"))]
+    {
+        let dump = lua.load("return 1").into_function()?.dump(false);
+        env.set("bytecode", lua.create_string(&dump)?)?;
+        let (func, _) = lua
+            .load("return load(bytecode)")
+            .set_environment(env.clone())
+            .eval::<(Value, Value)>()?;
+        assert_eq!(func, Value::Nil);
+
+        let env = policy.binary_chunks(true).create_environment(&lua)?;
+        env.set("bytecode", lua.create_string(&dump)?)?;
+        let result = lua
+            .load("return load(bytecode)()")
+            .set_environment(env)
+            .eval::<i64>()?;
+        assert_eq!(result, 1);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_sandbox_policy_build() -> Result<()> {
+    let policy = SandboxPolicy::new()
+        .allow("string")
+        .deny("string.dump")
+        .allow("tostring")
+        .freeze(true)
+        .globals(|lua, env| {
+            env.set(
+                "greet",
+                lua.create_function(|_, name: String| Ok(format!("hello, {name}")))?,
+            )
+        });
+    let lua = policy.build()?;
+
+    assert_eq!(lua.load(r#"return ("abc"):upper()"#).eval::<String>()?, "ABC");
+    assert_eq!(lua.load(r#"return greet("lua")"#).eval::<String>()?, "hello, lua");
+    assert_eq!(lua.load(r#"return ("abc").dump"#).eval::<Value>()?, Value::Nil);
+    assert!(lua.load(r#"return (""):dump()"#).exec().is_err());
+    assert_eq!(
+        lua.load("return os, require, getmetatable")
+            .eval::<(Value, Value, Value)>()?,
+        (Value::Nil, Value::Nil, Value::Nil)
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_sandbox_policy_build_string_metatable() -> Result<()> {
+    let policy = SandboxPolicy::new()
+        .allow("string")
+        .allow("getmetatable")
+        .allow("rawset")
+        .deny("string.dump")
+        .freeze(true);
+    let lua = policy.build()?;
+
+    // The string metatable is protected and refers to the frozen sandboxed library
+    assert_eq!(
+        lua.load(r#"return getmetatable("")"#).eval::<Value>()?,
+        Value::Boolean(false)
+    );
+    assert!(lua.load(r#"string.upper = nil"#).exec().is_err());
+    assert_eq!(lua.load(r#"return ("abc"):upper()"#).eval::<String>()?, "ABC");
+    assert_eq!(lua.load(r#"return ("").dump"#).eval::<Value>()?, Value::Nil);
+
+    // Without freezing the metatable is still the sandboxed one
+    let lua = SandboxPolicy::new()
+        .allow("string")
+        .allow("getmetatable")
+        .deny("string.dump")
+        .build()?;
+    let index = lua.load(r#"return getmetatable("").__index"#).eval::<Table>()?;
+    assert_eq!(index, lua.globals().get::<Table>("string")?);
+    assert_eq!(index.get::<Value>("dump")?, Value::Nil);
+
+    Ok(())
+}
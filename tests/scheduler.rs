@@ -0,0 +1,215 @@
+use std::time::Duration;
+
+use mlua::{Error, Function, Lua, ManualClock, Result, Scheduler};
+
+fn setup() -> Result<(Lua, Scheduler, ManualClock)> {
+    let lua = Lua::new();
+    let clock = ManualClock::new();
+    let scheduler = Scheduler::with_clock(clock.clone());
+    lua.globals().set("task", scheduler.create_library(&lua)?)?;
+    lua.globals().set("log", lua.create_table()?)?;
+    Ok((lua, scheduler, clock))
+}
+
+fn log(lua: &Lua) -> Result<Vec<String>> {
+    let log = lua.globals().get::<Vec<String>>("log")?;
+    lua.globals().set("log", lua.create_table()?)?;
+    Ok(log)
+}
+
+#[test]
+fn test_scheduler_wait() -> Result<()> {
+    let (lua, scheduler, clock) = setup()?;
+
+    let func: Function = lua
+        .load(
+            r#"
+            function(name, delay)
+                table.insert(log, name .. " start")
+                local elapsed = task.wait(delay)
+                table.insert(log, name .. " " .. string.format("%.1f", elapsed))
+                task.wait()
+                table.insert(log, name .. " end")
+            end
+        "#,
+        )
+        .eval()?;
+
+    let a = scheduler.spawn(func.clone(), ("a", 2))?;
+    let b = scheduler.spawn(func, ("b", 1))?;
+    assert_ne!(a, b);
+    assert_eq!(log(&lua)?, ["a start", "b start"]);
+    assert_eq!(scheduler.len(), 2);
+    assert_eq!(scheduler.next_wakeup(), Some(Duration::from_secs(1)));
+
+    assert!(scheduler.tick().is_empty());
+    assert!(log(&lua)?.is_empty());
+
+    clock.advance(Duration::from_millis(1500));
+    scheduler.tick();
+    assert_eq!(log(&lua)?, ["b 1.5"]);
+    assert_eq!(scheduler.next_wakeup(), Some(Duration::from_millis(1500)));
+
+    clock.advance(Duration::from_millis(500));
+    scheduler.tick();
+    assert_eq!(log(&lua)?, ["b end", "a 2.0"]);
+    assert!(scheduler.contains(a));
+    assert!(!scheduler.contains(b));
+
+    scheduler.tick();
+    assert_eq!(log(&lua)?, ["a end"]);
+    assert!(scheduler.is_empty());
+    assert_eq!(scheduler.next_wakeup(), None);
+
+    Ok(())
+}
+
+#[test]
+fn test_scheduler_wait_limits() -> Result<()> {
+    let (lua, scheduler, clock) = setup()?;
+    clock.set(Duration::from_secs(10_000));
+
+    // Delays that cannot be represented never wake up
+    let forever = [
+        "task.wait(math.huge)",
+        "task.wait(1e300)",
+        "task.wait(18446744073709549568)",
+    ]
+    .into_iter()
+    .map(|code| scheduler.spawn(lua.load(code).into_function()?, ()))
+    .collect::<Result<Vec<_>>>()?;
+    assert!(scheduler.tick().is_empty());
+    assert_eq!(scheduler.next_wakeup(), None);
+    clock.advance(Duration::from_secs(u32::MAX as u64));
+    assert!(scheduler.tick().is_empty());
+    for id in forever {
+        assert!(scheduler.contains(id));
+        assert!(scheduler.cancel(id));
+    }
+
+    // Negative and NaN delays are errors
+    for code in ["task.wait(-1)", "task.wait(0/0)", "task.wait('x')"] {
+        let id = scheduler.spawn(lua.load(code).into_function()?, ())?;
+        let errors = scheduler.tick();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].0, id);
+        let msg = errors[0].1.to_string();
+        assert!(msg.contains("non-negative number expected"), "{msg}");
+    }
+    assert!(scheduler.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn test_scheduler_signals() -> Result<()> {
+    let (lua, scheduler, _) = setup()?;
+
+    lua.load(
+        r#"
+        for i = 1, 3 do
+            task.spawn(function()
+                local a, b = task.wait_signal("ping")
+                table.insert(log, i .. ":" .. a .. b)
+                local n = task.signal("pong", i)
+                table.insert(log, "woken " .. n)
+            end)
+        end
+        task.defer(function()
+            local i = task.wait_signal("pong")
+            table.insert(log, "pong " .. i)
+        end)
+        "#,
+    )
+    .exec()?;
+    assert_eq!(scheduler.len(), 4);
+    assert_eq!(scheduler.signal("unknown", ())?, 0);
+
+    // Start the deferred task
+    scheduler.tick();
+    assert!(log(&lua)?.is_empty());
+
+    assert_eq!(scheduler.signal("ping", ("x", "y"))?, 3);
+    assert!(log(&lua)?.is_empty());
+    scheduler.tick();
+    assert_eq!(
+        log(&lua)?,
+        ["1:xy", "woken 1", "2:xy", "woken 0", "3:xy", "woken 0"]
+    );
+    scheduler.tick();
+    assert_eq!(log(&lua)?, ["pong 1"]);
+    assert!(scheduler.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn test_scheduler_errors_and_cancel() -> Result<()> {
+    let (lua, scheduler, _) = setup()?;
+
+    let failing = lua.load("task.wait() error('boom')").into_function()?;
+    let id = scheduler.spawn(failing, ())?;
+    let errors = scheduler.tick();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].0, id);
+    assert!(matches!(&errors[0].1, Error::RuntimeError(msg, _) if msg.contains("boom")));
+    assert!(!scheduler.contains(id));
+
+    let looping = lua
+        .load("while true do table.insert(log, 'tick') task.wait() end")
+        .into_function()?;
+    let id = scheduler.spawn(looping, ())?;
+    scheduler.tick();
+    assert_eq!(log(&lua)?, ["tick", "tick"]);
+    assert!(scheduler.cancel(id));
+    assert!(!scheduler.cancel(id));
+    scheduler.tick();
+    assert!(log(&lua)?.is_empty());
+
+    // Plain yields continue on the next tick
+    let yielding = lua
+        .load("coroutine.yield(1) table.insert(log, 'resumed')")
+        .into_function()?;
+    scheduler.spawn(yielding, ())?;
+    scheduler.tick();
+    assert_eq!(log(&lua)?, ["resumed"]);
+
+    Ok(())
+}
+
+#[cfg(any(feature = "lua54", feature = "lua53", feature = "luau"))]
+#[test]
+fn test_scheduler_budget() -> Result<()> {
+    use mlua::ExecutionBudget;
+
+    let (lua, scheduler, _) = setup()?;
+    scheduler.set_budget(Some(ExecutionBudget::instructions(1000)));
+
+    let func: Function = lua
+        .load(
+            r#"
+            function(name)
+                for i = 1, 2 do
+                    local x = 0
+                    for j = 1, 10000 do x = x + j end
+                    table.insert(log, name .. i)
+                end
+            end
+        "#,
+        )
+        .eval()?;
+    scheduler.spawn(func.clone(), "a")?;
+    scheduler.spawn(func, "b")?;
+
+    let mut ticks = 0;
+    while !scheduler.is_empty() {
+        let errors = scheduler.tick();
+        assert!(errors.is_empty(), "{errors:?}");
+        ticks += 1;
+    }
+    assert!(ticks > 10);
+    // Tasks are interleaved
+    assert_eq!(log(&lua)?, ["a1", "b1", "a2", "b2"]);
+
+    Ok(())
+}
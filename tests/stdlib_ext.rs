@@ -0,0 +1,173 @@
+#![cfg(feature = "stdlib-ext")]
+
+use std::path::Path;
+use std::sync::Arc;
+
+use mlua::stdlib_ext::{IoLib, OsLib};
+use mlua::{Error, Lua, MemoryVfs, Result, Table, Value, Vfs};
+
+#[test]
+fn test_io_lib() -> Result<()> {
+    let lua = Lua::new();
+    let vfs = Arc::new(MemoryVfs::new().with_file("input.txt", "1 2 3\nlast"));
+    IoLib::new().vfs(vfs.clone()).writable(true).install(&lua)?;
+
+    lua.load(
+        r#"
+        local f = assert(io.open("input.txt"))
+        assert(io.type(f) == "file")
+        local a, b, c = f:read("n", "n", "n")
+        assert(a + b + c == 6)
+        assert(f:read("l") == "")
+        assert(f:read("a") == "last")
+        f:close()
+
+        -- Default input and output files
+        assert(io.input("input.txt") ~= nil)
+        assert(io.read() == "1 2 3")
+        for line in io.lines() do
+            assert(line == "last")
+        end
+        local out = io.output("output.txt")
+        io.write("a", 1, "\n")
+        io.output():write("b")
+        io.close()
+        assert(io.type(out) == "closed file")
+
+        local count = 0
+        for line in io.lines("output.txt") do
+            count = count + 1
+        end
+        assert(count == 2)
+
+        -- Standard streams cannot be closed or seeked
+        local ok, err = io.stdout:close()
+        assert(ok == nil and err == "cannot close standard file")
+        assert(io.stdout:seek() == nil)
+        assert(io.stdin == nil)
+    "#,
+    )
+    .exec()?;
+    assert_eq!(vfs.read(Path::new("output.txt"))?, b"a1\nb");
+    assert!(
+        lua.globals()
+            .get::<Table>("package")?
+            .get::<Table>("loaded")?
+            .get::<Table>("io")?
+            == lua.globals().get::<Table>("io")?
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_io_lib_capabilities() -> Result<()> {
+    let lua = Lua::new();
+    lua.set_vfs(MemoryVfs::new().with_file("data.txt", "data"))?;
+
+    // Filesystem set by `Lua::set_vfs` is used by default and writing is not allowed
+    IoLib::new().stdout(false).install(&lua)?;
+    let data = lua
+        .load("return io.open('data.txt'):read('a')")
+        .eval::<String>()?;
+    assert_eq!(data, "data");
+    let (file, err) = lua
+        .load("return io.open('data.txt', 'a')")
+        .eval::<(Value, String)>()?;
+    assert_eq!(file, Value::Nil);
+    assert!(err.contains("Permission denied"), "{err}");
+    assert_eq!(lua.load("return io.stdout").eval::<Value>()?, Value::Nil);
+    assert!(lua.load("io.read()").exec().is_err());
+    match lua.load("io.write('hello')").exec() {
+        Err(Error::RuntimeError(msg, _)) => assert!(msg.contains("default output file is not available")),
+        r => panic!("expected RuntimeError, got {r:?}"),
+    }
+
+    // Without any filesystem nothing can be opened
+    let lua = Lua::new();
+    let table = IoLib::new().writable(true).create(&lua)?;
+    lua.globals().set("io2", table)?;
+    let (file, err) = lua
+        .load("return io2.open('data.txt')")
+        .eval::<(Value, String)>()?;
+    assert_eq!(file, Value::Nil);
+    assert!(err.contains("data.txt"), "{err}");
+    let (file, _) = lua
+        .load("return io2.open('new.txt', 'w')")
+        .eval::<(Value, String)>()?;
+    assert_eq!(file, Value::Nil);
+
+    Ok(())
+}
+
+#[test]
+fn test_os_lib() -> Result<()> {
+    let lua = Lua::new();
+    std::env::set_var("MLUA_TEST_ALLOWED", "yes");
+    std::env::set_var("MLUA_TEST_DENIED", "no");
+    OsLib::new().allow_env("MLUA_TEST_ALLOWED").install(&lua)?;
+
+    let (allowed, denied) = lua
+        .load("return os.getenv('MLUA_TEST_ALLOWED'), os.getenv('MLUA_TEST_DENIED')")
+        .eval::<(Option<String>, Option<String>)>()?;
+    assert_eq!(allowed.as_deref(), Some("yes"));
+    assert_eq!(denied, None);
+
+    assert_eq!(lua.load("return os.execute").eval::<Value>()?, Value::Nil);
+    assert!(lua.load("return os.clock() >= 0").eval::<bool>()?);
+    assert!(lua.load("return os.time() > 1600000000").eval::<bool>()?);
+    assert_eq!(lua.load("return os.difftime(10, 4)").eval::<f64>()?, 6.0);
+
+    // 2024-02-29 13:05:09 UTC
+    let time = 1709211909;
+    lua.globals().set("t", time)?;
+    let date = lua
+        .load("return os.date('!%Y-%m-%d %H:%M:%S', t)")
+        .eval::<String>()?;
+    assert_eq!(date, "2024-02-29 13:05:09");
+    let date = lua
+        .load("return os.date('%a %A %b %B %j %I %p %y %e %%', t)")
+        .eval::<String>()?;
+    assert_eq!(date, "Thu Thursday Feb February 060 01 PM 24 29 %");
+    assert_eq!(
+        lua.load("return os.date('%c', 0)").eval::<String>()?,
+        "Thu Jan  1 00:00:00 1970"
+    );
+
+    let table = lua.load("return os.date('*t', t)").eval::<Table>()?;
+    assert_eq!(table.get::<i64>("year")?, 2024);
+    assert_eq!(table.get::<i64>("month")?, 2);
+    assert_eq!(table.get::<i64>("day")?, 29);
+    assert_eq!(table.get::<i64>("wday")?, 5);
+    assert_eq!(table.get::<i64>("yday")?, 60);
+    assert!(!table.get::<bool>("isdst")?);
+    assert_eq!(lua.load("return os.time(os.date('*t', t))").eval::<i64>()?, time);
+
+    // Normalization of out of range values and default hour
+    let time = lua
+        .load("return os.time({ year = 2023, month = 14, day = 29, hour = 13, min = 5, sec = 9 })")
+        .eval::<i64>()?;
+    assert_eq!(time, 1709211909);
+    let time = lua
+        .load("return os.time({ year = 1970, month = 1, day = 1 })")
+        .eval::<i64>()?;
+    assert_eq!(time, 12 * 3600);
+    assert_eq!(
+        lua.load("return os.date('!%Y-%m-%d', -86400)").eval::<String>()?,
+        "1969-12-31"
+    );
+
+    assert!(lua.load("os.time({ year = 2000 })").exec().is_err());
+    for year in ["2^39", "-2^39", "2^31 + 1900"] {
+        let chunk = format!("os.time({{ year = {year}, month = 1, day = 1 }})");
+        let err = lua.load(chunk).exec().unwrap_err().to_string();
+        assert!(err.contains("field 'year' is out-of-bound"), "{err}");
+    }
+    let err = (lua.load("os.time({ year = 2000, month = 1, day = 2^40 })").exec())
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("field 'day' is out-of-bound"), "{err}");
+    assert!(lua.load("os.date('%Q')").exec().is_err());
+
+    Ok(())
+}
@@ -154,7 +154,7 @@ fn test_table_clear() -> Result<()> {
         t.set_readonly(true);
         assert!(matches!(
             t.clear(),
-            Err(Error::RuntimeError(err)) if err.contains("attempt to modify a readonly table")
+            Err(Error::RuntimeError(err, _)) if err.contains("attempt to modify a readonly table")
         ));
         t.set_readonly(false);
     }
@@ -470,7 +470,7 @@ fn test_table_object_like() -> Result<()> {
     assert_eq!(table.to_string()?, "table object");
 
     match table.call_method::<()>("non_existent", ()) {
-        Err(Error::RuntimeError(err)) => {
+        Err(Error::RuntimeError(err, _)) => {
             assert!(err.contains("attempt to call a nil value (function 'non_existent')"))
         }
         r => panic!("expected RuntimeError, got {r:?}"),
@@ -478,7 +478,7 @@ fn test_table_object_like() -> Result<()> {
 
     // Test calling non-callable table
     let table2 = lua.create_table()?;
-    assert!(matches!(table2.call::<()>(()), Err(Error::RuntimeError(_))));
+    assert!(matches!(table2.call::<()>(()), Err(Error::RuntimeError(..))));
 
     Ok(())
 }
@@ -61,7 +61,7 @@ fn test_safety() -> Result<()> {
         Ok(_) => panic!("expected CallbackError, got no error"),
     };
     match lua.load(r#"require "fake_ffi""#).exec() {
-        Err(Error::RuntimeError(msg)) => assert!(msg.contains("can't load C modules in safe mode")),
+        Err(Error::RuntimeError(msg, _)) => assert!(msg.contains("can't load C modules in safe mode")),
         Err(e) => panic!("expected RuntimeError, got {:?}", e),
         Ok(_) => panic!("expected RuntimeError, got no error"),
     }
@@ -163,7 +163,7 @@ fn test_replace_globals() -> Result<()> {
     {
         lua.sandbox(true)?;
         match lua.set_globals(globals) {
-            Err(Error::RuntimeError(msg))
+            Err(Error::RuntimeError(msg, _))
                 if msg.contains("cannot change globals in a sandboxed Lua state") => {}
             r => panic!("expected RuntimeError(...) with a specific error message, got {r:?}"),
         }
@@ -343,7 +343,7 @@ fn test_error() -> Result<()> {
 
     let lua_error = globals.get::<Function>("lua_error")?;
     match lua_error.call::<()>(()) {
-        Err(Error::RuntimeError(_)) => {}
+        Err(Error::RuntimeError(..)) => {}
         Err(e) => panic!("error is not RuntimeError kind, got {:?}", e),
         _ => panic!("error not returned"),
     }
@@ -475,7 +475,7 @@ fn test_panic() -> Result<()> {
         .exec()
     }) {
         Ok(Ok(_)) => panic!("no error was detected"),
-        Ok(Err(Error::RuntimeError(_))) => {}
+        Ok(Err(Error::RuntimeError(..))) => {}
         Ok(Err(e)) => panic!("expected RuntimeError, got {:?}", e),
         Err(_) => panic!("panic was detected"),
     }
@@ -1459,7 +1459,7 @@ fn test_warnings() -> Result<()> {
     lua.set_warning_function(|_, _, _| Err(Error::runtime("warning error")));
     assert!(matches!(
         lua.load(r#"warn("test")"#).exec(),
-        Err(Error::RuntimeError(ref err)) if err == "warning error"
+        Err(Error::RuntimeError(ref err, _)) if err == "warning error"
     ));
 
     // Recursive warning
@@ -1553,7 +1553,7 @@ fn test_exec_raw() -> Result<()> {
             ffi::lua_error(state);
         })
     };
-    assert!(matches!(res, Err(Error::RuntimeError(err)) if err.contains("test error")));
+    assert!(matches!(res, Err(Error::RuntimeError(err, _)) if err.contains("test error")));
 
     Ok(())
 }
@@ -0,0 +1,150 @@
+use mlua::{
+    AnyUserData, CloneAcrossStates, Error, Function, Lua, Result, Table, UserData, UserDataMethods, Value,
+};
+
+#[test]
+fn test_transfer_tables() -> Result<()> {
+    let lua = Lua::new();
+    let other = Lua::new();
+
+    let value: Table = lua
+        .load(
+            r#"
+            local shared = { 1, 2, 3 }
+            local t = { name = "root", a = shared, b = shared, [true] = 1.5 }
+            t.self = t
+            return setmetatable(t, { __index = function(_, k) return "default " .. k end })
+        "#,
+        )
+        .eval()?;
+
+    let copy: Table = other.unpack(lua.transfer(&other, &value)?)?;
+    assert_eq!(copy.get::<String>("name")?, "root");
+    assert_eq!(copy.get::<f64>(true)?, 1.5);
+    assert_eq!(copy.get::<Table>("self")?, copy);
+    assert_eq!(copy.get::<Table>("a")?, copy.get::<Table>("b")?);
+    assert_eq!(copy.get::<Vec<i64>>("a")?, [1, 2, 3]);
+    assert_eq!(copy.get::<String>("foo")?, "default foo");
+
+    // Copies are independent
+    copy.set("name", "copy")?;
+    assert_eq!(value.get::<String>("name")?, "root");
+
+    // Primitive values
+    let value = Value::String(lua.create_string("hello")?).deep_copy_into(&other)?;
+    assert_eq!(value.as_string().unwrap(), "hello");
+    assert_eq!(Value::Integer(5).deep_copy_into(&other)?, Value::Integer(5));
+
+    Ok(())
+}
+
+#[test]
+fn test_transfer_functions() -> Result<()> {
+    let lua = Lua::new();
+    let other = Lua::new();
+    other.globals().set("suffix", "!")?;
+
+    // C functions from the standard library
+    let format: Function = lua.load("string.format").eval()?;
+    let format: Function = other.unpack(Value::Function(format).deep_copy_into(&other)?)?;
+    assert_eq!(format.call::<String>(("%d-%d", 1, 2))?, "1-2");
+
+    // Rust functions cannot be copied
+    let func = lua.create_function(|_, ()| Ok(()))?;
+    match lua.transfer(&other, func) {
+        Err(Error::RuntimeError(msg, _)) => assert!(msg.contains("cannot copy C function")),
+        r => panic!("expected RuntimeError, got {r:?}"),
+    }
+
+    #[cfg(not(feature = "luau"))]
+    {
+        let counter: Table = lua
+            .load(
+                r#"
+                local count = 0
+                return {
+                    inc = function() count = count + 1 return count .. suffix end,
+                    get = function() return count end,
+                }
+            "#,
+            )
+            .eval()?;
+        lua.globals().set("suffix", "?")?;
+        counter.get::<Function>("inc")?.call::<()>(())?;
+
+        let copy: Table = other.unpack(lua.transfer(&other, &counter)?)?;
+        // Globals refer to the target state
+        assert_eq!(copy.get::<Function>("inc")?.call::<String>(())?, "2!");
+        #[cfg(any(feature = "lua54", feature = "lua53", feature = "lua52"))]
+        assert_eq!(copy.get::<Function>("get")?.call::<i64>(())?, 2);
+        assert_eq!(counter.get::<Function>("get")?.call::<i64>(())?, 1);
+    }
+
+    #[cfg(feature = "luau")]
+    {
+        let func: Function = lua.load("function() end").eval()?;
+        assert!(lua.transfer(&other, func).is_err());
+    }
+
+    Ok(())
+}
+
+#[derive(Clone)]
+struct Counter(i64);
+
+impl UserData for Counter {
+    fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+        methods.add_method("get", |_, this, ()| Ok(this.0));
+    }
+}
+
+impl CloneAcrossStates for Counter {
+    fn clone_across_states(&self, _: &Lua) -> Result<Self> {
+        Ok(self.clone())
+    }
+}
+
+#[test]
+fn test_transfer_userdata() -> Result<()> {
+    let lua = Lua::new();
+    let other = Lua::new();
+
+    let ud = lua.create_userdata(Counter(7))?;
+    let list = lua.create_sequence_from([&ud, &ud])?;
+    match lua.transfer(&other, &list) {
+        Err(Error::RuntimeError(msg, _)) => assert!(msg.contains("unregistered type")),
+        r => panic!("expected RuntimeError, got {r:?}"),
+    }
+
+    lua.register_transferable::<Counter>();
+    let copy: Table = other.unpack(lua.transfer(&other, &list)?)?;
+    let (a, b): (AnyUserData, AnyUserData) = (copy.get(1)?, copy.get(2)?);
+    assert_eq!(a, b);
+    assert_eq!(a.borrow::<Counter>()?.0, 7);
+    other.globals().set("counter", a)?;
+    assert_eq!(other.load("counter:get()").eval::<i64>()?, 7);
+
+    // Threads cannot be copied
+    let thread = lua.create_thread(lua.load("return").into_function()?)?;
+    assert!(lua.transfer(&other, thread).is_err());
+
+    Ok(())
+}
+
+#[cfg(feature = "luau")]
+#[test]
+fn test_transfer_luau_values() -> Result<()> {
+    let lua = Lua::new();
+    let other = Lua::new();
+
+    let value: Table = lua
+        .load("{ v = vector.create(1, 2, 3), b = buffer.fromstring('abc') }")
+        .eval()?;
+    value.set_readonly(true);
+    let copy: Table = other.unpack(Value::Table(value).deep_copy_into(&other)?)?;
+    assert!(copy.is_readonly());
+    assert_eq!(copy.get::<mlua::Vector>("v")?, mlua::Vector::new(1.0, 2.0, 3.0));
+    assert_eq!(copy.get::<mlua::Buffer>("b")?.to_vec(), b"abc");
+
+    Ok(())
+}
@@ -0,0 +1,207 @@
+use std::collections::HashMap;
+
+use mlua::{
+    AnyUserData, Lua, LuaOptions, MetaMethod, Result, StdLib, TypeInfo, UserData, UserDataFields,
+    UserDataMethods, UserDataRef, Variadic,
+};
+
+struct Point {
+    x: f64,
+    y: f64,
+}
+
+impl UserData for Point {
+    fn add_fields<F: UserDataFields<Self>>(fields: &mut F) {
+        fields.add_field_method_get("x", |_, this| Ok(this.x));
+        fields.add_field_method_set("x", |_, this, x| {
+            this.x = x;
+            Ok(())
+        });
+        fields.add_field_method_get("y", |_, this| Ok(this.y));
+    }
+
+    fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+        methods.add_method(
+            "len",
+            |_, this, ()| Ok((this.x * this.x + this.y * this.y).sqrt()),
+        );
+        methods.add_method_mut("scale", |_, this, k: f64| {
+            this.x *= k;
+            this.y *= k;
+            Ok(())
+        });
+        methods.add_function("new", |_, (x, y): (f64, f64)| Ok(Point { x, y }));
+        methods.add_meta_function(
+            MetaMethod::Add,
+            |_, (a, b): (UserDataRef<Point>, UserDataRef<Point>)| {
+                Ok(Point {
+                    x: a.x + b.x,
+                    y: a.y + b.y,
+                })
+            },
+        );
+    }
+}
+
+fn new_lua() -> Result<Lua> {
+    Lua::new_with(StdLib::ALL_SAFE, LuaOptions::new().type_definitions(true))
+}
+
+#[test]
+fn test_type_info() {
+    assert_eq!(<i32 as mlua::FromLua>::type_info(), TypeInfo::Integer);
+    assert_eq!(
+        <Option<String> as mlua::IntoLua>::type_info(),
+        TypeInfo::Optional(Box::new(TypeInfo::String))
+    );
+    assert_eq!(
+        <HashMap<String, Vec<f64>> as mlua::FromLua>::type_info(),
+        TypeInfo::Map(
+            Box::new(TypeInfo::String),
+            Box::new(TypeInfo::Array(Box::new(TypeInfo::Number)))
+        )
+    );
+    assert_eq!(
+        <(bool, Variadic<String>) as mlua::FromLuaMulti>::type_info_multi(),
+        vec![TypeInfo::Boolean, TypeInfo::Variadic(Box::new(TypeInfo::String))]
+    );
+    assert_eq!(<() as mlua::IntoLuaMulti>::type_info_multi(), vec![]);
+    assert_eq!(
+        <Point as mlua::IntoLua>::type_info(),
+        TypeInfo::Named("Point".into())
+    );
+    assert_eq!(<AnyUserData as mlua::FromLua>::type_info(), TypeInfo::UserData);
+}
+
+#[test]
+fn test_type_definitions_disabled() -> Result<()> {
+    let lua = Lua::new();
+    lua.globals().set("f", lua.create_function(|_, ()| Ok(()))?)?;
+    assert!(lua.generate_type_definitions().is_err());
+    Ok(())
+}
+
+#[test]
+fn test_type_definitions() -> Result<()> {
+    let lua = new_lua()?;
+    let globals = lua.globals();
+
+    globals.set("origin", Point { x: 0., y: 0. })?;
+    globals.set(
+        "add",
+        lua.create_function(|_, (a, b): (i64, Option<i64>)| Ok(a + b.unwrap_or(0)))?,
+    )?;
+    globals.set(
+        "join",
+        lua.create_function(|_, parts: Variadic<String>| Ok(parts.concat()))?,
+    )?;
+
+    let utils = lua.create_table()?;
+    utils.set(
+        "greet",
+        lua.create_function(|_, name: String| Ok(format!("hello {name}")))?,
+    )?;
+    let nested = lua.create_table()?;
+    nested.set("flag", lua.create_function(|_, ()| Ok(true))?)?;
+    utils.set("nested", nested)?;
+    // Functions without recorded signature are skipped
+    utils.set("lua_fn", lua.load("function() end").eval::<mlua::Function>()?)?;
+    globals.set("utils", utils)?;
+
+    let module = lua.create_table()?;
+    module.set("version", lua.create_function(|_, ()| Ok("1.0"))?)?;
+    #[cfg(not(feature = "luau"))]
+    lua.register_module("mymod", module)?;
+    #[cfg(feature = "luau")]
+    lua.register_module("@mymod", module)?;
+
+    let defs = lua.generate_type_definitions()?;
+
+    #[cfg(feature = "luau")]
+    assert_eq!(
+        defs,
+        r#"declare class Point
+	x: number
+	y: number
+	function len(self): number
+	function scale(self, arg1: number): ()
+	new: (arg1: number, arg2: number) -> Point
+	function __add(self, arg1: Point): Point
+end
+
+declare function add(arg1: number, arg2: number?): number
+
+declare function join(...: string): string
+
+declare origin: Point
+
+declare utils: {
+	greet: (arg1: string) -> string,
+	nested: {
+		flag: () -> boolean,
+	},
+}
+
+-- module "@mymod"
+type mymod = {
+	version: () -> string,
+}
+"#
+    );
+
+    #[cfg(not(feature = "luau"))]
+    assert_eq!(
+        defs,
+        r#"---@meta
+
+---@class Point
+---@field x number
+---@field y number
+---@operator add(Point): Point
+local Point = {}
+
+---@return number
+function Point:len() end
+
+---@param arg1 number
+function Point:scale(arg1) end
+
+---@param arg1 number
+---@param arg2 number
+---@return Point
+function Point.new(arg1, arg2) end
+
+---@param arg1 integer
+---@param arg2? integer
+---@return integer
+function add(arg1, arg2) end
+
+---@param ... string
+---@return string
+function join(...) end
+
+---@type Point
+origin = nil
+
+utils = {}
+
+---@param arg1 string
+---@return string
+function utils.greet(arg1) end
+
+utils.nested = {}
+
+---@return boolean
+function utils.nested.flag() end
+
+-- module "mymod"
+---@class mymod
+local mymod = {}
+
+---@return string
+function mymod.version() end
+"#
+    );
+
+    Ok(())
+}
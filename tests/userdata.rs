@@ -789,7 +789,7 @@ fn test_userdata_object_like() -> Result<()> {
     assert_eq!(ud.get::<u32>("n")?, 321);
     assert_eq!(ud.get::<Option<u32>>("non-existent")?, None);
     match ud.set("non-existent", 123) {
-        Err(Error::RuntimeError(_)) => {}
+        Err(Error::RuntimeError(..)) => {}
         r => panic!("expected RuntimeError, got {r:?}"),
     }
 
@@ -799,7 +799,7 @@ fn test_userdata_object_like() -> Result<()> {
     assert_eq!(ud.get::<u32>("n")?, 323);
 
     match ud.call_method::<()>("non_existent", ()) {
-        Err(Error::RuntimeError(err)) => {
+        Err(Error::RuntimeError(err, _)) => {
             assert!(err.contains("attempt to call a nil value (function 'non_existent')"))
         }
         r => panic!("expected RuntimeError, got {r:?}"),
@@ -868,7 +868,7 @@ fn test_userdata_derive() -> Result<()> {
 
     // Simple struct
 
-    #[derive(Clone, Copy, mlua::FromLua)]
+    #[derive(Clone, Copy, mlua::FromLua, mlua::IntoLua)]
     struct MyUserData(i32);
 
     lua.register_userdata_type::<MyUserData>(|reg| {
@@ -878,6 +878,14 @@ fn test_userdata_derive() -> Result<()> {
     lua.globals().set("ud", AnyUserData::wrap(MyUserData(123)))?;
     lua.load("assert(ud:val() == 123)").exec()?;
 
+    // Round trip through the derived `IntoLua`
+    lua.globals().set("ud", MyUserData(234))?;
+    lua.load("assert(type(ud) == 'userdata' and ud:val() == 234)")
+        .exec()?;
+    assert_eq!(lua.globals().get::<MyUserData>("ud")?.0, 234);
+    let double = lua.create_function(|_, ud: MyUserData| Ok(MyUserData(ud.0 * 2)))?;
+    assert_eq!(double.call::<MyUserData>(MyUserData(21))?.0, 42);
+
     // More complex struct where generics and where clause
 
     #[derive(Clone, Copy, mlua::FromLua)]
@@ -895,6 +903,152 @@ fn test_userdata_derive() -> Result<()> {
     Ok(())
 }
 
+#[cfg(feature = "macros")]
+#[test]
+fn test_userdata_derive_methods() -> Result<()> {
+    #[derive(Clone, mlua::FromLua, mlua::UserData)]
+    struct Vec2 {
+        pub x: i64,
+        #[lua(rename = "y_coord")]
+        pub y: i64,
+        #[lua(readonly)]
+        pub id: u32,
+        #[lua(skip)]
+        pub hidden: i64,
+        #[allow(unused)]
+        private: i64,
+    }
+
+    #[mlua::methods]
+    impl Vec2 {
+        pub fn sum(&self) -> i64 {
+            self.x + self.y
+        }
+
+        pub fn shift(&mut self, dx: i64, dy: i64) {
+            self.x += dx;
+            self.y += dy;
+        }
+
+        pub fn checked_div(&self, lua: &Lua, by: i64) -> Result<i64> {
+            let _ = lua.globals();
+            if by == 0 {
+                return Err(Error::runtime("division by zero"));
+            }
+            Ok(self.x / by)
+        }
+
+        pub fn origin() -> Vec2 {
+            Vec2 {
+                x: 0,
+                y: 0,
+                id: 0,
+                hidden: 0,
+                private: 0,
+            }
+        }
+
+        #[lua(rename = "hidden_value")]
+        pub fn get_hidden(&self) -> i64 {
+            self.hidden
+        }
+
+        #[lua(meta = "__add")]
+        pub fn add(&self, other: Vec2) -> Vec2 {
+            Vec2 {
+                x: self.x + other.x,
+                y: self.y + other.y,
+                ..self.clone()
+            }
+        }
+
+        #[lua(skip)]
+        #[allow(unused)]
+        pub fn skipped(&self) {}
+
+        #[allow(unused)]
+        fn private_method(&self) {}
+    }
+
+    // Type without methods impl
+    #[derive(mlua::UserData)]
+    struct Plain {
+        pub value: StdString,
+    }
+
+    let lua = Lua::new();
+    let v = Vec2 {
+        x: 1,
+        y: 2,
+        id: 7,
+        hidden: 42,
+        private: 0,
+    };
+    lua.globals().set("v", v)?;
+    lua.globals().set(
+        "plain",
+        Plain {
+            value: "hello".into(),
+        },
+    )?;
+    lua.load(
+        r#"
+        assert(v.x == 1 and v.y_coord == 2 and v.id == 7)
+        assert(v.y == nil and v.hidden == nil and v.private == nil)
+        assert(v:sum() == 3)
+        v:shift(9, 1)
+        assert(v.x == 10 and v.y_coord == 3)
+        v.x = 20
+        assert(v:checked_div(2) == 10)
+        local ok, err = pcall(v.checked_div, v, 0)
+        assert(not ok and tostring(err):find("division by zero"))
+        ok, err = pcall(function() v.id = 1 end)
+        assert(not ok)
+        assert(v:hidden_value() == 42)
+        assert(v.skipped == nil and v.private_method == nil)
+        assert(v.origin().x == 0)
+        local w = v + v
+        assert(w.x == 40 and w.y_coord == 6)
+        assert(plain.value == "hello")
+        plain.value = "world"
+        assert(plain.value == "world")
+    "#,
+    )
+    .exec()?;
+
+    Ok(())
+}
+
+#[cfg(all(feature = "macros", feature = "async"))]
+#[tokio::test]
+async fn test_userdata_derive_async_methods() -> Result<()> {
+    #[derive(mlua::UserData)]
+    struct Counter {
+        pub value: i64,
+    }
+
+    #[mlua::methods]
+    impl Counter {
+        pub async fn get(&self) -> i64 {
+            self.value
+        }
+
+        pub async fn incr(&mut self, lua: Lua, by: i64) -> Result<i64> {
+            lua.globals().set("last_incr", by)?;
+            self.value += by;
+            Ok(self.value)
+        }
+    }
+
+    let lua = Lua::new();
+    lua.globals().set("c", Counter { value: 1 })?;
+    lua.load("assert(c:incr(4) == 5); assert(c:get() == 5); assert(last_incr == 4)")
+        .exec_async()
+        .await?;
+
+    Ok(())
+}
+
 #[test]
 fn test_nested_userdata_gc() -> Result<()> {
     let lua = Lua::new();
@@ -0,0 +1,411 @@
+use std::path::Path;
+
+use mlua::{ArchiveVfs, DirVfs, Lua, MemoryVfs, Result, Vfs};
+
+#[cfg(not(feature = "luau"))]
+use {
+    mlua::{Error, Value},
+    std::sync::Arc,
+};
+
+#[test]
+fn test_memory_vfs() {
+    let vfs = MemoryVfs::new().with_file("/dir/a.lua", "return 1");
+    assert!(vfs.is_file(Path::new("dir/a.lua")));
+    assert!(vfs.is_file(Path::new("./dir/../dir/a.lua")));
+    assert!(vfs.is_dir(Path::new("/dir")));
+    assert!(vfs.is_dir(Path::new("/")));
+    assert!(!vfs.is_dir(Path::new("dir/a.lua")));
+    assert_eq!(vfs.read(Path::new("../../dir/a.lua")).unwrap(), b"return 1");
+    assert!(vfs.read(Path::new("b.lua")).is_err());
+
+    vfs.write(Path::new("b.lua"), b"return 2").unwrap();
+    assert_eq!(vfs.remove("b.lua").unwrap(), b"return 2");
+    assert!(vfs.write(Path::new("dir"), b"").is_err());
+
+    let vfs = vfs.read_only(true);
+    assert!(vfs.write(Path::new("b.lua"), b"").is_err());
+}
+
+#[test]
+fn test_dir_vfs() -> Result<()> {
+    let root = tempfile::tempdir()?;
+    std::fs::create_dir(root.path().join("scripts"))?;
+    std::fs::write(root.path().join("scripts/main.lua"), "return 'main'")?;
+    std::fs::write(
+        root.path().parent().unwrap().join("mlua_vfs_secret.txt"),
+        "secret",
+    )?;
+
+    let vfs = DirVfs::new(root.path())?;
+    assert!(vfs.is_dir(Path::new("/scripts")));
+    assert!(vfs.is_file(Path::new("scripts/main.lua")));
+    assert_eq!(vfs.read(Path::new("/scripts/main.lua"))?, b"return 'main'");
+    // Paths cannot escape the root
+    assert!(vfs.read(Path::new("../mlua_vfs_secret.txt")).is_err());
+
+    #[cfg(unix)]
+    {
+        let secret = root.path().parent().unwrap().join("mlua_vfs_secret.txt");
+        std::os::unix::fs::symlink(&secret, root.path().join("link.txt"))?;
+        assert!(vfs.read(Path::new("link.txt")).is_err());
+        assert!(vfs.write(Path::new("link.txt"), b"").is_err());
+    }
+
+    vfs.write(Path::new("scripts/new.lua"), b"return 'new'")?;
+    assert_eq!(
+        std::fs::read(root.path().join("scripts/new.lua"))?,
+        b"return 'new'"
+    );
+
+    std::fs::remove_file(root.path().parent().unwrap().join("mlua_vfs_secret.txt"))?;
+    Ok(())
+}
+
+fn tar_entry(name: &str, kind: u8, data: &[u8]) -> Vec<u8> {
+    let mut header = [0u8; 512];
+    header[..name.len()].copy_from_slice(name.as_bytes());
+    header[100..107].copy_from_slice(b"0000644");
+    header[124..135].copy_from_slice(format!("{:011o}", data.len()).as_bytes());
+    header[156] = kind;
+    header[257..263].copy_from_slice(b"ustar\0");
+    header[148..156].copy_from_slice(b"        ");
+    let checksum = header.iter().map(|&b| b as u32).sum::<u32>();
+    header[148..155].copy_from_slice(format!("{checksum:06o}\0").as_bytes());
+
+    let mut entry = header.to_vec();
+    entry.extend_from_slice(data);
+    entry.resize(512 + data.len().div_ceil(512) * 512, 0);
+    entry
+}
+
+#[test]
+fn test_archive_vfs() -> Result<()> {
+    let long_name = format!("{}/long.lua", "x".repeat(120));
+    let mut archive = Vec::new();
+    archive.extend(tar_entry("lib/", b'5', b""));
+    archive.extend(tar_entry("lib/util.lua", b'0', b"return 'util'"));
+    archive.extend(tar_entry(
+        "././@LongLink",
+        b'L',
+        format!("{long_name}\0").as_bytes(),
+    ));
+    archive.extend(tar_entry("truncated", b'0', b"return 'long'"));
+    archive.extend(tar_entry("link", b'2', b""));
+    archive.extend([0; 1024]);
+
+    let vfs = ArchiveVfs::from_tar(&archive)?;
+    assert!(vfs.is_dir(Path::new("/")));
+    assert!(vfs.is_dir(Path::new("lib")));
+    assert!(vfs.is_dir(Path::new(&"x".repeat(120))));
+    assert_eq!(vfs.read(Path::new("/lib/util.lua"))?, b"return 'util'");
+    assert_eq!(vfs.read(Path::new(&long_name))?, b"return 'long'");
+    assert!(!vfs.is_file(Path::new("link")));
+    assert!(vfs.write(Path::new("lib/util.lua"), b"").is_err());
+
+    // Truncated archive
+    assert!(ArchiveVfs::from_tar(&archive[..600]).is_err());
+
+    Ok(())
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &b in data {
+        crc ^= b as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                0xEDB88320 ^ (crc >> 1)
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+struct ZipEntry<'a> {
+    name: &'a str,
+    method: u16,
+    flags: u16,
+    mode: u32,
+    compressed: &'a [u8],
+    contents: &'a [u8],
+}
+
+impl<'a> ZipEntry<'a> {
+    fn new(name: &'a str, method: u16, compressed: &'a [u8], contents: &'a [u8]) -> Self {
+        let mode = if name.ends_with('/') { 0o040755 } else { 0o100644 };
+        ZipEntry {
+            name,
+            method,
+            flags: 0,
+            mode,
+            compressed,
+            contents,
+        }
+    }
+}
+
+fn zip_archive(entries: &[ZipEntry]) -> Vec<u8> {
+    let (mut archive, mut central) = (Vec::new(), Vec::new());
+    for entry in entries {
+        let offset = archive.len() as u32;
+        let mut common = Vec::new();
+        common.extend(20u16.to_le_bytes()); // version needed
+        common.extend(entry.flags.to_le_bytes());
+        common.extend(entry.method.to_le_bytes());
+        common.extend([0; 4]); // modification time
+        common.extend(crc32(entry.contents).to_le_bytes());
+        common.extend((entry.compressed.len() as u32).to_le_bytes());
+        common.extend((entry.contents.len() as u32).to_le_bytes());
+        common.extend((entry.name.len() as u16).to_le_bytes());
+        common.extend(0u16.to_le_bytes()); // extra field length
+
+        archive.extend(0x04034b50u32.to_le_bytes());
+        archive.extend(&common);
+        archive.extend(entry.name.as_bytes());
+        archive.extend(entry.compressed);
+
+        central.extend(0x02014b50u32.to_le_bytes());
+        central.extend(0x031eu16.to_le_bytes()); // made by Unix
+        central.extend(&common);
+        central.extend([0; 6]); // comment length, disk number, internal attributes
+        central.extend((entry.mode << 16).to_le_bytes());
+        central.extend(offset.to_le_bytes());
+        central.extend(entry.name.as_bytes());
+    }
+    let central_offset = archive.len() as u32;
+    archive.extend(&central);
+    archive.extend(0x06054b50u32.to_le_bytes());
+    archive.extend([0; 4]); // disk numbers
+    archive.extend((entries.len() as u16).to_le_bytes());
+    archive.extend((entries.len() as u16).to_le_bytes());
+    archive.extend((central.len() as u32).to_le_bytes());
+    archive.extend(central_offset.to_le_bytes());
+    archive.extend(7u16.to_le_bytes());
+    archive.extend(b"comment");
+    archive
+}
+
+#[test]
+fn test_archive_vfs_zip() -> Result<()> {
+    // Raw deflate streams with a fixed Huffman block, a dynamic Huffman block and a stored block
+    let fixed = [43, 74, 45, 41, 45, 202, 83, 80, 79, 203, 172, 72, 77, 81, 7, 0];
+    let dynamic = [
+        69, 205, 43, 18, 192, 32, 16, 4, 209, 171, 172, 91, 19, 193, 132, 124, 200, 129, 16, 136, 68, 80,
+        160, 82, 220, 29, 145, 154, 137, 123, 170, 187, 230, 214, 235, 99, 175, 121, 105, 249, 14, 190, 124,
+        0, 177, 18, 145, 216, 136, 157, 56, 136, 147, 72, 196, 165, 224, 159, 86, 27, 138, 67, 117, 40, 15,
+        245, 161, 1, 116, 64, 114, 27, 19,
+    ];
+    let stored = [
+        1, 15, 0, 240, 255, 114, 101, 116, 117, 114, 110, 32, 39, 115, 116, 111, 114, 101, 100, 39,
+    ];
+    let items = (0..19).map(|i| format!("'item{i}'")).collect::<Vec<_>>();
+    let items = format!("return {{ {} }}", items.join(", "));
+
+    let archive = zip_archive(&[
+        ZipEntry::new("lib/", 0, b"", b""),
+        ZipEntry::new("lib/util.lua", 0, b"return 'util'", b"return 'util'"),
+        ZipEntry::new("fixed.lua", 8, &fixed, b"return 'fixed'"),
+        ZipEntry::new("nested/dir/dynamic.lua", 8, &dynamic, items.as_bytes()),
+        ZipEntry::new("stored.lua", 8, &stored, b"return 'stored'"),
+        ZipEntry {
+            mode: 0o120777,
+            ..ZipEntry::new("link", 0, b"lib/util.lua", b"lib/util.lua")
+        },
+    ]);
+
+    let vfs = ArchiveVfs::from_zip(&archive)?;
+    assert!(vfs.is_dir(Path::new("/")));
+    assert!(vfs.is_dir(Path::new("lib")));
+    assert!(vfs.is_dir(Path::new("nested/dir")));
+    assert_eq!(vfs.read(Path::new("/lib/util.lua"))?, b"return 'util'");
+    assert_eq!(vfs.read(Path::new("fixed.lua"))?, b"return 'fixed'");
+    assert_eq!(vfs.read(Path::new("nested/dir/dynamic.lua"))?, items.as_bytes());
+    assert_eq!(vfs.read(Path::new("stored.lua"))?, b"return 'stored'");
+    assert!(!vfs.is_file(Path::new("link")));
+    assert!(vfs.write(Path::new("fixed.lua"), b"").is_err());
+
+    // Truncated archive and corrupted data
+    assert!(ArchiveVfs::from_zip(&archive[..archive.len() - 40]).is_err());
+    let corrupted = zip_archive(&[ZipEntry::new("fixed.lua", 8, &fixed, b"return 'fixes'")]);
+    assert_eq!(
+        ArchiveVfs::from_zip(&corrupted).unwrap_err().kind(),
+        std::io::ErrorKind::InvalidData
+    );
+    let truncated = zip_archive(&[ZipEntry::new("dynamic.lua", 8, &dynamic[..40], items.as_bytes())]);
+    assert!(ArchiveVfs::from_zip(&truncated).is_err());
+
+    // Unsupported features
+    let bzip2 = zip_archive(&[ZipEntry::new("a.lua", 12, b"", b"")]);
+    assert_eq!(
+        ArchiveVfs::from_zip(&bzip2).unwrap_err().kind(),
+        std::io::ErrorKind::Unsupported
+    );
+    let encrypted = zip_archive(&[ZipEntry {
+        flags: 1,
+        ..ZipEntry::new("a.lua", 0, b"", b"")
+    }]);
+    assert_eq!(
+        ArchiveVfs::from_zip(&encrypted).unwrap_err().kind(),
+        std::io::ErrorKind::Unsupported
+    );
+
+    Ok(())
+}
+
+#[cfg(not(feature = "luau"))]
+#[test]
+fn test_vfs_load() -> Result<()> {
+    let lua = Lua::new();
+    let vfs = MemoryVfs::new()
+        .with_file("main.lua", "#!/usr/bin/env lua\nreturn ..., select('#', ...)")
+        .with_file("broken.lua", "return +");
+    lua.set_vfs(vfs)?;
+
+    let (value, n) = lua.load("return dofile('main.lua')").eval::<(Value, i64)>()?;
+    assert_eq!((value, n), (Value::Nil, 0));
+    let (value, n) = lua
+        .load("return loadfile('/main.lua')(1, 2)")
+        .eval::<(i64, i64)>()?;
+    assert_eq!((value, n), (1, 2));
+
+    let (func, msg) = lua
+        .load("return loadfile('broken.lua')")
+        .eval::<(Value, String)>()?;
+    assert_eq!(func, Value::Nil);
+    assert!(msg.contains("broken.lua:1:"), "{msg}");
+    let (func, msg) = lua
+        .load("return loadfile('missing.lua')")
+        .eval::<(Value, String)>()?;
+    assert_eq!(func, Value::Nil);
+    assert!(msg.contains("cannot open missing.lua"), "{msg}");
+
+    match lua.load("dofile('broken.lua')").exec() {
+        Err(Error::RuntimeError(msg, _)) => assert!(msg.contains("broken.lua:1:")),
+        r => panic!("expected RuntimeError, got {r:?}"),
+    }
+
+    Ok(())
+}
+
+#[cfg(not(feature = "luau"))]
+#[test]
+fn test_vfs_require() -> Result<()> {
+    let lua = Lua::new();
+    let vfs = MemoryVfs::new()
+        .with_file(
+            "app/init.lua",
+            "return { name = 'app', util = require('app.util') }",
+        )
+        .with_file("app/util.lua", "return { name = ..., file = select(2, ...) }");
+    lua.set_vfs(vfs)?;
+
+    let (name, util, file) = lua
+        .load("local app = require('app') return app.name, app.util.name, app.util.file")
+        .eval::<(String, String, Option<String>)>()?;
+    assert_eq!(name, "app");
+    assert_eq!(util, "app.util");
+    #[cfg(any(feature = "lua54", feature = "lua53", feature = "lua52"))]
+    assert_eq!(file.as_deref(), Some("./app/util.lua"));
+    #[cfg(any(feature = "lua51", feature = "luajit"))]
+    assert_eq!(file, None);
+
+    match lua.load("require('missing')").exec() {
+        Err(Error::RuntimeError(msg, _)) => {
+            assert!(msg.contains("no file './missing.lua'"), "{msg}");
+            assert!(!msg.contains(".so"), "{msg}");
+        }
+        r => panic!("expected RuntimeError, got {r:?}"),
+    }
+
+    // Preloaded modules still work
+    lua.load("package.preload.virtual = function() return 'preloaded' end")
+        .exec()?;
+    assert_eq!(
+        lua.load("return require('virtual')").eval::<String>()?,
+        "preloaded"
+    );
+
+    Ok(())
+}
+
+#[cfg(not(feature = "luau"))]
+#[test]
+fn test_vfs_io() -> Result<()> {
+    let lua = Lua::new();
+    let vfs = Arc::new(MemoryVfs::new().with_file("data.txt", "first line\nsecond line\n42 0x10 -1.5\n"));
+    lua.set_vfs(vfs.clone())?;
+
+    lua.load(
+        r#"
+        local f = assert(io.open("data.txt"))
+        assert(io.type(f) == "file")
+        assert(f:read() == "first line")
+        assert(f:read("L") == "second line\n")
+        local a, b, c = f:read("n", "*n", "n")
+        assert(a == 42 and b == 16 and c == -1.5)
+        assert(f:read("a") == "\n")
+        assert(f:read("l") == nil)
+        assert(f:seek("set", 6) == 6)
+        assert(f:read(4) == "line")
+        assert(f:seek("end") == 36)
+        f:close()
+        assert(io.type(f) == "closed file")
+        assert(tostring(f) == "file (closed)")
+        assert(not pcall(f.read, f))
+        assert(io.type(42) == nil)
+
+        local lines = {}
+        for line in io.lines("data.txt") do
+            lines[#lines + 1] = line
+        end
+        assert(#lines == 3 and lines[2] == "second line")
+
+        local f, err = io.open("missing.txt")
+        assert(f == nil and err:find("missing.txt"))
+        assert(not pcall(io.lines, "missing.txt"))
+        assert(not pcall(io.open, "data.txt", "rw"))
+
+        local f = assert(io.open("out.txt", "w"))
+        assert(f:write("hello", " ", 1) == f)
+        assert(f:read() == nil)
+        f:close()
+        local f = assert(io.open("out.txt", "a"))
+        f:write("!")
+        f:close()
+    "#,
+    )
+    .exec()?;
+    assert_eq!(vfs.read(Path::new("out.txt"))?, b"hello 1!");
+
+    // Read-only filesystem
+    let lua = Lua::new();
+    lua.set_vfs(MemoryVfs::new().read_only(true))?;
+    let (file, err) = lua
+        .load("return io.open('out.txt', 'w')")
+        .eval::<(Value, String)>()?;
+    assert_eq!(file, Value::Nil);
+    assert!(err.contains("Read-only"), "{err}");
+
+    Ok(())
+}
+
+#[cfg(feature = "luau")]
+#[test]
+fn test_vfs_luau_require() -> Result<()> {
+    let lua = Lua::new();
+    let vfs = MemoryVfs::new()
+        .with_file("main.luau", "return require('./lib/util')")
+        .with_file("lib/util.luau", "return 'util'");
+    lua.set_vfs(vfs)?;
+
+    let result = lua
+        .load("return require('./main')")
+        .set_name("@main.luau")
+        .eval::<String>()?;
+    assert_eq!(result, "util");
+
+    Ok(())
+}